@@ -15,12 +15,13 @@ contract HubProposalMetadata {
     GOVERNOR = IGovernor(_governor);
   }
 
-  /// @notice A method to read both proposal id and proposal vote start. This is meant to be read using
-  /// Wormhole Queries when setting up a proposal to be voted on a spoke.
+  /// @notice A method to read the proposal id, the proposal vote start and the proposal vote end. This is meant to be
+  /// read using Wormhole Queries when setting up a proposal to be voted on a spoke.
   /// @param _proposalId The proposal id from which to read metadata.
-  /// @return The proposal id and the vote start.
-  function getProposalMetadata(uint256 _proposalId) external view returns (uint256, uint256) {
+  /// @return The proposal id, the vote start and the vote end.
+  function getProposalMetadata(uint256 _proposalId) external view returns (uint256, uint256, uint256) {
     uint256 _voteStart = GOVERNOR.proposalSnapshot(_proposalId);
-    return (_proposalId, _voteStart);
+    uint256 _voteEnd = GOVERNOR.proposalDeadline(_proposalId);
+    return (_proposalId, _voteStart, _voteEnd);
   }
 }
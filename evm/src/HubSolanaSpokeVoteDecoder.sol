@@ -29,6 +29,8 @@ contract HubSolanaSpokeVoteDecoder is ISpokeVoteDecoder, QueryResponse, ERC165 {
   uint256 public constant DEFAULT_QUERY_VALUE = 0;
   bytes32 public constant PROPOSAL_SEED = bytes32("proposal");
   bytes8 public constant PROPOSAL_DISCRIMINATOR = bytes8(sha256("account:ProposalData"));
+  /// @notice The length of Solana proposal accounts created before the vote end was added.
+  uint256 public constant LEGACY_PROPOSAL_DATA_LENGTH = 72;
 
   /// @notice The hub vote pool used to validate message emitter.
   HubVotePool public immutable HUB_VOTE_POOL;
@@ -135,6 +137,9 @@ contract HubSolanaSpokeVoteDecoder is ISpokeVoteDecoder, QueryResponse, ERC165 {
   }
 
   // @notice Parse the vote data from a solana pda query.
+  // @dev Proposal accounts with the legacy layout end with the vote start, and their tallies are returned. The current
+  // layout continues with the vote end and the published tallies, which are returned instead, as the spoke only
+  // publishes tallies that do not decrease.
   // @param _data The solana query result.
   // @return The proposals id and vote totals.
   function _parseData(bytes memory _data) internal pure returns (bytes32, uint64, uint64, uint64, uint64) {
@@ -155,7 +160,16 @@ contract HubSolanaSpokeVoteDecoder is ISpokeVoteDecoder, QueryResponse, ERC165 {
     (_abstainVotes, _offset) = _data.asUint64Unchecked(_offset);
     (_voteStart, _offset) = _data.asUint64Unchecked(_offset);
 
-    // Verify the total length of the data (72 bytes)
+    if (_data.length != LEGACY_PROPOSAL_DATA_LENGTH) {
+      // Skip the vote end
+      _offset += 8;
+
+      (_againstVotes, _offset) = _data.asUint64Unchecked(_offset);
+      (_forVotes, _offset) = _data.asUint64Unchecked(_offset);
+      (_abstainVotes, _offset) = _data.asUint64Unchecked(_offset);
+    }
+
+    // Verify the total length of the data (72 or 104 bytes)
     _data.checkLength(_offset);
 
     return
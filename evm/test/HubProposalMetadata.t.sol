@@ -118,9 +118,10 @@ contract GetProposalMetadata is HubProposalMetadataTest {
     uint256 _proposalId =
       _queueAndVoteAndExecuteProposal(builder.targets(), builder.values(), builder.calldatas(), _proposalDescription);
 
-    (uint256 proposalId, uint256 voteStart) = hubProposalMetadata.getProposalMetadata(_proposalId);
+    (uint256 proposalId, uint256 voteStart, uint256 voteEnd) = hubProposalMetadata.getProposalMetadata(_proposalId);
 
     assertEq(proposalId, _proposalId);
     assertEq(voteStart, governor.proposalSnapshot(_proposalId));
+    assertEq(voteEnd, governor.proposalDeadline(_proposalId));
   }
 }
@@ -255,14 +255,25 @@ contract Decode is HubSolanaSpokeVoteDecoderTest, ProposalTest {
     uint64 _againstVotes,
     uint64 _forVotes,
     uint64 _abstainVotes,
-    uint64 _voteStart
+    uint64 _voteStart,
+    uint64 _voteEnd
   ) public {
     _setGovernor(hubGovernor);
 
     uint256 proposalId = _createEmptyProposal();
     bytes32 proposalIdBytes = bytes32(proposalId);
-    bytes memory voteData =
-      abi.encodePacked(proposalIdBytes, uint64(_againstVotes), uint64(_forVotes), uint64(_abstainVotes), _voteStart);
+    // The live tallies are skipped, the published tallies are returned
+    bytes memory voteData = abi.encodePacked(
+      proposalIdBytes,
+      type(uint64).max,
+      type(uint64).max,
+      type(uint64).max,
+      _voteStart,
+      _voteEnd,
+      _againstVotes,
+      _forVotes,
+      _abstainVotes
+    );
 
     bytes memory voteQueryResponseRaw = _buildSolanaVoteQueryResponse(proposalIdBytes, SPOKE_CHAIN_ID, voteData);
 
@@ -280,6 +291,84 @@ contract Decode is HubSolanaSpokeVoteDecoderTest, ProposalTest {
     assertEq(queryVote.chainId, SPOKE_CHAIN_ID, "Chain ID mismatch");
   }
 
+  function testFuzz_CorrectlyParseLegacyChainResponse(
+    uint64 _againstVotes,
+    uint64 _forVotes,
+    uint64 _abstainVotes,
+    uint64 _voteStart
+  ) public {
+    _setGovernor(hubGovernor);
+
+    uint256 proposalId = _createEmptyProposal();
+    bytes32 proposalIdBytes = bytes32(proposalId);
+    // Proposal accounts created before the vote end was added end with the vote start
+    bytes memory voteData = abi.encodePacked(proposalIdBytes, _againstVotes, _forVotes, _abstainVotes, _voteStart);
+
+    bytes memory voteQueryResponseRaw = _buildSolanaVoteQueryResponse(proposalIdBytes, SPOKE_CHAIN_ID, voteData);
+
+    ParsedQueryResponse memory parsedResp =
+      hubSolanaSpokeVoteDecoder.parseAndVerifyQueryResponse(voteQueryResponseRaw, _getSignatures(voteQueryResponseRaw));
+
+    ISpokeVoteDecoder.QueryVote memory queryVote =
+      hubSolanaSpokeVoteDecoder.decode(parsedResp.responses[0], IGovernor(address(hubGovernor)));
+
+    assertEq(queryVote.proposalId, proposalId, "Proposal ID mismatch");
+    assertEq(queryVote.proposalVote.abstainVotes, _scale(_reverse(_abstainVotes)), "Abstain votes mismatch");
+    assertEq(queryVote.proposalVote.againstVotes, _scale(_reverse(_againstVotes)), "Against votes mismatch");
+    assertEq(queryVote.proposalVote.forVotes, _scale(_reverse(_forVotes)), "For votes mismatch");
+  }
+
+  function test_CrossChainVoteWithRevisedVotes() public {
+    _setGovernor(hubGovernor);
+    vm.prank(address(timelock));
+    hubVotePool.registerQueryType(hubSolanaSpokeVoteDecoder.QT_SOL_PDA(), address(hubSolanaSpokeVoteDecoder));
+
+    uint256 proposalId = _createEmptyProposal();
+    bytes32 proposalIdBytes = bytes32(proposalId);
+    vm.warp(hubGovernor.proposalSnapshot(proposalId) + 1);
+
+    // 10 against and 20 for votes are published, 5 more against votes are cast but not published yet
+    bytes memory voteData = _proposalVoteData(proposalIdBytes, [uint64(15), 20, 0], [uint64(10), 20, 0]);
+    bytes memory voteQueryResponseRaw = _buildSolanaVoteQueryResponse(proposalIdBytes, SPOKE_CHAIN_ID, voteData);
+    hubVotePool.crossChainVote(voteQueryResponseRaw, _getSignatures(voteQueryResponseRaw));
+
+    // The 5 unpublished against votes are revised to abstain, which lowers the against tally. The published tallies
+    // do not decrease, so the votes are bridged again, and once more after the revision is published
+    voteData = _proposalVoteData(proposalIdBytes, [uint64(10), 20, 5], [uint64(10), 20, 0]);
+    voteQueryResponseRaw = _buildSolanaVoteQueryResponse(proposalIdBytes, SPOKE_CHAIN_ID, voteData);
+    hubVotePool.crossChainVote(voteQueryResponseRaw, _getSignatures(voteQueryResponseRaw));
+
+    voteData = _proposalVoteData(proposalIdBytes, [uint64(10), 20, 5], [uint64(10), 20, 5]);
+    voteQueryResponseRaw = _buildSolanaVoteQueryResponse(proposalIdBytes, SPOKE_CHAIN_ID, voteData);
+    hubVotePool.crossChainVote(voteQueryResponseRaw, _getSignatures(voteQueryResponseRaw));
+
+    (uint256 _againstVotes, uint256 _forVotes, uint256 _abstainVotes) =
+      hubVotePool.spokeProposalVotes(keccak256(abi.encode(SPOKE_CHAIN_ID, proposalId)));
+    assertEq(_againstVotes, _scale(10), "Against votes mismatch");
+    assertEq(_forVotes, _scale(20), "For votes mismatch");
+    assertEq(_abstainVotes, _scale(5), "Abstain votes mismatch");
+  }
+
+  // Builds the data of a proposal account with the current layout from the live and the published tallies, in the
+  // order against, for and abstain
+  function _proposalVoteData(bytes32 _proposalIdBytes, uint64[3] memory _tallies, uint64[3] memory _publishedTallies)
+    internal
+    pure
+    returns (bytes memory)
+  {
+    return abi.encodePacked(
+      _proposalIdBytes,
+      _reverse(_tallies[0]),
+      _reverse(_tallies[1]),
+      _reverse(_tallies[2]),
+      uint64(0), // vote start
+      uint64(0), // vote end
+      _reverse(_publishedTallies[0]),
+      _reverse(_publishedTallies[1]),
+      _reverse(_publishedTallies[2])
+    );
+  }
+
   function testFuzz_RevertIf_InvalidDataSlice(
     uint64 _requestDataSliceOffset,
     uint64 _requestDataSliceLength,
@@ -402,8 +491,8 @@ contract Decode is HubSolanaSpokeVoteDecoderTest, ProposalTest {
     ParsedQueryResponse memory parsedResp =
       hubSolanaSpokeVoteDecoder.parseAndVerifyQueryResponse(voteQueryResponseRaw, _getSignatures(voteQueryResponseRaw));
 
-    vm.expectRevert(abi.encodeWithSelector(BytesParsing.LengthMismatch.selector, voteData.length + 8, 72)); // 8 is the
-      // length of the discriminator
+    // 8 is the length of the discriminator
+    vm.expectRevert(abi.encodeWithSelector(BytesParsing.LengthMismatch.selector, voteData.length + 8, 104));
     hubSolanaSpokeVoteDecoder.decode(parsedResp.responses[0], IGovernor(address(hubGovernor)));
   }
 
@@ -8,9 +8,12 @@ import {
 } from "@coral-xyz/anchor";
 import {
   Connection,
+  Ed25519Program,
   Keypair,
+  AccountMeta,
   PublicKey,
   SYSVAR_CLOCK_PUBKEY,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
   TransactionInstruction,
 } from "@solana/web3.js";
 import * as importedWasm from "@wormhole/staking-wasm";
@@ -177,7 +180,7 @@ export class StakeConnection {
       [
         utils.bytes.utf8.encode(wasm.Constants.CHECKPOINT_DATA_SEED()),
         user.toBuffer(),
-        Buffer.from([index, 0]),
+        new BN(index).toArrayLike(Buffer, "le", 2),
       ],
       this.program.programId,
     )[0];
@@ -210,7 +213,7 @@ export class StakeConnection {
       [
         utils.bytes.utf8.encode(wasm.Constants.CHECKPOINT_DATA_SEED()),
         account.owner.toBuffer(),
-        Buffer.from([currentIndex, 0]),
+        new BN(currentIndex).toArrayLike(Buffer, "le", 2),
       ],
       this.program.programId,
     )[0];
@@ -221,6 +224,223 @@ export class StakeConnection {
     return accountData !== null ? checkpointDataAccountPublicKey : undefined;
   }
 
+  /** Gets the address of the CheckpointData account that the user's checkpoints
+   *  roll over to once the current CheckpointData account is full.
+   */
+  public async getNextStakeAccountCheckpointsAddress(
+    user: PublicKey,
+  ): Promise<PublicKey> {
+    const stakeAccountMetadataAddress = PublicKey.findProgramAddressSync(
+      [
+        utils.bytes.utf8.encode(wasm.Constants.STAKE_ACCOUNT_METADATA_SEED()),
+        user.toBuffer(),
+      ],
+      this.program.programId,
+    )[0];
+
+    const account =
+      await this.program.account.stakeAccountMetadata.fetchNullable(
+        stakeAccountMetadataAddress,
+      );
+    const nextIndex = (account?.stakeAccountCheckpointsLastIndex ?? 0) + 1;
+
+    return PublicKey.findProgramAddressSync(
+      [
+        utils.bytes.utf8.encode(wasm.Constants.CHECKPOINT_DATA_SEED()),
+        user.toBuffer(),
+        new BN(nextIndex).toArrayLike(Buffer, "le", 2),
+      ],
+      this.program.programId,
+    )[0];
+  }
+
+  /** Gets the address of the DelegationSplit account of the user. */
+  public getDelegationSplitAddress(user: PublicKey): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [
+        utils.bytes.utf8.encode(wasm.Constants.DELEGATION_SPLIT_SEED()),
+        user.toBuffer(),
+      ],
+      this.program.programId,
+    )[0];
+  }
+
+  public getDelegationNonceAddress(user: PublicKey): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [
+        utils.bytes.utf8.encode(wasm.Constants.DELEGATION_NONCE_SEED()),
+        user.toBuffer(),
+      ],
+      this.program.programId,
+    )[0];
+  }
+
+  public getPendingWithdrawalAddress(user: PublicKey): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [
+        utils.bytes.utf8.encode(wasm.Constants.PENDING_WITHDRAWAL_SEED()),
+        user.toBuffer(),
+      ],
+      this.program.programId,
+    )[0];
+  }
+
+  /** Gets the nonce the next delegate_by_sig signature of the user has to use. */
+  public async getDelegationNonce(user: PublicKey): Promise<BN> {
+    const delegationNonce =
+      await this.program.account.delegationNonce.fetchNullable(
+        this.getDelegationNonceAddress(user),
+      );
+    return delegationNonce?.nonce ?? new BN(0);
+  }
+
+  /** Builds the message the stake account owner signs to delegate by signature:
+   *  DELEGATE_BY_SIG_DOMAIN || program id (32 bytes) || delegatee (32 bytes)
+   *  || nonce (u64 LE) || expiry (i64 LE).
+   */
+  public static delegateBySigMessage(
+    programId: PublicKey,
+    delegatee: PublicKey,
+    nonce: BN,
+    expiry: BN,
+  ): Buffer {
+    return Buffer.concat([
+      Buffer.from(wasm.Constants.DELEGATE_BY_SIG_DOMAIN()),
+      programId.toBuffer(),
+      delegatee.toBuffer(),
+      nonce.toArrayLike(Buffer, "le", 8),
+      expiry.toTwos(64).toArrayLike(Buffer, "le", 8),
+    ]);
+  }
+
+  /** Gets the delegates of the user's active delegation split,
+   *  or an empty list if the user has no active split.
+   */
+  public async getDelegationSplitDelegates(
+    user: PublicKey,
+  ): Promise<PublicKey[]> {
+    const delegationSplit =
+      await this.program.account.delegationSplit.fetchNullable(
+        this.getDelegationSplitAddress(user),
+      );
+    return delegationSplit?.shares.map((share) => share.delegate) ?? [];
+  }
+
+  /** Builds the remaining accounts of a delegation split instruction: the current CheckpointData,
+   *  the next CheckpointData and the stake metadata account of every delegate, in order.
+   */
+  public async getDelegationSplitRemainingAccounts(
+    delegates: PublicKey[],
+  ): Promise<AccountMeta[]> {
+    const remainingAccounts: AccountMeta[] = [];
+    for (const delegate of delegates) {
+      const delegateStakeAccountMetadataAddress =
+        await this.getStakeMetadataAddress(delegate);
+      remainingAccounts.push(
+        {
+          pubkey: await this.getStakeAccountCheckpointsAddressByMetadata(
+            delegateStakeAccountMetadataAddress,
+            false,
+          ),
+          isSigner: false,
+          isWritable: true,
+        },
+        {
+          pubkey: await this.getNextStakeAccountCheckpointsAddress(delegate),
+          isSigner: false,
+          isWritable: true,
+        },
+        {
+          pubkey: delegateStakeAccountMetadataAddress,
+          isSigner: false,
+          isWritable: true,
+        },
+      );
+    }
+    return remainingAccounts;
+  }
+
+  /** Gets the remaining accounts of the delegation chain that follows the delegate
+   *  with liquid delegation: the StakeAccountMetadata of every following stake account,
+   *  then the current and next CheckpointData of the stake account at the end of the chain.
+   */
+  public async getDelegationChainRemainingAccounts(
+    delegate: PublicKey,
+  ): Promise<AccountMeta[]> {
+    if (!("liquid" in this.config.delegationPolicy)) {
+      return [];
+    }
+
+    const remainingAccounts: AccountMeta[] = [];
+    let chainDelegate = delegate;
+    let chainDelegateMetadata =
+      await this.fetchStakeAccountMetadata(chainDelegate);
+    while (!chainDelegateMetadata.delegate.equals(chainDelegate)) {
+      chainDelegate = chainDelegateMetadata.delegate;
+      chainDelegateMetadata =
+        await this.fetchStakeAccountMetadata(chainDelegate);
+      remainingAccounts.push({
+        pubkey: await this.getStakeMetadataAddress(chainDelegate),
+        isSigner: false,
+        isWritable: true,
+      });
+    }
+
+    if (remainingAccounts.length > 0) {
+      remainingAccounts.push(
+        {
+          pubkey: await this.getStakeAccountCheckpointsAddressByMetadata(
+            await this.getStakeMetadataAddress(chainDelegate),
+            false,
+          ),
+          isSigner: false,
+          isWritable: true,
+        },
+        {
+          pubkey:
+            await this.getNextStakeAccountCheckpointsAddress(chainDelegate),
+          isSigner: false,
+          isWritable: true,
+        },
+      );
+    }
+    return remainingAccounts;
+  }
+
+  /** Gets the remaining accounts of the delegation chains a delegation moves the votes of the
+   *  stake account between with liquid delegation: the chain of the current delegate when it
+   *  is another stake account, followed by the chain of the delegatee. Both chains are passed
+   *  even without votes, as they count the delegation chains ending at their stake accounts.
+   */
+  public async getDelegationChainsRemainingAccounts(
+    stakeAccountOwner: PublicKey,
+    currentDelegate: PublicKey,
+    delegatee: PublicKey,
+  ): Promise<AccountMeta[]> {
+    if (!("liquid" in this.config.delegationPolicy)) {
+      return [];
+    }
+
+    if (currentDelegate.equals(delegatee)) {
+      return delegatee.equals(stakeAccountOwner)
+        ? []
+        : await this.getDelegationChainRemainingAccounts(delegatee);
+    }
+
+    const remainingAccounts: AccountMeta[] = [];
+    if (!currentDelegate.equals(stakeAccountOwner)) {
+      remainingAccounts.push(
+        ...(await this.getDelegationChainRemainingAccounts(currentDelegate)),
+      );
+    }
+    if (!delegatee.equals(stakeAccountOwner)) {
+      remainingAccounts.push(
+        ...(await this.getDelegationChainRemainingAccounts(delegatee)),
+      );
+    }
+    return remainingAccounts;
+  }
+
   /** Gets the address of the stake metadata account for a given owner,
    *  or returns undefined if the account does not exist.
    */
@@ -538,12 +758,27 @@ export class StakeConnection {
         .accountsPartial({
           currentDelegateStakeAccountCheckpoints:
             currentDelegateStakeAccountCheckpointsAddress,
+          currentDelegateStakeAccountCheckpointsNext:
+            await this.getNextStakeAccountCheckpointsAddress(
+              currentDelegateStakeAccountOwner,
+            ),
           delegateeStakeAccountCheckpoints:
             delegateeStakeAccountCheckpointsAddress,
+          delegateeStakeAccountCheckpointsNext:
+            await this.getNextStakeAccountCheckpointsAddress(
+              delegateeStakeAccountOwner,
+            ),
           vestingConfig: vestingConfigAccount,
           vestingBalance: vestingBalanceAccount,
           mint: this.config.votingTokenMint,
         })
+        .remainingAccounts(
+          await this.getDelegationChainsRemainingAccounts(
+            this.userPublicKey(),
+            currentDelegateStakeAccountOwner,
+            delegateeStakeAccountOwner,
+          ),
+        )
         .instruction(),
     );
 
@@ -559,6 +794,12 @@ export class StakeConnection {
     delegatee: PublicKey | undefined,
     amount: WHTokenBalance,
   ): Promise<PublicKey> {
+    // Stake accounts created with a previous layout are migrated on first use
+    await this.migrateStakeAccountMetadata(this.userPublicKey());
+    if (delegatee) {
+      await this.migrateStakeAccountMetadata(delegatee);
+    }
+
     let stakeAccountMetadataAddress = await this.getStakeMetadataAddress(
       this.userPublicKey(),
     );
@@ -623,18 +864,42 @@ export class StakeConnection {
       );
     }
 
+    // Delegating to a single delegatee removes an active delegation split,
+    // the delegatee's own share is updated through the delegatee accounts
+    const delegationSplitDelegates = (
+      await this.getDelegationSplitDelegates(this.userPublicKey())
+    ).filter((delegate) => !delegate.equals(delegateeStakeAccountOwner));
+
     instructions.push(
       await this.program.methods
         .delegate(delegateeStakeAccountOwner, currentDelegateStakeAccountOwner)
         .accountsPartial({
           currentDelegateStakeAccountCheckpoints:
             currentDelegateStakeAccountCheckpointsAddress,
+          currentDelegateStakeAccountCheckpointsNext:
+            await this.getNextStakeAccountCheckpointsAddress(
+              currentDelegateStakeAccountOwner,
+            ),
           delegateeStakeAccountCheckpoints:
             delegateeStakeAccountCheckpointsAddress,
+          delegateeStakeAccountCheckpointsNext:
+            await this.getNextStakeAccountCheckpointsAddress(
+              delegateeStakeAccountOwner,
+            ),
           vestingConfig: null,
           vestingBalance: null,
           mint: this.config.votingTokenMint,
         })
+        .remainingAccounts([
+          ...(await this.getDelegationSplitRemainingAccounts(
+            delegationSplitDelegates,
+          )),
+          ...(await this.getDelegationChainsRemainingAccounts(
+            this.userPublicKey(),
+            currentDelegateStakeAccountOwner,
+            delegateeStakeAccountOwner,
+          )),
+        ])
         .instruction(),
     );
 
@@ -643,6 +908,222 @@ export class StakeConnection {
     return stakeAccountCheckpointsAddress;
   }
 
+  /** Delegates the stake account of another user with their ed25519 signature
+   *  of delegateBySigMessage(programId, delegatee, nonce, expiry), paying for the transaction.
+   */
+  public async delegateBySig(
+    stakeAccountOwner: PublicKey,
+    delegatee: PublicKey,
+    nonce: BN,
+    expiry: BN,
+    signature: Uint8Array,
+  ): Promise<void> {
+    const currentDelegateStakeAccountOwner =
+      await this.delegates(stakeAccountOwner);
+    const currentDelegateStakeAccountMetadataAddress =
+      await this.getStakeMetadataAddress(currentDelegateStakeAccountOwner);
+    const delegateeStakeAccountMetadataAddress =
+      await this.getStakeMetadataAddress(delegatee);
+
+    const instructions: TransactionInstruction[] = [
+      Ed25519Program.createInstructionWithPublicKey({
+        publicKey: stakeAccountOwner.toBytes(),
+        message: StakeConnection.delegateBySigMessage(
+          this.program.programId,
+          delegatee,
+          nonce,
+          expiry,
+        ),
+        signature,
+      }),
+    ];
+
+    // Delegating to a single delegatee removes an active delegation split,
+    // the delegatee's own share is updated through the delegatee accounts
+    const delegationSplitDelegates = (
+      await this.getDelegationSplitDelegates(stakeAccountOwner)
+    ).filter((delegate) => !delegate.equals(delegatee));
+
+    instructions.push(
+      await this.program.methods
+        .delegateBySig(
+          delegatee,
+          currentDelegateStakeAccountOwner,
+          stakeAccountOwner,
+          nonce,
+          expiry,
+        )
+        .accountsPartial({
+          currentDelegateStakeAccountCheckpoints:
+            await this.getStakeAccountCheckpointsAddressByMetadata(
+              currentDelegateStakeAccountMetadataAddress,
+              false,
+            ),
+          currentDelegateStakeAccountCheckpointsNext:
+            await this.getNextStakeAccountCheckpointsAddress(
+              currentDelegateStakeAccountOwner,
+            ),
+          delegateeStakeAccountCheckpoints:
+            await this.getStakeAccountCheckpointsAddressByMetadata(
+              delegateeStakeAccountMetadataAddress,
+              false,
+            ),
+          delegateeStakeAccountCheckpointsNext:
+            await this.getNextStakeAccountCheckpointsAddress(delegatee),
+          vestingConfig: null,
+          vestingBalance: null,
+          mint: this.config.votingTokenMint,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        })
+        .remainingAccounts([
+          ...(await this.getDelegationSplitRemainingAccounts(
+            delegationSplitDelegates,
+          )),
+          ...(await this.getDelegationChainsRemainingAccounts(
+            stakeAccountOwner,
+            currentDelegateStakeAccountOwner,
+            delegatee,
+          )),
+        ])
+        .instruction(),
+    );
+
+    await this.sendAndConfirmAsVersionedTransaction(instructions);
+  }
+
+  /** Deposits tokens from the user's associated token account into the user's stake account
+   *  and checkpoints the new balance for the current delegate.
+   */
+  public async depositTokens(amount: WHTokenBalance): Promise<void> {
+    const source = await getAssociatedTokenAddress(
+      this.config.votingTokenMint,
+      this.provider.wallet.publicKey,
+      true,
+    );
+
+    const currentDelegateStakeAccountOwner = await this.delegates(
+      this.userPublicKey(),
+    );
+    const currentDelegateStakeAccountMetadataAddress =
+      await this.getStakeMetadataAddress(currentDelegateStakeAccountOwner);
+
+    await this.program.methods
+      .depositTokens(amount.toBN(), currentDelegateStakeAccountOwner)
+      .accountsPartial({
+        currentDelegateStakeAccountCheckpoints:
+          await this.getStakeAccountCheckpointsAddressByMetadata(
+            currentDelegateStakeAccountMetadataAddress,
+            false,
+          ),
+        currentDelegateStakeAccountCheckpointsNext:
+          await this.getNextStakeAccountCheckpointsAddress(
+            currentDelegateStakeAccountOwner,
+          ),
+        source,
+        mint: this.config.votingTokenMint,
+      })
+      .remainingAccounts([
+        ...(await this.getDelegationSplitRemainingAccounts(
+          await this.getDelegationSplitDelegates(this.userPublicKey()),
+        )),
+        ...(await this.getDelegationChainRemainingAccounts(
+          currentDelegateStakeAccountOwner,
+        )),
+      ])
+      .rpc();
+  }
+
+  /** Records tokens sent directly to the custody account of a stake account
+   *  and checkpoints them for its current delegate. Anyone can sync any stake account.
+   */
+  public async syncBalance(stakeAccountOwner: PublicKey): Promise<void> {
+    const currentDelegateStakeAccountOwner =
+      await this.delegates(stakeAccountOwner);
+    const currentDelegateStakeAccountMetadataAddress =
+      await this.getStakeMetadataAddress(currentDelegateStakeAccountOwner);
+
+    await this.program.methods
+      .syncBalance(stakeAccountOwner, currentDelegateStakeAccountOwner)
+      .accountsPartial({
+        currentDelegateStakeAccountCheckpoints:
+          await this.getStakeAccountCheckpointsAddressByMetadata(
+            currentDelegateStakeAccountMetadataAddress,
+            false,
+          ),
+        currentDelegateStakeAccountCheckpointsNext:
+          await this.getNextStakeAccountCheckpointsAddress(
+            currentDelegateStakeAccountOwner,
+          ),
+      })
+      .remainingAccounts([
+        ...(await this.getDelegationSplitRemainingAccounts(
+          await this.getDelegationSplitDelegates(stakeAccountOwner),
+        )),
+        ...(await this.getDelegationChainRemainingAccounts(
+          currentDelegateStakeAccountOwner,
+        )),
+      ])
+      .rpc();
+  }
+
+  /** Splits the user's voting power between several delegates by basis points. */
+  public async setDelegationSplit(
+    shares: { delegate: PublicKey; basisPoints: number }[],
+  ): Promise<void> {
+    const currentDelegateStakeAccountOwner = await this.delegates(
+      this.userPublicKey(),
+    );
+    const currentDelegateStakeAccountMetadataAddress =
+      await this.getStakeMetadataAddress(currentDelegateStakeAccountOwner);
+
+    await this.program.methods
+      .setDelegationSplit(shares, currentDelegateStakeAccountOwner)
+      .accountsPartial({
+        currentDelegateStakeAccountCheckpoints:
+          await this.getStakeAccountCheckpointsAddressByMetadata(
+            currentDelegateStakeAccountMetadataAddress,
+            false,
+          ),
+        currentDelegateStakeAccountCheckpointsNext:
+          await this.getNextStakeAccountCheckpointsAddress(
+            currentDelegateStakeAccountOwner,
+          ),
+      })
+      .remainingAccounts(
+        await this.getDelegationSplitRemainingAccounts(
+          shares
+            .map((share) => share.delegate)
+            .filter(
+              (delegate) => !delegate.equals(currentDelegateStakeAccountOwner),
+            ),
+        ),
+      )
+      .rpc();
+  }
+
+  /** Replaces the shares of the user's active delegation split. */
+  public async updateDelegationSplit(
+    shares: { delegate: PublicKey; basisPoints: number }[],
+  ): Promise<void> {
+    const newDelegates = shares.map((share) => share.delegate);
+    const removedDelegates = (
+      await this.getDelegationSplitDelegates(this.userPublicKey())
+    ).filter(
+      (delegate) =>
+        !newDelegates.some((newDelegate) => newDelegate.equals(delegate)),
+    );
+
+    await this.program.methods
+      .updateDelegationSplit(shares)
+      .remainingAccounts(
+        await this.getDelegationSplitRemainingAccounts([
+          ...newDelegates,
+          ...removedDelegates,
+        ]),
+      )
+      .rpc();
+  }
+
   /** Casts a vote on a proposal using the voter's stake account checkpoint data,
    *  determined dynamically based on the user's public key and the provided checkpoint index.
    *  The function constructs the transaction instructions with the given vote counts
@@ -654,7 +1135,10 @@ export class StakeConnection {
     forVotes: BN,
     abstainVotes: BN,
     checkpointIndex: number = 0,
+    lastCheckpointIndex: number = checkpointIndex + 1,
   ): Promise<void> {
+    await this.migrateStakeAccountMetadata(this.userPublicKey());
+
     let voterStakeAccountCheckpointsAddress =
       await this.getStakeAccountCheckpointsAddress(
         this.userPublicKey(),
@@ -666,6 +1150,27 @@ export class StakeConnection {
         checkpointIndex + 1,
       );
 
+    // CheckpointData accounts after the next one, for vote windows spanning more than two accounts
+    const remainingAccounts = [];
+    for (
+      let index = checkpointIndex + 2;
+      index <= lastCheckpointIndex;
+      index++
+    ) {
+      remainingAccounts.push({
+        pubkey: PublicKey.findProgramAddressSync(
+          [
+            utils.bytes.utf8.encode(wasm.Constants.CHECKPOINT_DATA_SEED()),
+            this.userPublicKey().toBuffer(),
+            new BN(index).toArrayLike(Buffer, "le", 2),
+          ],
+          this.program.programId,
+        )[0],
+        isSigner: false,
+        isWritable: false,
+      });
+    }
+
     const instructions: TransactionInstruction[] = [];
     const { proposalAccount } = await this.fetchProposalAccount(proposalId);
 
@@ -686,12 +1191,46 @@ export class StakeConnection {
               ? null
               : nextVoterStakeAccountCheckpointsAddress,
         })
+        .remainingAccounts(remainingAccounts)
         .instruction(),
     );
 
     await this.sendAndConfirmAsVersionedTransaction(instructions);
   }
 
+  /** Replaces the votes the user cast on a proposal with the given
+   *  "against," "for," and "abstain" votes, zero votes retract the votes.
+   */
+  public async reviseVote(
+    proposalId: Buffer,
+    againstVotes: BN,
+    forVotes: BN,
+    abstainVotes: BN,
+  ): Promise<void> {
+    const { proposalAccount } = await this.fetchProposalAccount(proposalId);
+
+    await this.program.methods
+      .reviseVote(Array.from(proposalId), againstVotes, forVotes, abstainVotes)
+      .accountsPartial({
+        proposal: proposalAccount,
+      })
+      .rpc();
+  }
+
+  /** Publishes the current tallies of the specified proposal id to the hub,
+   *  which only reads the published tallies.
+   */
+  public async publishVotes(proposalId: Buffer): Promise<void> {
+    const { proposalAccount } = await this.fetchProposalAccount(proposalId);
+
+    await this.program.methods
+      .publishVotes(Array.from(proposalId))
+      .accountsPartial({
+        proposal: proposalAccount,
+      })
+      .rpc();
+  }
+
   /** Retrieves the current voting results (against, for, and abstain votes) for the specified proposal id. */
   public async proposalVotes(proposalId: Buffer): Promise<{
     proposalId: Buffer;
@@ -778,6 +1317,181 @@ export class StakeConnection {
     }
   }
 
+  /** Adds a batch of proposals from a query response with one getProposalMetadata
+   *  call per proposal, in the order of the given proposal ids.
+   */
+  public async addProposals(
+    proposalIds: Buffer[],
+    ethProposalsResponseBytes: Uint8Array,
+    guardianSignatures: PublicKey,
+    guardianSetIndex: number,
+  ): Promise<void> {
+    const networkType = this.provider.connection.rpcEndpoint.includes("mainnet")
+      ? "Mainnet"
+      : "Testnet";
+    const coreBridge = new PublicKey(
+      contracts.coreBridge.get(networkType, "Solana"),
+    );
+
+    const remainingAccounts: AccountMeta[] = [];
+    for (const proposalId of proposalIds) {
+      const { proposalAccount } = await this.fetchProposalAccount(proposalId);
+      remainingAccounts.push({
+        pubkey: proposalAccount,
+        isSigner: false,
+        isWritable: true,
+      });
+    }
+
+    const instructions: TransactionInstruction[] = [];
+
+    instructions.push(
+      await this.program.methods
+        .addProposals(Buffer.from(ethProposalsResponseBytes), guardianSetIndex)
+        .accountsPartial({
+          guardianSignatures: guardianSignatures,
+          guardianSet: deriveGuardianSetKey(coreBridge, guardianSetIndex),
+        })
+        .remainingAccounts(remainingAccounts)
+        .instruction(),
+    );
+
+    await this.sendAndConfirmAsVersionedTransaction(instructions);
+  }
+
+  /** Syncs the vote end of a proposal with the extended deadline reported by
+   *  the hub proposal extender, verified with the given guardian signatures.
+   */
+  public async extendProposal(
+    proposalId: Buffer,
+    ethExtendedDeadlineResponseBytes: Uint8Array,
+    guardianSignatures: PublicKey,
+    guardianSetIndex: number,
+  ): Promise<void> {
+    const { proposalAccount } = await this.fetchProposalAccount(proposalId);
+    const networkType = this.provider.connection.rpcEndpoint.includes("mainnet")
+      ? "Mainnet"
+      : "Testnet";
+    const coreBridge = new PublicKey(
+      contracts.coreBridge.get(networkType, "Solana"),
+    );
+
+    await this.program.methods
+      .extendProposal(
+        Buffer.from(ethExtendedDeadlineResponseBytes),
+        Array.from(proposalId),
+        guardianSetIndex,
+      )
+      .accountsPartial({
+        proposal: proposalAccount,
+        guardianSignatures: guardianSignatures,
+        guardianSet: deriveGuardianSetKey(coreBridge, guardianSetIndex),
+      })
+      .rpc()
+      .then(this.confirm);
+  }
+
+  /** Migrates a proposal account created with a previous layout. */
+  public async migrateProposal(proposalId: Buffer): Promise<void> {
+    const { proposalAccount } = await this.fetchProposalAccount(proposalId);
+
+    await this.program.methods
+      .migrateProposal(Array.from(proposalId))
+      .accountsPartial({
+        proposal: proposalAccount,
+      })
+      .rpc();
+  }
+
+  /** Migrates the stake account metadata of the owner if it was created with a
+   *  previous layout, after the metadata of its delegate, which counts it as a
+   *  delegator. Returns whether the metadata was migrated.
+   */
+  public async migrateStakeAccountMetadata(owner: PublicKey): Promise<boolean> {
+    const stakeAccountMetadataAddress = PublicKey.findProgramAddressSync(
+      [
+        utils.bytes.utf8.encode(wasm.Constants.STAKE_ACCOUNT_METADATA_SEED()),
+        owner.toBuffer(),
+      ],
+      this.program.programId,
+    )[0];
+    const accountInfo = await this.provider.connection.getAccountInfo(
+      stakeAccountMetadataAddress,
+    );
+    if (
+      accountInfo === null ||
+      accountInfo.data.length >= this.program.account.stakeAccountMetadata.size
+    ) {
+      return false;
+    }
+
+    // The delegate follows the discriminator, the bumps, the recorded balances
+    // and the owner in every layout
+    const delegate = new PublicKey(accountInfo.data.subarray(59, 91));
+    let delegateStakeAccountMetadata: PublicKey | null = null;
+    if (!delegate.equals(owner)) {
+      await this.migrateStakeAccountMetadata(delegate);
+      delegateStakeAccountMetadata = PublicKey.findProgramAddressSync(
+        [
+          utils.bytes.utf8.encode(
+            wasm.Constants.STAKE_ACCOUNT_METADATA_SEED(),
+          ),
+          delegate.toBuffer(),
+        ],
+        this.program.programId,
+      )[0];
+    }
+
+    await this.program.methods
+      .migrateStakeAccountMetadata(owner)
+      .accountsPartial({
+        delegateStakeAccountMetadata,
+      })
+      .rpc()
+      .then(this.confirm);
+    return true;
+  }
+
+  /** Gets the current voting weight of the given user. */
+  public async getVotes(user: PublicKey): Promise<BN> {
+    return await this.program.methods.getVotes(user).view();
+  }
+
+  /** Gets the voting weight of the given user at a past timestamp, searching the
+   *  user's CheckpointData accounts from firstCheckpointIndex to lastCheckpointIndex.
+   */
+  public async getPastVotes(
+    user: PublicKey,
+    timestamp: BN,
+    firstCheckpointIndex: number,
+    lastCheckpointIndex: number,
+  ): Promise<BN> {
+    const remainingAccounts = [];
+    for (
+      let index = firstCheckpointIndex;
+      index <= lastCheckpointIndex;
+      index++
+    ) {
+      remainingAccounts.push({
+        pubkey: PublicKey.findProgramAddressSync(
+          [
+            utils.bytes.utf8.encode(wasm.Constants.CHECKPOINT_DATA_SEED()),
+            user.toBuffer(),
+            new BN(index).toArrayLike(Buffer, "le", 2),
+          ],
+          this.program.programId,
+        )[0],
+        isSigner: false,
+        isWritable: false,
+      });
+    }
+
+    return await this.program.methods
+      .getPastVotes(user, timestamp, firstCheckpointIndex)
+      .remainingAccounts(remainingAccounts)
+      .view();
+  }
+
   /** Gets the current delegate's public key associated with the user public key. */
   public async delegates(user: PublicKey): Promise<PublicKey> {
     const stakeAccountMetadata = await this.fetchStakeAccountMetadata(user);
@@ -839,6 +1553,115 @@ export class StakeConnection {
         .accountsPartial({
           currentDelegateStakeAccountCheckpoints:
             currentDelegateStakeAccountCheckpointsAddress,
+          currentDelegateStakeAccountCheckpointsNext:
+            await this.getNextStakeAccountCheckpointsAddress(
+              currentDelegateStakeAccountCheckpointsOwner,
+            ),
+          destination: toAccount,
+        })
+        .remainingAccounts([
+          ...(await this.getDelegationSplitRemainingAccounts(
+            await this.getDelegationSplitDelegates(
+              stakeAccountCheckpointsData.owner,
+            ),
+          )),
+          ...(await this.getDelegationChainRemainingAccounts(
+            currentDelegateStakeAccountCheckpointsOwner,
+          )),
+        ])
+        .instruction(),
+    );
+
+    await this.sendAndConfirmAsVersionedTransaction(instructions);
+  }
+
+  /** Requests a withdrawal while the withdrawal cooldown is enabled.
+   *  The votes of the amount are removed immediately and the tokens
+   *  can be withdrawn with completeWithdrawal once the cooldown has elapsed.
+   */
+  public async requestWithdrawal(
+    stakeAccount: StakeAccount,
+    amount: WHTokenBalance,
+  ) {
+    if (amount.toBN().gt(stakeAccount.getBalanceSummary().balance.toBN())) {
+      throw new Error("Amount exceeds withdrawable.");
+    }
+
+    let stakeAccountCheckpointsData =
+      await this.program.account.checkpointData.fetch(stakeAccount.address);
+
+    let currentDelegateStakeAccountCheckpointsOwner = await this.delegates(
+      stakeAccountCheckpointsData.owner,
+    );
+    let currentDelegateStakeAccountMetadataAddress =
+      await this.getStakeMetadataAddress(
+        currentDelegateStakeAccountCheckpointsOwner,
+      );
+    let currentDelegateStakeAccountCheckpointsAddress =
+      await this.getStakeAccountCheckpointsAddressByMetadata(
+        currentDelegateStakeAccountMetadataAddress,
+        false,
+      );
+
+    const instructions: TransactionInstruction[] = [];
+    instructions.push(
+      await this.program.methods
+        .requestWithdrawal(
+          amount.toBN(),
+          currentDelegateStakeAccountCheckpointsOwner,
+          stakeAccountCheckpointsData.owner,
+        )
+        .accountsPartial({
+          currentDelegateStakeAccountCheckpoints:
+            currentDelegateStakeAccountCheckpointsAddress,
+          currentDelegateStakeAccountCheckpointsNext:
+            await this.getNextStakeAccountCheckpointsAddress(
+              currentDelegateStakeAccountCheckpointsOwner,
+            ),
+          mint: this.config.votingTokenMint,
+        })
+        .remainingAccounts([
+          ...(await this.getDelegationSplitRemainingAccounts(
+            await this.getDelegationSplitDelegates(
+              stakeAccountCheckpointsData.owner,
+            ),
+          )),
+          ...(await this.getDelegationChainRemainingAccounts(
+            currentDelegateStakeAccountCheckpointsOwner,
+          )),
+        ])
+        .instruction(),
+    );
+
+    await this.sendAndConfirmAsVersionedTransaction(instructions);
+  }
+
+  /** Transfers the tokens of the user's pending withdrawal to their associated
+   *  token account once the withdrawal cooldown has elapsed.
+   */
+  public async completeWithdrawal() {
+    const toAccount = await getAssociatedTokenAddress(
+      this.config.votingTokenMint,
+      this.provider.wallet.publicKey,
+      true,
+    );
+
+    const instructions: TransactionInstruction[] = [];
+    if ((await this.provider.connection.getAccountInfo(toAccount)) == null) {
+      instructions.push(
+        createAssociatedTokenAccountInstruction(
+          this.provider.wallet.publicKey,
+          toAccount,
+          this.provider.wallet.publicKey,
+          this.config.votingTokenMint,
+        ),
+      );
+    }
+
+    instructions.push(
+      await this.program.methods
+        .completeWithdrawal()
+        .accounts({
           destination: toAccount,
         })
         .instruction(),
@@ -846,6 +1669,121 @@ export class StakeConnection {
 
     await this.sendAndConfirmAsVersionedTransaction(instructions);
   }
+
+  /** Closes the user's retired CheckpointData account with the given index
+   *  once it is no longer needed for vote weight calculation, refunding its rent.
+   */
+  public async compactCheckpoints(checkpointIndex: number): Promise<void> {
+    const instructions: TransactionInstruction[] = [];
+    instructions.push(
+      await this.program.methods
+        .compactCheckpoints(checkpointIndex)
+        .accounts({ owner: this.userPublicKey() })
+        .instruction(),
+    );
+
+    await this.sendAndConfirmAsVersionedTransaction(instructions);
+  }
+
+  /** Proposes a new owner for the user's stake account,
+   *  who takes it over by calling acceptStakeOwner.
+   */
+  public async proposeStakeOwner(newOwner: PublicKey): Promise<void> {
+    const instructions: TransactionInstruction[] = [];
+    instructions.push(
+      await this.program.methods
+        .proposeStakeOwner()
+        .accounts({ owner: this.userPublicKey(), newOwner })
+        .instruction(),
+    );
+
+    await this.sendAndConfirmAsVersionedTransaction(instructions);
+  }
+
+  /** Moves the stake account of the previous owner, who proposed the user as its new owner,
+   *  to a new stake account of the user along with the vesting balances linked to it.
+   */
+  public async acceptStakeOwner(previousOwner: PublicKey): Promise<void> {
+    const stakeAccountMetadataAddress =
+      await this.getStakeMetadataAddress(previousOwner);
+
+    const vestingBalances = await this.program.account.vestingBalance.all();
+    const remainingAccounts: AccountMeta[] = vestingBalances
+      .filter((vestingBalance) =>
+        vestingBalance.account.stakeAccountMetadata.equals(
+          stakeAccountMetadataAddress,
+        ),
+      )
+      .map((vestingBalance) => ({
+        pubkey: vestingBalance.publicKey,
+        isSigner: false,
+        isWritable: true,
+      }));
+
+    const instructions: TransactionInstruction[] = [];
+    instructions.push(
+      await this.program.methods
+        .acceptStakeOwner()
+        .accountsPartial({
+          newOwner: this.userPublicKey(),
+          owner: previousOwner,
+          stakeAccountCheckpoints:
+            await this.getStakeAccountCheckpointsAddressByMetadata(
+              stakeAccountMetadataAddress,
+              false,
+            ),
+          stakeAccountCheckpointsNext:
+            await this.getNextStakeAccountCheckpointsAddress(previousOwner),
+          mint: this.config.votingTokenMint,
+        })
+        .remainingAccounts(remainingAccounts)
+        .instruction(),
+    );
+
+    await this.sendAndConfirmAsVersionedTransaction(instructions);
+  }
+
+  /** Closes the user's stake account once it holds no tokens and no voting power,
+   *  refunding the rent of all its accounts.
+   */
+  public async closeStakeAccount(): Promise<void> {
+    const stakeAccountMetadata =
+      await this.program.account.stakeAccountMetadata.fetch(
+        await this.getStakeMetadataAddress(this.userPublicKey()),
+      );
+
+    // Every previous CheckpointData account, including the ones already compacted
+    const remainingAccounts: AccountMeta[] = [];
+    for (
+      let index = 0;
+      index < stakeAccountMetadata.stakeAccountCheckpointsLastIndex;
+      index++
+    ) {
+      remainingAccounts.push({
+        pubkey: PublicKey.findProgramAddressSync(
+          [
+            utils.bytes.utf8.encode(wasm.Constants.CHECKPOINT_DATA_SEED()),
+            this.userPublicKey().toBuffer(),
+            new BN(index).toArrayLike(Buffer, "le", 2),
+          ],
+          this.program.programId,
+        )[0],
+        isSigner: false,
+        isWritable: true,
+      });
+    }
+
+    const instructions: TransactionInstruction[] = [];
+    instructions.push(
+      await this.program.methods
+        .closeStakeAccount()
+        .accounts({ owner: this.userPublicKey() })
+        .remainingAccounts(remainingAccounts)
+        .instruction(),
+    );
+
+    await this.sendAndConfirmAsVersionedTransaction(instructions);
+  }
 }
 
 export interface BalanceSummary {
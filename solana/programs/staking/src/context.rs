@@ -1,22 +1,15 @@
 use crate::state::*;
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::program_memory::sol_memcpy;
-use anchor_lang::solana_program::secp256k1_recover::secp256k1_recover;
-use anchor_lang::solana_program::{self, keccak};
-use anchor_spl::token::{Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token::{CloseAccount, Mint, Token, TokenAccount, Transfer};
 
 use wormhole_solana_consts::CORE_BRIDGE_PROGRAM_ID;
 
-use crate::error::{ErrorCode, QueriesSolanaVerifyError};
+use crate::error::ErrorCode;
 use crate::state::{GuardianSignatures, WormholeGuardianSet};
-use anchor_lang::prelude::Clock;
 use wormhole_anchor_sdk::wormhole::PostedVaa;
-use wormhole_query_sdk::{MESSAGE_PREFIX, QUERY_MESSAGE_LEN};
 
 use crate::utils::execute_message::Message;
 use crate::MessageExecutorError;
-use wormhole_raw_vaas::utils::quorum;
-use wormhole_raw_vaas::GuardianSetSig;
 
 pub const AUTHORITY_SEED: &str = "authority";
 pub const CUSTODY_SEED: &str = "custody";
@@ -27,12 +20,21 @@ pub const PROPOSAL_SEED: &str = "proposal";
 pub const VESTING_CONFIG_SEED: &str = "vesting_config";
 pub const VEST_SEED: &str = "vest";
 pub const VESTING_BALANCE_SEED: &str = "vesting_balance";
+pub const VESTING_SCHEDULE_SEED: &str = "vesting_schedule";
+pub const VESTING_MERKLE_DISTRIBUTOR_SEED: &str = "vesting_merkle_distributor";
+pub const VESTING_MERKLE_CLAIM_BITMAP_SEED: &str = "vesting_merkle_claim_bitmap";
 pub const SPOKE_MESSAGE_EXECUTOR_SEED: &str = "spoke_message_executor";
 pub const MESSAGE_RECEIVED: &str = "message_received";
 pub const AIRLOCK_SEED: &str = "airlock";
 pub const SPOKE_METADATA_COLLECTOR_SEED: &str = "spoke_metadata_collector";
 pub const VOTE_WEIGHT_WINDOW_LENGTHS_SEED: &str = "vote_weight_window_lengths";
 pub const GUARDIAN_SIGNATURES_SEED: &str = "guardian_signatures";
+pub const DELEGATION_SPLIT_SEED: &str = "delegation_split";
+pub const DELEGATION_NONCE_SEED: &str = "delegation_nonce";
+pub const PENDING_STAKE_OWNER_SEED: &str = "pending_stake_owner";
+pub const PENDING_WITHDRAWAL_SEED: &str = "pending_withdrawal";
+pub const WITHDRAWAL_CUSTODY_SEED: &str = "withdrawal_custody";
+pub const DELEGATE_BY_SIG_DOMAIN: &str = "wormhole_staking_delegate_by_sig";
 
 #[derive(Accounts)]
 pub struct InitConfig<'info> {
@@ -53,6 +55,26 @@ pub struct InitConfig<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct MigrateGlobalConfig<'info> {
+    // The governance authority is checked against the deserialized config, and covers the rent
+    // of the larger account
+    #[account(mut)]
+    pub governance_signer: Signer<'info>,
+
+    /// CHECK: GlobalConfig PDA with a previous layout, which can not be deserialized as
+    /// GlobalConfig
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump
+    )]
+    pub config: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
 #[event_cpi]
 #[derive(Accounts)]
 #[instruction(delegatee: Pubkey, current_delegate_stake_account_owner: Pubkey)]
@@ -73,6 +95,10 @@ pub struct Delegate<'info> {
     )]
     pub current_delegate_stake_account_checkpoints:
         AccountLoader<'info, checkpoints::CheckpointData>,
+    /// CHECK: Next CheckpointData PDA of the current delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub current_delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
     #[account(
         mut,
         seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), current_delegate_stake_account_owner.as_ref()],
@@ -92,6 +118,10 @@ pub struct Delegate<'info> {
         bump
     )]
     pub delegatee_stake_account_checkpoints: AccountLoader<'info, checkpoints::CheckpointData>,
+    /// CHECK: Next CheckpointData PDA of the delegatee, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub delegatee_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
     #[account(
         mut,
         seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), delegatee.as_ref()],
@@ -108,6 +138,10 @@ pub struct Delegate<'info> {
             @ ErrorCode::InvalidCurrentDelegate
     )]
     pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    /// CHECK: DelegationSplit PDA of the user, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), payer.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
 
     /// CHECK : This AccountInfo is safe because it's a checked PDA
     #[account(seeds = [AUTHORITY_SEED.as_bytes(), payer.key().as_ref()], bump)]
@@ -129,7 +163,8 @@ pub struct Delegate<'info> {
     #[account(mut)]
     pub vesting_balance: Option<Account<'info, VestingBalance>>,
 
-    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    // Counts the stake accounts that start or stop delegating
+    #[account(mut, seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
     pub config: Box<Account<'info, global_config::GlobalConfig>>,
     // Wormhole token mint:
     #[account(address = config.voting_token_mint)]
@@ -165,9 +200,20 @@ pub struct CastVote<'info> {
     pub voter_checkpoints: AccountLoader<'info, checkpoints::CheckpointData>,
 
     /// Next CheckpointData account if it exists
-    /// Necessary for handle the case when the vote window contains checkpoints stored on two accounts
+    /// Necessary for handle the case when the vote window contains checkpoints stored on two accounts.
+    /// When the vote window spans more accounts, the following CheckpointData accounts
+    /// are passed in order as remaining accounts
     pub voter_checkpoints_next: Option<AccountLoader<'info, checkpoints::CheckpointData>>,
 
+    /// StakeAccountMetadata of the voter, its checkpoints before owner_changed_at
+    /// are copied from the previous owner of the stake account
+    #[account(
+        has_one = owner,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), owner.key().as_ref()],
+        bump = voter_stake_account_metadata.metadata_bump
+    )]
+    pub voter_stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+
     #[account(
         init_if_needed,
         payer = owner,
@@ -191,6 +237,135 @@ pub struct CastVote<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(proposal_id: [u8; 32])]
+pub struct ReviseVote<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROPOSAL_SEED.as_bytes(), &proposal_id],
+        bump
+    )]
+    pub proposal: Account<'info, proposal::ProposalData>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal_voters_weight_cast", proposal.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub proposal_voters_weight_cast:
+        Account<'info, proposal_voters_weight_cast::ProposalVotersWeightCast>,
+
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(proposal_id: [u8; 32])]
+pub struct PublishVotes<'info> {
+    // Anyone can publish the tallies of a proposal
+    #[account(
+        mut,
+        seeds = [PROPOSAL_SEED.as_bytes(), &proposal_id],
+        bump
+    )]
+    pub proposal: Account<'info, proposal::ProposalData>,
+}
+
+#[derive(Accounts)]
+#[instruction(proposal_id: [u8; 32])]
+pub struct MigrateProposal<'info> {
+    // Anyone can migrate a proposal, the payer covers the rent of the larger account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: ProposalData PDA with the legacy layout, which can not be deserialized as ProposalData
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [PROPOSAL_SEED.as_bytes(), &proposal_id],
+        bump
+    )]
+    pub proposal: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateSpokeMetadataCollector<'info> {
+    // Anyone can migrate the account, the payer covers the rent of the larger account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: SpokeMetadataCollector PDA with a previous layout, which can not be deserialized
+    /// as SpokeMetadataCollector
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [SPOKE_METADATA_COLLECTOR_SEED.as_bytes()],
+        bump
+    )]
+    pub spoke_metadata_collector: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct MigrateStakeAccountMetadata<'info> {
+    // Anyone can migrate the stake account, the payer covers the rent of the larger account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: StakeAccountMetadata PDA of the owner with a previous layout, which can not be
+    /// deserialized as StakeAccountMetadata
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), owner.as_ref()],
+        bump
+    )]
+    pub stake_account_metadata: AccountInfo<'info>,
+    /// CHECK: DelegationSplit PDA of the owner, read if it was ever initialized
+    #[account(seeds = [DELEGATION_SPLIT_SEED.as_bytes(), owner.as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
+    /// CHECK: StakeAccountMetadata PDA of the delegate recorded in the migrated account, which
+    /// counts it as a delegator. Only passed when the stake account delegates to another one,
+    /// the address is checked in the instruction
+    #[account(mut)]
+    pub delegate_stake_account_metadata: Option<UncheckedAccount<'info>>,
+
+    #[account(mut, seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(voter: Pubkey)]
+pub struct MigrateProposalVotersWeightCast<'info> {
+    // Anyone can migrate the account, the payer covers the rent of the larger account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub proposal: Account<'info, proposal::ProposalData>,
+
+    /// CHECK: ProposalVotersWeightCast PDA with the legacy layout, which can not be deserialized
+    /// as ProposalVotersWeightCast
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"proposal_voters_weight_cast", proposal.key().as_ref(), voter.as_ref()],
+        bump
+    )]
+    pub proposal_voters_weight_cast: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct InitializeSpokeMetadataCollector<'info> {
     #[account(mut, address = config.governance_authority)]
@@ -232,6 +407,72 @@ pub struct UpdateHubProposalMetadata<'info> {
     pub config: Box<Account<'info, global_config::GlobalConfig>>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateHubProposalExtender<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [AIRLOCK_SEED.as_bytes()],
+        bump = airlock.bump,
+    )]
+    pub airlock: Account<'info, SpokeAirlock>,
+
+    #[account(
+        mut,
+        seeds = [SPOKE_METADATA_COLLECTOR_SEED.as_bytes()],
+        bump = spoke_metadata_collector.bump
+    )]
+    pub spoke_metadata_collector: Account<'info, SpokeMetadataCollector>,
+
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateHubProposalMetadataSources<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [AIRLOCK_SEED.as_bytes()],
+        bump = airlock.bump,
+    )]
+    pub airlock: Account<'info, SpokeAirlock>,
+
+    #[account(
+        mut,
+        seeds = [SPOKE_METADATA_COLLECTOR_SEED.as_bytes()],
+        bump = spoke_metadata_collector.bump
+    )]
+    pub spoke_metadata_collector: Account<'info, SpokeMetadataCollector>,
+
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateQueryPolicy<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [AIRLOCK_SEED.as_bytes()],
+        bump = airlock.bump,
+    )]
+    pub airlock: Account<'info, SpokeAirlock>,
+
+    #[account(
+        mut,
+        seeds = [SPOKE_METADATA_COLLECTOR_SEED.as_bytes()],
+        bump = spoke_metadata_collector.bump
+    )]
+    pub spoke_metadata_collector: Account<'info, SpokeMetadataCollector>,
+
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+}
+
 #[derive(Accounts)]
 pub struct RelinquishAdminControlOverHubProposalMetadata<'info> {
     #[account(mut, address = config.governance_authority)]
@@ -317,115 +558,90 @@ pub struct AddProposal<'info> {
     )]
     pub spoke_metadata_collector: Account<'info, SpokeMetadataCollector>,
 
+    #[account(mut, seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+
     pub system_program: Program<'info, System>,
 }
 
-impl<'info> AddProposal<'info> {
-    pub fn constraints(ctx: &Context<Self>, bytes: &Vec<u8>) -> Result<()> {
-        let guardian_set = &ctx.accounts.guardian_set;
-
-        // Check that the guardian set is still active.
-        let timestamp = Clock::get()?
-            .unix_timestamp
-            .try_into()
-            .expect("timestamp overflow");
-        require!(
-            guardian_set.is_active(&timestamp),
-            QueriesSolanaVerifyError::GuardianSetExpired
-        );
-
-        // Compute the message hash.
-        let message_hash = [
-            MESSAGE_PREFIX,
-            &solana_program::keccak::hashv(&[bytes]).to_bytes(),
-        ]
-        .concat();
-
-        // SECURITY: defense-in-depth, check again that these are the expected length
-        require_eq!(
-            message_hash.len(),
-            QUERY_MESSAGE_LEN,
-            QueriesSolanaVerifyError::InvalidMessageHash
-        );
-
-        let guardian_signatures = &ctx.accounts.guardian_signatures.guardian_signatures;
-
-        // This section is borrowed from https://github.com/wormhole-foundation/wormhole/blob/wen/solana-rewrite/solana/programs/core-bridge/src/processor/parse_and_verify_vaa/verify_encoded_vaa_v1.rs#L72-L103
-        // Also similarly used here https://github.com/pyth-network/pyth-crosschain/blob/6771c2c6998f53effee9247347cb0ac71612b3dc/target_chains/solana/programs/pyth-solana-receiver/src/lib.rs#L121-L159
-        // Do we have enough signatures for quorum?
-        let guardian_keys = &guardian_set.keys;
-        let quorum = quorum(guardian_keys.len());
-        require!(
-            guardian_signatures.len() >= quorum,
-            QueriesSolanaVerifyError::NoQuorum
-        );
-
-        let digest = keccak::hash(message_hash.as_slice());
-
-        // Verify signatures
-        let mut last_guardian_index = None;
-        for sig_bytes in guardian_signatures {
-            let sig = GuardianSetSig::try_from(sig_bytes.as_slice())
-                .map_err(|_| QueriesSolanaVerifyError::InvalidSignature)?;
-            // We do not allow for non-increasing guardian signature indices.
-            let index = usize::from(sig.guardian_index());
-            if let Some(last_index) = last_guardian_index {
-                require!(
-                    index > last_index,
-                    QueriesSolanaVerifyError::InvalidGuardianIndexNonIncreasing
-                );
-            }
-
-            // Does this guardian index exist in this guardian set?
-            let guardian_pubkey = guardian_keys
-                .get(index)
-                .ok_or_else(|| error!(QueriesSolanaVerifyError::InvalidGuardianIndexOutOfRange))?;
-
-            // Now verify that the signature agrees with the expected Guardian's pubkey.
-            verify_guardian_signature(&sig, guardian_pubkey, digest.as_ref())?;
-
-            last_guardian_index = Some(index);
-        }
-        // End borrowed section
-
-        // Done.
-        Ok(())
-    }
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(_bytes: Vec<u8>, guardian_set_index: u32)]
+pub struct AddProposals<'info> {
+    /// Guardian set used for signature verification.
+    #[account(
+        seeds = [
+            WormholeGuardianSet::SEED_PREFIX,
+            guardian_set_index.to_be_bytes().as_ref()
+        ],
+        bump,
+        seeds::program = CORE_BRIDGE_PROGRAM_ID
+    )]
+    pub guardian_set: Account<'info, WormholeGuardianSet>,
+
+    /// Stores unverified guardian signatures as they are too large to fit in the instruction data.
+    #[account(mut, has_one = refund_recipient, close = refund_recipient)]
+    pub guardian_signatures: Account<'info, GuardianSignatures>,
+
+    /// CHECK: This account is the refund recipient for the above signature_set
+    #[account(mut, address = guardian_signatures.refund_recipient)]
+    pub refund_recipient: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [SPOKE_METADATA_COLLECTOR_SEED.as_bytes()],
+        bump = spoke_metadata_collector.bump
+    )]
+    pub spoke_metadata_collector: Account<'info, SpokeMetadataCollector>,
+
+    #[account(mut, seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+
+    pub system_program: Program<'info, System>,
 }
 
-/**
- * Borrowed from https://github.com/wormhole-foundation/wormhole/blob/wen/solana-rewrite/solana/programs/core-bridge/src/processor/parse_and_verify_vaa/verify_encoded_vaa_v1.rs#L121
- * Also used here https://github.com/pyth-network/pyth-crosschain/blob/6771c2c6998f53effee9247347cb0ac71612b3dc/target_chains/solana/programs/pyth-solana-receiver/src/lib.rs#L432
- */
-fn verify_guardian_signature(
-    sig: &GuardianSetSig,
-    guardian_pubkey: &[u8; 20],
-    digest: &[u8],
-) -> Result<()> {
-    // Recover using `solana_program::secp256k1_recover`. Public key recovery costs 25k compute
-    // units. And hashing this public key to recover the Ethereum public key costs about 13k.
-    let recovered = {
-        // Recover EC public key (64 bytes).
-        let pubkey = secp256k1_recover(digest, sig.recovery_id(), &sig.rs())
-            .map_err(|_| QueriesSolanaVerifyError::InvalidSignature)?;
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(_bytes: Vec<u8>, proposal_id : [u8; 32], guardian_set_index: u32)]
+pub struct ExtendProposal<'info> {
+    /// Guardian set used for signature verification.
+    #[account(
+        seeds = [
+            WormholeGuardianSet::SEED_PREFIX,
+            guardian_set_index.to_be_bytes().as_ref()
+        ],
+        bump,
+        seeds::program = CORE_BRIDGE_PROGRAM_ID
+    )]
+    pub guardian_set: Account<'info, WormholeGuardianSet>,
 
-        // The Ethereum public key is the last 20 bytes of keccak hashed public key above.
-        let hashed = keccak::hash(&pubkey.to_bytes());
+    /// Stores unverified guardian signatures as they are too large to fit in the instruction data.
+    #[account(mut, has_one = refund_recipient, close = refund_recipient)]
+    pub guardian_signatures: Account<'info, GuardianSignatures>,
 
-        let mut eth_pubkey = [0; 20];
-        sol_memcpy(&mut eth_pubkey, &hashed.0[12..], 20);
+    /// CHECK: This account is the refund recipient for the above signature_set
+    #[account(mut, address = guardian_signatures.refund_recipient)]
+    pub refund_recipient: AccountInfo<'info>,
 
-        eth_pubkey
-    };
+    #[account(
+        mut,
+        seeds = [PROPOSAL_SEED.as_bytes(), &proposal_id],
+        bump
+    )]
+    pub proposal: Account<'info, proposal::ProposalData>,
 
-    // The recovered public key should agree with the Guardian's public key at this index.
-    require!(
-        recovered == *guardian_pubkey,
-        QueriesSolanaVerifyError::InvalidGuardianKeyRecovery
-    );
+    #[account(
+        mut,
+        seeds = [SPOKE_METADATA_COLLECTOR_SEED.as_bytes()],
+        bump = spoke_metadata_collector.bump
+    )]
+    pub spoke_metadata_collector: Account<'info, SpokeMetadataCollector>,
 
-    // Done.
-    Ok(())
+    #[account(mut, seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
 }
 
 #[derive(Accounts)]
@@ -479,13 +695,37 @@ pub struct ClaimVestingAdmin<'info> {
 }
 
 #[derive(Accounts)]
-pub struct CreateStakeAccount<'info> {
-    // Native payer:
-    #[account(mut)]
-    pub payer: Signer<'info>,
+pub struct UpdateWithdrawalCooldown<'info> {
+    #[account(address = config.governance_authority)]
+    pub governance_signer: Signer<'info>,
+    #[account(mut, seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Account<'info, global_config::GlobalConfig>,
+}
 
-    // Stake program accounts:
-    #[account(
+#[derive(Accounts)]
+pub struct UpdateVoteRevisionCutoff<'info> {
+    #[account(address = config.governance_authority)]
+    pub governance_signer: Signer<'info>,
+    #[account(mut, seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Account<'info, global_config::GlobalConfig>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDelegationPolicy<'info> {
+    #[account(address = config.governance_authority)]
+    pub governance_signer: Signer<'info>,
+    #[account(mut, seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Account<'info, global_config::GlobalConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CreateStakeAccount<'info> {
+    // Native payer:
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    // Stake program accounts:
+    #[account(
         init,
         seeds = [CHECKPOINT_DATA_SEED.as_bytes(), payer.key().as_ref(), 0u16.to_le_bytes().as_ref()],
         bump,
@@ -554,6 +794,299 @@ pub struct CreateCheckpoints<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct GetVotes<'info> {
+    #[account(
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), owner.as_ref()],
+        bump = stake_account_metadata.metadata_bump
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+
+    #[account(
+        seeds = [
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            owner.as_ref(),
+            stake_account_metadata.stake_account_checkpoints_last_index.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub stake_account_checkpoints: AccountLoader<'info, checkpoints::CheckpointData>,
+}
+
+/// The consecutive CheckpointData accounts to search are passed as remaining accounts
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct GetPastVotes<'info> {
+    #[account(
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), owner.as_ref()],
+        bump = stake_account_metadata.metadata_bump
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeStakeOwner<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        has_one = owner,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), owner.key().as_ref()],
+        bump = stake_account_metadata.metadata_bump
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = stake_account::PendingStakeOwner::LEN,
+        seeds = [PENDING_STAKE_OWNER_SEED.as_bytes(), owner.key().as_ref()],
+        bump
+    )]
+    pub pending_stake_owner: Box<Account<'info, stake_account::PendingStakeOwner>>,
+    /// CHECK: This account will be the signer in the [accept_stake_owner] instruction.
+    #[account(constraint = new_owner.key() != owner.key() @ ErrorCode::InvalidPendingStakeOwner)]
+    pub new_owner: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AcceptStakeOwner<'info> {
+    // Native payer
+    #[account(mut)]
+    pub new_owner: Signer<'info>,
+    /// CHECK: Current owner of the stake account, refunded the rent of pending_stake_owner
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        has_one = owner,
+        close = owner,
+        seeds = [PENDING_STAKE_OWNER_SEED.as_bytes(), owner.key().as_ref()],
+        bump = pending_stake_owner.bump,
+        constraint = pending_stake_owner.pending_owner == new_owner.key()
+            @ ErrorCode::InvalidPendingStakeOwner
+    )]
+    pub pending_stake_owner: Box<Account<'info, stake_account::PendingStakeOwner>>,
+
+    // Current stake account
+    #[account(
+        mut,
+        seeds = [
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            owner.key().as_ref(),
+            stake_account_metadata.stake_account_checkpoints_last_index.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub stake_account_checkpoints: AccountLoader<'info, checkpoints::CheckpointData>,
+    /// CHECK: Next CheckpointData PDA of the current owner, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), owner.key().as_ref()],
+        bump = stake_account_metadata.metadata_bump
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    /// CHECK: DelegationSplit PDA of the current owner, it is only deserialized when it is initialized
+    #[account(seeds = [DELEGATION_SPLIT_SEED.as_bytes(), owner.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
+    /// CHECK : This AccountInfo is safe because it's a checked PDA
+    #[account(
+        seeds = [AUTHORITY_SEED.as_bytes(), owner.key().as_ref()],
+        bump = stake_account_metadata.authority_bump
+    )]
+    pub custody_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [CUSTODY_SEED.as_bytes(), owner.key().as_ref()],
+        bump = stake_account_metadata.custody_bump
+    )]
+    pub stake_account_custody: Box<Account<'info, TokenAccount>>,
+
+    // New stake account
+    #[account(
+        init,
+        payer = new_owner,
+        space = checkpoints::CheckpointData::LEN,
+        seeds = [CHECKPOINT_DATA_SEED.as_bytes(), new_owner.key().as_ref(), 0u16.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub new_stake_account_checkpoints: AccountLoader<'info, checkpoints::CheckpointData>,
+    #[account(
+        init,
+        payer = new_owner,
+        space = stake_account::StakeAccountMetadata::LEN,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), new_owner.key().as_ref()],
+        bump
+    )]
+    pub new_stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    /// CHECK : This AccountInfo is safe because it's a checked PDA
+    #[account(seeds = [AUTHORITY_SEED.as_bytes(), new_owner.key().as_ref()], bump)]
+    pub new_custody_authority: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = new_owner,
+        seeds = [CUSTODY_SEED.as_bytes(), new_owner.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = new_custody_authority,
+    )]
+    pub new_stake_account_custody: Box<Account<'info, TokenAccount>>,
+
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+    // Wormhole token mint:
+    #[account(address = config.voting_token_mint)]
+    pub mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'a, 'b, 'c, 'info> From<&AcceptStakeOwner<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, Transfer<'info>>
+{
+    fn from(accounts: &AcceptStakeOwner<'info>) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: accounts.stake_account_custody.to_account_info(),
+            to: accounts.new_stake_account_custody.to_account_info(),
+            authority: accounts.custody_authority.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct CloseStakeAccount<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// Latest CheckpointData account, its last checkpoint holds the voting power of the owner.
+    /// The previous CheckpointData accounts are passed as remaining accounts
+    #[account(
+        mut,
+        has_one = owner,
+        close = owner,
+        seeds = [
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            owner.key().as_ref(),
+            stake_account_metadata.stake_account_checkpoints_last_index.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub stake_account_checkpoints: AccountLoader<'info, checkpoints::CheckpointData>,
+    #[account(
+        mut,
+        has_one = owner,
+        close = owner,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), owner.key().as_ref()],
+        bump = stake_account_metadata.metadata_bump,
+        constraint = stake_account_metadata.recorded_balance == 0
+            && stake_account_metadata.recorded_vesting_balance == 0
+            @ ErrorCode::StakeAccountNotEmpty,
+        constraint = stake_account_metadata.delegator_count == 0
+            @ ErrorCode::StakeAccountHasDelegators,
+        constraint = stake_account_metadata.is_self_delegated() @ ErrorCode::StakeAccountDelegated
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    /// CHECK: DelegationSplit PDA of the owner, it is closed if it was ever initialized
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), owner.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
+    /// CHECK: PendingStakeOwner PDA of the owner, it is closed if a new owner was proposed
+    #[account(mut, seeds = [PENDING_STAKE_OWNER_SEED.as_bytes(), owner.key().as_ref()], bump)]
+    pub pending_stake_owner: UncheckedAccount<'info>,
+    /// CHECK: PendingWithdrawal PDA of the owner, the withdrawal has to be completed first
+    #[account(
+        seeds = [PENDING_WITHDRAWAL_SEED.as_bytes(), owner.key().as_ref()],
+        bump,
+        constraint = *pending_withdrawal.owner != crate::ID @ ErrorCode::PendingWithdrawalOutstanding
+    )]
+    pub pending_withdrawal: UncheckedAccount<'info>,
+    /// CHECK : This AccountInfo is safe because it's a checked PDA
+    #[account(
+        seeds = [AUTHORITY_SEED.as_bytes(), owner.key().as_ref()],
+        bump = stake_account_metadata.authority_bump
+    )]
+    pub custody_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [CUSTODY_SEED.as_bytes(), owner.key().as_ref()],
+        bump = stake_account_metadata.custody_bump,
+        constraint = stake_account_custody.amount == 0 @ ErrorCode::StakeAccountNotEmpty
+    )]
+    pub stake_account_custody: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [VOTE_WEIGHT_WINDOW_LENGTHS_SEED.as_bytes()],
+        bump
+    )]
+    pub vote_weight_window_lengths: AccountLoader<'info, VoteWeightWindowLengths>,
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'a, 'b, 'c, 'info> From<&CloseStakeAccount<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, CloseAccount<'info>>
+{
+    fn from(
+        accounts: &CloseStakeAccount<'info>,
+    ) -> CpiContext<'a, 'b, 'c, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: accounts.stake_account_custody.to_account_info(),
+            destination: accounts.owner.to_account_info(),
+            authority: accounts.custody_authority.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(stake_account_checkpoints_index: u16)]
+pub struct CompactCheckpoints<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// Retired CheckpointData account that is closed and refunded to the owner
+    #[account(
+        mut,
+        has_one = owner,
+        close = owner,
+        seeds = [CHECKPOINT_DATA_SEED.as_bytes(), owner.key().as_ref(), stake_account_checkpoints_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub stake_account_checkpoints: AccountLoader<'info, checkpoints::CheckpointData>,
+
+    /// CheckpointData account that starts with a copy of the latest checkpoint of the closed account
+    #[account(
+        seeds = [CHECKPOINT_DATA_SEED.as_bytes(), owner.key().as_ref(), (stake_account_checkpoints_index + 1).to_le_bytes().as_ref()],
+        bump
+    )]
+    pub next_stake_account_checkpoints: AccountLoader<'info, checkpoints::CheckpointData>,
+
+    #[account(
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), owner.key().as_ref()],
+        bump = stake_account_metadata.metadata_bump,
+        constraint = stake_account_checkpoints_index < stake_account_metadata.stake_account_checkpoints_last_index
+            @ ErrorCode::CheckpointDataAccountNotRetired
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+
+    #[account(
+        seeds = [VOTE_WEIGHT_WINDOW_LENGTHS_SEED.as_bytes()],
+        bump
+    )]
+    pub vote_weight_window_lengths: AccountLoader<'info, VoteWeightWindowLengths>,
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+}
+
 #[event_cpi]
 #[derive(Accounts)]
 #[instruction(amount: u64, _current_delegate_stake_account_metadata_owner: Pubkey, _stake_account_metadata_owner: Pubkey
@@ -575,6 +1108,10 @@ pub struct WithdrawTokens<'info> {
     )]
     pub current_delegate_stake_account_checkpoints:
         AccountLoader<'info, checkpoints::CheckpointData>,
+    /// CHECK: Next CheckpointData PDA of the current delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub current_delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
     #[account(
         mut,
         seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), _current_delegate_stake_account_metadata_owner.as_ref()],
@@ -595,6 +1132,10 @@ pub struct WithdrawTokens<'info> {
             @ ErrorCode::InvalidCurrentDelegate
     )]
     pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    /// CHECK: DelegationSplit PDA of the user, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), payer.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
     #[account(
         mut,
         seeds = [CUSTODY_SEED.as_bytes(), payer.key().as_ref()],
@@ -626,6 +1167,394 @@ impl<'a, 'b, 'c, 'info> From<&WithdrawTokens<'info>>
     }
 }
 
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(amount: u64, _current_delegate_stake_account_metadata_owner: Pubkey, _stake_account_metadata_owner: Pubkey
+)]
+pub struct RequestWithdrawal<'info> {
+    // Native payer:
+    #[account(mut, address = stake_account_metadata.owner)]
+    pub payer: Signer<'info>,
+
+    // Current delegate stake account:
+    #[account(
+        mut,
+        seeds = [
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            stake_account_metadata.delegate.as_ref(),
+            current_delegate_stake_account_metadata.stake_account_checkpoints_last_index.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub current_delegate_stake_account_checkpoints:
+        AccountLoader<'info, checkpoints::CheckpointData>,
+    /// CHECK: Next CheckpointData PDA of the current delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub current_delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), _current_delegate_stake_account_metadata_owner.as_ref()],
+        bump = current_delegate_stake_account_metadata.metadata_bump
+    )]
+    pub current_delegate_stake_account_metadata:
+        Box<Account<'info, stake_account::StakeAccountMetadata>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = pending_withdrawal::PendingWithdrawal::LEN,
+        seeds = [PENDING_WITHDRAWAL_SEED.as_bytes(), payer.key().as_ref()],
+        bump
+    )]
+    pub pending_withdrawal: Account<'info, pending_withdrawal::PendingWithdrawal>,
+    // Tokens of the pending withdrawal, held until the withdrawal cooldown elapses
+    #[account(
+        init_if_needed,
+        payer = payer,
+        token::mint = mint,
+        token::authority = custody_authority,
+        seeds = [WITHDRAWAL_CUSTODY_SEED.as_bytes(), payer.key().as_ref()],
+        bump,
+    )]
+    pub withdrawal_custody: Account<'info, TokenAccount>,
+    // Stake program accounts:
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), _stake_account_metadata_owner.as_ref()],
+        bump = stake_account_metadata.metadata_bump,
+        constraint = stake_account_metadata.delegate == _current_delegate_stake_account_metadata_owner
+            @ ErrorCode::InvalidCurrentDelegate
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    /// CHECK: DelegationSplit PDA of the user, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), payer.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [CUSTODY_SEED.as_bytes(), payer.key().as_ref()],
+        bump = stake_account_metadata.custody_bump,
+    )]
+    pub stake_account_custody: Account<'info, TokenAccount>,
+    /// CHECK : This AccountInfo is safe because it's a checked PDA
+    #[account(seeds = [AUTHORITY_SEED.as_bytes(), payer.key().as_ref()], bump = stake_account_metadata.authority_bump
+    )]
+    pub custody_authority: AccountInfo<'info>,
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Account<'info, global_config::GlobalConfig>,
+    #[account(address = config.voting_token_mint)]
+    pub mint: Account<'info, Mint>,
+    // Primitive accounts :
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'a, 'b, 'c, 'info> From<&RequestWithdrawal<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, Transfer<'info>>
+{
+    fn from(accounts: &RequestWithdrawal<'info>) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: accounts.stake_account_custody.to_account_info(),
+            to: accounts.withdrawal_custody.to_account_info(),
+            authority: accounts.custody_authority.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct CompleteWithdrawal<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        has_one = owner,
+        close = owner,
+        seeds = [PENDING_WITHDRAWAL_SEED.as_bytes(), owner.key().as_ref()],
+        bump = pending_withdrawal.bump
+    )]
+    pub pending_withdrawal: Account<'info, pending_withdrawal::PendingWithdrawal>,
+    #[account(
+        mut,
+        seeds = [WITHDRAWAL_CUSTODY_SEED.as_bytes(), owner.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_custody: Account<'info, TokenAccount>,
+    /// CHECK : This AccountInfo is safe because it's a checked PDA
+    #[account(seeds = [AUTHORITY_SEED.as_bytes(), owner.key().as_ref()], bump)]
+    pub custody_authority: AccountInfo<'info>,
+    // Destination
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'a, 'b, 'c, 'info> From<&CompleteWithdrawal<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, Transfer<'info>>
+{
+    fn from(
+        accounts: &CompleteWithdrawal<'info>,
+    ) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: accounts.withdrawal_custody.to_account_info(),
+            to: accounts.destination.to_account_info(),
+            authority: accounts.custody_authority.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+impl<'a, 'b, 'c, 'info> From<&CompleteWithdrawal<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, CloseAccount<'info>>
+{
+    fn from(
+        accounts: &CompleteWithdrawal<'info>,
+    ) -> CpiContext<'a, 'b, 'c, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: accounts.withdrawal_custody.to_account_info(),
+            destination: accounts.owner.to_account_info(),
+            authority: accounts.custody_authority.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(amount: u64, current_delegate_stake_account_owner: Pubkey)]
+pub struct DepositTokens<'info> {
+    // Native payer:
+    #[account(mut, address = stake_account_metadata.owner)]
+    pub payer: Signer<'info>,
+
+    // Current delegate stake account:
+    #[account(
+        mut,
+        seeds = [
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            stake_account_metadata.delegate.as_ref(),
+            current_delegate_stake_account_metadata.stake_account_checkpoints_last_index.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub current_delegate_stake_account_checkpoints:
+        AccountLoader<'info, checkpoints::CheckpointData>,
+    /// CHECK: Next CheckpointData PDA of the current delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub current_delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), current_delegate_stake_account_owner.as_ref()],
+        bump = current_delegate_stake_account_metadata.metadata_bump
+    )]
+    pub current_delegate_stake_account_metadata:
+        Box<Account<'info, stake_account::StakeAccountMetadata>>,
+
+    // Source
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = payer,
+    )]
+    pub source: Box<Account<'info, TokenAccount>>,
+    // Stake program accounts:
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), payer.key().as_ref()],
+        bump = stake_account_metadata.metadata_bump,
+        constraint = stake_account_metadata.delegate == current_delegate_stake_account_owner
+            @ ErrorCode::InvalidCurrentDelegate
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    /// CHECK: DelegationSplit PDA of the user, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), payer.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [CUSTODY_SEED.as_bytes(), payer.key().as_ref()],
+        bump = stake_account_metadata.custody_bump,
+    )]
+    pub stake_account_custody: Box<Account<'info, TokenAccount>>,
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+    // Wormhole token mint:
+    #[account(address = config.voting_token_mint)]
+    pub mint: Box<Account<'info, Mint>>,
+    // Primitive accounts :
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'a, 'b, 'c, 'info> From<&DepositTokens<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, Transfer<'info>>
+{
+    fn from(accounts: &DepositTokens<'info>) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: accounts.source.to_account_info(),
+            to: accounts.stake_account_custody.to_account_info(),
+            authority: accounts.payer.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(stake_account_owner: Pubkey, current_delegate_stake_account_owner: Pubkey)]
+pub struct SyncBalance<'info> {
+    // Anyone can sync a stake account, the payer covers the checkpoint account reallocations
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    // Current delegate stake account:
+    #[account(
+        mut,
+        seeds = [
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            stake_account_metadata.delegate.as_ref(),
+            current_delegate_stake_account_metadata.stake_account_checkpoints_last_index.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub current_delegate_stake_account_checkpoints:
+        AccountLoader<'info, checkpoints::CheckpointData>,
+    /// CHECK: Next CheckpointData PDA of the current delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub current_delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), current_delegate_stake_account_owner.as_ref()],
+        bump = current_delegate_stake_account_metadata.metadata_bump
+    )]
+    pub current_delegate_stake_account_metadata:
+        Box<Account<'info, stake_account::StakeAccountMetadata>>,
+
+    // Stake program accounts:
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), stake_account_owner.as_ref()],
+        bump = stake_account_metadata.metadata_bump,
+        constraint = stake_account_metadata.delegate == current_delegate_stake_account_owner
+            @ ErrorCode::InvalidCurrentDelegate
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    /// CHECK: DelegationSplit PDA of the stake account owner, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), stake_account_owner.as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
+    #[account(
+        seeds = [CUSTODY_SEED.as_bytes(), stake_account_owner.as_ref()],
+        bump = stake_account_metadata.custody_bump,
+    )]
+    pub stake_account_custody: Box<Account<'info, TokenAccount>>,
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(shares: Vec<delegation_split::DelegationShareArgs>, current_delegate_stake_account_owner: Pubkey)]
+pub struct SetDelegationSplit<'info> {
+    // Native payer
+    #[account(mut, address = stake_account_metadata.owner)]
+    pub payer: Signer<'info>,
+
+    // Current delegate stake account
+    #[account(
+        mut,
+        seeds = [
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            stake_account_metadata.delegate.as_ref(),
+            current_delegate_stake_account_metadata.stake_account_checkpoints_last_index.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub current_delegate_stake_account_checkpoints:
+        AccountLoader<'info, checkpoints::CheckpointData>,
+    /// CHECK: Next CheckpointData PDA of the current delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub current_delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), current_delegate_stake_account_owner.as_ref()],
+        bump = current_delegate_stake_account_metadata.metadata_bump
+    )]
+    pub current_delegate_stake_account_metadata:
+        Box<Account<'info, stake_account::StakeAccountMetadata>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = delegation_split::DelegationSplit::LEN,
+        seeds = [DELEGATION_SPLIT_SEED.as_bytes(), payer.key().as_ref()],
+        bump
+    )]
+    pub delegation_split: Box<Account<'info, delegation_split::DelegationSplit>>,
+
+    // User stake account
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), payer.key().as_ref()],
+        bump = stake_account_metadata.metadata_bump,
+        constraint = stake_account_metadata.delegate == current_delegate_stake_account_owner
+            @ ErrorCode::InvalidCurrentDelegate
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    #[account(
+        seeds = [CUSTODY_SEED.as_bytes(), payer.key().as_ref()],
+        bump = stake_account_metadata.custody_bump,
+    )]
+    pub stake_account_custody: Box<Account<'info, TokenAccount>>,
+
+    // Counts the stake accounts that start delegating through a delegation split
+    #[account(mut, seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateDelegationSplit<'info> {
+    // Native payer
+    #[account(mut, address = stake_account_metadata.owner)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [DELEGATION_SPLIT_SEED.as_bytes(), payer.key().as_ref()],
+        bump = delegation_split.bump,
+        constraint = delegation_split.is_active() @ ErrorCode::DelegationSplitNotActive
+    )]
+    pub delegation_split: Box<Account<'info, delegation_split::DelegationSplit>>,
+
+    // User stake account
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), payer.key().as_ref()],
+        bump = stake_account_metadata.metadata_bump
+    )]
+    pub stake_account_metadata: Box<Account<'info, stake_account::StakeAccountMetadata>>,
+    #[account(
+        seeds = [CUSTODY_SEED.as_bytes(), payer.key().as_ref()],
+        bump = stake_account_metadata.custody_bump,
+    )]
+    pub stake_account_custody: Box<Account<'info, TokenAccount>>,
+
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, global_config::GlobalConfig>>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct InitializeSpokeMessageExecutor<'info> {
     #[account(mut, address = config.governance_authority)]
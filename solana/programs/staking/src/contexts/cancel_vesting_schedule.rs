@@ -0,0 +1,71 @@
+use crate::context::{
+    CONFIG_SEED, VESTING_BALANCE_SEED, VESTING_CONFIG_SEED, VESTING_SCHEDULE_SEED,
+};
+use crate::error::VestingError;
+use crate::state::global_config::GlobalConfig;
+use crate::state::{VestingBalance, VestingConfig, VestingSchedule};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token};
+
+#[derive(Accounts)]
+#[instruction(vester: Pubkey)]
+pub struct CancelVestingSchedule<'info> {
+    #[account(
+        mut,
+        constraint = global_config.vesting_admin == admin.key()
+            @ VestingError::InvalidVestingAdmin
+    )]
+    admin: Signer<'info>,
+    mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = !config.finalized @ VestingError::VestingFinalized, // A vesting schedule cannot be cancelled after vest is finalized
+        has_one = mint, // Arbitrary check as mint is baked into the PDA
+        seeds = [VESTING_CONFIG_SEED.as_bytes(), mint.key().as_ref(), config.seed.to_le_bytes().as_ref()],
+        bump = config.bump
+    )]
+    config: Account<'info, VestingConfig>,
+    #[account(
+        mut,
+        close = admin,
+        constraint = vesting_schedule.vester == vester @ VestingError::InvalidVester,
+        has_one = config, // This check is arbitrary, as config is baked into the PDA
+        seeds = [VESTING_SCHEDULE_SEED.as_bytes(), config.key().as_ref(), vester.as_ref(), vesting_schedule.start.to_le_bytes().as_ref()],
+        bump = vesting_schedule.bump
+    )]
+    vesting_schedule: Account<'info, VestingSchedule>,
+    #[account(
+        mut,
+        seeds = [VESTING_BALANCE_SEED.as_bytes(), config.key().as_ref(), vester.as_ref()],
+        bump = vesting_balance.bump
+    )]
+    vesting_balance: Account<'info, VestingBalance>,
+    #[account(
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump = global_config.bump,
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+    token_program: Program<'info, Token>,
+    system_program: Program<'info, System>,
+}
+
+impl<'info> CancelVestingSchedule<'info> {
+    pub fn cancel_vesting_schedule(&mut self) -> Result<()> {
+        // Nothing can be claimed before the config is finalized
+        let amount = self.vesting_schedule.remaining_amount();
+
+        self.config.vested = self
+            .config
+            .vested
+            .checked_sub(amount)
+            .ok_or(VestingError::Underflow)?;
+
+        self.vesting_balance.total_vesting_balance = self
+            .vesting_balance
+            .total_vesting_balance
+            .checked_sub(amount)
+            .ok_or(VestingError::Underflow)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use crate::context::{
+    CONFIG_SEED, DELEGATION_SPLIT_SEED, VESTING_BALANCE_SEED, VESTING_CONFIG_SEED,
+    VESTING_MERKLE_CLAIM_BITMAP_SEED, VESTING_MERKLE_DISTRIBUTOR_SEED, VEST_SEED,
+};
+use crate::contexts::{CloseVestingEvents, VestingVotes};
+use crate::state::checkpoints::{CheckpointData, Operation};
+use crate::state::global_config::GlobalConfig;
+use crate::state::stake_account::StakeAccountMetadata;
+use crate::{
+    error::VestingError,
+    state::{
+        Vesting, VestingBalance, VestingConfig, VestingMerkleClaimBitmap, VestingMerkleDistributor,
+    },
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::Mint;
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(_index: u32, _amount: u64, maturation: i64)]
+pub struct ClaimVestFromMerkle<'info> {
+    #[account(mut)]
+    vester: Signer<'info>,
+    mint: Account<'info, Mint>,
+    #[account(
+        constraint = config.finalized @ VestingError::VestingUnfinalized,
+        seeds = [VESTING_CONFIG_SEED.as_bytes(), mint.key().as_ref(), config.seed.to_le_bytes().as_ref()],
+        bump = config.bump
+    )]
+    config: Box<Account<'info, VestingConfig>>,
+    #[account(
+        mut,
+        constraint = Clock::get()?.unix_timestamp < distributor.claim_deadline
+            @ VestingError::MerkleClaimDeadlinePassed,
+        has_one = config, // This check is arbitrary, as config is baked into the PDA
+        seeds = [VESTING_MERKLE_DISTRIBUTOR_SEED.as_bytes(), config.key().as_ref(), distributor.index.to_le_bytes().as_ref()],
+        bump = distributor.bump
+    )]
+    distributor: Box<Account<'info, VestingMerkleDistributor>>,
+    #[account(
+        mut,
+        has_one = distributor, // This check is arbitrary, as distributor is baked into the PDA
+        seeds = [VESTING_MERKLE_CLAIM_BITMAP_SEED.as_bytes(), distributor.key().as_ref()],
+        bump
+    )]
+    claim_bitmap: Box<Account<'info, VestingMerkleClaimBitmap>>,
+    #[account(
+        init_if_needed,
+        payer = vester,
+        space = Vesting::LEN,
+        seeds = [VEST_SEED.as_bytes(), config.key().as_ref(), vester.key().as_ref(), maturation.to_le_bytes().as_ref()],
+        bump
+    )]
+    vest: Box<Account<'info, Vesting>>,
+    #[account(
+        init_if_needed,
+        payer = vester,
+        space = VestingBalance::LEN,
+        seeds = [VESTING_BALANCE_SEED.as_bytes(), config.key().as_ref(), vester.key().as_ref()],
+        bump
+    )]
+    vesting_balance: Box<Account<'info, VestingBalance>>,
+    /// CheckpointData and StakeAccountMetadata accounts are optional because
+    /// in order to be able to claim vests to a vesting balance that has not been delegated
+    #[account(mut)]
+    pub delegate_stake_account_checkpoints: Option<AccountLoader<'info, CheckpointData>>,
+    /// CHECK: Next CheckpointData PDA of the delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub delegate_stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
+    #[account(mut)]
+    pub stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
+    /// CHECK: DelegationSplit PDA of the vester, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    /// instead of the delegate accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), vester.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
+    #[account(
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump = global_config.bump,
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+    system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimVestFromMerkle<'info> {
+    pub fn claim_vest_from_merkle(
+        &mut self,
+        index: u32,
+        amount: u64,
+        maturation: i64,
+        proof: Vec<[u8; 32]>,
+        vest_bump: u8,
+        vesting_balance_bump: u8,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<Option<CloseVestingEvents>> {
+        require!(
+            index < self.distributor.num_leaves,
+            VestingError::InvalidMerkleLeafIndex
+        );
+
+        let leaf =
+            VestingMerkleDistributor::leaf_hash(index, &self.vester.key(), amount, maturation);
+        require!(
+            self.distributor.verify_proof(leaf, &proof),
+            VestingError::InvalidMerkleProof
+        );
+
+        self.claim_bitmap.set_claimed(index)?;
+
+        self.distributor.claimed_amount = self
+            .distributor
+            .claimed_amount
+            .checked_add(amount)
+            .ok_or(VestingError::Overflow)?;
+        // The leaves of the root cannot vest more than was added to the config
+        require!(
+            self.distributor.claimed_amount <= self.distributor.total_amount,
+            VestingError::InvalidMerkleDistributor
+        );
+
+        // The vested amount of the config already includes the leaves, only the votes of a
+        // vesting balance that was delegated before the claim have to be added
+        let close_vesting_events = VestingVotes {
+            vester: self.vester.to_account_info(),
+            vesting_balance: &self.vesting_balance,
+            delegate_stake_account_checkpoints: self.delegate_stake_account_checkpoints.as_mut(),
+            delegate_stake_account_checkpoints_next: self
+                .delegate_stake_account_checkpoints_next
+                .as_deref(),
+            delegate_stake_account_metadata: self.delegate_stake_account_metadata.as_deref_mut(),
+            stake_account_metadata: self.stake_account_metadata.as_deref_mut(),
+            delegation_split: self.delegation_split.to_account_info(),
+            global_config: &self.global_config,
+            system_program: self.system_program.to_account_info(),
+        }
+        .update(amount, Operation::Add, remaining_accounts)?;
+
+        self.vest.set_inner(Vesting {
+            vester: self.vester.key(),
+            config: self.config.key(),
+            amount: self
+                .vest
+                .amount
+                .checked_add(amount)
+                .ok_or(VestingError::Overflow)?,
+            maturation,
+            bump: vest_bump,
+        });
+
+        self.vesting_balance.set_inner(VestingBalance {
+            vester: self.vester.key(),
+            stake_account_metadata: self.vesting_balance.stake_account_metadata,
+            total_vesting_balance: self
+                .vesting_balance
+                .total_vesting_balance
+                .checked_add(amount)
+                .ok_or(VestingError::Overflow)?,
+            bump: vesting_balance_bump,
+            rent_payer: if self.vesting_balance.rent_payer == Pubkey::default() {
+                self.vester.key()
+            } else {
+                self.vesting_balance.rent_payer
+            },
+        });
+
+        Ok(close_vesting_events)
+    }
+}
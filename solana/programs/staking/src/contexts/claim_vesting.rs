@@ -1,5 +1,11 @@
-use crate::context::{CONFIG_SEED, VESTING_BALANCE_SEED, VESTING_CONFIG_SEED, VEST_SEED};
-use crate::state::checkpoints::{push_checkpoint, CheckpointData, DelegateVotesChanged, Operation};
+use crate::context::{
+    CONFIG_SEED, DELEGATION_SPLIT_SEED, VESTING_BALANCE_SEED, VESTING_CONFIG_SEED, VEST_SEED,
+};
+use crate::state::checkpoints::{
+    push_checkpoint, CheckpointData, CheckpointsRollover, DelegateVotesChanged, Operation,
+};
+use crate::state::delegation_chain::push_delegation_chain_votes;
+use crate::state::delegation_split::push_delegation_split_votes;
 use crate::state::global_config::GlobalConfig;
 use crate::state::stake_account::{RecordedVestingBalanceChanged, StakeAccountMetadata};
 use crate::{
@@ -59,10 +65,19 @@ pub struct ClaimVesting<'info> {
     /// in order to be able to claim vests that have not been delegated
     #[account(mut)]
     pub delegate_stake_account_checkpoints: Option<AccountLoader<'info, CheckpointData>>,
+    /// CHECK: Next CheckpointData PDA of the delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
     #[account(mut)]
     pub delegate_stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
     #[account(mut)]
     pub stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
+    /// CHECK: DelegationSplit PDA of the vester, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    /// instead of the delegate accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), vester.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
     #[account(
         seeds = [CONFIG_SEED.as_bytes()],
         bump = global_config.bump,
@@ -82,105 +97,87 @@ pub struct ClaimVesting<'info> {
 #[derive(AnchorDeserialize, AnchorSerialize)]
 pub struct CloseVestingEvents {
     pub recorded_vesting_balance_changed: RecordedVestingBalanceChanged,
-    pub delegate_votes_changed: DelegateVotesChanged,
+    pub delegate_votes_changed: Vec<DelegateVotesChanged>,
 }
 
 impl<'info> ClaimVesting<'info> {
-    pub fn close_vesting(&mut self) -> Result<Option<CloseVestingEvents>> {
-        let mut close_vesting_events = None;
-        // If vesting_balance.stake_account_metadata is not set it means that vester has not
-        // delegated his vests
-        if self.vesting_balance.stake_account_metadata != Pubkey::default() {
-            if let (
-                Some(stake_account_metadata),
-                Some(delegate_stake_account_metadata),
-                Some(delegate_stake_account_checkpoints),
-            ) = (
-                &mut self.stake_account_metadata,
-                &mut self.delegate_stake_account_metadata,
-                &mut self.delegate_stake_account_checkpoints,
-            ) {
-                // Check if stake account checkpoints is out of bounds
-                let loaded_checkpoints = delegate_stake_account_checkpoints.load()?;
-                require!(
-                    loaded_checkpoints.next_index
-                        < self.global_config.max_checkpoints_account_limit.into(),
-                    ErrorCode::TooManyCheckpoints,
-                );
-
-                // Verify that the actual delegate_stake_account_checkpoints address matches the expected one
-                require!(
-                    stake_account_metadata.delegate.key() == loaded_checkpoints.owner,
-                    VestingError::InvalidStakeAccountCheckpoints
-                );
-                drop(loaded_checkpoints);
-
-                // Additional checks to ensure the owner matches
-                require!(
-                    stake_account_metadata.owner == self.vesting_balance.vester,
-                    VestingError::InvalidStakeAccountOwner
-                );
-
-                // Verify that the actual delegate_stake_account_metadata address matches the expected one
-                require!(
-                    stake_account_metadata.delegate == delegate_stake_account_metadata.owner,
-                    VestingError::InvalidStakeAccountOwner
-                );
-
-                let new_recorded_vesting_balance = stake_account_metadata
-                    .recorded_vesting_balance
-                    .checked_sub(self.vest.amount)
-                    .ok_or(VestingError::Underflow)?;
-
-                // Update the recorded vesting balance
-                let recorded_vesting_balance_changed = stake_account_metadata
-                    .update_recorded_vesting_balance(new_recorded_vesting_balance);
+    pub fn close_vesting(
+        &mut self,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<Option<CloseVestingEvents>> {
+        let amount = self.vest.amount;
 
-                // Update checkpoints
-                let current_delegate_checkpoints_account_info =
-                    delegate_stake_account_checkpoints.to_account_info();
+        VestingRelease {
+            vester: self.vester.to_account_info(),
+            mint: &self.mint,
+            vault: self.vault.to_account_info(),
+            destination: self.vester_ta.to_account_info(),
+            config: &mut self.config,
+            vesting_balance: &mut self.vesting_balance,
+            delegate_stake_account_checkpoints: self.delegate_stake_account_checkpoints.as_mut(),
+            delegate_stake_account_checkpoints_next: self
+                .delegate_stake_account_checkpoints_next
+                .as_deref(),
+            delegate_stake_account_metadata: self.delegate_stake_account_metadata.as_deref_mut(),
+            stake_account_metadata: self.stake_account_metadata.as_deref_mut(),
+            delegation_split: self.delegation_split.to_account_info(),
+            global_config: &self.global_config,
+            token_program: self.token_program.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+        }
+        .release(amount, remaining_accounts)
+    }
+}
 
-                let current_timestamp: u64 = Clock::get()?.unix_timestamp.try_into()?;
+/// Accounts updated when vested tokens leave the vault, shared by the instructions claiming
+/// vested tokens: the vested totals and the votes of the delegates of the vester are lowered
+/// by the released amount, which is transferred from the vault to the destination
+pub struct VestingRelease<'a, 'info> {
+    pub vester: AccountInfo<'info>,
+    pub mint: &'a Account<'info, Mint>,
+    pub vault: AccountInfo<'info>,
+    pub destination: AccountInfo<'info>,
+    pub config: &'a mut Account<'info, VestingConfig>,
+    pub vesting_balance: &'a mut Account<'info, VestingBalance>,
+    pub delegate_stake_account_checkpoints: Option<&'a mut AccountLoader<'info, CheckpointData>>,
+    pub delegate_stake_account_checkpoints_next: Option<&'a AccountInfo<'info>>,
+    pub delegate_stake_account_metadata: Option<&'a mut Account<'info, StakeAccountMetadata>>,
+    pub stake_account_metadata: Option<&'a mut Account<'info, StakeAccountMetadata>>,
+    pub delegation_split: AccountInfo<'info>,
+    pub global_config: &'a GlobalConfig,
+    pub token_program: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+}
 
-                let delegate_votes_changed = push_checkpoint(
-                    delegate_stake_account_checkpoints,
-                    &current_delegate_checkpoints_account_info,
-                    self.vest.amount,
-                    Operation::Subtract,
-                    current_timestamp,
-                    &self.vester.to_account_info(),
-                    &self.system_program.to_account_info(),
-                )?;
-
-                let loaded_checkpoints = delegate_stake_account_checkpoints.load()?;
-                if loaded_checkpoints.next_index
-                    >= self.global_config.max_checkpoints_account_limit.into()
-                {
-                    if delegate_stake_account_metadata.key() == stake_account_metadata.key() {
-                        stake_account_metadata.stake_account_checkpoints_last_index += 1;
-                    } else {
-                        delegate_stake_account_metadata.stake_account_checkpoints_last_index += 1;
-                    }
-                }
-
-                close_vesting_events = Some(CloseVestingEvents {
-                    recorded_vesting_balance_changed,
-                    delegate_votes_changed,
-                })
-            } else {
-                return err!(VestingError::ErrorOfAccountParsing);
-            }
+impl<'a, 'info> VestingRelease<'a, 'info> {
+    pub fn release(
+        self,
+        amount: u64,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<Option<CloseVestingEvents>> {
+        let close_vesting_events = VestingVotes {
+            vester: self.vester,
+            vesting_balance: self.vesting_balance,
+            delegate_stake_account_checkpoints: self.delegate_stake_account_checkpoints,
+            delegate_stake_account_checkpoints_next: self.delegate_stake_account_checkpoints_next,
+            delegate_stake_account_metadata: self.delegate_stake_account_metadata,
+            stake_account_metadata: self.stake_account_metadata,
+            delegation_split: self.delegation_split,
+            global_config: self.global_config,
+            system_program: self.system_program,
         }
+        .update(amount, Operation::Subtract, remaining_accounts)?;
+
         self.config.vested = self
             .config
             .vested
-            .checked_sub(self.vest.amount)
+            .checked_sub(amount)
             .ok_or(VestingError::Underflow)?;
 
         self.vesting_balance.total_vesting_balance = self
             .vesting_balance
             .total_vesting_balance
-            .checked_sub(self.vest.amount)
+            .checked_sub(amount)
             .ok_or(VestingError::Underflow)?;
 
         // Binding to solve for lifetime issues
@@ -198,15 +195,213 @@ impl<'info> ClaimVesting<'info> {
             self.token_program.to_account_info(),
             TransferChecked {
                 from: self.vault.to_account_info(),
-                to: self.vester_ta.to_account_info(),
+                to: self.destination.to_account_info(),
                 mint: self.mint.to_account_info(),
                 authority: self.config.to_account_info(),
             },
             &signer_seeds,
         );
 
-        transfer_checked(ctx, self.vest.amount, self.mint.decimals)?;
+        transfer_checked(ctx, amount, self.mint.decimals)?;
 
         Ok(close_vesting_events)
     }
 }
+
+/// Accounts holding the votes of the vests of a vester once their vesting balance is linked
+/// to a stake account, updated when the vested total of the vester changes
+pub struct VestingVotes<'a, 'info> {
+    pub vester: AccountInfo<'info>,
+    pub vesting_balance: &'a Account<'info, VestingBalance>,
+    pub delegate_stake_account_checkpoints: Option<&'a mut AccountLoader<'info, CheckpointData>>,
+    pub delegate_stake_account_checkpoints_next: Option<&'a AccountInfo<'info>>,
+    pub delegate_stake_account_metadata: Option<&'a mut Account<'info, StakeAccountMetadata>>,
+    pub stake_account_metadata: Option<&'a mut Account<'info, StakeAccountMetadata>>,
+    pub delegation_split: AccountInfo<'info>,
+    pub global_config: &'a GlobalConfig,
+    pub system_program: AccountInfo<'info>,
+}
+
+impl<'a, 'info> VestingVotes<'a, 'info> {
+    pub fn update(
+        mut self,
+        amount: u64,
+        operation: Operation,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<Option<CloseVestingEvents>> {
+        // If vesting_balance.stake_account_metadata is not set it means that vester has not
+        // delegated his vests
+        if self.vesting_balance.stake_account_metadata == Pubkey::default() {
+            return Ok(None);
+        }
+
+        let apply = |balance: u64| match operation {
+            Operation::Add => balance.checked_add(amount).ok_or(VestingError::Overflow),
+            Operation::Subtract => balance.checked_sub(amount).ok_or(VestingError::Underflow),
+        };
+
+        // A stake account moved to another owner with accept_stake_owner keeps the vests
+        // of the vester, its delegation split is passed as the first remaining account
+        let stake_account_owner = self
+            .stake_account_metadata
+            .as_ref()
+            .map(|stake_account_metadata| stake_account_metadata.owner);
+        let (delegation_split_account_info, remaining_accounts) = match stake_account_owner {
+            Some(owner) if owner != self.vesting_balance.vester => {
+                let (delegation_split_account_info, remaining_accounts) = remaining_accounts
+                    .split_first()
+                    .ok_or(ErrorCode::InvalidDelegationSplitAccounts)?;
+                let (expected_delegation_split_pda, _) = Pubkey::find_program_address(
+                    &[DELEGATION_SPLIT_SEED.as_bytes(), owner.as_ref()],
+                    &crate::ID,
+                );
+                require!(
+                    delegation_split_account_info.key() == expected_delegation_split_pda,
+                    ErrorCode::InvalidDelegationSplitAccounts
+                );
+                (delegation_split_account_info.clone(), remaining_accounts)
+            }
+            _ => (self.delegation_split.to_account_info(), remaining_accounts),
+        };
+
+        if let Some(stake_account_metadata) = self
+            .stake_account_metadata
+            .as_mut()
+            .filter(|stake_account_metadata| stake_account_metadata.has_delegation_split)
+        {
+            // Additional checks to ensure the vests are linked to the stake account
+            require!(
+                stake_account_metadata.key() == self.vesting_balance.stake_account_metadata,
+                VestingError::InvalidStakeAccountOwner
+            );
+
+            let new_recorded_vesting_balance =
+                apply(stake_account_metadata.recorded_vesting_balance)?;
+
+            // Update the recorded vesting balance
+            let recorded_vesting_balance_changed = stake_account_metadata
+                .update_recorded_vesting_balance(new_recorded_vesting_balance);
+
+            let current_timestamp: u64 = Clock::get()?.unix_timestamp.try_into()?;
+
+            // The votes are split between the split delegates
+            let delegate_votes_changed = push_delegation_split_votes(
+                &delegation_split_account_info,
+                |total_votes| Ok(apply(total_votes)?),
+                remaining_accounts,
+                &mut ***stake_account_metadata,
+                &self.vester.to_account_info(),
+                &self.system_program.to_account_info(),
+                current_timestamp,
+                self.global_config.max_checkpoints_account_limit,
+            )?;
+
+            Ok(Some(CloseVestingEvents {
+                recorded_vesting_balance_changed,
+                delegate_votes_changed,
+            }))
+        } else if let (
+            Some(stake_account_metadata),
+            Some(delegate_stake_account_metadata),
+            Some(delegate_stake_account_checkpoints),
+        ) = (
+            &mut self.stake_account_metadata,
+            &mut self.delegate_stake_account_metadata,
+            &mut self.delegate_stake_account_checkpoints,
+        ) {
+            // Check if stake account checkpoints is out of bounds
+            let loaded_checkpoints = delegate_stake_account_checkpoints.load()?;
+            require!(
+                loaded_checkpoints.next_index
+                    < self.global_config.max_checkpoints_account_limit.into(),
+                ErrorCode::TooManyCheckpoints,
+            );
+
+            // Verify that the actual delegate_stake_account_checkpoints address matches the expected one
+            require!(
+                stake_account_metadata.delegate.key() == loaded_checkpoints.owner,
+                VestingError::InvalidStakeAccountCheckpoints
+            );
+            drop(loaded_checkpoints);
+
+            // Additional checks to ensure the vests are linked to the stake account
+            require!(
+                stake_account_metadata.key() == self.vesting_balance.stake_account_metadata,
+                VestingError::InvalidStakeAccountOwner
+            );
+
+            // Verify that the actual delegate_stake_account_metadata address matches the expected one
+            require!(
+                stake_account_metadata.delegate == delegate_stake_account_metadata.owner,
+                VestingError::InvalidStakeAccountOwner
+            );
+
+            let new_recorded_vesting_balance =
+                apply(stake_account_metadata.recorded_vesting_balance)?;
+
+            // Update the recorded vesting balance
+            let recorded_vesting_balance_changed = stake_account_metadata
+                .update_recorded_vesting_balance(new_recorded_vesting_balance);
+
+            // Update checkpoints
+            let current_delegate_checkpoints_account_info =
+                delegate_stake_account_checkpoints.to_account_info();
+
+            let current_timestamp: u64 = Clock::get()?.unix_timestamp.try_into()?;
+
+            // stake_account_metadata is serialized after delegate_stake_account_metadata,
+            // so it has to be the one updated on rollover when both are the same account
+            let delegate_is_vester =
+                delegate_stake_account_metadata.key() == stake_account_metadata.key();
+
+            let delegate_votes_changed = if !delegate_is_vester
+                && self.global_config.delegation_policy.is_liquid()
+            {
+                // With liquid delegation the votes follow the delegations of the delegate
+                push_delegation_chain_votes(
+                    &stake_account_metadata.owner,
+                    &mut ***delegate_stake_account_metadata,
+                    delegate_stake_account_checkpoints,
+                    self.delegate_stake_account_checkpoints_next,
+                    amount,
+                    operation,
+                    None,
+                    remaining_accounts,
+                    &self.vester.to_account_info(),
+                    &self.system_program.to_account_info(),
+                    current_timestamp,
+                    self.global_config.max_checkpoints_account_limit,
+                )?
+                .0
+            } else {
+                Some(push_checkpoint(
+                    delegate_stake_account_checkpoints,
+                    &current_delegate_checkpoints_account_info,
+                    amount,
+                    operation,
+                    current_timestamp,
+                    &self.vester.to_account_info(),
+                    &self.system_program.to_account_info(),
+                    CheckpointsRollover {
+                        stake_account_metadata: if delegate_is_vester {
+                            &mut ***stake_account_metadata
+                        } else {
+                            &mut ***delegate_stake_account_metadata
+                        },
+                        next_checkpoints_account_info: self.delegate_stake_account_checkpoints_next,
+                        max_checkpoints_account_limit: self
+                            .global_config
+                            .max_checkpoints_account_limit,
+                    },
+                )?)
+            };
+
+            Ok(Some(CloseVestingEvents {
+                recorded_vesting_balance_changed,
+                delegate_votes_changed: delegate_votes_changed.into_iter().collect(),
+            }))
+        } else {
+            err!(VestingError::ErrorOfAccountParsing)
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::context::{
+    CONFIG_SEED, DELEGATION_SPLIT_SEED, VESTING_BALANCE_SEED, VESTING_CONFIG_SEED, VEST_SEED,
+};
+use crate::contexts::{CloseVestingEvents, VestingRelease};
+use crate::state::checkpoints::CheckpointData;
+use crate::state::global_config::GlobalConfig;
+use crate::state::stake_account::StakeAccountMetadata;
+use crate::{
+    error::VestingError,
+    state::{Vesting, VestingBalance, VestingConfig},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimVestingPartial<'info> {
+    #[account(mut)]
+    vester: Signer<'info>,
+    mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = vester,
+        token::token_program = token_program
+    )]
+    vester_ta: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = config.finalized @ VestingError::VestingUnfinalized,
+        seeds = [VESTING_CONFIG_SEED.as_bytes(), mint.key().as_ref(), config.seed.to_le_bytes().as_ref()],
+        bump = config.bump
+    )]
+    config: Account<'info, VestingConfig>,
+    #[account(
+        mut,
+        constraint = Clock::get()?.unix_timestamp >= vest.maturation @ VestingError::NotFullyVested,
+        constraint = vest.vester == vester_ta.owner @ VestingError::InvalidVester,
+        has_one = config, // This check is arbitrary, as ATA is baked into the PDA
+        seeds = [VEST_SEED.as_bytes(), config.key().as_ref(), vester_ta.owner.key().as_ref(), vest.maturation.to_le_bytes().as_ref()],
+        bump = vest.bump
+    )]
+    vest: Account<'info, Vesting>,
+    #[account(
+        mut,
+        has_one = vester,
+        seeds = [VESTING_BALANCE_SEED.as_bytes(), config.key().as_ref(), vester_ta.owner.key().as_ref()],
+        bump = vesting_balance.bump
+    )]
+    vesting_balance: Account<'info, VestingBalance>,
+    /// CheckpointData and StakeAccountMetadata accounts are optional because
+    /// in order to be able to claim vests that have not been delegated
+    #[account(mut)]
+    pub delegate_stake_account_checkpoints: Option<AccountLoader<'info, CheckpointData>>,
+    /// CHECK: Next CheckpointData PDA of the delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub delegate_stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
+    #[account(mut)]
+    pub stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
+    /// CHECK: DelegationSplit PDA of the vester, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    /// instead of the delegate accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), vester.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
+    #[account(
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump = global_config.bump,
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    /// CHECK: The admin is the refund recipient for the vest account and is checked in the config account constraints
+    #[account(mut,
+        constraint = global_config.vesting_admin == admin.key()
+    )]
+    admin: AccountInfo<'info>,
+    associated_token_program: Program<'info, AssociatedToken>,
+    token_program: Program<'info, Token>,
+    system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimVestingPartial<'info> {
+    /// Releases part of a matured vest, the rest stays vested and keeps its votes.
+    /// The vest is closed once its amount reaches zero
+    pub fn claim_vesting_partial(
+        &mut self,
+        amount: u64,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<Option<CloseVestingEvents>> {
+        require!(
+            amount > 0 && amount <= self.vest.amount,
+            VestingError::InvalidClaimAmount
+        );
+
+        self.vest.amount -= amount;
+
+        let close_vesting_events = VestingRelease {
+            vester: self.vester.to_account_info(),
+            mint: &self.mint,
+            vault: self.vault.to_account_info(),
+            destination: self.vester_ta.to_account_info(),
+            config: &mut self.config,
+            vesting_balance: &mut self.vesting_balance,
+            delegate_stake_account_checkpoints: self.delegate_stake_account_checkpoints.as_mut(),
+            delegate_stake_account_checkpoints_next: self
+                .delegate_stake_account_checkpoints_next
+                .as_deref(),
+            delegate_stake_account_metadata: self.delegate_stake_account_metadata.as_deref_mut(),
+            stake_account_metadata: self.stake_account_metadata.as_deref_mut(),
+            delegation_split: self.delegation_split.to_account_info(),
+            global_config: &self.global_config,
+            token_program: self.token_program.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+        }
+        .release(amount, remaining_accounts)?;
+
+        if self.vest.amount == 0 {
+            self.vest.close(self.admin.to_account_info())?;
+        }
+
+        Ok(close_vesting_events)
+    }
+}
@@ -0,0 +1,152 @@
+use crate::context::{
+    CONFIG_SEED, CUSTODY_SEED, VESTING_BALANCE_SEED, VESTING_CONFIG_SEED, VEST_SEED,
+};
+use crate::state::global_config::GlobalConfig;
+use crate::state::stake_account::{
+    RecordedBalanceChanged, RecordedVestingBalanceChanged, StakeAccountMetadata,
+};
+use crate::{
+    error::VestingError,
+    state::{Vesting, VestingBalance, VestingConfig},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{transfer_checked, Mint, Token, TokenAccount, TransferChecked};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimVestingToStake<'info> {
+    #[account(mut)]
+    vester: Signer<'info>,
+    mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = config.finalized @ VestingError::VestingUnfinalized,
+        seeds = [VESTING_CONFIG_SEED.as_bytes(), mint.key().as_ref(), config.seed.to_le_bytes().as_ref()],
+        bump = config.bump
+    )]
+    config: Account<'info, VestingConfig>,
+    #[account(
+        mut,
+        close = admin,
+        constraint = Clock::get()?.unix_timestamp >= vest.maturation @ VestingError::NotFullyVested,
+        constraint = vest.vester == vester.key() @ VestingError::InvalidVester,
+        has_one = config, // This check is arbitrary, as config is baked into the PDA
+        seeds = [VEST_SEED.as_bytes(), config.key().as_ref(), vester.key().as_ref(), vest.maturation.to_le_bytes().as_ref()],
+        bump = vest.bump
+    )]
+    vest: Account<'info, Vesting>,
+    #[account(
+        mut,
+        has_one = vester,
+        // The vests have to be counted in the votes of the stake account for the votes
+        // to stay the same when they move to its custody
+        constraint = vesting_balance.stake_account_metadata != Pubkey::default()
+            @ VestingError::NoStakeAccountMetadata,
+        seeds = [VESTING_BALANCE_SEED.as_bytes(), config.key().as_ref(), vester.key().as_ref()],
+        bump = vesting_balance.bump
+    )]
+    vesting_balance: Account<'info, VestingBalance>,
+    /// The stake account the vests are linked to, which may have been moved to another owner
+    #[account(
+        mut,
+        address = vesting_balance.stake_account_metadata @ VestingError::InvalidStakeAccountOwner
+    )]
+    pub stake_account_metadata: Box<Account<'info, StakeAccountMetadata>>,
+    #[account(
+        mut,
+        seeds = [CUSTODY_SEED.as_bytes(), stake_account_metadata.owner.as_ref()],
+        bump = stake_account_metadata.custody_bump,
+        token::mint = mint,
+        token::token_program = token_program
+    )]
+    pub stake_account_custody: Box<Account<'info, TokenAccount>>,
+    #[account(
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump = global_config.bump,
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    /// CHECK: The admin is the refund recipient for the vest account and is checked in the config account constraints
+    #[account(mut,
+        constraint = global_config.vesting_admin == admin.key()
+    )]
+    admin: AccountInfo<'info>,
+    associated_token_program: Program<'info, AssociatedToken>,
+    token_program: Program<'info, Token>,
+    system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimVestingToStake<'info> {
+    /// Moves a matured vest from the vault to the custody of the stake account it is linked to.
+    /// The vested balance of the stake account becomes part of its balance, so its votes and
+    /// the checkpoints of its delegates do not change
+    pub fn claim_vesting_to_stake(
+        &mut self,
+    ) -> Result<(RecordedVestingBalanceChanged, RecordedBalanceChanged)> {
+        let amount = self.vest.amount;
+
+        let new_recorded_vesting_balance = self
+            .stake_account_metadata
+            .recorded_vesting_balance
+            .checked_sub(amount)
+            .ok_or(VestingError::Underflow)?;
+        let new_recorded_balance = self
+            .stake_account_metadata
+            .recorded_balance
+            .checked_add(amount)
+            .ok_or(VestingError::Overflow)?;
+
+        let recorded_vesting_balance_changed = self
+            .stake_account_metadata
+            .update_recorded_vesting_balance(new_recorded_vesting_balance);
+        let recorded_balance_changed = self
+            .stake_account_metadata
+            .update_recorded_balance(new_recorded_balance);
+
+        self.config.vested = self
+            .config
+            .vested
+            .checked_sub(amount)
+            .ok_or(VestingError::Underflow)?;
+
+        self.vesting_balance.total_vesting_balance = self
+            .vesting_balance
+            .total_vesting_balance
+            .checked_sub(amount)
+            .ok_or(VestingError::Underflow)?;
+
+        // Binding to solve for lifetime issues
+        let seed = self.config.seed.to_le_bytes();
+        let bump = [self.config.bump];
+
+        let signer_seeds = [&[
+            VESTING_CONFIG_SEED.as_bytes(),
+            self.config.mint.as_ref(),
+            &seed,
+            &bump,
+        ][..]];
+
+        let ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from: self.vault.to_account_info(),
+                to: self.stake_account_custody.to_account_info(),
+                mint: self.mint.to_account_info(),
+                authority: self.config.to_account_info(),
+            },
+            &signer_seeds,
+        );
+
+        transfer_checked(ctx, amount, self.mint.decimals)?;
+
+        Ok((recorded_vesting_balance_changed, recorded_balance_changed))
+    }
+}
@@ -0,0 +1,71 @@
+use crate::context::{
+    CONFIG_SEED, VESTING_CONFIG_SEED, VESTING_MERKLE_CLAIM_BITMAP_SEED,
+    VESTING_MERKLE_DISTRIBUTOR_SEED,
+};
+use crate::error::VestingError;
+use crate::state::global_config::GlobalConfig;
+use crate::state::{VestingConfig, VestingMerkleClaimBitmap, VestingMerkleDistributor};
+use anchor_lang::prelude::*;
+use anchor_spl::token::Mint;
+
+#[derive(Accounts)]
+pub struct CloseVestingMerkleDistributor<'info> {
+    #[account(
+        mut,
+        constraint = global_config.vesting_admin == admin.key()
+            @ VestingError::InvalidVestingAdmin
+    )]
+    admin: Signer<'info>,
+    mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        has_one = mint, // This check is arbitrary, as mint is baked into the PDA
+        seeds = [VESTING_CONFIG_SEED.as_bytes(), mint.key().as_ref(), config.seed.to_le_bytes().as_ref()],
+        bump = config.bump
+    )]
+    config: Account<'info, VestingConfig>,
+    #[account(
+        mut,
+        close = admin,
+        constraint = Clock::get()?.unix_timestamp >= distributor.claim_deadline
+            @ VestingError::MerkleClaimDeadlineNotReached,
+        has_one = config, // This check is arbitrary, as config is baked into the PDA
+        seeds = [VESTING_MERKLE_DISTRIBUTOR_SEED.as_bytes(), config.key().as_ref(), distributor.index.to_le_bytes().as_ref()],
+        bump = distributor.bump
+    )]
+    distributor: Account<'info, VestingMerkleDistributor>,
+    #[account(
+        mut,
+        close = admin,
+        has_one = distributor, // This check is arbitrary, as distributor is baked into the PDA
+        seeds = [VESTING_MERKLE_CLAIM_BITMAP_SEED.as_bytes(), distributor.key().as_ref()],
+        bump
+    )]
+    claim_bitmap: Account<'info, VestingMerkleClaimBitmap>,
+    #[account(
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump = global_config.bump,
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+    system_program: Program<'info, System>,
+}
+
+impl<'info> CloseVestingMerkleDistributor<'info> {
+    pub fn close_vesting_merkle_distributor(&mut self) -> Result<()> {
+        // The leaves that were not claimed before the deadline are no longer vested, so the
+        // admin can withdraw them from the vault as surplus
+        let unclaimed_amount = self
+            .distributor
+            .total_amount
+            .checked_sub(self.distributor.claimed_amount)
+            .ok_or(VestingError::Underflow)?;
+
+        self.config.vested = self
+            .config
+            .vested
+            .checked_sub(unclaimed_amount)
+            .ok_or(VestingError::Underflow)?;
+
+        Ok(())
+    }
+}
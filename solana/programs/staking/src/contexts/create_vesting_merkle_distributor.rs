@@ -0,0 +1,97 @@
+use crate::context::{
+    CONFIG_SEED, VESTING_CONFIG_SEED, VESTING_MERKLE_CLAIM_BITMAP_SEED,
+    VESTING_MERKLE_DISTRIBUTOR_SEED,
+};
+use crate::error::VestingError;
+use crate::state::global_config::GlobalConfig;
+use crate::state::{VestingConfig, VestingMerkleClaimBitmap, VestingMerkleDistributor};
+use anchor_lang::prelude::*;
+use anchor_spl::token::Mint;
+
+#[derive(Accounts)]
+#[instruction(index: u64, _root: [u8; 32], _total_amount: u64, num_leaves: u32)]
+pub struct CreateVestingMerkleDistributor<'info> {
+    #[account(
+        mut,
+        constraint = global_config.vesting_admin == admin.key()
+            @ VestingError::InvalidVestingAdmin
+    )]
+    admin: Signer<'info>,
+    mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = !config.finalized @ VestingError::VestingFinalized, // The leaves are vested amounts, so they can only be committed before a vest is finalized
+        has_one = mint, // This check is arbitrary, as mint is baked into the PDA
+        seeds = [VESTING_CONFIG_SEED.as_bytes(), mint.key().as_ref(), config.seed.to_le_bytes().as_ref()],
+        bump = config.bump
+    )]
+    config: Account<'info, VestingConfig>,
+    #[account(
+        init,
+        payer = admin,
+        space = VestingMerkleDistributor::LEN,
+        seeds = [VESTING_MERKLE_DISTRIBUTOR_SEED.as_bytes(), config.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump
+    )]
+    distributor: Account<'info, VestingMerkleDistributor>,
+    #[account(
+        init,
+        payer = admin,
+        space = VestingMerkleClaimBitmap::space(num_leaves),
+        seeds = [VESTING_MERKLE_CLAIM_BITMAP_SEED.as_bytes(), distributor.key().as_ref()],
+        bump
+    )]
+    claim_bitmap: Account<'info, VestingMerkleClaimBitmap>,
+    #[account(
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump = global_config.bump,
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+    system_program: Program<'info, System>,
+}
+
+impl<'info> CreateVestingMerkleDistributor<'info> {
+    pub fn create_vesting_merkle_distributor(
+        &mut self,
+        index: u64,
+        root: [u8; 32],
+        total_amount: u64,
+        num_leaves: u32,
+        claim_deadline: i64,
+        bump: u8,
+    ) -> Result<()> {
+        require!(
+            total_amount > 0
+                && num_leaves > 0
+                && num_leaves <= VestingMerkleClaimBitmap::MAX_LEAVES
+                && claim_deadline > Clock::get()?.unix_timestamp,
+            VestingError::InvalidMerkleDistributor
+        );
+
+        // The leaves are vested as soon as the root is committed, so that the vault has to
+        // hold them before the config is finalized
+        self.config.vested = self
+            .config
+            .vested
+            .checked_add(total_amount)
+            .ok_or(VestingError::Overflow)?;
+
+        self.distributor.set_inner(VestingMerkleDistributor {
+            config: self.config.key(),
+            index,
+            root,
+            total_amount,
+            claimed_amount: 0,
+            num_leaves,
+            claim_deadline,
+            bump,
+        });
+
+        self.claim_bitmap.set_inner(VestingMerkleClaimBitmap {
+            distributor: self.distributor.key(),
+            bits: vec![0; usize::try_from(num_leaves)?.div_ceil(8)],
+        });
+
+        Ok(())
+    }
+}
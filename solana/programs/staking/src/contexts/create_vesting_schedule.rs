@@ -0,0 +1,91 @@
+use crate::context::{
+    CONFIG_SEED, VESTING_BALANCE_SEED, VESTING_CONFIG_SEED, VESTING_SCHEDULE_SEED,
+};
+use crate::error::VestingError;
+use crate::state::global_config::GlobalConfig;
+use crate::state::{VestingBalance, VestingConfig, VestingSchedule};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token};
+
+#[derive(Accounts)]
+#[instruction(vester: Pubkey, start: i64)]
+pub struct CreateVestingSchedule<'info> {
+    #[account(
+        mut,
+        constraint = global_config.vesting_admin == admin.key()
+            @ VestingError::InvalidVestingAdmin
+    )]
+    admin: Signer<'info>,
+    mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = !config.finalized @ VestingError::VestingFinalized, // A vesting schedule can only be created before a vest is finalized
+        has_one = mint, // This check is arbitrary, as mint is baked into the PDA
+        seeds = [VESTING_CONFIG_SEED.as_bytes(), mint.key().as_ref(), config.seed.to_le_bytes().as_ref()],
+        bump = config.bump
+    )]
+    config: Account<'info, VestingConfig>,
+    #[account(
+        init,
+        payer = admin,
+        space = VestingSchedule::LEN,
+        seeds = [VESTING_SCHEDULE_SEED.as_bytes(), config.key().as_ref(), vester.as_ref(), start.to_le_bytes().as_ref()],
+        bump
+    )]
+    vesting_schedule: Account<'info, VestingSchedule>,
+    #[account(
+        mut,
+        seeds = [VESTING_BALANCE_SEED.as_bytes(), config.key().as_ref(), vester.as_ref()],
+        bump = vesting_balance.bump
+    )]
+    vesting_balance: Account<'info, VestingBalance>,
+    #[account(
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump = global_config.bump,
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+    token_program: Program<'info, Token>,
+    system_program: Program<'info, System>,
+}
+
+impl<'info> CreateVestingSchedule<'info> {
+    pub fn create_vesting_schedule(
+        &mut self,
+        vester: Pubkey,
+        start: i64,
+        cliff: i64,
+        end: i64,
+        period: i64,
+        amount: u64,
+        bump: u8,
+    ) -> Result<()> {
+        VestingSchedule::check_schedule(amount, start, cliff, end, period)?;
+
+        // Add to total vested amount
+        self.config.vested = self
+            .config
+            .vested
+            .checked_add(amount)
+            .ok_or(VestingError::Overflow)?;
+
+        self.vesting_balance.total_vesting_balance = self
+            .vesting_balance
+            .total_vesting_balance
+            .checked_add(amount)
+            .ok_or(VestingError::Overflow)?;
+
+        self.vesting_schedule.set_inner(VestingSchedule {
+            vester,
+            config: self.config.key(),
+            total_amount: amount,
+            claimed_amount: 0,
+            start,
+            cliff,
+            end,
+            period,
+            bump,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,622 @@
+use crate::context::{Delegate, VESTING_BALANCE_SEED};
+use crate::error::{ErrorCode, VestingError};
+use crate::state::checkpoints::{
+    push_checkpoint, CheckpointData, CheckpointsRollover, DelegateVotesChanged, Operation,
+};
+use crate::state::delegation_chain::{push_delegation_chain_votes, DelegationChainChange};
+use crate::state::delegation_split::{push_delegate_votes_updates, DelegationSplit};
+use crate::state::global_config::GlobalConfig;
+use crate::state::stake_account::{
+    RecordedBalanceChanged, RecordedVestingBalanceChanged, StakeAccountMetadata,
+};
+use crate::state::{VestingBalance, VestingConfig};
+use crate::utils::clock;
+use crate::DelegateChanged;
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use std::convert::TryInto;
+
+/// Accounts used to move the voting power of a stake account to a delegatee.
+/// They are shared by the delegate and delegate_by_sig instructions, which only
+/// differ in how the stake account owner authorizes the delegation
+pub struct DelegateStake<'a, 'info> {
+    pub payer: AccountInfo<'info>,
+    pub current_delegate_stake_account_checkpoints: &'a mut AccountLoader<'info, CheckpointData>,
+    pub current_delegate_stake_account_checkpoints_next: Option<AccountInfo<'info>>,
+    pub current_delegate_stake_account_metadata: &'a mut Account<'info, StakeAccountMetadata>,
+    pub delegatee_stake_account_checkpoints: &'a mut AccountLoader<'info, CheckpointData>,
+    pub delegatee_stake_account_checkpoints_next: Option<AccountInfo<'info>>,
+    pub delegatee_stake_account_metadata: &'a mut Account<'info, StakeAccountMetadata>,
+    pub stake_account_metadata: &'a mut Account<'info, StakeAccountMetadata>,
+    pub delegation_split: AccountInfo<'info>,
+    pub stake_account_custody: &'a Account<'info, TokenAccount>,
+    pub vesting_config: Option<&'a Account<'info, VestingConfig>>,
+    pub vesting_balance: Option<&'a mut Account<'info, VestingBalance>>,
+    pub config: &'a mut GlobalConfig,
+    pub system_program: AccountInfo<'info>,
+}
+
+pub struct DelegateEvents {
+    pub recorded_vesting_balance_changed: Option<RecordedVestingBalanceChanged>,
+    pub delegate_changed: Vec<DelegateChanged>,
+    pub delegate_votes_changed: Vec<DelegateVotesChanged>,
+    pub recorded_balance_changed: Option<RecordedBalanceChanged>,
+}
+
+impl<'info> Delegate<'info> {
+    pub fn delegate_stake(&mut self) -> DelegateStake<'_, 'info> {
+        DelegateStake {
+            payer: self.payer.to_account_info(),
+            current_delegate_stake_account_checkpoints: &mut self
+                .current_delegate_stake_account_checkpoints,
+            current_delegate_stake_account_checkpoints_next: self
+                .current_delegate_stake_account_checkpoints_next
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            current_delegate_stake_account_metadata: &mut self
+                .current_delegate_stake_account_metadata,
+            delegatee_stake_account_checkpoints: &mut self.delegatee_stake_account_checkpoints,
+            delegatee_stake_account_checkpoints_next: self
+                .delegatee_stake_account_checkpoints_next
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            delegatee_stake_account_metadata: &mut self.delegatee_stake_account_metadata,
+            stake_account_metadata: &mut self.stake_account_metadata,
+            delegation_split: self.delegation_split.to_account_info(),
+            stake_account_custody: &self.stake_account_custody,
+            vesting_config: self.vesting_config.as_ref(),
+            vesting_balance: self.vesting_balance.as_mut(),
+            config: &mut self.config,
+            system_program: self.system_program.to_account_info(),
+        }
+    }
+}
+
+impl<'a, 'info> DelegateStake<'a, 'info> {
+    /// Delegates the whole voting power of the stake account to the delegatee,
+    /// removing an active delegation split, and returns the events to emit
+    pub fn delegate(
+        mut self,
+        delegatee: Pubkey,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<DelegateEvents> {
+        let delegator = self.stake_account_metadata.owner;
+        let stake_account_metadata = &mut *self.stake_account_metadata;
+        let config = &*self.config;
+
+        let delegatee_stake_account_checkpoints =
+            self.delegatee_stake_account_checkpoints.load()?;
+        let current_delegate_stake_account_checkpoints =
+            self.current_delegate_stake_account_checkpoints.load()?;
+        require!(
+            delegatee_stake_account_checkpoints.next_index
+                < config.max_checkpoints_account_limit.into(),
+            ErrorCode::TooManyCheckpoints,
+        );
+        require!(
+            current_delegate_stake_account_checkpoints.next_index
+                < config.max_checkpoints_account_limit.into(),
+            ErrorCode::TooManyCheckpoints,
+        );
+        drop(delegatee_stake_account_checkpoints);
+        drop(current_delegate_stake_account_checkpoints);
+
+        let prev_recorded_total_balance = stake_account_metadata
+            .recorded_balance
+            .checked_add(stake_account_metadata.recorded_vesting_balance)
+            .unwrap();
+
+        let current_stake_balance = self.stake_account_custody.amount;
+
+        let mut recorded_vesting_balance_changed_event = None;
+        let mut delegate_votes_changed_events = Vec::new();
+        let mut recorded_balance_changed_event = None;
+
+        if let Some(vesting_config) = self.vesting_config {
+            if vesting_config.finalized {
+                if let Some(vesting_balance) = &mut self.vesting_balance {
+                    let (expected_vesting_balance_pda, _) = Pubkey::find_program_address(
+                        &[
+                            VESTING_BALANCE_SEED.as_bytes(),
+                            vesting_config.key().as_ref(),
+                            stake_account_metadata.owner.as_ref(),
+                        ],
+                        &crate::ID,
+                    );
+                    require!(
+                        expected_vesting_balance_pda == vesting_balance.key(),
+                        VestingError::InvalidVestingBalancePDA
+                    );
+
+                    if vesting_balance.stake_account_metadata == Pubkey::default() {
+                        vesting_balance.stake_account_metadata = stake_account_metadata.key();
+
+                        let new_recorded_vesting_balance = stake_account_metadata
+                            .recorded_vesting_balance
+                            .checked_add(vesting_balance.total_vesting_balance)
+                            .ok_or(VestingError::Overflow)?;
+
+                        // Update the recorded vesting balance
+                        let recorded_vesting_balance_changed = stake_account_metadata
+                            .update_recorded_vesting_balance(new_recorded_vesting_balance);
+                        recorded_vesting_balance_changed_event =
+                            Some(recorded_vesting_balance_changed);
+                    }
+                }
+            }
+        }
+
+        // While a delegation split is active, the votes are delegated to the split delegates
+        // and delegating to a single delegatee removes the split
+        let delegation_split = if stake_account_metadata.has_delegation_split {
+            Some(
+                DelegationSplit::load_active(&self.delegation_split)?
+                    .ok_or(ErrorCode::InvalidDelegationSplitAccounts)?,
+            )
+        } else {
+            None
+        };
+
+        let was_delegating = !stake_account_metadata.is_self_delegated();
+        let current_delegate = stake_account_metadata.delegate;
+        stake_account_metadata.delegate = delegatee;
+        stake_account_metadata.has_delegation_split = false;
+
+        let total_delegated_votes = current_stake_balance
+            .checked_add(stake_account_metadata.recorded_vesting_balance)
+            .unwrap();
+
+        // The votes move away from every split delegate, so a DelegateChanged event is emitted
+        // for each of them
+        let from_delegates = match &delegation_split {
+            Some(delegation_split) => delegation_split
+                .shares
+                .iter()
+                .map(|share| share.delegate)
+                .collect(),
+            None => vec![current_delegate],
+        };
+
+        let mut delegate_changed_events = Vec::new();
+        for from_delegate in from_delegates {
+            emit!(DelegateChanged {
+                delegator,
+                from_delegate,
+                to_delegate: delegatee,
+                total_delegated_votes,
+            });
+
+            delegate_changed_events.push(DelegateChanged {
+                delegator,
+                from_delegate,
+                to_delegate: delegatee,
+                total_delegated_votes,
+            });
+        }
+
+        let current_timestamp: u64 = clock::get_current_time().try_into().unwrap();
+        let max_checkpoints_account_limit = config.max_checkpoints_account_limit;
+
+        // Checkpoint rollovers have to update the metadata copy that is serialized last,
+        // so stake_account_metadata is updated when the delegate is the stake account owner
+        let current_delegate_is_user =
+            self.current_delegate_stake_account_metadata.key() == self.stake_account_metadata.key();
+        let delegatee_is_user =
+            self.delegatee_stake_account_metadata.key() == self.stake_account_metadata.key();
+
+        // Delegator counts only track delegations to the stake accounts of other owners
+        let delegatee_was_delegate = match &delegation_split {
+            Some(delegation_split) => delegation_split
+                .shares
+                .iter()
+                .any(|share| share.delegate == delegatee),
+            None => current_delegate == delegatee,
+        };
+        if !delegatee_was_delegate {
+            if delegation_split.is_none() && !current_delegate_is_user {
+                self.current_delegate_stake_account_metadata
+                    .update_delegator_count(&Operation::Subtract)?;
+            }
+            if !delegatee_is_user {
+                self.delegatee_stake_account_metadata
+                    .update_delegator_count(&Operation::Add)?;
+            }
+        }
+
+        if let Some(mut delegation_split) = delegation_split {
+            // The delegatee keeps the votes it already holds from the split,
+            // so only the other split delegates are checkpointed through the remaining accounts
+            let mut updates = delegation_split.clear();
+            let delegatee_previous_votes = updates
+                .iter()
+                .find(|update| update.delegate == delegatee)
+                .map_or(0, |update| update.previous_votes);
+            updates.retain(|update| update.delegate != delegatee);
+
+            let delegate_votes_changed = push_delegate_votes_updates(
+                &updates,
+                remaining_accounts,
+                &mut **self.stake_account_metadata,
+                &self.payer,
+                &self.system_program,
+                current_timestamp,
+                max_checkpoints_account_limit,
+            )?;
+            delegate_votes_changed_events.extend(delegate_votes_changed);
+
+            if total_delegated_votes != delegatee_previous_votes {
+                let delegatee_checkpoints_account_info =
+                    self.delegatee_stake_account_checkpoints.to_account_info();
+
+                let (amount_delta, operation) = if total_delegated_votes > delegatee_previous_votes
+                {
+                    (
+                        total_delegated_votes - delegatee_previous_votes,
+                        Operation::Add,
+                    )
+                } else {
+                    (
+                        delegatee_previous_votes - total_delegated_votes,
+                        Operation::Subtract,
+                    )
+                };
+
+                let delegate_votes_changed = push_checkpoint(
+                    &mut self.delegatee_stake_account_checkpoints,
+                    &delegatee_checkpoints_account_info,
+                    amount_delta,
+                    operation,
+                    current_timestamp,
+                    &self.payer,
+                    &self.system_program,
+                    CheckpointsRollover {
+                        stake_account_metadata: if delegatee_is_user {
+                            &mut **self.stake_account_metadata
+                        } else {
+                            &mut **self.delegatee_stake_account_metadata
+                        },
+                        next_checkpoints_account_info: self
+                            .delegatee_stake_account_checkpoints_next
+                            .as_ref(),
+                        max_checkpoints_account_limit,
+                    },
+                )?;
+                delegate_votes_changed_events.push(delegate_votes_changed);
+            }
+
+            delegation_split.store(&self.delegation_split)?;
+        } else if config.delegation_policy.is_liquid() {
+            let delegate_votes_changed = self.push_liquid_delegation_votes(
+                current_delegate,
+                delegatee,
+                prev_recorded_total_balance,
+                total_delegated_votes,
+                remaining_accounts,
+                current_timestamp,
+            )?;
+            delegate_votes_changed_events.extend(delegate_votes_changed);
+        } else if current_delegate != delegatee {
+            if prev_recorded_total_balance > 0 {
+                let current_delegate_checkpoints_account_info = self
+                    .current_delegate_stake_account_checkpoints
+                    .to_account_info();
+
+                let delegate_votes_changed = push_checkpoint(
+                    &mut self.current_delegate_stake_account_checkpoints,
+                    &current_delegate_checkpoints_account_info,
+                    prev_recorded_total_balance,
+                    Operation::Subtract,
+                    current_timestamp,
+                    &self.payer,
+                    &self.system_program,
+                    CheckpointsRollover {
+                        stake_account_metadata: if current_delegate_is_user {
+                            &mut **self.stake_account_metadata
+                        } else {
+                            &mut **self.current_delegate_stake_account_metadata
+                        },
+                        next_checkpoints_account_info: self
+                            .current_delegate_stake_account_checkpoints_next
+                            .as_ref(),
+                        max_checkpoints_account_limit,
+                    },
+                )?;
+                delegate_votes_changed_events.push(delegate_votes_changed);
+            }
+
+            if total_delegated_votes > 0 {
+                let delegatee_checkpoints_account_info =
+                    self.delegatee_stake_account_checkpoints.to_account_info();
+
+                let delegate_votes_changed = push_checkpoint(
+                    &mut self.delegatee_stake_account_checkpoints,
+                    &delegatee_checkpoints_account_info,
+                    total_delegated_votes,
+                    Operation::Add,
+                    current_timestamp,
+                    &self.payer,
+                    &self.system_program,
+                    CheckpointsRollover {
+                        stake_account_metadata: if delegatee_is_user {
+                            &mut **self.stake_account_metadata
+                        } else {
+                            &mut **self.delegatee_stake_account_metadata
+                        },
+                        next_checkpoints_account_info: self
+                            .delegatee_stake_account_checkpoints_next
+                            .as_ref(),
+                        max_checkpoints_account_limit,
+                    },
+                )?;
+                delegate_votes_changed_events.push(delegate_votes_changed);
+            }
+        } else if total_delegated_votes != prev_recorded_total_balance {
+            let delegatee_checkpoints_account_info =
+                self.delegatee_stake_account_checkpoints.to_account_info();
+
+            let (amount_delta, operation) = if total_delegated_votes > prev_recorded_total_balance {
+                (
+                    total_delegated_votes
+                        .checked_sub(prev_recorded_total_balance)
+                        .unwrap(),
+                    Operation::Add,
+                )
+            } else {
+                (
+                    prev_recorded_total_balance
+                        .checked_sub(total_delegated_votes)
+                        .unwrap(),
+                    Operation::Subtract,
+                )
+            };
+
+            // current_delegate_stake_account_metadata and delegatee_stake_account_metadata are the same
+            // account here, and delegatee_stake_account_metadata is serialized after it
+            let delegate_votes_changed = push_checkpoint(
+                &mut self.delegatee_stake_account_checkpoints,
+                &delegatee_checkpoints_account_info,
+                amount_delta,
+                operation,
+                current_timestamp,
+                &self.payer,
+                &self.system_program,
+                CheckpointsRollover {
+                    stake_account_metadata: if delegatee_is_user {
+                        &mut **self.stake_account_metadata
+                    } else {
+                        &mut **self.delegatee_stake_account_metadata
+                    },
+                    next_checkpoints_account_info: self
+                        .delegatee_stake_account_checkpoints_next
+                        .as_ref(),
+                    max_checkpoints_account_limit,
+                },
+            )?;
+            delegate_votes_changed_events.push(delegate_votes_changed);
+        }
+
+        // The delegation policy can only be switched while no stake account delegates
+        let is_delegating = delegatee != delegator;
+        if is_delegating != was_delegating {
+            let operation = if is_delegating {
+                Operation::Add
+            } else {
+                Operation::Subtract
+            };
+            self.config.update_delegating_stake_accounts(&operation)?;
+        }
+
+        if current_stake_balance != self.stake_account_metadata.recorded_balance {
+            let recorded_balance_changed = self
+                .stake_account_metadata
+                .update_recorded_balance(current_stake_balance);
+            recorded_balance_changed_event = Some(recorded_balance_changed);
+        }
+
+        Ok(DelegateEvents {
+            recorded_vesting_balance_changed: recorded_vesting_balance_changed_event,
+            delegate_changed: delegate_changed_events,
+            delegate_votes_changed: delegate_votes_changed_events,
+            recorded_balance_changed: recorded_balance_changed_event,
+        })
+    }
+
+    /// Moves the votes flowing through the stake account with liquid delegation, its own votes
+    /// and the votes delegated to it, from the delegation chain of the current delegate to the
+    /// delegation chain of the delegatee, along with the delegation chains ending at the stake
+    /// account. The remaining accounts hold the chain of the current delegate when it is another
+    /// stake account, followed by the chain of the delegatee
+    fn push_liquid_delegation_votes(
+        &mut self,
+        current_delegate: Pubkey,
+        delegatee: Pubkey,
+        prev_recorded_total_balance: u64,
+        total_delegated_votes: u64,
+        remaining_accounts: &'info [AccountInfo<'info>],
+        current_timestamp: u64,
+    ) -> Result<Vec<DelegateVotesChanged>> {
+        let delegator = self.stake_account_metadata.owner;
+        let delegated_votes = self.stake_account_metadata.delegated_votes;
+        let previous_votes = prev_recorded_total_balance
+            .checked_add(delegated_votes)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        let votes = total_delegated_votes
+            .checked_add(delegated_votes)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        let max_checkpoints_account_limit = self.config.max_checkpoints_account_limit;
+
+        let current_delegate_is_user =
+            self.current_delegate_stake_account_metadata.key() == self.stake_account_metadata.key();
+        let delegatee_is_user =
+            self.delegatee_stake_account_metadata.key() == self.stake_account_metadata.key();
+
+        let mut delegate_votes_changed_events = Vec::new();
+
+        if current_delegate == delegatee {
+            if votes == previous_votes {
+                return Ok(delegate_votes_changed_events);
+            }
+
+            let (amount_delta, operation) = if votes > previous_votes {
+                (votes - previous_votes, Operation::Add)
+            } else {
+                (previous_votes - votes, Operation::Subtract)
+            };
+
+            // current_delegate_stake_account_metadata and delegatee_stake_account_metadata are the same
+            // account here, and delegatee_stake_account_metadata is serialized after it
+            if delegatee_is_user {
+                let delegatee_checkpoints_account_info =
+                    self.delegatee_stake_account_checkpoints.to_account_info();
+                delegate_votes_changed_events.push(push_checkpoint(
+                    &mut self.delegatee_stake_account_checkpoints,
+                    &delegatee_checkpoints_account_info,
+                    amount_delta,
+                    operation,
+                    current_timestamp,
+                    &self.payer,
+                    &self.system_program,
+                    CheckpointsRollover {
+                        stake_account_metadata: &mut **self.stake_account_metadata,
+                        next_checkpoints_account_info: self
+                            .delegatee_stake_account_checkpoints_next
+                            .as_ref(),
+                        max_checkpoints_account_limit,
+                    },
+                )?);
+            } else {
+                let (delegate_votes_changed, _) = push_delegation_chain_votes(
+                    &delegator,
+                    &mut **self.delegatee_stake_account_metadata,
+                    &mut self.delegatee_stake_account_checkpoints,
+                    self.delegatee_stake_account_checkpoints_next.as_ref(),
+                    amount_delta,
+                    operation,
+                    None,
+                    remaining_accounts,
+                    &self.payer,
+                    &self.system_program,
+                    current_timestamp,
+                    max_checkpoints_account_limit,
+                )?;
+                delegate_votes_changed_events.extend(delegate_votes_changed);
+            }
+
+            return Ok(delegate_votes_changed_events);
+        }
+
+        let max_depth = self
+            .config
+            .delegation_policy
+            .max_depth()
+            .ok_or(ErrorCode::InvalidDelegationPolicy)?;
+        let delegator_chain_counts = self.stake_account_metadata.delegation_chain_counts;
+
+        let mut used_accounts_count = 0;
+        if current_delegate_is_user {
+            if previous_votes > 0 {
+                let current_delegate_checkpoints_account_info = self
+                    .current_delegate_stake_account_checkpoints
+                    .to_account_info();
+                delegate_votes_changed_events.push(push_checkpoint(
+                    &mut self.current_delegate_stake_account_checkpoints,
+                    &current_delegate_checkpoints_account_info,
+                    previous_votes,
+                    Operation::Subtract,
+                    current_timestamp,
+                    &self.payer,
+                    &self.system_program,
+                    CheckpointsRollover {
+                        stake_account_metadata: &mut **self.stake_account_metadata,
+                        next_checkpoints_account_info: self
+                            .current_delegate_stake_account_checkpoints_next
+                            .as_ref(),
+                        max_checkpoints_account_limit,
+                    },
+                )?);
+            }
+        } else {
+            // The delegation chains ending at the stake account are removed from the chain
+            // of the current delegate even when no votes are moved
+            let (delegate_votes_changed, current_delegate_accounts_count) =
+                push_delegation_chain_votes(
+                    &delegator,
+                    &mut **self.current_delegate_stake_account_metadata,
+                    &mut self.current_delegate_stake_account_checkpoints,
+                    self.current_delegate_stake_account_checkpoints_next
+                        .as_ref(),
+                    previous_votes,
+                    Operation::Subtract,
+                    Some(DelegationChainChange {
+                        delegator_chain_counts,
+                        max_depth,
+                    }),
+                    remaining_accounts,
+                    &self.payer,
+                    &self.system_program,
+                    current_timestamp,
+                    max_checkpoints_account_limit,
+                )?;
+            delegate_votes_changed_events.extend(delegate_votes_changed);
+            used_accounts_count = current_delegate_accounts_count;
+
+            // Both delegation chains can share stake accounts, so the chain of the delegatee
+            // has to see the changes made to the chain of the current delegate
+            self.current_delegate_stake_account_metadata
+                .exit(&crate::ID)?;
+            if !delegatee_is_user {
+                self.delegatee_stake_account_metadata.reload()?;
+            }
+        }
+
+        if delegatee_is_user {
+            if votes > 0 {
+                let delegatee_checkpoints_account_info =
+                    self.delegatee_stake_account_checkpoints.to_account_info();
+                delegate_votes_changed_events.push(push_checkpoint(
+                    &mut self.delegatee_stake_account_checkpoints,
+                    &delegatee_checkpoints_account_info,
+                    votes,
+                    Operation::Add,
+                    current_timestamp,
+                    &self.payer,
+                    &self.system_program,
+                    CheckpointsRollover {
+                        stake_account_metadata: &mut **self.stake_account_metadata,
+                        next_checkpoints_account_info: self
+                            .delegatee_stake_account_checkpoints_next
+                            .as_ref(),
+                        max_checkpoints_account_limit,
+                    },
+                )?);
+            }
+        } else {
+            // The delegation chain of the delegatee is checked for cycles and depth
+            // even when no votes are moved
+            let (delegate_votes_changed, _) = push_delegation_chain_votes(
+                &delegator,
+                &mut **self.delegatee_stake_account_metadata,
+                &mut self.delegatee_stake_account_checkpoints,
+                self.delegatee_stake_account_checkpoints_next.as_ref(),
+                votes,
+                Operation::Add,
+                Some(DelegationChainChange {
+                    delegator_chain_counts,
+                    max_depth,
+                }),
+                &remaining_accounts[used_accounts_count..],
+                &self.payer,
+                &self.system_program,
+                current_timestamp,
+                max_checkpoints_account_limit,
+            )?;
+            delegate_votes_changed_events.extend(delegate_votes_changed);
+
+            if !current_delegate_is_user {
+                self.current_delegate_stake_account_metadata.reload()?;
+            }
+        }
+
+        Ok(delegate_votes_changed_events)
+    }
+}
@@ -0,0 +1,186 @@
+use crate::context::{
+    AUTHORITY_SEED, CHECKPOINT_DATA_SEED, CONFIG_SEED, CUSTODY_SEED, DELEGATION_NONCE_SEED,
+    DELEGATION_SPLIT_SEED, STAKE_ACCOUNT_METADATA_SEED,
+};
+use crate::contexts::DelegateStake;
+use crate::error::ErrorCode;
+use crate::state::checkpoints::CheckpointData;
+use crate::state::delegation_nonce::DelegationNonce;
+use crate::state::global_config::GlobalConfig;
+use crate::state::stake_account::StakeAccountMetadata;
+use crate::state::{VestingBalance, VestingConfig};
+use crate::utils::ed25519::verify_ed25519_instruction;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar;
+use anchor_spl::token::{Mint, TokenAccount};
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(delegatee: Pubkey, current_delegate_stake_account_owner: Pubkey, stake_account_owner: Pubkey)]
+pub struct DelegateBySig<'info> {
+    // Relayer paying for the delegation, the stake account owner signs off-chain
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    // Current delegate stake account
+    #[account(
+        mut,
+        seeds = [
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            stake_account_metadata.delegate.as_ref(),
+            current_delegate_stake_account_metadata.stake_account_checkpoints_last_index.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub current_delegate_stake_account_checkpoints: AccountLoader<'info, CheckpointData>,
+    /// CHECK: Next CheckpointData PDA of the current delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub current_delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), current_delegate_stake_account_owner.as_ref()],
+        bump = current_delegate_stake_account_metadata.metadata_bump
+    )]
+    pub current_delegate_stake_account_metadata: Box<Account<'info, StakeAccountMetadata>>,
+
+    // Delegatee stake account
+    #[account(
+        mut,
+        seeds = [
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            delegatee.as_ref(),
+            delegatee_stake_account_metadata.stake_account_checkpoints_last_index.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub delegatee_stake_account_checkpoints: AccountLoader<'info, CheckpointData>,
+    /// CHECK: Next CheckpointData PDA of the delegatee, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub delegatee_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), delegatee.as_ref()],
+        bump = delegatee_stake_account_metadata.metadata_bump
+    )]
+    pub delegatee_stake_account_metadata: Box<Account<'info, StakeAccountMetadata>>,
+
+    // User stake account
+    #[account(
+        mut,
+        seeds = [STAKE_ACCOUNT_METADATA_SEED.as_bytes(), stake_account_owner.as_ref()],
+        bump = stake_account_metadata.metadata_bump,
+        constraint = stake_account_metadata.delegate == current_delegate_stake_account_owner
+            @ ErrorCode::InvalidCurrentDelegate
+    )]
+    pub stake_account_metadata: Box<Account<'info, StakeAccountMetadata>>,
+    /// CHECK: DelegationSplit PDA of the user, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), stake_account_owner.as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = DelegationNonce::LEN,
+        seeds = [DELEGATION_NONCE_SEED.as_bytes(), stake_account_owner.as_ref()],
+        bump
+    )]
+    pub delegation_nonce: Box<Account<'info, DelegationNonce>>,
+
+    /// CHECK : This AccountInfo is safe because it's a checked PDA
+    #[account(seeds = [AUTHORITY_SEED.as_bytes(), stake_account_owner.as_ref()], bump)]
+    pub custody_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [
+            CUSTODY_SEED.as_bytes(),
+            stake_account_owner.as_ref()
+        ],
+        bump,
+        token::mint = mint,
+        token::authority = custody_authority,
+    )]
+    pub stake_account_custody: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub vesting_config: Option<Account<'info, VestingConfig>>,
+    #[account(mut)]
+    pub vesting_balance: Option<Account<'info, VestingBalance>>,
+
+    // Counts the stake accounts that start or stop delegating
+    #[account(mut, seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, GlobalConfig>>,
+    // Wormhole token mint:
+    #[account(address = config.voting_token_mint)]
+    pub mint: Account<'info, Mint>,
+    /// CHECK: Instructions sysvar, used to read the ed25519 signature verification instruction
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> DelegateBySig<'info> {
+    /// Checks the ed25519 signature of the delegation by the stake account owner
+    /// and consumes the nonce of the owner
+    pub fn use_delegation_signature(
+        &mut self,
+        delegation_nonce_bump: u8,
+        delegatee: Pubkey,
+        stake_account_owner: Pubkey,
+        nonce: u64,
+        expiry: i64,
+    ) -> Result<()> {
+        require!(
+            Clock::get()?.unix_timestamp <= expiry,
+            ErrorCode::DelegationSignatureExpired
+        );
+
+        let delegation_nonce = &mut self.delegation_nonce;
+        if delegation_nonce.owner == Pubkey::default() {
+            delegation_nonce.bump = delegation_nonce_bump;
+            delegation_nonce.owner = stake_account_owner;
+        }
+        require!(
+            delegation_nonce.nonce == nonce,
+            ErrorCode::InvalidDelegationNonce
+        );
+
+        verify_ed25519_instruction(
+            &self.instructions,
+            &stake_account_owner,
+            &DelegationNonce::delegate_by_sig_message(&crate::ID, &delegatee, nonce, expiry),
+        )?;
+
+        delegation_nonce.nonce = nonce.checked_add(1).ok_or(ErrorCode::GenericOverflow)?;
+
+        Ok(())
+    }
+
+    pub fn delegate_stake(&mut self) -> DelegateStake<'_, 'info> {
+        DelegateStake {
+            payer: self.payer.to_account_info(),
+            current_delegate_stake_account_checkpoints: &mut self
+                .current_delegate_stake_account_checkpoints,
+            current_delegate_stake_account_checkpoints_next: self
+                .current_delegate_stake_account_checkpoints_next
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            current_delegate_stake_account_metadata: &mut self
+                .current_delegate_stake_account_metadata,
+            delegatee_stake_account_checkpoints: &mut self.delegatee_stake_account_checkpoints,
+            delegatee_stake_account_checkpoints_next: self
+                .delegatee_stake_account_checkpoints_next
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            delegatee_stake_account_metadata: &mut self.delegatee_stake_account_metadata,
+            stake_account_metadata: &mut self.stake_account_metadata,
+            delegation_split: self.delegation_split.to_account_info(),
+            stake_account_custody: &self.stake_account_custody,
+            vesting_config: self.vesting_config.as_ref(),
+            vesting_balance: self.vesting_balance.as_mut(),
+            config: &mut self.config,
+            system_program: self.system_program.to_account_info(),
+        }
+    }
+}
@@ -10,6 +10,30 @@ pub use create_vesting::*;
 pub mod claim_vesting;
 pub use claim_vesting::*;
 
+pub mod claim_vesting_partial;
+pub use claim_vesting_partial::*;
+
+pub mod claim_vesting_to_stake;
+pub use claim_vesting_to_stake::*;
+
+pub mod create_vesting_schedule;
+pub use create_vesting_schedule::*;
+
+pub mod claim_vested;
+pub use claim_vested::*;
+
+pub mod cancel_vesting_schedule;
+pub use cancel_vesting_schedule::*;
+
+pub mod create_vesting_merkle_distributor;
+pub use create_vesting_merkle_distributor::*;
+
+pub mod claim_vest_from_merkle;
+pub use claim_vest_from_merkle::*;
+
+pub mod close_vesting_merkle_distributor;
+pub use close_vesting_merkle_distributor::*;
+
 pub mod cancel_vesting;
 pub use cancel_vesting::*;
 
@@ -23,3 +47,15 @@ pub use create_vesting_balance::*;
 
 pub mod transfer_vesting;
 pub use transfer_vesting::*;
+
+pub mod transfer_vesting_partial;
+pub use transfer_vesting_partial::*;
+
+pub mod delegate;
+pub use delegate::*;
+
+pub mod delegate_by_sig;
+pub use delegate_by_sig::*;
+
+pub mod record_balance;
+pub use record_balance::*;
@@ -0,0 +1,223 @@
+use crate::context::{DepositTokens, RequestWithdrawal, SyncBalance, WithdrawTokens};
+use crate::error::ErrorCode;
+use crate::state::checkpoints::{
+    push_checkpoint, CheckpointData, CheckpointsRollover, DelegateVotesChanged, Operation,
+};
+use crate::state::delegation_chain::push_delegation_chain_votes;
+use crate::state::delegation_split::push_delegation_split_votes;
+use crate::state::global_config::GlobalConfig;
+use crate::state::stake_account::{RecordedBalanceChanged, StakeAccountMetadata};
+use crate::utils::clock;
+use anchor_lang::prelude::*;
+use std::convert::TryInto;
+
+/// Accounts used to record the custody balance of a stake account and checkpoint its change.
+/// They are shared by the deposit_tokens, sync_balance, withdraw_tokens and request_withdrawal
+/// instructions, which only differ in how the tokens reach or leave the custody account
+pub struct RecordStakeBalance<'a, 'info> {
+    pub payer: AccountInfo<'info>,
+    pub current_delegate_stake_account_checkpoints: &'a mut AccountLoader<'info, CheckpointData>,
+    pub current_delegate_stake_account_checkpoints_next: Option<AccountInfo<'info>>,
+    pub current_delegate_stake_account_metadata: &'a mut Account<'info, StakeAccountMetadata>,
+    pub stake_account_metadata: &'a mut Account<'info, StakeAccountMetadata>,
+    pub delegation_split: AccountInfo<'info>,
+    pub config: &'a GlobalConfig,
+    pub system_program: AccountInfo<'info>,
+}
+
+pub struct RecordBalanceEvents {
+    pub delegate_votes_changed: Vec<DelegateVotesChanged>,
+    pub recorded_balance_changed: RecordedBalanceChanged,
+}
+
+impl<'info> DepositTokens<'info> {
+    pub fn record_stake_balance(&mut self) -> RecordStakeBalance<'_, 'info> {
+        RecordStakeBalance {
+            payer: self.payer.to_account_info(),
+            current_delegate_stake_account_checkpoints: &mut self
+                .current_delegate_stake_account_checkpoints,
+            current_delegate_stake_account_checkpoints_next: self
+                .current_delegate_stake_account_checkpoints_next
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            current_delegate_stake_account_metadata: &mut self
+                .current_delegate_stake_account_metadata,
+            stake_account_metadata: &mut self.stake_account_metadata,
+            delegation_split: self.delegation_split.to_account_info(),
+            config: &self.config,
+            system_program: self.system_program.to_account_info(),
+        }
+    }
+}
+
+impl<'info> SyncBalance<'info> {
+    pub fn record_stake_balance(&mut self) -> RecordStakeBalance<'_, 'info> {
+        RecordStakeBalance {
+            payer: self.payer.to_account_info(),
+            current_delegate_stake_account_checkpoints: &mut self
+                .current_delegate_stake_account_checkpoints,
+            current_delegate_stake_account_checkpoints_next: self
+                .current_delegate_stake_account_checkpoints_next
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            current_delegate_stake_account_metadata: &mut self
+                .current_delegate_stake_account_metadata,
+            stake_account_metadata: &mut self.stake_account_metadata,
+            delegation_split: self.delegation_split.to_account_info(),
+            config: &self.config,
+            system_program: self.system_program.to_account_info(),
+        }
+    }
+}
+
+impl<'info> WithdrawTokens<'info> {
+    pub fn record_stake_balance(&mut self) -> RecordStakeBalance<'_, 'info> {
+        RecordStakeBalance {
+            payer: self.payer.to_account_info(),
+            current_delegate_stake_account_checkpoints: &mut self
+                .current_delegate_stake_account_checkpoints,
+            current_delegate_stake_account_checkpoints_next: self
+                .current_delegate_stake_account_checkpoints_next
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            current_delegate_stake_account_metadata: &mut self
+                .current_delegate_stake_account_metadata,
+            stake_account_metadata: &mut self.stake_account_metadata,
+            delegation_split: self.delegation_split.to_account_info(),
+            config: &self.config,
+            system_program: self.system_program.to_account_info(),
+        }
+    }
+}
+
+impl<'info> RequestWithdrawal<'info> {
+    pub fn record_stake_balance(&mut self) -> RecordStakeBalance<'_, 'info> {
+        RecordStakeBalance {
+            payer: self.payer.to_account_info(),
+            current_delegate_stake_account_checkpoints: &mut self
+                .current_delegate_stake_account_checkpoints,
+            current_delegate_stake_account_checkpoints_next: self
+                .current_delegate_stake_account_checkpoints_next
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            current_delegate_stake_account_metadata: &mut self
+                .current_delegate_stake_account_metadata,
+            stake_account_metadata: &mut self.stake_account_metadata,
+            delegation_split: self.delegation_split.to_account_info(),
+            config: &self.config,
+            system_program: self.system_program.to_account_info(),
+        }
+    }
+}
+
+impl<'a, 'info> RecordStakeBalance<'a, 'info> {
+    /// Records current_stake_balance as the balance of the stake account and checkpoints
+    /// the change for the current delegate, following the delegation chain with liquid
+    /// delegation, or for the split delegates while a delegation split is active
+    pub fn record(
+        self,
+        current_stake_balance: u64,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<RecordBalanceEvents> {
+        let recorded_balance = self.stake_account_metadata.recorded_balance;
+        let config = self.config;
+
+        let loaded_checkpoints = self.current_delegate_stake_account_checkpoints.load()?;
+        require!(
+            loaded_checkpoints.next_index < config.max_checkpoints_account_limit.into(),
+            ErrorCode::TooManyCheckpoints,
+        );
+        drop(loaded_checkpoints);
+
+        let current_timestamp: u64 = clock::get_current_time().try_into().unwrap();
+
+        let delegate_votes_changed = if current_stake_balance == recorded_balance {
+            Vec::new()
+        } else if self.stake_account_metadata.has_delegation_split {
+            // The votes are divided again between the split delegates
+            let total_delegated_votes = current_stake_balance
+                .checked_add(self.stake_account_metadata.recorded_vesting_balance)
+                .ok_or(ErrorCode::GenericOverflow)?;
+            push_delegation_split_votes(
+                &self.delegation_split,
+                |_| Ok(total_delegated_votes),
+                remaining_accounts,
+                &mut **self.stake_account_metadata,
+                &self.payer,
+                &self.system_program,
+                current_timestamp,
+                config.max_checkpoints_account_limit,
+            )?
+        } else {
+            let (amount_delta, operation) = if current_stake_balance > recorded_balance {
+                (current_stake_balance - recorded_balance, Operation::Add)
+            } else {
+                (
+                    recorded_balance - current_stake_balance,
+                    Operation::Subtract,
+                )
+            };
+
+            // Checkpoint rollovers have to update the metadata copy that is serialized last,
+            // so stake_account_metadata is updated when the delegate is the stake account owner
+            let current_delegate_is_user = self.current_delegate_stake_account_metadata.key()
+                == self.stake_account_metadata.key();
+
+            let delegate_votes_changed =
+                if !current_delegate_is_user && config.delegation_policy.is_liquid() {
+                    // With liquid delegation the votes follow the delegations of the current delegate
+                    push_delegation_chain_votes(
+                        &self.stake_account_metadata.owner,
+                        &mut **self.current_delegate_stake_account_metadata,
+                        self.current_delegate_stake_account_checkpoints,
+                        self.current_delegate_stake_account_checkpoints_next
+                            .as_ref(),
+                        amount_delta,
+                        operation,
+                        None,
+                        remaining_accounts,
+                        &self.payer,
+                        &self.system_program,
+                        current_timestamp,
+                        config.max_checkpoints_account_limit,
+                    )?
+                    .0
+                } else {
+                    let current_delegate_checkpoints_account_info = self
+                        .current_delegate_stake_account_checkpoints
+                        .to_account_info();
+
+                    Some(push_checkpoint(
+                        self.current_delegate_stake_account_checkpoints,
+                        &current_delegate_checkpoints_account_info,
+                        amount_delta,
+                        operation,
+                        current_timestamp,
+                        &self.payer,
+                        &self.system_program,
+                        CheckpointsRollover {
+                            stake_account_metadata: if current_delegate_is_user {
+                                &mut **self.stake_account_metadata
+                            } else {
+                                &mut **self.current_delegate_stake_account_metadata
+                            },
+                            next_checkpoints_account_info: self
+                                .current_delegate_stake_account_checkpoints_next
+                                .as_ref(),
+                            max_checkpoints_account_limit: config.max_checkpoints_account_limit,
+                        },
+                    )?)
+                };
+            delegate_votes_changed.into_iter().collect()
+        };
+
+        let recorded_balance_changed = self
+            .stake_account_metadata
+            .update_recorded_balance(current_stake_balance);
+
+        Ok(RecordBalanceEvents {
+            delegate_votes_changed,
+            recorded_balance_changed,
+        })
+    }
+}
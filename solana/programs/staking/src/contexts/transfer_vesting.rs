@@ -1,8 +1,12 @@
 use crate::context::{
-    CHECKPOINT_DATA_SEED, CONFIG_SEED, STAKE_ACCOUNT_METADATA_SEED, VESTING_BALANCE_SEED,
-    VESTING_CONFIG_SEED, VEST_SEED,
+    CHECKPOINT_DATA_SEED, CONFIG_SEED, DELEGATION_SPLIT_SEED, STAKE_ACCOUNT_METADATA_SEED,
+    VESTING_BALANCE_SEED, VESTING_CONFIG_SEED, VEST_SEED,
 };
-use crate::state::checkpoints::{push_checkpoint, CheckpointData, DelegateVotesChanged, Operation};
+use crate::state::checkpoints::{
+    push_checkpoint, CheckpointData, CheckpointsRollover, DelegateVotesChanged, Operation,
+};
+use crate::state::delegation_chain::push_delegation_chain_votes;
+use crate::state::delegation_split::push_delegation_split_votes;
 use crate::state::global_config::GlobalConfig;
 use crate::state::stake_account::{RecordedVestingBalanceChanged, StakeAccountMetadata};
 use crate::state::{Vesting, VestingBalance, VestingConfig};
@@ -66,12 +70,21 @@ pub struct TransferVesting<'info> {
     /// in order to be able to transfer vests that have not been delegated
     #[account(mut)]
     pub delegate_stake_account_checkpoints: Option<AccountLoader<'info, CheckpointData>>,
+    /// CHECK: Next CheckpointData PDA of the delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
     #[account(mut)]
     pub delegate_stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
     #[account(mut)]
     pub stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
     #[account(mut)]
     pub new_stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
+    /// CHECK: DelegationSplit PDA of the vester, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    /// instead of the delegate accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), vester.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
 
     token_program: Program<'info, Token>,
     system_program: Program<'info, System>,
@@ -80,7 +93,7 @@ pub struct TransferVesting<'info> {
 #[derive(AnchorDeserialize, AnchorSerialize)]
 pub struct StakeAccountMetadataEvents {
     pub recorded_vesting_balance_changed: RecordedVestingBalanceChanged,
-    pub delegate_votes_changed: Option<DelegateVotesChanged>,
+    pub delegate_votes_changed: Vec<DelegateVotesChanged>,
 }
 
 #[derive(AnchorDeserialize, AnchorSerialize)]
@@ -95,6 +108,64 @@ impl<'info> crate::contexts::TransferVesting<'info> {
         new_vester: Pubkey,
         new_vest_bump: u8,
         new_vesting_balance_bump: u8,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<TransferVestingEvents> {
+        let amount = self.vest.amount;
+
+        VestingTransfer {
+            vester: self.vester.to_account_info(),
+            vest: &self.vest,
+            new_vest: &mut self.new_vest,
+            vesting_balance: &mut self.vesting_balance,
+            new_vesting_balance: &mut self.new_vesting_balance,
+            global_config: &self.global_config,
+            delegate_stake_account_checkpoints: self.delegate_stake_account_checkpoints.as_mut(),
+            delegate_stake_account_checkpoints_next: self
+                .delegate_stake_account_checkpoints_next
+                .as_deref(),
+            delegate_stake_account_metadata: self.delegate_stake_account_metadata.as_deref_mut(),
+            stake_account_metadata: self.stake_account_metadata.as_deref_mut(),
+            new_stake_account_metadata: self.new_stake_account_metadata.as_deref_mut(),
+            delegation_split: self.delegation_split.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+        }
+        .transfer(
+            new_vester,
+            amount,
+            new_vest_bump,
+            new_vesting_balance_bump,
+            remaining_accounts,
+        )
+    }
+}
+
+/// Accounts updated when vested tokens move to another vester, shared by the instructions
+/// transferring vests: the amount is added to the vest of the new vester with the same
+/// maturation, and the votes of the vested amount follow it to the delegates of the new vester
+pub struct VestingTransfer<'a, 'info> {
+    pub vester: AccountInfo<'info>,
+    pub vest: &'a Account<'info, Vesting>,
+    pub new_vest: &'a mut Account<'info, Vesting>,
+    pub vesting_balance: &'a mut Account<'info, VestingBalance>,
+    pub new_vesting_balance: &'a mut Account<'info, VestingBalance>,
+    pub global_config: &'a GlobalConfig,
+    pub delegate_stake_account_checkpoints: Option<&'a mut AccountLoader<'info, CheckpointData>>,
+    pub delegate_stake_account_checkpoints_next: Option<&'a AccountInfo<'info>>,
+    pub delegate_stake_account_metadata: Option<&'a mut Account<'info, StakeAccountMetadata>>,
+    pub stake_account_metadata: Option<&'a mut Account<'info, StakeAccountMetadata>>,
+    pub new_stake_account_metadata: Option<&'a mut Account<'info, StakeAccountMetadata>>,
+    pub delegation_split: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+}
+
+impl<'a, 'info> VestingTransfer<'a, 'info> {
+    pub fn transfer(
+        mut self,
+        new_vester: Pubkey,
+        amount: u64,
+        new_vest_bump: u8,
+        new_vesting_balance_bump: u8,
+        remaining_accounts: &'info [AccountInfo<'info>],
     ) -> Result<TransferVestingEvents> {
         let mut transfer_vesting_events = TransferVestingEvents {
             stake_account_metadata: None,
@@ -111,6 +182,15 @@ impl<'info> crate::contexts::TransferVesting<'info> {
         let recipient_has_delegated_vest =
             self.new_vesting_balance.stake_account_metadata != Pubkey::default();
 
+        let delegation_split_is_active = self
+            .stake_account_metadata
+            .as_ref()
+            .is_some_and(|metadata| metadata.has_delegation_split);
+        let new_delegation_split_is_active = self
+            .new_stake_account_metadata
+            .as_ref()
+            .is_some_and(|metadata| metadata.has_delegation_split);
+
         // There are only 3 valid input account permutations.
         let delegate_votes_changed = match (
             sender_has_delegated_vest,
@@ -130,6 +210,13 @@ impl<'info> crate::contexts::TransferVesting<'info> {
                 Some(stake_account_metadata),
                 Some(new_stake_account_metadata),
             ) => {
+                // Delegation splits move the votes away from the delegate, so the transfer
+                // changes the voting power of the split delegates
+                require!(
+                    !delegation_split_is_active && !new_delegation_split_is_active,
+                    ErrorCode::DelegationSplitActive
+                );
+
                 // The sender must be delegated to the same address as the recipient.
                 require!(
                     stake_account_metadata.delegate == new_stake_account_metadata.delegate,
@@ -161,19 +248,29 @@ impl<'info> crate::contexts::TransferVesting<'info> {
 
                 let new_recorded_vesting_balance = new_stake_account_metadata
                     .recorded_vesting_balance
-                    .checked_add(self.vest.amount)
+                    .checked_add(amount)
                     .ok_or(VestingError::Overflow)?;
 
                 let recorded_vesting_balance_changed = new_stake_account_metadata
                     .update_recorded_vesting_balance(new_recorded_vesting_balance);
-                let delegate_votes_changed = None;
+
+                // With liquid delegation the votes the sender delegated to the recipient
+                // become votes of the recipient's own stake account
+                if self.global_config.delegation_policy.is_liquid()
+                    && new_stake_account_metadata.is_self_delegated()
+                {
+                    new_stake_account_metadata
+                        .update_delegated_votes(amount, &Operation::Subtract)?;
+                }
+
+                let delegate_votes_changed = Vec::new();
                 transfer_vesting_events.new_stake_account_metadata =
                     Some(StakeAccountMetadataEvents {
                         recorded_vesting_balance_changed,
                         delegate_votes_changed,
                     });
 
-                Ok(None)
+                Ok(Vec::new())
             }
             // If the sender has delegated their vest, but the recipient hasn't then all bar the new_stake_account_metadata account must be provided
             (
@@ -187,6 +284,12 @@ impl<'info> crate::contexts::TransferVesting<'info> {
                 // In this case the current delegate will have their voting balance reduced. That's what we do below.
                 // We don't update the recorded vesting balance of the sender here, we instead do it later outside of the match cases
 
+                // The votes of a split delegation are held by the split delegates instead
+                require!(
+                    !delegation_split_is_active,
+                    ErrorCode::DelegationSplitActive
+                );
+
                 // Check if stake account checkpoints is out of bounds
                 let loaded_checkpoints = delegate_stake_account_checkpoints.load()?;
                 require!(
@@ -232,31 +335,84 @@ impl<'info> crate::contexts::TransferVesting<'info> {
 
                 let current_timestamp: u64 = Clock::get()?.unix_timestamp.try_into()?;
 
-                let delegate_votes_changed = Some(push_checkpoint(
-                    delegate_stake_account_checkpoints,
-                    &delegate_checkpoints_account_info,
-                    self.vest.amount,
-                    Operation::Subtract,
-                    current_timestamp,
-                    &self.vester.to_account_info(),
-                    &self.system_program.to_account_info(),
-                )?);
-                let loaded_checkpoints = delegate_stake_account_checkpoints.load()?;
-                if loaded_checkpoints.next_index
-                    >= self.global_config.max_checkpoints_account_limit.into()
-                {
-                    if delegate_stake_account_metadata.key() == stake_account_metadata.key() {
-                        stake_account_metadata.stake_account_checkpoints_last_index += 1;
+                // stake_account_metadata is serialized after delegate_stake_account_metadata,
+                // so it has to be the one updated on rollover when both are the same account
+                let delegate_is_vester =
+                    delegate_stake_account_metadata.key() == stake_account_metadata.key();
+
+                let delegate_votes_changed =
+                    if !delegate_is_vester && self.global_config.delegation_policy.is_liquid() {
+                        // With liquid delegation the votes follow the delegations of the delegate
+                        push_delegation_chain_votes(
+                            &stake_account_metadata.owner,
+                            &mut ***delegate_stake_account_metadata,
+                            delegate_stake_account_checkpoints,
+                            self.delegate_stake_account_checkpoints_next,
+                            amount,
+                            Operation::Subtract,
+                            None,
+                            remaining_accounts,
+                            &self.vester.to_account_info(),
+                            &self.system_program.to_account_info(),
+                            current_timestamp,
+                            self.global_config.max_checkpoints_account_limit,
+                        )?
+                        .0
+                        .into_iter()
+                        .collect()
                     } else {
-                        delegate_stake_account_metadata.stake_account_checkpoints_last_index += 1;
-                    }
-                }
+                        vec![push_checkpoint(
+                            delegate_stake_account_checkpoints,
+                            &delegate_checkpoints_account_info,
+                            amount,
+                            Operation::Subtract,
+                            current_timestamp,
+                            &self.vester.to_account_info(),
+                            &self.system_program.to_account_info(),
+                            CheckpointsRollover {
+                                stake_account_metadata: if delegate_is_vester {
+                                    &mut ***stake_account_metadata
+                                } else {
+                                    &mut ***delegate_stake_account_metadata
+                                },
+                                next_checkpoints_account_info: self
+                                    .delegate_stake_account_checkpoints_next,
+                                max_checkpoints_account_limit: self
+                                    .global_config
+                                    .max_checkpoints_account_limit,
+                            },
+                        )?]
+                    };
 
                 Ok(delegate_votes_changed)
             }
+            // If the sender has delegated their vest through a delegation split, but the recipient hasn't then
+            // the accounts of the split delegates are passed as remaining accounts instead of the delegate accounts
+            (true, false, None, None, Some(stake_account_metadata), None)
+                if delegation_split_is_active =>
+            {
+                // The transferred votes are taken from the split delegates.
+                // We don't update the recorded vesting balance of the sender here, we instead do it later outside of the match cases
+                let current_timestamp: u64 = Clock::get()?.unix_timestamp.try_into()?;
+
+                push_delegation_split_votes(
+                    &self.delegation_split,
+                    |total_votes| {
+                        Ok(total_votes
+                            .checked_sub(amount)
+                            .ok_or(VestingError::Underflow)?)
+                    },
+                    remaining_accounts,
+                    &mut ***stake_account_metadata,
+                    &self.vester.to_account_info(),
+                    &self.system_program.to_account_info(),
+                    current_timestamp,
+                    self.global_config.max_checkpoints_account_limit,
+                )
+            }
             // If neither the sender or receiver has delegated their vests, then none of the optional accounts are needed
             // No changes need to be made to any accounts apart from the vesting balance accounts
-            (false, false, None, None, None, None) => Ok(None),
+            (false, false, None, None, None, None) => Ok(Vec::new()),
             // Any other combination is invalid
             _ => err!(VestingError::ErrorOfAccountParsing),
         }?;
@@ -285,7 +441,7 @@ impl<'info> crate::contexts::TransferVesting<'info> {
 
             let new_recorded_vesting_balance = stake_account_metadata
                 .recorded_vesting_balance
-                .checked_sub(self.vest.amount)
+                .checked_sub(amount)
                 .ok_or(VestingError::Underflow)?;
 
             let recorded_vesting_balance_changed = stake_account_metadata
@@ -303,7 +459,7 @@ impl<'info> crate::contexts::TransferVesting<'info> {
             amount: self
                 .new_vest
                 .amount
-                .checked_add(self.vest.amount)
+                .checked_add(amount)
                 .ok_or(VestingError::Overflow)?,
             maturation: self.vest.maturation,
             bump: new_vest_bump,
@@ -315,7 +471,7 @@ impl<'info> crate::contexts::TransferVesting<'info> {
             total_vesting_balance: self
                 .new_vesting_balance
                 .total_vesting_balance
-                .checked_add(self.vest.amount)
+                .checked_add(amount)
                 .ok_or(VestingError::Overflow)?,
             bump: new_vesting_balance_bump,
             rent_payer: if self.new_vesting_balance.rent_payer == Pubkey::default() {
@@ -328,7 +484,7 @@ impl<'info> crate::contexts::TransferVesting<'info> {
         self.vesting_balance.total_vesting_balance = self
             .vesting_balance
             .total_vesting_balance
-            .checked_sub(self.vest.amount)
+            .checked_sub(amount)
             .ok_or(VestingError::Underflow)?;
 
         Ok(transfer_vesting_events)
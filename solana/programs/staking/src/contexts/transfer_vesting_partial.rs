@@ -0,0 +1,137 @@
+use crate::context::{
+    CONFIG_SEED, DELEGATION_SPLIT_SEED, VESTING_BALANCE_SEED, VESTING_CONFIG_SEED, VEST_SEED,
+};
+use crate::contexts::{TransferVestingEvents, VestingTransfer};
+use crate::error::VestingError;
+use crate::state::checkpoints::CheckpointData;
+use crate::state::global_config::GlobalConfig;
+use crate::state::stake_account::StakeAccountMetadata;
+use crate::state::{Vesting, VestingBalance, VestingConfig};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token};
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(new_vester: Pubkey)]
+pub struct TransferVestingPartial<'info> {
+    #[account(mut)]
+    vester: Signer<'info>,
+    mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = config.finalized @ VestingError::VestingUnfinalized,
+        seeds = [VESTING_CONFIG_SEED.as_bytes(), mint.key().as_ref(), config.seed.to_le_bytes().as_ref()],
+        bump = config.bump
+    )]
+    config: Box<Account<'info, VestingConfig>>,
+    #[account(
+        mut,
+        constraint = vest.vester == vester.key() @ VestingError::InvalidVester,
+        has_one = config, // This check is arbitrary, as config is baked into the PDA
+        seeds = [VEST_SEED.as_bytes(), config.key().as_ref(), vester.key().as_ref(), vest.maturation.to_le_bytes().as_ref()],
+        bump = vest.bump
+    )]
+    vest: Box<Account<'info, Vesting>>,
+    #[account(
+        init_if_needed,
+        payer = vester,
+        space = Vesting::LEN,
+        seeds = [VEST_SEED.as_bytes(), config.key().as_ref(), new_vester.as_ref(), vest.maturation.to_le_bytes().as_ref()],
+        bump
+    )]
+    new_vest: Box<Account<'info, Vesting>>,
+    #[account(
+        mut,
+        has_one = vester, // This check is arbitrary, as vester is baked into the PDA
+        seeds = [VESTING_BALANCE_SEED.as_bytes(), config.key().as_ref(), vester.key().as_ref()],
+        bump = vesting_balance.bump
+    )]
+    vesting_balance: Box<Account<'info, VestingBalance>>,
+    #[account(
+        init_if_needed,
+        payer = vester,
+        space = VestingBalance::LEN,
+        seeds = [VESTING_BALANCE_SEED.as_bytes(), config.key().as_ref(), new_vester.as_ref()],
+        bump
+    )]
+    new_vesting_balance: Box<Account<'info, VestingBalance>>,
+    #[account(
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump = global_config.bump,
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    /// CheckpointData and StakeAccountMetadata accounts are optional because
+    /// in order to be able to transfer vests that have not been delegated
+    #[account(mut)]
+    pub delegate_stake_account_checkpoints: Option<AccountLoader<'info, CheckpointData>>,
+    /// CHECK: Next CheckpointData PDA of the delegate, created when the current one fills up.
+    /// The address is checked in push_checkpoint
+    #[account(mut)]
+    pub delegate_stake_account_checkpoints_next: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub delegate_stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
+    #[account(mut)]
+    pub stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
+    #[account(mut)]
+    pub new_stake_account_metadata: Option<Box<Account<'info, StakeAccountMetadata>>>,
+    /// CHECK: DelegationSplit PDA of the vester, it is only deserialized when it is initialized.
+    /// While the split is active, the accounts of its delegates are passed as remaining accounts
+    /// instead of the delegate accounts
+    #[account(mut, seeds = [DELEGATION_SPLIT_SEED.as_bytes(), vester.key().as_ref()], bump)]
+    pub delegation_split: UncheckedAccount<'info>,
+
+    token_program: Program<'info, Token>,
+    system_program: Program<'info, System>,
+}
+
+impl<'info> TransferVestingPartial<'info> {
+    /// Moves part of a vest to the vest of the new vester with the same maturation, the rest
+    /// stays with the vester. The vest is closed once its amount reaches zero
+    pub fn transfer_vesting_partial(
+        &mut self,
+        new_vester: Pubkey,
+        amount: u64,
+        new_vest_bump: u8,
+        new_vesting_balance_bump: u8,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<TransferVestingEvents> {
+        require!(
+            amount > 0 && amount <= self.vest.amount,
+            VestingError::InvalidTransferAmount
+        );
+
+        let transfer_vesting_events = VestingTransfer {
+            vester: self.vester.to_account_info(),
+            vest: &self.vest,
+            new_vest: &mut self.new_vest,
+            vesting_balance: &mut self.vesting_balance,
+            new_vesting_balance: &mut self.new_vesting_balance,
+            global_config: &self.global_config,
+            delegate_stake_account_checkpoints: self.delegate_stake_account_checkpoints.as_mut(),
+            delegate_stake_account_checkpoints_next: self
+                .delegate_stake_account_checkpoints_next
+                .as_deref(),
+            delegate_stake_account_metadata: self.delegate_stake_account_metadata.as_deref_mut(),
+            stake_account_metadata: self.stake_account_metadata.as_deref_mut(),
+            new_stake_account_metadata: self.new_stake_account_metadata.as_deref_mut(),
+            delegation_split: self.delegation_split.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+        }
+        .transfer(
+            new_vester,
+            amount,
+            new_vest_bump,
+            new_vesting_balance_bump,
+            remaining_accounts,
+        )?;
+
+        self.vest.amount -= amount;
+
+        if self.vest.amount == 0 {
+            self.vest.close(self.vester.to_account_info())?;
+        }
+
+        Ok(transfer_vesting_events)
+    }
+}
@@ -61,6 +61,88 @@ pub enum ErrorCode {
     InvalidAuthority,
     #[msg("Invalid pending authority")]
     InvalidPendingAuthority,
+    #[msg("Invalid next checkpoint data account")]
+    InvalidNextCheckpointDataAccount,
+    #[msg("Checkpoint data account is not retired")]
+    CheckpointDataAccountNotRetired,
+    #[msg("Checkpoints are still within the vote weight window")]
+    CheckpointsWithinVoteWeightWindow,
+    #[msg("Invalid checkpoint data account")]
+    InvalidCheckpointDataAccount,
+    #[msg("Checkpoint data accounts do not cover the timestamp")]
+    CheckpointDataAccountsOutOfRange,
+    #[msg("Invalid delegation split")]
+    InvalidDelegationSplit,
+    #[msg("Delegation split is active")]
+    DelegationSplitActive,
+    #[msg("Delegation split is not active")]
+    DelegationSplitNotActive,
+    #[msg("Invalid delegation split delegate accounts")]
+    InvalidDelegationSplitAccounts,
+    #[msg("Zero deposits not permitted")]
+    ZeroDeposit,
+    #[msg("Missing or invalid ed25519 signature verification instruction")]
+    InvalidEd25519Instruction,
+    #[msg("The delegation is not signed by the stake account owner")]
+    InvalidDelegationSignature,
+    #[msg("The delegation signature has expired")]
+    DelegationSignatureExpired,
+    #[msg("Invalid delegation nonce")]
+    InvalidDelegationNonce,
+    #[msg("Can't close a stake account with a non-zero staking or vesting balance")]
+    StakeAccountNotEmpty,
+    #[msg("Voting power is still delegated to the stake account")]
+    DelegatedVotesOutstanding,
+    #[msg("Invalid pending stake account owner")]
+    InvalidPendingStakeOwner,
+    #[msg("Vesting balance accounts do not match the recorded vesting balance")]
+    InvalidVestingBalanceAccounts,
+    #[msg("Withdrawals have to be requested while the withdrawal cooldown is enabled")]
+    WithdrawalCooldownEnabled,
+    #[msg("The withdrawal cooldown is not enabled")]
+    WithdrawalCooldownDisabled,
+    #[msg("The withdrawal cooldown has not elapsed")]
+    WithdrawalCooldownNotElapsed,
+    #[msg("Account already migrated")]
+    AccountAlreadyMigrated,
+    #[msg("Invalid delegation policy")]
+    InvalidDelegationPolicy,
+    #[msg("The delegation creates a delegation cycle")]
+    DelegationCycle,
+    #[msg("The delegation chain exceeds the maximum depth")]
+    DelegationChainTooDeep,
+    #[msg("Invalid delegation chain accounts")]
+    InvalidDelegationChainAccounts,
+    #[msg("Delegation splits are not supported with liquid delegation")]
+    DelegationSplitNotSupported,
+    #[msg("Votes can no longer be revised")]
+    VoteRevisionClosed,
+    #[msg("Voting period ended")]
+    VotingPeriodEnded,
+    #[msg("Vote end is not extended")]
+    VoteEndNotExtended,
+    #[msg("Invalid proposal accounts")]
+    InvalidProposalAccounts,
+    #[msg("Invalid hub proposal metadata source")]
+    InvalidHubProposalMetadataSource,
+    #[msg("Too many hub proposal metadata sources")]
+    TooManyHubProposalMetadataSources,
+    #[msg("Hub proposal metadata source not found")]
+    HubProposalMetadataSourceNotFound,
+    #[msg("Other stake accounts still delegate to the stake account")]
+    StakeAccountHasDelegators,
+    #[msg("The pending withdrawal has to be completed first")]
+    PendingWithdrawalOutstanding,
+    #[msg("The proposal started before the stake account moved to its owner")]
+    ProposalPredatesStakeOwnerChange,
+    #[msg("Stake account delegates to another stake account")]
+    StakeAccountDelegated,
+    #[msg("The delegation policy can not be switched while stake accounts delegate")]
+    DelegationPolicySwitchWithDelegations,
+    #[msg("The stake account metadata of the delegate has to be migrated first")]
+    DelegateNotMigrated,
+    #[msg("The revision lowers a tally below the votes published to the hub")]
+    VoteRevisionBelowPublishedVotes,
     #[msg("Other")]
     Other,
 }
@@ -109,6 +191,26 @@ pub enum VestingError {
     FrozenVesterAccount,
     #[msg("The token account owner does not match the vesting account")]
     InvalidVester,
+    #[msg("Invalid vesting schedule")]
+    InvalidVestingSchedule,
+    #[msg("Nothing to claim")]
+    NothingToClaim,
+    #[msg("Invalid claim amount")]
+    InvalidClaimAmount,
+    #[msg("Invalid vesting merkle distributor")]
+    InvalidMerkleDistributor,
+    #[msg("Invalid merkle proof")]
+    InvalidMerkleProof,
+    #[msg("Invalid merkle leaf index")]
+    InvalidMerkleLeafIndex,
+    #[msg("Vest already claimed")]
+    VestAlreadyClaimed,
+    #[msg("Invalid transfer amount")]
+    InvalidTransferAmount,
+    #[msg("The claim deadline of the merkle distributor has passed")]
+    MerkleClaimDeadlinePassed,
+    #[msg("The claim deadline of the merkle distributor has not passed yet")]
+    MerkleClaimDeadlineNotReached,
 }
 
 #[error_code]
@@ -165,6 +267,14 @@ pub enum ProposalWormholeMessageError {
     InvalidFunctionSignature,
     #[msg("Proposal not initialized since start is zero")]
     ProposalNotInitialized,
+    #[msg("Error of vote_end parsing")]
+    ErrorOfVoteEndParsing,
+    #[msg("Invalid hub proposal extender contract")]
+    InvalidHubProposalExtenderContract,
+    #[msg("The queried block is not old enough")]
+    QueryBlockTooRecent,
+    #[msg("The hub proposal metadata source is not active")]
+    InactiveHubProposalMetadataSource,
 }
 
 #[error_code]
@@ -9,28 +9,40 @@
 use crate::error::MessageExecutorError;
 use anchor_lang::prelude::borsh::BorshSchema;
 use anchor_lang::prelude::*;
-use anchor_spl::token::transfer;
+use anchor_spl::token::{close_account, transfer};
 use context::*;
 use contexts::*;
 use state::checkpoints::{
-    find_checkpoint_le, push_checkpoint, push_checkpoint_init, read_checkpoint_at_index, Operation,
+    check_checkpoints_account, check_checkpoints_account_data, checkpoints_len, find_checkpoint_le,
+    find_checkpoint_le_in_accounts, init_checkpoints_from_history, push_checkpoint,
+    push_checkpoint_init, read_checkpoint_at_index, require_checkpoint_expired, resize_account,
+    CheckpointData, CheckpointsRollover, DelegateVotesChanged, Operation,
 };
+use state::delegation_split::{
+    push_delegate_votes_updates, DelegationShareArgs, DelegationSplit, DelegationSplitChanged,
+};
+use state::global_config::DelegationPolicy;
+use state::pending_withdrawal::{WithdrawalCompleted, WithdrawalRequested};
+use state::proposal::ProposalData;
+use state::proposal_voters_weight_cast::ProposalVotersWeightCast;
+use state::spoke_metadata_collector::{
+    HubProposalMetadataSource, QueryPolicy, SpokeMetadataCollector,
+};
+use state::stake_account::{PendingStakeOwner, StakeAccountMetadata, StakeOwnerChanged};
 use std::convert::TryInto;
+use utils::query::VerifiedQueryResponse;
 
 use wormhole_solana_consts::{CORE_BRIDGE_PROGRAM_ID, SOLANA_CHAIN};
 
 use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
 use anchor_lang::solana_program::program::invoke_signed;
 
-use wormhole_query_sdk::structs::{
-    ChainSpecificQuery, ChainSpecificResponse, EthCallData, QueryResponse,
-};
-
-use crate::error::{
-    ErrorCode, ProposalWormholeMessageError, QueriesSolanaVerifyError, VestingError,
-};
+use crate::error::{ErrorCode, ProposalWormholeMessageError, QueriesSolanaVerifyError};
 use crate::state::GuardianSignatures;
-use crate::state::{find_window_length_le, init_window_length, push_new_window_length};
+use crate::state::{
+    find_max_window_length, find_window_length_le, init_window_length, push_new_window_length,
+    VestingBalance,
+};
 
 mod context;
 mod contexts;
@@ -66,10 +78,36 @@ pub struct VoteCast {
     pub abstain_votes: u64,
 }
 
+#[event]
+pub struct VoteRevised {
+    pub voter: Pubkey,
+    pub proposal_id: [u8; 32],
+    pub previous_weight: u64,
+    pub weight: u64,
+    pub against_votes: u64,
+    pub for_votes: u64,
+    pub abstain_votes: u64,
+}
+
+#[event]
+pub struct VotesPublished {
+    pub proposal_id: [u8; 32],
+    pub against_votes: u64,
+    pub for_votes: u64,
+    pub abstain_votes: u64,
+}
+
 #[event]
 pub struct ProposalCreated {
     pub proposal_id: [u8; 32],
     pub vote_start: u64,
+    pub vote_end: u64,
+}
+
+#[event]
+pub struct ProposalExtended {
+    pub proposal_id: [u8; 32],
+    pub vote_end: u64,
 }
 
 declare_id!("AFuHPdrQGsW8rNQ4oEFF35sm5fg36gwrxyqjkjKvi6ap");
@@ -99,20 +137,44 @@ pub mod staking {
             args.max_checkpoints_account_limit <= 655_000,
             ErrorCode::InvalidCheckpointAccountLimit
         );
-        // Similarly make sure max_checkpoints_account_limit > MAX_VOTE_WEIGHT_WINDOW_LENGTH so we can't have
-        // 3 checkpoint accounts fall across a window. We don't mind for our tests
-        #[cfg(not(feature = "testing"))]
-        {
-            require!(args.max_checkpoints_account_limit > state::vote_weight_window_lengths::VoteWeightWindowLengths::MAX_VOTE_WEIGHT_WINDOW_LENGTH as u32,
-            ErrorCode::InvalidCheckpointAccountLimit);
-        }
+        // cast_vote skips the last checkpoint of a filled checkpoint account, so every account
+        // has to hold at least one more checkpoint. A vote weight window may span any number
+        // of checkpoint accounts, they are passed to cast_vote as remaining accounts
+        require!(
+            args.max_checkpoints_account_limit >= 2,
+            ErrorCode::InvalidCheckpointAccountLimit
+        );
         config_account.max_checkpoints_account_limit = args.max_checkpoints_account_limit;
         config_account.pending_governance_authority = None;
         config_account.pending_vesting_admin = None;
+        config_account.withdrawal_cooldown = 0;
 
         Ok(())
     }
 
+    /// Grows a global config created with a previous layout. The fields added since then
+    /// are zeroed, which leaves the settings they hold disabled
+    pub fn migrate_global_config(ctx: Context<MigrateGlobalConfig>) -> Result<()> {
+        let config_info = ctx.accounts.config.to_account_info();
+        let config = state::global_config::GlobalConfig::from_previous_account_data(
+            &config_info.try_borrow_data()?,
+        )?;
+        require!(
+            config.governance_authority == ctx.accounts.governance_signer.key(),
+            ErrorCode::NotGovernanceAuthority
+        );
+
+        state::checkpoints::resize_account(
+            &config_info,
+            &ctx.accounts.governance_signer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            state::global_config::GlobalConfig::LEN,
+        )?;
+
+        let mut data = config_info.try_borrow_mut_data()?;
+        config.try_serialize(&mut &mut data[..])
+    }
+
     pub fn update_governance_authority(ctx: Context<UpdateGovernanceAuthority>) -> Result<()> {
         let config = &mut ctx.accounts.config;
         config.pending_governance_authority = Some(ctx.accounts.new_authority.key());
@@ -139,6 +201,53 @@ pub mod staking {
         Ok(())
     }
 
+    /// Sets the delay between a withdrawal request and its completion
+    /// While the cooldown is 0, withdraw_tokens transfers the tokens immediately
+    pub fn update_withdrawal_cooldown(
+        ctx: Context<UpdateWithdrawalCooldown>,
+        withdrawal_cooldown: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.withdrawal_cooldown = withdrawal_cooldown;
+        Ok(())
+    }
+
+    /// Sets how many seconds before the vote end of a proposal votes can no longer be revised
+    pub fn update_vote_revision_cutoff(
+        ctx: Context<UpdateVoteRevisionCutoff>,
+        vote_revision_cutoff: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.vote_revision_cutoff = vote_revision_cutoff;
+        Ok(())
+    }
+
+    /// Chooses between single hop and liquid delegation. The delegated votes of liquid
+    /// delegation are only tracked while it is enabled, so switching between single hop
+    /// and liquid delegation is rejected while stake accounts delegate to other stake accounts.
+    /// Stake accounts created with a previous layout are only counted once migrated, so they
+    /// have to be migrated before switching
+    pub fn update_delegation_policy(
+        ctx: Context<UpdateDelegationPolicy>,
+        delegation_policy: DelegationPolicy,
+    ) -> Result<()> {
+        if let Some(max_depth) = delegation_policy.max_depth() {
+            require!(
+                max_depth >= 1 && max_depth <= DelegationPolicy::MAX_DEPTH,
+                ErrorCode::InvalidDelegationPolicy
+            );
+        }
+        let config = &mut ctx.accounts.config;
+        if config.delegation_policy.is_liquid() != delegation_policy.is_liquid() {
+            require!(
+                config.delegating_stake_accounts == 0,
+                ErrorCode::DelegationPolicySwitchWithDelegations
+            );
+        }
+        config.delegation_policy = delegation_policy;
+        Ok(())
+    }
+
     /// Trustless instruction that creates a stake account for a user
     #[inline(never)]
     pub fn create_stake_account(ctx: Context<CreateStakeAccount>) -> Result<()> {
@@ -160,6 +269,295 @@ pub mod staking {
         Ok(())
     }
 
+    /// Closes the stake account of the owner and refunds the rent of its metadata, custody,
+    /// delegation split, pending stake owner and CheckpointData accounts. The stake account must
+    /// hold no tokens or vesting balance, have no pending withdrawal, be self-delegated, no other
+    /// stake account may delegate to it and its latest checkpoint must be older than the largest vote weight window
+    /// plus the longest voting period of the stored proposals, as for compact_checkpoints.
+    /// Delegators with a previous layout are only counted once migrated, the empty latest
+    /// checkpoint shows they hold no votes, and they delegate to themselves again when migrated.
+    /// The DelegationNonce is kept, so that delegate_by_sig signatures of the owner
+    /// cannot be replayed if the stake account is created again.
+    /// Every previous CheckpointData account, from index 0, is passed as a remaining account,
+    /// including the ones already closed by compact_checkpoints
+    pub fn close_stake_account<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CloseStakeAccount<'info>>,
+    ) -> Result<()> {
+        let owner = ctx.accounts.owner.key();
+
+        require!(
+            !ctx.accounts.stake_account_metadata.has_delegation_split,
+            ErrorCode::DelegationSplitActive
+        );
+        // Stake accounts delegating to it with liquid delegation still route their votes through it
+        require!(
+            ctx.accounts.stake_account_metadata.delegated_votes == 0,
+            ErrorCode::DelegatedVotesOutstanding
+        );
+
+        let stake_account_checkpoints_account_info =
+            ctx.accounts.stake_account_checkpoints.to_account_info();
+        let stake_account_checkpoints = ctx.accounts.stake_account_checkpoints.load()?;
+        if stake_account_checkpoints.next_index > 0 {
+            let latest_checkpoint = read_checkpoint_at_index(
+                &stake_account_checkpoints_account_info,
+                stake_account_checkpoints.next_index as usize - 1,
+            )?;
+            require!(
+                latest_checkpoint.value == 0,
+                ErrorCode::DelegatedVotesOutstanding
+            );
+
+            let max_window_length =
+                find_max_window_length(&ctx.accounts.vote_weight_window_lengths.to_account_info())?;
+            let current_timestamp: u64 = utils::clock::get_current_time().try_into()?;
+            require_checkpoint_expired(
+                &latest_checkpoint,
+                max_window_length,
+                ctx.accounts.config.max_voting_period,
+                current_timestamp,
+            )?;
+        }
+        drop(stake_account_checkpoints);
+
+        // Every CheckpointData account has to be closed, so that the stake account
+        // can be created again from index 0
+        let stake_account_checkpoints_last_index = ctx
+            .accounts
+            .stake_account_metadata
+            .stake_account_checkpoints_last_index;
+        require!(
+            ctx.remaining_accounts.len() == usize::from(stake_account_checkpoints_last_index),
+            ErrorCode::InvalidCheckpointDataAccount
+        );
+        for (index, checkpoints_account_info) in ctx.remaining_accounts.iter().enumerate() {
+            let (expected_address, _) = Pubkey::find_program_address(
+                &[
+                    CHECKPOINT_DATA_SEED.as_bytes(),
+                    owner.as_ref(),
+                    (index as u16).to_le_bytes().as_ref(),
+                ],
+                &crate::ID,
+            );
+            require!(
+                checkpoints_account_info.key() == expected_address,
+                ErrorCode::InvalidCheckpointDataAccount
+            );
+
+            // Accounts closed by compact_checkpoints are owned by the system program again
+            if *checkpoints_account_info.owner == crate::ID {
+                AccountLoader::<CheckpointData>::try_from(checkpoints_account_info)?
+                    .close(ctx.accounts.owner.to_account_info())?;
+            }
+        }
+
+        let delegation_split_account_info = ctx.accounts.delegation_split.to_account_info();
+        if *delegation_split_account_info.owner == crate::ID {
+            Account::<DelegationSplit>::try_from(&delegation_split_account_info)?
+                .close(ctx.accounts.owner.to_account_info())?;
+        }
+
+        let pending_stake_owner_account_info = ctx.accounts.pending_stake_owner.to_account_info();
+        if *pending_stake_owner_account_info.owner == crate::ID {
+            Account::<PendingStakeOwner>::try_from(&pending_stake_owner_account_info)?
+                .close(ctx.accounts.owner.to_account_info())?;
+        }
+
+        close_account(CpiContext::from(&*ctx.accounts).with_signer(&[&[
+            AUTHORITY_SEED.as_bytes(),
+            owner.as_ref(),
+            &[ctx.accounts.stake_account_metadata.authority_bump],
+        ]]))?;
+
+        Ok(())
+    }
+
+    /// Proposes a new owner for the stake account of the signer,
+    /// who becomes the owner once they call accept_stake_owner
+    pub fn propose_stake_owner(ctx: Context<ProposeStakeOwner>) -> Result<()> {
+        let pending_stake_owner = &mut ctx.accounts.pending_stake_owner;
+        pending_stake_owner.bump = ctx.bumps.pending_stake_owner;
+        pending_stake_owner.owner = ctx.accounts.owner.key();
+        pending_stake_owner.pending_owner = ctx.accounts.new_owner.key();
+        Ok(())
+    }
+
+    /// Moves the stake account to the pending owner. The custody tokens, the recorded balances,
+    /// the delegation and the vesting balances linked to the stake account are migrated to
+    /// a new stake account of the pending owner, and the previous stake account is left empty.
+    /// The checkpoints of the latest CheckpointData account of a self-delegated stake account are
+    /// copied to the new owner, so that the vote weight windows spanning the transfer can be read.
+    /// The votes already cast with them are recorded for the previous owner, so the new owner
+    /// cannot vote on proposals that started before the transfer.
+    /// Every VestingBalance linked to the stake account is passed as a remaining account
+    pub fn accept_stake_owner<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AcceptStakeOwner<'info>>,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.stake_account_metadata.has_delegation_split,
+            ErrorCode::DelegationSplitActive
+        );
+        // Stake accounts delegating to it with liquid delegation still route their votes through it
+        require!(
+            ctx.accounts.stake_account_metadata.delegated_votes == 0,
+            ErrorCode::DelegatedVotesOutstanding
+        );
+        // The delegation chains ending at it would stay counted in the delegation chain
+        // of its delegate, which is taken over by the new stake account
+        require!(
+            ctx.accounts.stake_account_metadata.is_self_delegated()
+                || ctx.accounts.stake_account_metadata.delegation_depth() == 0,
+            ErrorCode::StakeAccountHasDelegators
+        );
+
+        let owner = ctx.accounts.owner.key();
+        let new_owner = ctx.accounts.new_owner.key();
+        let stake_account_metadata_key = ctx.accounts.stake_account_metadata.key();
+        let new_stake_account_metadata_key = ctx.accounts.new_stake_account_metadata.key();
+
+        let recorded_balance = ctx.accounts.stake_account_metadata.recorded_balance;
+        let recorded_vesting_balance = ctx.accounts.stake_account_metadata.recorded_vesting_balance;
+
+        // The vesting balances linked to the stake account have to add up to its recorded vesting
+        // balance, so that all of them are moved to the new stake account
+        let mut linked_vesting_balance: u64 = 0;
+        for vesting_balance_account_info in ctx.remaining_accounts {
+            let mut vesting_balance =
+                Account::<VestingBalance>::try_from(vesting_balance_account_info)?;
+            require!(
+                vesting_balance.stake_account_metadata == stake_account_metadata_key,
+                ErrorCode::InvalidVestingBalanceAccounts
+            );
+            vesting_balance.stake_account_metadata = new_stake_account_metadata_key;
+            linked_vesting_balance = linked_vesting_balance
+                .checked_add(vesting_balance.total_vesting_balance)
+                .ok_or(ErrorCode::GenericOverflow)?;
+            vesting_balance.exit(&crate::ID)?;
+        }
+        require!(
+            linked_vesting_balance == recorded_vesting_balance,
+            ErrorCode::InvalidVestingBalanceAccounts
+        );
+
+        let custody_balance = ctx.accounts.stake_account_custody.amount;
+        if custody_balance > 0 {
+            transfer(
+                CpiContext::from(&*ctx.accounts).with_signer(&[&[
+                    AUTHORITY_SEED.as_bytes(),
+                    owner.as_ref(),
+                    &[ctx.accounts.stake_account_metadata.authority_bump],
+                ]]),
+                custody_balance,
+            )?;
+        }
+
+        // Votes delegated to another account stay with that delegate,
+        // while the votes of a self-delegated stake account move to the new owner
+        let current_delegate = ctx.accounts.stake_account_metadata.delegate;
+        let self_delegated = ctx.accounts.stake_account_metadata.is_self_delegated();
+        let new_delegate = if self_delegated {
+            new_owner
+        } else {
+            current_delegate
+        };
+
+        let current_timestamp: u64 = utils::clock::get_current_time().try_into().unwrap();
+
+        ctx.accounts.new_stake_account_metadata.initialize(
+            ctx.bumps.new_stake_account_metadata,
+            ctx.bumps.new_stake_account_custody,
+            ctx.bumps.new_custody_authority,
+            &new_owner,
+            &new_delegate,
+            0u16,
+        );
+        ctx.accounts.new_stake_account_metadata.owner_changed_at = current_timestamp;
+        let mut new_stake_account_checkpoints =
+            ctx.accounts.new_stake_account_checkpoints.load_init()?;
+        new_stake_account_checkpoints.initialize(&new_owner);
+        drop(new_stake_account_checkpoints);
+
+        let total_delegated_votes = recorded_balance
+            .checked_add(recorded_vesting_balance)
+            .ok_or(ErrorCode::GenericOverflow)?;
+
+        if self_delegated && total_delegated_votes > 0 {
+            let config = &ctx.accounts.config;
+            let loaded_checkpoints = ctx.accounts.stake_account_checkpoints.load()?;
+            require!(
+                loaded_checkpoints.next_index < config.max_checkpoints_account_limit.into(),
+                ErrorCode::TooManyCheckpoints,
+            );
+            drop(loaded_checkpoints);
+
+            // The history is copied before the votes of the previous owner are checkpointed
+            let new_stake_account_checkpoints_account_info =
+                ctx.accounts.new_stake_account_checkpoints.to_account_info();
+            init_checkpoints_from_history(
+                &mut ctx.accounts.new_stake_account_checkpoints,
+                &new_stake_account_checkpoints_account_info,
+                &ctx.accounts.stake_account_checkpoints,
+                total_delegated_votes,
+                current_timestamp,
+                config.max_checkpoints_account_limit,
+                &ctx.accounts.new_owner.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
+            emit_cpi!(DelegateVotesChanged {
+                delegate: new_owner,
+                previous_balance: 0,
+                new_balance: total_delegated_votes,
+            });
+
+            let stake_account_checkpoints_account_info =
+                ctx.accounts.stake_account_checkpoints.to_account_info();
+            let delegate_votes_changed = push_checkpoint(
+                &mut ctx.accounts.stake_account_checkpoints,
+                &stake_account_checkpoints_account_info,
+                total_delegated_votes,
+                Operation::Subtract,
+                current_timestamp,
+                &ctx.accounts.new_owner.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                CheckpointsRollover {
+                    stake_account_metadata: &mut **ctx.accounts.stake_account_metadata,
+                    next_checkpoints_account_info: ctx
+                        .accounts
+                        .stake_account_checkpoints_next
+                        .as_deref(),
+                    max_checkpoints_account_limit: config.max_checkpoints_account_limit,
+                },
+            )?;
+            emit_cpi!(delegate_votes_changed);
+        }
+
+        let stake_account_metadata = &mut ctx.accounts.stake_account_metadata;
+        let recorded_balance_changed = stake_account_metadata.update_recorded_balance(0);
+        emit_cpi!(recorded_balance_changed);
+        let recorded_vesting_balance_changed =
+            stake_account_metadata.update_recorded_vesting_balance(0);
+        emit_cpi!(recorded_vesting_balance_changed);
+        stake_account_metadata.delegate = owner;
+
+        let new_stake_account_metadata = &mut ctx.accounts.new_stake_account_metadata;
+        let recorded_balance_changed =
+            new_stake_account_metadata.update_recorded_balance(recorded_balance);
+        emit_cpi!(recorded_balance_changed);
+        let recorded_vesting_balance_changed =
+            new_stake_account_metadata.update_recorded_vesting_balance(recorded_vesting_balance);
+        emit_cpi!(recorded_vesting_balance_changed);
+
+        emit_cpi!(StakeOwnerChanged {
+            previous_owner: owner,
+            new_owner,
+            stake_account_metadata: new_stake_account_metadata_key,
+        });
+
+        Ok(())
+    }
+
+    /// Finishes a checkpoint rollover of a previous program version, which advanced
+    /// stake_account_checkpoints_last_index without creating the next CheckpointData account
     pub fn create_checkpoints(ctx: Context<CreateCheckpoints>) -> Result<()> {
         let mut new_stake_account_checkpoints =
             ctx.accounts.new_stake_account_checkpoints.load_init()?;
@@ -187,244 +585,206 @@ pub mod staking {
         Ok(())
     }
 
-    pub fn delegate(
-        ctx: Context<Delegate>,
-        delegatee: Pubkey,
-        _current_delegate_stake_account_owner: Pubkey,
+    /// Closes a retired CheckpointData account and refunds its rent to the owner.
+    /// The account can only be closed once its latest checkpoint is older than the largest
+    /// vote weight window ever recorded plus the longest voting period of the stored proposals,
+    /// so the closed history is not needed for any proposal that still accepts votes.
+    /// Proposals whose vote end is unknown are not covered until their vote end is extended.
+    /// Later windows are covered by the following CheckpointData accounts, the first of which
+    /// starts with a copy of that checkpoint
+    pub fn compact_checkpoints(
+        ctx: Context<CompactCheckpoints>,
+        _stake_account_checkpoints_index: u16,
     ) -> Result<()> {
-        let stake_account_metadata = &mut ctx.accounts.stake_account_metadata;
-        let config = &ctx.accounts.config;
-
-        let delegatee_stake_account_checkpoints =
-            ctx.accounts.delegatee_stake_account_checkpoints.load()?;
-        let current_delegate_stake_account_checkpoints = ctx
-            .accounts
-            .current_delegate_stake_account_checkpoints
-            .load()?;
-        require!(
-            delegatee_stake_account_checkpoints.next_index
-                < config.max_checkpoints_account_limit.into(),
-            ErrorCode::TooManyCheckpoints,
-        );
-        require!(
-            current_delegate_stake_account_checkpoints.next_index
-                < config.max_checkpoints_account_limit.into(),
-            ErrorCode::TooManyCheckpoints,
-        );
-        drop(delegatee_stake_account_checkpoints);
-        drop(current_delegate_stake_account_checkpoints);
+        let stake_account_checkpoints_account_info =
+            ctx.accounts.stake_account_checkpoints.to_account_info();
+        let stake_account_checkpoints = ctx.accounts.stake_account_checkpoints.load()?;
 
-        let prev_recorded_total_balance = stake_account_metadata
-            .recorded_balance
-            .checked_add(stake_account_metadata.recorded_vesting_balance)
-            .unwrap();
+        if stake_account_checkpoints.next_index > 0 {
+            let latest_checkpoint = read_checkpoint_at_index(
+                &stake_account_checkpoints_account_info,
+                stake_account_checkpoints.next_index as usize - 1,
+            )?;
 
-        let current_stake_balance = ctx.accounts.stake_account_custody.amount;
+            let max_window_length =
+                find_max_window_length(&ctx.accounts.vote_weight_window_lengths.to_account_info())?;
+            let current_timestamp: u64 = utils::clock::get_current_time().try_into()?;
 
-        if let Some(vesting_config) = &mut ctx.accounts.vesting_config {
-            if vesting_config.finalized {
-                if let Some(vesting_balance) = &mut ctx.accounts.vesting_balance {
-                    let (expected_vesting_balance_pda, _) = Pubkey::find_program_address(
-                        &[
-                            VESTING_BALANCE_SEED.as_bytes(),
-                            vesting_config.key().as_ref(),
-                            stake_account_metadata.owner.as_ref(),
-                        ],
-                        &crate::ID,
-                    );
-                    require!(
-                        expected_vesting_balance_pda == vesting_balance.key(),
-                        VestingError::InvalidVestingBalancePDA
-                    );
+            require_checkpoint_expired(
+                &latest_checkpoint,
+                max_window_length,
+                ctx.accounts.config.max_voting_period,
+                current_timestamp,
+            )?;
+        }
 
-                    if vesting_balance.stake_account_metadata == Pubkey::default() {
-                        vesting_balance.stake_account_metadata = stake_account_metadata.key();
+        Ok(())
+    }
 
-                        let new_recorded_vesting_balance = stake_account_metadata
-                            .recorded_vesting_balance
-                            .checked_add(vesting_balance.total_vesting_balance)
-                            .ok_or(VestingError::Overflow)?;
+    /// Returns the current voting weight of owner
+    pub fn get_votes(ctx: Context<GetVotes>, _owner: Pubkey) -> Result<u64> {
+        let stake_account_checkpoints_account_info =
+            ctx.accounts.stake_account_checkpoints.to_account_info();
+        let stake_account_checkpoints = ctx.accounts.stake_account_checkpoints.load()?;
 
-                        // Update the recorded vesting balance
-                        let recorded_vesting_balance_changed = stake_account_metadata
-                            .update_recorded_vesting_balance(new_recorded_vesting_balance);
-                        emit_cpi!(recorded_vesting_balance_changed);
-                    }
-                }
-            }
+        if stake_account_checkpoints.next_index == 0 {
+            return Ok(0);
         }
 
-        let current_delegate = stake_account_metadata.delegate;
-        stake_account_metadata.delegate = delegatee;
+        let latest_checkpoint = read_checkpoint_at_index(
+            &stake_account_checkpoints_account_info,
+            stake_account_checkpoints.next_index as usize - 1,
+        )?;
 
-        let total_delegated_votes = current_stake_balance
-            .checked_add(stake_account_metadata.recorded_vesting_balance)
-            .unwrap();
+        Ok(latest_checkpoint.value)
+    }
 
-        emit!(DelegateChanged {
-            delegator: ctx.accounts.payer.key(),
-            from_delegate: current_delegate,
-            to_delegate: delegatee,
-            total_delegated_votes,
-        });
+    /// Returns the voting weight of owner at a past timestamp.
+    /// The consecutive CheckpointData accounts of owner starting at first_stake_account_checkpoints_index
+    /// are passed as remaining accounts and have to include the account that holds the timestamp
+    pub fn get_past_votes(
+        ctx: Context<GetPastVotes>,
+        owner: Pubkey,
+        timestamp: u64,
+        first_stake_account_checkpoints_index: u16,
+    ) -> Result<u64> {
+        let current_timestamp: u64 = utils::clock::get_current_time().try_into()?;
+        require!(timestamp < current_timestamp, ErrorCode::InvalidTimestamp);
 
-        emit_cpi!(DelegateChanged {
-            delegator: ctx.accounts.payer.key(),
-            from_delegate: current_delegate,
-            to_delegate: delegatee,
-            total_delegated_votes,
-        });
+        let checkpoints_account_infos = ctx.remaining_accounts;
+        require!(
+            !checkpoints_account_infos.is_empty(),
+            ErrorCode::CheckpointDataAccountsOutOfRange
+        );
 
-        let current_timestamp: u64 = utils::clock::get_current_time().try_into().unwrap();
+        for (position, checkpoints_account_info) in checkpoints_account_infos.iter().enumerate() {
+            let index = u16::try_from(position)
+                .ok()
+                .and_then(|position| first_stake_account_checkpoints_index.checked_add(position))
+                .ok_or(ErrorCode::GenericOverflow)?;
+            check_checkpoints_account(checkpoints_account_info, &owner, index)?;
+        }
 
-        if current_delegate != delegatee {
-            if prev_recorded_total_balance > 0 {
-                let current_delegate_checkpoints_account_info = ctx
-                    .accounts
-                    .current_delegate_stake_account_checkpoints
-                    .to_account_info();
-
-                let delegate_votes_changed = push_checkpoint(
-                    &mut ctx.accounts.current_delegate_stake_account_checkpoints,
-                    &current_delegate_checkpoints_account_info,
-                    prev_recorded_total_balance,
-                    Operation::Subtract,
-                    current_timestamp,
-                    &ctx.accounts.payer.to_account_info(),
-                    &ctx.accounts.system_program.to_account_info(),
-                )?;
-                emit_cpi!(delegate_votes_changed);
-            }
+        let last_position = checkpoints_account_infos.len() - 1;
+        let last_index = first_stake_account_checkpoints_index + last_position as u16;
+
+        match find_checkpoint_le_in_accounts(checkpoints_account_infos, timestamp)? {
+            Some((position, checkpoint)) => {
+                // A retired account ends with the checkpoint copied to the next account,
+                // so the next account has to be provided for timestamps from that checkpoint on
+                if position == last_position
+                    && last_index
+                        < ctx
+                            .accounts
+                            .stake_account_metadata
+                            .stake_account_checkpoints_last_index
+                {
+                    let last_checkpoint = read_checkpoint_at_index(
+                        &checkpoints_account_infos[position],
+                        checkpoints_len(&checkpoints_account_infos[position])? - 1,
+                    )?;
+                    require!(
+                        timestamp < last_checkpoint.timestamp,
+                        ErrorCode::CheckpointDataAccountsOutOfRange
+                    );
+                }
 
-            if total_delegated_votes > 0 {
-                let delegatee_checkpoints_account_info = ctx
-                    .accounts
-                    .delegatee_stake_account_checkpoints
-                    .to_account_info();
-
-                let delegate_votes_changed = push_checkpoint(
-                    &mut ctx.accounts.delegatee_stake_account_checkpoints,
-                    &delegatee_checkpoints_account_info,
-                    total_delegated_votes,
-                    Operation::Add,
-                    current_timestamp,
-                    &ctx.accounts.payer.to_account_info(),
-                    &ctx.accounts.system_program.to_account_info(),
-                )?;
-                emit_cpi!(delegate_votes_changed);
+                Ok(checkpoint.value)
             }
-        } else if total_delegated_votes != prev_recorded_total_balance {
-            let delegatee_checkpoints_account_info = ctx
-                .accounts
-                .delegatee_stake_account_checkpoints
-                .to_account_info();
+            None => {
+                // There is no voting weight before the first checkpoint of the first account
+                require!(
+                    first_stake_account_checkpoints_index == 0,
+                    ErrorCode::CheckpointDataAccountsOutOfRange
+                );
+
+                Ok(0)
+            }
+        }
+    }
 
-            let (amount_delta, operation) = if total_delegated_votes > prev_recorded_total_balance {
-                (
-                    total_delegated_votes
-                        .checked_sub(prev_recorded_total_balance)
-                        .unwrap(),
-                    Operation::Add,
-                )
-            } else {
-                (
-                    prev_recorded_total_balance
-                        .checked_sub(total_delegated_votes)
-                        .unwrap(),
-                    Operation::Subtract,
-                )
-            };
+    pub fn delegate<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Delegate<'info>>,
+        delegatee: Pubkey,
+        _current_delegate_stake_account_owner: Pubkey,
+    ) -> Result<()> {
+        let delegate_events = ctx
+            .accounts
+            .delegate_stake()
+            .delegate(delegatee, ctx.remaining_accounts)?;
 
-            let delegate_votes_changed = push_checkpoint(
-                &mut ctx.accounts.delegatee_stake_account_checkpoints,
-                &delegatee_checkpoints_account_info,
-                amount_delta,
-                operation,
-                current_timestamp,
-                &ctx.accounts.payer.to_account_info(),
-                &ctx.accounts.system_program.to_account_info(),
-            )?;
+        if let Some(recorded_vesting_balance_changed) =
+            delegate_events.recorded_vesting_balance_changed
+        {
+            emit_cpi!(recorded_vesting_balance_changed);
+        }
+        for delegate_changed in delegate_events.delegate_changed {
+            emit_cpi!(delegate_changed);
+        }
+        for delegate_votes_changed in delegate_events.delegate_votes_changed {
             emit_cpi!(delegate_votes_changed);
         }
-
-        if current_stake_balance != stake_account_metadata.recorded_balance {
-            let recorded_balance_changed =
-                stake_account_metadata.update_recorded_balance(current_stake_balance);
+        if let Some(recorded_balance_changed) = delegate_events.recorded_balance_changed {
             emit_cpi!(recorded_balance_changed);
         }
 
-        let delegatee_stake_account_checkpoints =
-            ctx.accounts.delegatee_stake_account_checkpoints.load()?;
-        let current_delegate_stake_account_checkpoints = ctx
+        Ok(())
+    }
+
+    /// Delegates the voting power of a stake account with an off-chain ed25519 signature
+    /// of its owner over (delegatee, nonce, expiry), prefixed by a domain string and the program id,
+    /// so that a relayer can pay for the delegation.
+    /// The signature is verified by an ed25519 program instruction preceding this one
+    pub fn delegate_by_sig<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DelegateBySig<'info>>,
+        delegatee: Pubkey,
+        _current_delegate_stake_account_owner: Pubkey,
+        stake_account_owner: Pubkey,
+        nonce: u64,
+        expiry: i64,
+    ) -> Result<()> {
+        ctx.accounts.use_delegation_signature(
+            ctx.bumps.delegation_nonce,
+            delegatee,
+            stake_account_owner,
+            nonce,
+            expiry,
+        )?;
+
+        let delegate_events = ctx
             .accounts
-            .current_delegate_stake_account_checkpoints
-            .load()?;
+            .delegate_stake()
+            .delegate(delegatee, ctx.remaining_accounts)?;
 
-        if ctx.accounts.delegatee_stake_account_checkpoints.key()
-            == ctx
-                .accounts
-                .current_delegate_stake_account_checkpoints
-                .key()
+        if let Some(recorded_vesting_balance_changed) =
+            delegate_events.recorded_vesting_balance_changed
         {
-            if delegatee_stake_account_checkpoints.next_index
-                >= config.max_checkpoints_account_limit.into()
-            {
-                if ctx.accounts.delegatee_stake_account_metadata.key()
-                    == ctx.accounts.stake_account_metadata.key()
-                {
-                    ctx.accounts
-                        .stake_account_metadata
-                        .stake_account_checkpoints_last_index += 1;
-                } else {
-                    ctx.accounts
-                        .delegatee_stake_account_metadata
-                        .stake_account_checkpoints_last_index += 1;
-                }
-            }
-        } else {
-            if delegatee_stake_account_checkpoints.next_index
-                >= config.max_checkpoints_account_limit.into()
-            {
-                if ctx.accounts.delegatee_stake_account_metadata.key()
-                    == ctx.accounts.stake_account_metadata.key()
-                {
-                    ctx.accounts
-                        .stake_account_metadata
-                        .stake_account_checkpoints_last_index += 1;
-                } else {
-                    ctx.accounts
-                        .delegatee_stake_account_metadata
-                        .stake_account_checkpoints_last_index += 1;
-                }
-            }
-
-            if current_delegate_stake_account_checkpoints.next_index
-                >= config.max_checkpoints_account_limit.into()
-            {
-                if ctx.accounts.current_delegate_stake_account_metadata.key()
-                    == ctx.accounts.stake_account_metadata.key()
-                {
-                    ctx.accounts
-                        .stake_account_metadata
-                        .stake_account_checkpoints_last_index += 1;
-                } else {
-                    ctx.accounts
-                        .current_delegate_stake_account_metadata
-                        .stake_account_checkpoints_last_index += 1;
-                }
-            }
+            emit_cpi!(recorded_vesting_balance_changed);
+        }
+        for delegate_changed in delegate_events.delegate_changed {
+            emit_cpi!(delegate_changed);
+        }
+        for delegate_votes_changed in delegate_events.delegate_votes_changed {
+            emit_cpi!(delegate_votes_changed);
+        }
+        if let Some(recorded_balance_changed) = delegate_events.recorded_balance_changed {
+            emit_cpi!(recorded_balance_changed);
         }
 
         Ok(())
     }
 
-    pub fn withdraw_tokens(
-        ctx: Context<WithdrawTokens>,
+    pub fn withdraw_tokens<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawTokens<'info>>,
         amount: u64,
         _current_delegate_stake_account_metadata_owner: Pubkey,
         _stake_account_metadata_owner: Pubkey,
     ) -> Result<()> {
         require!(amount != 0, ErrorCode::ZeroWithdrawal);
+        require!(
+            ctx.accounts.config.withdrawal_cooldown == 0,
+            ErrorCode::WithdrawalCooldownEnabled
+        );
 
         let stake_account_metadata = &ctx.accounts.stake_account_metadata;
 
@@ -445,72 +805,376 @@ pub mod staking {
         )?;
 
         ctx.accounts.stake_account_custody.reload()?;
+        let current_stake_balance = ctx.accounts.stake_account_custody.amount;
+
+        let record_balance_events = ctx
+            .accounts
+            .record_stake_balance()
+            .record(current_stake_balance, ctx.remaining_accounts)?;
+        for delegate_votes_changed in record_balance_events.delegate_votes_changed {
+            emit_cpi!(delegate_votes_changed);
+        }
+        emit_cpi!(record_balance_events.recorded_balance_changed);
+
+        Ok(())
+    }
+
+    /// Moves tokens from the stake account custody into the withdrawal custody
+    /// and removes their votes immediately. The tokens can be withdrawn with
+    /// complete_withdrawal once the withdrawal cooldown has elapsed
+    pub fn request_withdrawal<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RequestWithdrawal<'info>>,
+        amount: u64,
+        _current_delegate_stake_account_metadata_owner: Pubkey,
+        _stake_account_metadata_owner: Pubkey,
+    ) -> Result<()> {
+        require!(amount != 0, ErrorCode::ZeroWithdrawal);
+        require!(
+            ctx.accounts.config.withdrawal_cooldown != 0,
+            ErrorCode::WithdrawalCooldownDisabled
+        );
+
+        let stake_account_metadata = &ctx.accounts.stake_account_metadata;
+
+        transfer(
+            CpiContext::from(&*ctx.accounts).with_signer(&[&[
+                AUTHORITY_SEED.as_bytes(),
+                ctx.accounts.payer.key().as_ref(),
+                &[stake_account_metadata.authority_bump],
+            ]]),
+            amount,
+        )?;
+
+        ctx.accounts.stake_account_custody.reload()?;
+        let current_stake_balance = ctx.accounts.stake_account_custody.amount;
+
+        let record_balance_events = ctx
+            .accounts
+            .record_stake_balance()
+            .record(current_stake_balance, ctx.remaining_accounts)?;
+        for delegate_votes_changed in record_balance_events.delegate_votes_changed {
+            emit_cpi!(delegate_votes_changed);
+        }
+        emit_cpi!(record_balance_events.recorded_balance_changed);
+
+        let current_timestamp: u64 = utils::clock::get_current_time().try_into().unwrap();
+        let withdrawal_cooldown = ctx.accounts.config.withdrawal_cooldown;
+
+        // Every new request adds to the pending amount and restarts the cooldown
+        let pending_withdrawal = &mut ctx.accounts.pending_withdrawal;
+        pending_withdrawal.bump = ctx.bumps.pending_withdrawal;
+        pending_withdrawal.owner = ctx.accounts.payer.key();
+        pending_withdrawal.amount = pending_withdrawal
+            .amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        pending_withdrawal.release_time = current_timestamp
+            .checked_add(withdrawal_cooldown)
+            .ok_or(ErrorCode::GenericOverflow)?;
+
+        let withdrawal_requested = WithdrawalRequested {
+            owner: pending_withdrawal.owner,
+            amount,
+            total_pending_amount: pending_withdrawal.amount,
+            release_time: pending_withdrawal.release_time,
+        };
+        emit_cpi!(withdrawal_requested);
+
+        Ok(())
+    }
+
+    /// Transfers the tokens of a pending withdrawal to the owner once the withdrawal cooldown has elapsed
+    pub fn complete_withdrawal(ctx: Context<CompleteWithdrawal>) -> Result<()> {
+        let current_timestamp: u64 = utils::clock::get_current_time().try_into().unwrap();
+        require!(
+            current_timestamp >= ctx.accounts.pending_withdrawal.release_time,
+            ErrorCode::WithdrawalCooldownNotElapsed
+        );
+
+        if ctx.accounts.destination.owner != ctx.accounts.owner.key() {
+            return Err(error!(ErrorCode::WithdrawToUnauthorizedAccount));
+        }
+
+        let owner = ctx.accounts.owner.key();
+        let amount = ctx.accounts.pending_withdrawal.amount;
+        let custody_authority_seeds: &[&[u8]] = &[
+            AUTHORITY_SEED.as_bytes(),
+            owner.as_ref(),
+            &[ctx.bumps.custody_authority],
+        ];
+
+        transfer(
+            CpiContext::from(&*ctx.accounts).with_signer(&[custody_authority_seeds]),
+            amount,
+        )?;
+
+        // The withdrawal custody is created again by the next request_withdrawal
+        close_account(CpiContext::from(&*ctx.accounts).with_signer(&[custody_authority_seeds]))?;
 
-        let recorded_balance = &stake_account_metadata.recorded_balance;
-        let current_stake_balance = &ctx.accounts.stake_account_custody.amount;
+        emit!(WithdrawalCompleted { owner, amount });
 
+        Ok(())
+    }
+
+    /// Transfers tokens from the owner's token account into the stake account custody,
+    /// records the new balance and checkpoints it for the current delegate
+    pub fn deposit_tokens<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DepositTokens<'info>>,
+        amount: u64,
+        _current_delegate_stake_account_owner: Pubkey,
+    ) -> Result<()> {
+        require!(amount != 0, ErrorCode::ZeroDeposit);
+
+        transfer(CpiContext::from(&*ctx.accounts), amount)?;
+
+        ctx.accounts.stake_account_custody.reload()?;
+        let current_stake_balance = ctx.accounts.stake_account_custody.amount;
+
+        let record_balance_events = ctx
+            .accounts
+            .record_stake_balance()
+            .record(current_stake_balance, ctx.remaining_accounts)?;
+        for delegate_votes_changed in record_balance_events.delegate_votes_changed {
+            emit_cpi!(delegate_votes_changed);
+        }
+        emit_cpi!(record_balance_events.recorded_balance_changed);
+
+        Ok(())
+    }
+
+    /// Permissionless instruction that records tokens sent directly to a stake account custody
+    /// and checkpoints the difference for the current delegate
+    pub fn sync_balance<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SyncBalance<'info>>,
+        _stake_account_owner: Pubkey,
+        _current_delegate_stake_account_owner: Pubkey,
+    ) -> Result<()> {
+        let recorded_balance = ctx.accounts.stake_account_metadata.recorded_balance;
+        let current_stake_balance = ctx.accounts.stake_account_custody.amount;
+
+        if current_stake_balance == recorded_balance {
+            return Ok(());
+        }
+
+        let record_balance_events = ctx
+            .accounts
+            .record_stake_balance()
+            .record(current_stake_balance, ctx.remaining_accounts)?;
+        for delegate_votes_changed in record_balance_events.delegate_votes_changed {
+            emit_cpi!(delegate_votes_changed);
+        }
+        emit_cpi!(record_balance_events.recorded_balance_changed);
+
+        Ok(())
+    }
+
+    /// Splits the voting power of the stake account between several delegates by basis points.
+    /// The votes are moved from the current delegate, and the accounts of every share delegate
+    /// other than the current delegate are passed as remaining accounts in the order of the shares
+    pub fn set_delegation_split<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SetDelegationSplit<'info>>,
+        shares: Vec<DelegationShareArgs>,
+        _current_delegate_stake_account_owner: Pubkey,
+    ) -> Result<()> {
         let config = &ctx.accounts.config;
-        let loaded_checkpoints = ctx
+        require!(
+            !config.delegation_policy.is_liquid(),
+            ErrorCode::DelegationSplitNotSupported
+        );
+        let delegation_split = &mut ctx.accounts.delegation_split;
+        require!(
+            !delegation_split.is_active(),
+            ErrorCode::DelegationSplitActive
+        );
+        delegation_split.bump = ctx.bumps.delegation_split;
+        delegation_split.owner = ctx.accounts.payer.key();
+
+        let current_delegate_stake_account_checkpoints = ctx
             .accounts
             .current_delegate_stake_account_checkpoints
             .load()?;
         require!(
-            loaded_checkpoints.next_index < config.max_checkpoints_account_limit.into(),
+            current_delegate_stake_account_checkpoints.next_index
+                < config.max_checkpoints_account_limit.into(),
             ErrorCode::TooManyCheckpoints,
         );
-        drop(loaded_checkpoints);
+        drop(current_delegate_stake_account_checkpoints);
 
-        let current_delegate_account_info = ctx
-            .accounts
-            .current_delegate_stake_account_checkpoints
-            .to_account_info();
+        let stake_account_metadata = &ctx.accounts.stake_account_metadata;
+        let prev_recorded_total_balance = stake_account_metadata
+            .recorded_balance
+            .checked_add(stake_account_metadata.recorded_vesting_balance)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        let current_stake_balance = ctx.accounts.stake_account_custody.amount;
+        let total_delegated_votes = current_stake_balance
+            .checked_add(stake_account_metadata.recorded_vesting_balance)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        let current_delegate = stake_account_metadata.delegate;
+
+        // The current delegate keeps its share of the votes if it is one of the split delegates,
+        // so it is checkpointed only once through the current delegate accounts
+        let mut updates = delegation_split.update_shares(&shares, total_delegated_votes)?;
+        let current_delegate_share = updates
+            .iter()
+            .find(|update| update.delegate == current_delegate);
+        let current_delegate_new_votes =
+            current_delegate_share.map_or(0, |update| update.new_votes);
+        let current_delegate_is_share = current_delegate_share.is_some();
+        updates.retain(|update| update.delegate != current_delegate);
+
+        emit!(DelegationSplitChanged {
+            delegator: ctx.accounts.payer.key(),
+            shares: delegation_split.shares.clone(),
+            total_delegated_votes,
+        });
+
+        emit_cpi!(DelegationSplitChanged {
+            delegator: ctx.accounts.payer.key(),
+            shares: delegation_split.shares.clone(),
+            total_delegated_votes,
+        });
+
+        // While the split is active the split delegates hold the votes and the delegate stays at the owner
+        ctx.accounts.stake_account_metadata.delegate = ctx.accounts.payer.key();
+        ctx.accounts.stake_account_metadata.has_delegation_split = true;
 
         let current_timestamp: u64 = utils::clock::get_current_time().try_into().unwrap();
+        let max_checkpoints_account_limit = config.max_checkpoints_account_limit;
+
+        // Checkpoint rollovers have to update the metadata copy that is serialized last,
+        // so stake_account_metadata is updated when the delegate is the stake account owner
+        let current_delegate_is_user = ctx.accounts.current_delegate_stake_account_metadata.key()
+            == ctx.accounts.stake_account_metadata.key();
+
+        // The share delegates are counted through push_delegate_votes_updates
+        if !current_delegate_is_user && !current_delegate_is_share {
+            ctx.accounts
+                .current_delegate_stake_account_metadata
+                .update_delegator_count(&Operation::Subtract)?;
+        }
+        // The delegation policy can only be switched while no stake account delegates
+        if current_delegate_is_user {
+            ctx.accounts
+                .config
+                .update_delegating_stake_accounts(&Operation::Add)?;
+        }
 
-        let (amount_delta, operation) = if current_stake_balance > recorded_balance {
-            (current_stake_balance - recorded_balance, Operation::Add)
-        } else {
-            (
-                recorded_balance - current_stake_balance,
-                Operation::Subtract,
-            )
-        };
+        if prev_recorded_total_balance != current_delegate_new_votes {
+            let current_delegate_checkpoints_account_info = ctx
+                .accounts
+                .current_delegate_stake_account_checkpoints
+                .to_account_info();
 
-        let delegate_votes_changed = push_checkpoint(
-            &mut ctx.accounts.current_delegate_stake_account_checkpoints,
-            &current_delegate_account_info,
-            amount_delta,
-            operation,
-            current_timestamp,
+            let (amount_delta, operation) =
+                if current_delegate_new_votes > prev_recorded_total_balance {
+                    (
+                        current_delegate_new_votes - prev_recorded_total_balance,
+                        Operation::Add,
+                    )
+                } else {
+                    (
+                        prev_recorded_total_balance - current_delegate_new_votes,
+                        Operation::Subtract,
+                    )
+                };
+
+            let delegate_votes_changed = push_checkpoint(
+                &mut ctx.accounts.current_delegate_stake_account_checkpoints,
+                &current_delegate_checkpoints_account_info,
+                amount_delta,
+                operation,
+                current_timestamp,
+                &ctx.accounts.payer.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                CheckpointsRollover {
+                    stake_account_metadata: if current_delegate_is_user {
+                        &mut **ctx.accounts.stake_account_metadata
+                    } else {
+                        &mut **ctx.accounts.current_delegate_stake_account_metadata
+                    },
+                    next_checkpoints_account_info: ctx
+                        .accounts
+                        .current_delegate_stake_account_checkpoints_next
+                        .as_deref(),
+                    max_checkpoints_account_limit,
+                },
+            )?;
+            emit_cpi!(delegate_votes_changed);
+        }
+
+        let delegate_votes_changed = push_delegate_votes_updates(
+            &updates,
+            ctx.remaining_accounts,
+            &mut **ctx.accounts.stake_account_metadata,
             &ctx.accounts.payer.to_account_info(),
             &ctx.accounts.system_program.to_account_info(),
+            current_timestamp,
+            max_checkpoints_account_limit,
         )?;
-        emit_cpi!(delegate_votes_changed);
+        for delegate_votes_changed in delegate_votes_changed {
+            emit_cpi!(delegate_votes_changed);
+        }
 
-        let loaded_checkpoints = ctx
-            .accounts
-            .current_delegate_stake_account_checkpoints
-            .load()?;
-        if loaded_checkpoints.next_index >= config.max_checkpoints_account_limit.into() {
-            if ctx.accounts.current_delegate_stake_account_metadata.key()
-                != ctx.accounts.stake_account_metadata.key()
-            {
-                ctx.accounts
-                    .current_delegate_stake_account_metadata
-                    .stake_account_checkpoints_last_index += 1;
-            } else {
-                ctx.accounts
-                    .stake_account_metadata
-                    .stake_account_checkpoints_last_index += 1;
-            }
+        if current_stake_balance != ctx.accounts.stake_account_metadata.recorded_balance {
+            let recorded_balance_changed = ctx
+                .accounts
+                .stake_account_metadata
+                .update_recorded_balance(current_stake_balance);
+            emit_cpi!(recorded_balance_changed);
         }
-        drop(loaded_checkpoints);
 
-        let recorded_balance_changed = ctx
-            .accounts
-            .stake_account_metadata
-            .update_recorded_balance(*current_stake_balance);
-        emit_cpi!(recorded_balance_changed);
+        Ok(())
+    }
+
+    /// Replaces the shares of an active delegation split. The accounts of every new share delegate
+    /// are passed as remaining accounts in the order of the shares, followed by the accounts of
+    /// the previous delegates that are no longer part of the split
+    pub fn update_delegation_split<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdateDelegationSplit<'info>>,
+        shares: Vec<DelegationShareArgs>,
+    ) -> Result<()> {
+        let current_stake_balance = ctx.accounts.stake_account_custody.amount;
+        let total_delegated_votes = current_stake_balance
+            .checked_add(ctx.accounts.stake_account_metadata.recorded_vesting_balance)
+            .ok_or(ErrorCode::GenericOverflow)?;
+
+        let delegation_split = &mut ctx.accounts.delegation_split;
+        let updates = delegation_split.update_shares(&shares, total_delegated_votes)?;
+
+        emit!(DelegationSplitChanged {
+            delegator: ctx.accounts.payer.key(),
+            shares: delegation_split.shares.clone(),
+            total_delegated_votes,
+        });
+
+        emit_cpi!(DelegationSplitChanged {
+            delegator: ctx.accounts.payer.key(),
+            shares: delegation_split.shares.clone(),
+            total_delegated_votes,
+        });
+
+        let current_timestamp: u64 = utils::clock::get_current_time().try_into().unwrap();
+
+        let delegate_votes_changed = push_delegate_votes_updates(
+            &updates,
+            ctx.remaining_accounts,
+            &mut **ctx.accounts.stake_account_metadata,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            current_timestamp,
+            ctx.accounts.config.max_checkpoints_account_limit,
+        )?;
+        for delegate_votes_changed in delegate_votes_changed {
+            emit_cpi!(delegate_votes_changed);
+        }
+
+        if current_stake_balance != ctx.accounts.stake_account_metadata.recorded_balance {
+            let recorded_balance_changed = ctx
+                .accounts
+                .stake_account_metadata
+                .update_recorded_balance(current_stake_balance);
+            emit_cpi!(recorded_balance_changed);
+        }
 
         Ok(())
     }
@@ -529,6 +1193,16 @@ pub mod staking {
         let current_timestamp: u64 = utils::clock::get_current_time().try_into()?;
         let vote_start = proposal.vote_start;
         require!(current_timestamp > vote_start, ErrorCode::ProposalInactive);
+        require!(
+            !proposal.voting_period_ended(current_timestamp),
+            ErrorCode::VotingPeriodEnded
+        );
+        // The previous owner of the stake account can vote with the same checkpoints
+        // on the proposals that started before the owner change
+        require!(
+            vote_start >= ctx.accounts.voter_stake_account_metadata.owner_changed_at,
+            ErrorCode::ProposalPredatesStakeOwnerChange
+        );
 
         let (_, window_length) = find_window_length_le(
             &ctx.accounts.vote_weight_window_lengths.to_account_info(),
@@ -538,165 +1212,232 @@ pub mod staking {
 
         let window_start = proposal.vote_start - window_length.value;
 
-        // Use the AccountInfo directly from ctx.accounts without storing in a variable
-        if let Some((window_start_checkpoint_index, window_start_checkpoint)) = find_checkpoint_le(
-            &ctx.accounts.voter_checkpoints.to_account_info(),
-            window_start,
-        )? {
-            // Check if checkpoint is not the last in fully loaded checkpoints account
+        // Consecutive CheckpointData accounts of the voter, starting at stake_account_checkpoints_index.
+        // voter_checkpoints_next is the account that follows voter_checkpoints,
+        // the remaining accounts continue the sequence
+        let mut voter_checkpoints_accounts = vec![ctx.accounts.voter_checkpoints.to_account_info()];
+        if let Some(voter_checkpoints_next) = &ctx.accounts.voter_checkpoints_next {
+            voter_checkpoints_accounts.push(voter_checkpoints_next.to_account_info());
+        }
+        voter_checkpoints_accounts.extend(ctx.remaining_accounts.iter().cloned());
+
+        for (offset, voter_checkpoints_account) in
+            voter_checkpoints_accounts.iter().enumerate().skip(1)
+        {
+            let voter_checkpoints_account_index = u16::try_from(offset)
+                .ok()
+                .and_then(|offset| stake_account_checkpoints_index.checked_add(offset))
+                .ok_or(ErrorCode::GenericOverflow)?;
+
+            let expected_voter_checkpoints_address = Pubkey::find_program_address(
+                &[
+                    CHECKPOINT_DATA_SEED.as_bytes(),
+                    ctx.accounts.owner.key().as_ref(),
+                    voter_checkpoints_account_index.to_le_bytes().as_ref(),
+                ],
+                &crate::ID,
+            )
+            .0;
+
             require!(
-                config.max_checkpoints_account_limit != (window_start_checkpoint_index as u32) + 1,
-                ErrorCode::CheckpointOutOfBounds
+                voter_checkpoints_account.key() == expected_voter_checkpoints_address,
+                ErrorCode::InvalidNextVoterCheckpoints
             );
+            check_checkpoints_account_data(voter_checkpoints_account)?;
+        }
 
-            let mut total_weight = window_start_checkpoint.value;
-            let mut checkpoint_index = window_start_checkpoint_index + 1;
-            let mut reading_from_next_account = false;
-
-            // The loop below is guaranteed to exit because:
-            // 1. It breaks when there are no more checkpoints in the current or next account.
-            // 2. It breaks when a checkpoint's timestamp exceeds the `vote_start` timestamp.
-            // This ensures that the loop will not run indefinitely
-            loop {
-                // We have to skip the last checkpoint in the filled checkpoints account.
-                // Instead of the last checkpoint of a filled checkpoints account,
-                // we should consider the first checkpoint of the next checkpoints account.
-                if !reading_from_next_account
-                    && config.max_checkpoints_account_limit == (checkpoint_index as u32) + 1
-                {
-                    let voter_checkpoints_loader = &ctx.accounts.voter_checkpoints;
-                    let voter_checkpoints_data = voter_checkpoints_loader.load()?;
-                    if checkpoint_index >= voter_checkpoints_data.next_index as usize {
-                        // If the checkpoint account is not completely filled, exit the loop
-                        break;
-                    }
+        let (window_start_checkpoint_index, window_start_checkpoint) =
+            find_checkpoint_le(&voter_checkpoints_accounts[0], window_start)?
+                .ok_or(ErrorCode::CheckpointNotFound)?;
 
-                    // Switch to the next account
+        // Check if checkpoint is not the last in fully loaded checkpoints account
+        require!(
+            config.max_checkpoints_account_limit != (window_start_checkpoint_index as u32) + 1,
+            ErrorCode::CheckpointOutOfBounds
+        );
 
-                    // Ensure the next voter checkpoints account exists
-                    let voter_checkpoints_next = ctx
-                        .accounts
-                        .voter_checkpoints_next
-                        .as_ref()
-                        .ok_or_else(|| error!(ErrorCode::MissingNextCheckpointDataAccount))?;
-
-                    let expected_voter_checkpoints_next_address = Pubkey::find_program_address(
-                        &[
-                            CHECKPOINT_DATA_SEED.as_bytes(),
-                            ctx.accounts.owner.key().as_ref(),
-                            (stake_account_checkpoints_index + 1).to_le_bytes().as_ref(),
-                        ],
-                        &crate::ID,
-                    )
-                    .0;
+        let mut total_weight = window_start_checkpoint.value;
+        let mut account_position = 0;
+        let mut checkpoint_index = window_start_checkpoint_index + 1;
 
-                    require!(
-                        voter_checkpoints_next.key() == expected_voter_checkpoints_next_address,
-                        ErrorCode::InvalidNextVoterCheckpoints
-                    );
+        // The loop below is guaranteed to exit because:
+        // 1. It breaks when there are no more checkpoints in a checkpoints account that is not filled.
+        // 2. It breaks when a checkpoint's timestamp exceeds the `vote_start` timestamp.
+        // 3. It fails when a filled checkpoints account is not followed by a provided account.
+        loop {
+            let voter_checkpoints_account = &voter_checkpoints_accounts[account_position];
 
-                    // Reset checkpoint_index for the next account
-                    checkpoint_index = 0;
-                    // Now reading from the next account
-                    reading_from_next_account = true;
-                    // Continue to the next iteration to read further checkpoints from the next account
-                    continue;
-                } else {
-                    // Read from the current or next account based on reading_from_next_account
-                    let (voter_checkpoints_loader, voter_checkpoints_data) =
-                        if reading_from_next_account {
-                            let voter_checkpoints_next_loader =
-                                ctx.accounts.voter_checkpoints_next.as_ref().unwrap();
-                            let voter_checkpoints_next_data =
-                                voter_checkpoints_next_loader.load()?;
-                            (voter_checkpoints_next_loader, voter_checkpoints_next_data)
-                        } else {
-                            let voter_checkpoints_loader = &ctx.accounts.voter_checkpoints;
-                            let voter_checkpoints_data = voter_checkpoints_loader.load()?;
-                            (voter_checkpoints_loader, voter_checkpoints_data)
-                        };
-
-                    if checkpoint_index >= voter_checkpoints_data.next_index as usize {
-                        // No more checkpoints in account
-                        break;
-                    }
-
-                    let checkpoint = read_checkpoint_at_index(
-                        &voter_checkpoints_loader.to_account_info(),
-                        checkpoint_index,
-                    )?;
+            if checkpoint_index >= checkpoints_len(voter_checkpoints_account)? {
+                // No more checkpoints in account
+                break;
+            }
 
-                    if checkpoint.timestamp > vote_start {
-                        // Checkpoint is beyond the vote start time
-                        break;
-                    }
+            // We have to skip the last checkpoint in the filled checkpoints account.
+            // Instead of the last checkpoint of a filled checkpoints account,
+            // we should consider the first checkpoint of the next checkpoints account.
+            if config.max_checkpoints_account_limit == (checkpoint_index as u32) + 1 {
+                account_position += 1;
+                require!(
+                    account_position < voter_checkpoints_accounts.len(),
+                    ErrorCode::MissingNextCheckpointDataAccount
+                );
+
+                // Reset checkpoint_index for the next account
+                checkpoint_index = 0;
+                continue;
+            }
 
-                    if checkpoint.value < total_weight {
-                        total_weight = checkpoint.value;
-                    }
+            let checkpoint = read_checkpoint_at_index(voter_checkpoints_account, checkpoint_index)?;
 
-                    checkpoint_index += 1;
-                }
+            if checkpoint.timestamp > vote_start {
+                // Checkpoint is beyond the vote start time
+                break;
             }
 
-            require!(total_weight > 0, ErrorCode::NoWeight);
+            if checkpoint.value < total_weight {
+                total_weight = checkpoint.value;
+            }
 
-            let proposal_voters_weight_cast = &mut ctx.accounts.proposal_voters_weight_cast;
+            checkpoint_index += 1;
+        }
 
-            // Initialize proposal_voters_weight_cast if it hasn't been initialized yet
-            if proposal_voters_weight_cast.value == 0 {
-                proposal_voters_weight_cast.initialize(proposal_id, &ctx.accounts.owner.key());
-            }
+        require!(total_weight > 0, ErrorCode::NoWeight);
 
-            require!(
-                proposal_voters_weight_cast.value <= total_weight,
-                ErrorCode::AllWeightCast
-            );
+        let proposal_voters_weight_cast = &mut ctx.accounts.proposal_voters_weight_cast;
 
-            let new_weight = against_votes
-                .checked_add(for_votes)
-                .and_then(|v| v.checked_add(abstain_votes))
-                .and_then(|v| v.checked_add(proposal_voters_weight_cast.value))
-                .ok_or(ErrorCode::VoteWouldExceedWeight)?;
+        // Initialize proposal_voters_weight_cast if it hasn't been initialized yet
+        if proposal_voters_weight_cast.value == 0 {
+            proposal_voters_weight_cast.initialize(proposal_id, &ctx.accounts.owner.key());
+        }
 
-            require!(new_weight <= total_weight, ErrorCode::VoteWouldExceedWeight);
+        require!(
+            proposal_voters_weight_cast.value <= total_weight,
+            ErrorCode::AllWeightCast
+        );
 
-            proposal_voters_weight_cast.set(new_weight);
+        let new_weight = proposal_voters_weight_cast
+            .cast_votes(proposal, against_votes, for_votes, abstain_votes)?
+            .checked_add(proposal_voters_weight_cast.value)
+            .ok_or(ErrorCode::VoteWouldExceedWeight)?;
 
-            proposal.against_votes = proposal
-                .against_votes
-                .checked_add(against_votes)
-                .ok_or(ErrorCode::GenericOverflow)?;
-            proposal.for_votes = proposal
-                .for_votes
-                .checked_add(for_votes)
-                .ok_or(ErrorCode::GenericOverflow)?;
-            proposal.abstain_votes = proposal
-                .abstain_votes
-                .checked_add(abstain_votes)
-                .ok_or(ErrorCode::GenericOverflow)?;
+        require!(new_weight <= total_weight, ErrorCode::VoteWouldExceedWeight);
 
-            emit!(VoteCast {
-                voter: ctx.accounts.owner.key(),
-                proposal_id,
-                weight: total_weight,
-                against_votes,
-                for_votes,
-                abstain_votes
-            });
+        proposal_voters_weight_cast.set(new_weight);
 
-            emit_cpi!(VoteCast {
-                voter: ctx.accounts.owner.key(),
-                proposal_id,
-                weight: total_weight,
-                against_votes,
-                for_votes,
-                abstain_votes
-            });
-        } else {
-            return Err(error!(ErrorCode::CheckpointNotFound));
+        // Votes that can no longer be revised are published to the hub right away
+        if !proposal.vote_revision_open(current_timestamp, config.vote_revision_cutoff) {
+            proposal.publish_votes();
         }
 
+        emit!(VoteCast {
+            voter: ctx.accounts.owner.key(),
+            proposal_id,
+            weight: total_weight,
+            against_votes,
+            for_votes,
+            abstain_votes,
+        });
+
+        emit_cpi!(VoteCast {
+            voter: ctx.accounts.owner.key(),
+            proposal_id,
+            weight: total_weight,
+            against_votes,
+            for_votes,
+            abstain_votes,
+        });
+
+        Ok(())
+    }
+
+    /// Replaces the votes cast by the voter on a proposal with the given votes.
+    /// The revised votes can not exceed the weight already cast,
+    /// any weight left after a revision or a retraction can be cast again with cast_vote.
+    /// Votes published to the hub stay in place: no tally can drop below its published value
+    pub fn revise_vote(
+        ctx: Context<ReviseVote>,
+        proposal_id: [u8; 32],
+        against_votes: u64,
+        for_votes: u64,
+        abstain_votes: u64,
+    ) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        let proposal_voters_weight_cast = &mut ctx.accounts.proposal_voters_weight_cast;
+
+        let current_timestamp: u64 = utils::clock::get_current_time().try_into()?;
+        require!(
+            proposal
+                .vote_revision_open(current_timestamp, ctx.accounts.config.vote_revision_cutoff),
+            ErrorCode::VoteRevisionClosed
+        );
+        require!(
+            proposal_voters_weight_cast.has_votes_breakdown(),
+            ErrorCode::VoteRevisionClosed
+        );
+
+        proposal_voters_weight_cast.retract_votes(proposal)?;
+        let new_weight = proposal_voters_weight_cast.cast_votes(
+            proposal,
+            against_votes,
+            for_votes,
+            abstain_votes,
+        )?;
+        require!(
+            proposal.covers_published_votes(),
+            ErrorCode::VoteRevisionBelowPublishedVotes
+        );
+
+        let previous_weight = proposal_voters_weight_cast.value;
+        require!(
+            new_weight <= previous_weight,
+            ErrorCode::VoteWouldExceedWeight
+        );
+
+        proposal_voters_weight_cast.set(new_weight);
+
+        emit!(VoteRevised {
+            voter: ctx.accounts.owner.key(),
+            proposal_id,
+            previous_weight,
+            weight: new_weight,
+            against_votes,
+            for_votes,
+            abstain_votes,
+        });
+
+        emit_cpi!(VoteRevised {
+            voter: ctx.accounts.owner.key(),
+            proposal_id,
+            previous_weight,
+            weight: new_weight,
+            against_votes,
+            for_votes,
+            abstain_votes,
+        });
+
+        Ok(())
+    }
+
+    /// Publishes the current tallies of a proposal to the hub, which only reads
+    /// the published tallies. Anyone can publish them, typically right before bridging
+    pub fn publish_votes(ctx: Context<PublishVotes>, proposal_id: [u8; 32]) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.publish_votes();
+
+        emit!(VotesPublished {
+            proposal_id,
+            against_votes: proposal.published_against_votes,
+            for_votes: proposal.published_for_votes,
+            abstain_votes: proposal.published_abstain_votes,
+        });
+
+        emit_cpi!(VotesPublished {
+            proposal_id,
+            against_votes: proposal.published_against_votes,
+            for_votes: proposal.published_for_votes,
+            abstain_votes: proposal.published_abstain_votes,
+        });
+
         Ok(())
     }
 
@@ -736,29 +1477,201 @@ pub mod staking {
             .create_vesting(vester, maturation, amount, ctx.bumps.vest)
     }
 
-    // Claim from and close a Vesting account
-    pub fn claim_vesting(ctx: Context<ClaimVesting>) -> Result<()> {
-        let close_vesting_events = ctx.accounts.close_vesting()?;
+    // Claim from and close a Vesting account
+    pub fn claim_vesting<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimVesting<'info>>,
+    ) -> Result<()> {
+        let close_vesting_events = ctx.accounts.close_vesting(ctx.remaining_accounts)?;
+
+        if let Some(close_vesting_events) = close_vesting_events {
+            emit_cpi!(close_vesting_events.recorded_vesting_balance_changed);
+            for delegate_votes_changed in close_vesting_events.delegate_votes_changed {
+                emit_cpi!(delegate_votes_changed);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Claim part of a Vesting account, closing it once fully claimed
+    pub fn claim_vesting_partial<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimVestingPartial<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let close_vesting_events = ctx
+            .accounts
+            .claim_vesting_partial(amount, ctx.remaining_accounts)?;
+
+        if let Some(close_vesting_events) = close_vesting_events {
+            emit_cpi!(close_vesting_events.recorded_vesting_balance_changed);
+            for delegate_votes_changed in close_vesting_events.delegate_votes_changed {
+                emit_cpi!(delegate_votes_changed);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Claim a Vesting account into the custody of the stake account its vests are linked to,
+    // keeping the votes of the stake account
+    pub fn claim_vesting_to_stake(ctx: Context<ClaimVestingToStake>) -> Result<()> {
+        let (recorded_vesting_balance_changed, recorded_balance_changed) =
+            ctx.accounts.claim_vesting_to_stake()?;
+
+        emit_cpi!(recorded_vesting_balance_changed);
+        emit_cpi!(recorded_balance_changed);
+
+        Ok(())
+    }
+
+    // Open a new VestingSchedule account unlocking the amount over time
+    pub fn create_vesting_schedule(
+        ctx: Context<CreateVestingSchedule>,
+        vester: Pubkey,
+        start: i64,
+        cliff: i64,
+        end: i64,
+        period: i64,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.create_vesting_schedule(
+            vester,
+            start,
+            cliff,
+            end,
+            period,
+            amount,
+            ctx.bumps.vesting_schedule,
+        )
+    }
+
+    // Claim the unlocked part of a VestingSchedule account, closing it once fully claimed
+    pub fn claim_vested<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimVested<'info>>,
+    ) -> Result<()> {
+        let close_vesting_events = ctx.accounts.claim_vested(ctx.remaining_accounts)?;
+
+        if let Some(close_vesting_events) = close_vesting_events {
+            emit_cpi!(close_vesting_events.recorded_vesting_balance_changed);
+            for delegate_votes_changed in close_vesting_events.delegate_votes_changed {
+                emit_cpi!(delegate_votes_changed);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Cancel and close a VestingSchedule account for a non-finalized Config
+    pub fn cancel_vesting_schedule(
+        ctx: Context<CancelVestingSchedule>,
+        _vester: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.cancel_vesting_schedule()
+    }
+
+    // Commit the root of a merkle tree of vests for a non-finalized Config, claimable until
+    // the claim deadline. A Config can have a distributor for every index
+    pub fn create_vesting_merkle_distributor(
+        ctx: Context<CreateVestingMerkleDistributor>,
+        index: u64,
+        root: [u8; 32],
+        total_amount: u64,
+        num_leaves: u32,
+        claim_deadline: i64,
+    ) -> Result<()> {
+        ctx.accounts.create_vesting_merkle_distributor(
+            index,
+            root,
+            total_amount,
+            num_leaves,
+            claim_deadline,
+            ctx.bumps.distributor,
+        )
+    }
+
+    // Open or top up a Vesting account of the vester from a leaf of the merkle tree of vests
+    pub fn claim_vest_from_merkle<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimVestFromMerkle<'info>>,
+        index: u32,
+        amount: u64,
+        maturation: i64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let close_vesting_events = ctx.accounts.claim_vest_from_merkle(
+            index,
+            amount,
+            maturation,
+            proof,
+            ctx.bumps.vest,
+            ctx.bumps.vesting_balance,
+            ctx.remaining_accounts,
+        )?;
 
         if let Some(close_vesting_events) = close_vesting_events {
             emit_cpi!(close_vesting_events.recorded_vesting_balance_changed);
-            emit_cpi!(close_vesting_events.delegate_votes_changed);
+            for delegate_votes_changed in close_vesting_events.delegate_votes_changed {
+                emit_cpi!(delegate_votes_changed);
+            }
         }
 
         Ok(())
     }
 
+    // Close a merkle distributor after its claim deadline, removing its unclaimed leaves from
+    // the vested amount of the Config
+    pub fn close_vesting_merkle_distributor(
+        ctx: Context<CloseVestingMerkleDistributor>,
+    ) -> Result<()> {
+        ctx.accounts.close_vesting_merkle_distributor()
+    }
+
     // Transfer vesting to a new vester
-    pub fn transfer_vesting(ctx: Context<TransferVesting>, new_vester: Pubkey) -> Result<()> {
+    pub fn transfer_vesting<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TransferVesting<'info>>,
+        new_vester: Pubkey,
+    ) -> Result<()> {
         let transfer_vesting_events = ctx.accounts.transfer_vesting(
             new_vester,
             ctx.bumps.new_vest,
             ctx.bumps.new_vesting_balance,
+            ctx.remaining_accounts,
+        )?;
+
+        if let Some(stake_account_metadata) = transfer_vesting_events.stake_account_metadata {
+            emit_cpi!(stake_account_metadata.recorded_vesting_balance_changed);
+            for delegate_votes_changed in stake_account_metadata.delegate_votes_changed {
+                emit_cpi!(delegate_votes_changed);
+            }
+        }
+
+        if let Some(new_stake_account_metadata) = transfer_vesting_events.new_stake_account_metadata
+        {
+            emit_cpi!(new_stake_account_metadata.recorded_vesting_balance_changed);
+            for delegate_votes_changed in new_stake_account_metadata.delegate_votes_changed {
+                emit_cpi!(delegate_votes_changed);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Transfer part of a vesting to a new vester
+    pub fn transfer_vesting_partial<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TransferVestingPartial<'info>>,
+        new_vester: Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let transfer_vesting_events = ctx.accounts.transfer_vesting_partial(
+            new_vester,
+            amount,
+            ctx.bumps.new_vest,
+            ctx.bumps.new_vesting_balance,
+            ctx.remaining_accounts,
         )?;
 
         if let Some(stake_account_metadata) = transfer_vesting_events.stake_account_metadata {
             emit_cpi!(stake_account_metadata.recorded_vesting_balance_changed);
-            if let Some(delegate_votes_changed) = stake_account_metadata.delegate_votes_changed {
+            for delegate_votes_changed in stake_account_metadata.delegate_votes_changed {
                 emit_cpi!(delegate_votes_changed);
             }
         }
@@ -766,8 +1679,7 @@ pub mod staking {
         if let Some(new_stake_account_metadata) = transfer_vesting_events.new_stake_account_metadata
         {
             emit_cpi!(new_stake_account_metadata.recorded_vesting_balance_changed);
-            if let Some(delegate_votes_changed) = new_stake_account_metadata.delegate_votes_changed
-            {
+            for delegate_votes_changed in new_stake_account_metadata.delegate_votes_changed {
                 emit_cpi!(delegate_votes_changed);
             }
         }
@@ -924,6 +1836,94 @@ pub mod staking {
         Ok(())
     }
 
+    /// Adds a hub proposal metadata contract whose proposals are accepted from its activation
+    /// until its deprecation, or updates the activation and deprecation of a known one
+    pub fn set_hub_proposal_metadata_source(
+        ctx: Context<UpdateHubProposalMetadataSources>,
+        source: HubProposalMetadataSource,
+    ) -> Result<()> {
+        let spoke_metadata_collector = &mut ctx.accounts.spoke_metadata_collector;
+
+        if spoke_metadata_collector.updates_controlled_by_governance {
+            require!(
+                ctx.accounts.payer.key() == ctx.accounts.config.governance_authority,
+                ErrorCode::NotGovernanceAuthority
+            );
+        } else {
+            require!(
+                ctx.accounts.airlock.to_account_info().is_signer,
+                ErrorCode::AirlockNotSigner
+            );
+        }
+
+        spoke_metadata_collector.set_hub_proposal_metadata_source(source)
+    }
+
+    pub fn remove_hub_proposal_metadata_source(
+        ctx: Context<UpdateHubProposalMetadataSources>,
+        chain_id: u16,
+        contract: [u8; 20],
+    ) -> Result<()> {
+        let spoke_metadata_collector = &mut ctx.accounts.spoke_metadata_collector;
+
+        if spoke_metadata_collector.updates_controlled_by_governance {
+            require!(
+                ctx.accounts.payer.key() == ctx.accounts.config.governance_authority,
+                ErrorCode::NotGovernanceAuthority
+            );
+        } else {
+            require!(
+                ctx.accounts.airlock.to_account_info().is_signer,
+                ErrorCode::AirlockNotSigner
+            );
+        }
+
+        spoke_metadata_collector.remove_hub_proposal_metadata_source(chain_id, contract)
+    }
+
+    /// Sets which Wormhole queries of the hub are accepted besides eth_calls on finalized blocks
+    pub fn update_query_policy(
+        ctx: Context<UpdateQueryPolicy>,
+        new_query_policy: QueryPolicy,
+    ) -> Result<()> {
+        let spoke_metadata_collector = &mut ctx.accounts.spoke_metadata_collector;
+
+        if spoke_metadata_collector.updates_controlled_by_governance {
+            require!(
+                ctx.accounts.payer.key() == ctx.accounts.config.governance_authority,
+                ErrorCode::NotGovernanceAuthority
+            );
+        } else {
+            require!(
+                ctx.accounts.airlock.to_account_info().is_signer,
+                ErrorCode::AirlockNotSigner
+            );
+        }
+
+        spoke_metadata_collector.update_query_policy(new_query_policy)
+    }
+
+    pub fn update_hub_proposal_extender(
+        ctx: Context<UpdateHubProposalExtender>,
+        new_hub_proposal_extender: [u8; 20],
+    ) -> Result<()> {
+        let spoke_metadata_collector = &mut ctx.accounts.spoke_metadata_collector;
+
+        if spoke_metadata_collector.updates_controlled_by_governance {
+            require!(
+                ctx.accounts.payer.key() == ctx.accounts.config.governance_authority,
+                ErrorCode::NotGovernanceAuthority
+            );
+        } else {
+            require!(
+                ctx.accounts.airlock.to_account_info().is_signer,
+                ErrorCode::AirlockNotSigner
+            );
+        }
+
+        spoke_metadata_collector.update_hub_proposal_extender(new_hub_proposal_extender)
+    }
+
     pub fn relinquish_admin_control_over_hub_proposal_metadata(
         ctx: Context<RelinquishAdminControlOverHubProposalMetadata>,
     ) -> Result<()> {
@@ -987,40 +1987,114 @@ pub mod staking {
         Ok(())
     }
 
-    #[access_control(AddProposal::constraints(&ctx, &bytes))]
     pub fn add_proposal(
         ctx: Context<AddProposal>,
         bytes: Vec<u8>,
         proposal_id: [u8; 32],
         _guardian_set_index: u32,
     ) -> Result<()> {
-        let response = QueryResponse::deserialize(&bytes)
-            .map_err(|_| QueriesSolanaVerifyError::FailedToParseResponse)?;
+        let verified_response = VerifiedQueryResponse::verify(
+            &ctx.accounts.guardian_set,
+            &ctx.accounts.guardian_signatures,
+            &bytes,
+        )?;
+
+        let spoke_metadata_collector = &mut ctx.accounts.spoke_metadata_collector;
+
+        let eth_call = verified_response.eth_call(&spoke_metadata_collector.query_policy)?;
+
+        let current_timestamp: u64 = utils::clock::get_current_time().try_into()?;
+        spoke_metadata_collector.check_hub_proposal_metadata_source(
+            eth_call.chain_id,
+            eth_call.to,
+            current_timestamp,
+        )?;
+
+        let proposal_query_request_data =
+            spoke_metadata_collector.parse_proposal_query_request_data(eth_call.data)?;
+
+        // The function signature should be
+        // bytes4(keccak256(bytes("getProposalMetadata(uint256)")))
+        require!(
+            proposal_query_request_data.signature == [0xeb, 0x9b, 0x98, 0x38],
+            ProposalWormholeMessageError::InvalidFunctionSignature
+        );
+
+        let proposal_data =
+            spoke_metadata_collector.parse_eth_response_proposal_data(eth_call.result)?;
+
+        require!(
+            proposal_data.proposal_id == proposal_id,
+            ProposalWormholeMessageError::InvalidProposalId
+        );
+
+        let proposal = &mut ctx.accounts.proposal;
 
         require!(
-            response.responses.len() == 1,
-            ProposalWormholeMessageError::TooManyQueryResponses
+            proposal_data.vote_start != 0,
+            ProposalWormholeMessageError::ProposalNotInitialized
         );
 
+        proposal.add_proposal(
+            proposal_data.proposal_id,
+            proposal_data.vote_start,
+            proposal_data.vote_end,
+        )?;
+        ctx.accounts
+            .config
+            .record_voting_period(proposal_data.vote_start, proposal_data.vote_end);
+
+        emit!(ProposalCreated {
+            proposal_id: proposal_data.proposal_id,
+            vote_start: proposal_data.vote_start,
+            vote_end: proposal_data.vote_end,
+        });
+
+        emit_cpi!(ProposalCreated {
+            proposal_id: proposal_data.proposal_id,
+            vote_start: proposal_data.vote_start,
+            vote_end: proposal_data.vote_end,
+        });
+
+        Ok(())
+    }
+
+    /// Adds the proposals of a query response with one getProposalMetadata call per proposal.
+    /// The proposal accounts are passed as remaining accounts, in the order of the calls
+    pub fn add_proposals<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AddProposals<'info>>,
+        bytes: Vec<u8>,
+        _guardian_set_index: u32,
+    ) -> Result<()> {
+        let verified_response = VerifiedQueryResponse::verify(
+            &ctx.accounts.guardian_set,
+            &ctx.accounts.guardian_signatures,
+            &bytes,
+        )?;
+
         let spoke_metadata_collector = &mut ctx.accounts.spoke_metadata_collector;
 
-        if let ChainSpecificQuery::EthCallWithFinalityQueryRequest(eth_request) =
-            &response.request.requests[0].query
-        {
-            require!(
-                eth_request.finality == "finalized",
-                ProposalWormholeMessageError::NonFinalizedBlock
-            );
+        let eth_calls = verified_response.eth_calls(&spoke_metadata_collector.query_policy)?;
 
-            let EthCallData { to, data } = &eth_request.call_data[0];
+        require!(
+            ctx.remaining_accounts.len() == eth_calls.len(),
+            ErrorCode::InvalidProposalAccounts
+        );
 
-            require!(
-                *to == spoke_metadata_collector.hub_proposal_metadata,
-                ProposalWormholeMessageError::InvalidHubProposalMetadataContract
-            );
+        let payer_account_info = ctx.accounts.payer.to_account_info();
+        let system_program_account_info = ctx.accounts.system_program.to_account_info();
+
+        let current_timestamp: u64 = utils::clock::get_current_time().try_into()?;
+
+        for (eth_call, proposal_account_info) in eth_calls.iter().zip(ctx.remaining_accounts) {
+            spoke_metadata_collector.check_hub_proposal_metadata_source(
+                eth_call.chain_id,
+                eth_call.to,
+                current_timestamp,
+            )?;
 
             let proposal_query_request_data =
-                spoke_metadata_collector.parse_proposal_query_request_data(data)?;
+                spoke_metadata_collector.parse_proposal_query_request_data(eth_call.data)?;
 
             // The function signature should be
             // bytes4(keccak256(bytes("getProposalMetadata(uint256)")))
@@ -1028,57 +2102,270 @@ pub mod staking {
                 proposal_query_request_data.signature == [0xeb, 0x9b, 0x98, 0x38],
                 ProposalWormholeMessageError::InvalidFunctionSignature
             );
-        } else {
-            return Err(ProposalWormholeMessageError::InvalidChainSpecificQuery.into());
-        }
-
-        let response = &response.responses[0];
-
-        require!(
-            response.chain_id == spoke_metadata_collector.hub_chain_id,
-            ProposalWormholeMessageError::SenderChainMismatch
-        );
-
-        if let ChainSpecificResponse::EthCallWithFinalityQueryResponse(eth_response) =
-            &response.response
-        {
-            require!(
-                eth_response.results.len() == 1,
-                ProposalWormholeMessageError::TooManyEthCallResults
-            );
 
-            let proposal_data = spoke_metadata_collector
-                .parse_eth_response_proposal_data(&eth_response.results[0])?;
+            let proposal_data =
+                spoke_metadata_collector.parse_eth_response_proposal_data(eth_call.result)?;
 
             require!(
-                proposal_data.proposal_id == proposal_id,
+                proposal_data.proposal_id == proposal_query_request_data.proposal_id,
                 ProposalWormholeMessageError::InvalidProposalId
             );
 
-            let proposal = &mut ctx.accounts.proposal;
-
             require!(
                 proposal_data.vote_start != 0,
                 ProposalWormholeMessageError::ProposalNotInitialized
             );
 
-            let _ = proposal.add_proposal(proposal_data.proposal_id, proposal_data.vote_start);
+            state::proposal::create_proposal_account(
+                proposal_account_info,
+                proposal_data.proposal_id,
+                proposal_data.vote_start,
+                proposal_data.vote_end,
+                &payer_account_info,
+                &system_program_account_info,
+            )?;
+            ctx.accounts
+                .config
+                .record_voting_period(proposal_data.vote_start, proposal_data.vote_end);
 
             emit!(ProposalCreated {
                 proposal_id: proposal_data.proposal_id,
                 vote_start: proposal_data.vote_start,
+                vote_end: proposal_data.vote_end,
             });
 
             emit_cpi!(ProposalCreated {
                 proposal_id: proposal_data.proposal_id,
                 vote_start: proposal_data.vote_start,
+                vote_end: proposal_data.vote_end,
             });
-        } else {
-            return Err(ProposalWormholeMessageError::InvalidChainSpecificResponse.into());
         }
 
         Ok(())
     }
+
+    /// Syncs the vote end of a proposal with the deadline set by the hub proposal extender,
+    /// so that votes are accepted during the extension
+    pub fn extend_proposal(
+        ctx: Context<ExtendProposal>,
+        bytes: Vec<u8>,
+        proposal_id: [u8; 32],
+        _guardian_set_index: u32,
+    ) -> Result<()> {
+        let verified_response = VerifiedQueryResponse::verify(
+            &ctx.accounts.guardian_set,
+            &ctx.accounts.guardian_signatures,
+            &bytes,
+        )?;
+
+        let spoke_metadata_collector = &mut ctx.accounts.spoke_metadata_collector;
+
+        require!(
+            spoke_metadata_collector.hub_proposal_extender != [0; 20],
+            ProposalWormholeMessageError::InvalidHubProposalExtenderContract
+        );
+
+        let eth_call = verified_response.eth_call(&spoke_metadata_collector.query_policy)?;
+
+        require!(
+            eth_call.chain_id == spoke_metadata_collector.hub_chain_id,
+            ProposalWormholeMessageError::SenderChainMismatch
+        );
+
+        require!(
+            *eth_call.to == spoke_metadata_collector.hub_proposal_extender,
+            ProposalWormholeMessageError::InvalidHubProposalExtenderContract
+        );
+
+        let proposal_query_request_data =
+            spoke_metadata_collector.parse_proposal_query_request_data(eth_call.data)?;
+
+        // The function signature should be
+        // bytes4(keccak256(bytes("extendedDeadlines(uint256)")))
+        require!(
+            proposal_query_request_data.signature == [0x15, 0x14, 0x92, 0x41],
+            ProposalWormholeMessageError::InvalidFunctionSignature
+        );
+
+        // The result of the call does not contain the proposal id
+        require!(
+            proposal_query_request_data.proposal_id == proposal_id,
+            ProposalWormholeMessageError::InvalidProposalId
+        );
+
+        let vote_end =
+            spoke_metadata_collector.parse_eth_response_extended_vote_end(eth_call.result)?;
+
+        ctx.accounts.proposal.extend_vote_end(vote_end)?;
+        ctx.accounts
+            .config
+            .record_voting_period(ctx.accounts.proposal.vote_start, vote_end);
+
+        emit!(ProposalExtended {
+            proposal_id,
+            vote_end,
+        });
+
+        emit_cpi!(ProposalExtended {
+            proposal_id,
+            vote_end,
+        });
+
+        Ok(())
+    }
+
+    /// Migrates a proposal account with the legacy 72-byte layout. The vote end of
+    /// the migrated proposal is unknown, so votes are not rejected after it
+    pub fn migrate_proposal(ctx: Context<MigrateProposal>, _proposal_id: [u8; 32]) -> Result<()> {
+        let proposal_info = ctx.accounts.proposal.to_account_info();
+        let proposal = ProposalData::from_legacy_account_data(&proposal_info.try_borrow_data()?)?;
+
+        resize_account(
+            &proposal_info,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ProposalData::LEN,
+        )?;
+
+        let mut data = proposal_info.try_borrow_mut_data()?;
+        proposal.try_serialize(&mut &mut data[..])
+    }
+
+    /// Migrates the account recording the weight a voter cast on a proposal
+    /// from the legacy layout without the breakdown of the cast weight
+    pub fn migrate_proposal_voters_weight_cast(
+        ctx: Context<MigrateProposalVotersWeightCast>,
+        _voter: Pubkey,
+    ) -> Result<()> {
+        let proposal_voters_weight_cast_info =
+            ctx.accounts.proposal_voters_weight_cast.to_account_info();
+        let proposal_voters_weight_cast = ProposalVotersWeightCast::from_legacy_account_data(
+            &proposal_voters_weight_cast_info.try_borrow_data()?,
+        )?;
+
+        resize_account(
+            &proposal_voters_weight_cast_info,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ProposalVotersWeightCast::LEN,
+        )?;
+
+        let mut data = proposal_voters_weight_cast_info.try_borrow_mut_data()?;
+        proposal_voters_weight_cast.try_serialize(&mut &mut data[..])
+    }
+
+    /// Grows the spoke metadata collector created before the hub proposal extender,
+    /// the query policy and the metadata sources were added. The added fields are zeroed,
+    /// so that proposal extensions are not synced and only queries on finalized blocks
+    /// of the hub proposal metadata contract are accepted
+    pub fn migrate_spoke_metadata_collector(
+        ctx: Context<MigrateSpokeMetadataCollector>,
+    ) -> Result<()> {
+        let spoke_metadata_collector_info = ctx.accounts.spoke_metadata_collector.to_account_info();
+        let current_len = spoke_metadata_collector_info.data_len();
+
+        require!(
+            current_len < SpokeMetadataCollector::LEN,
+            ErrorCode::AccountAlreadyMigrated
+        );
+        require!(
+            spoke_metadata_collector_info.try_borrow_data()?[..8]
+                == *SpokeMetadataCollector::DISCRIMINATOR,
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+
+        resize_account(
+            &spoke_metadata_collector_info,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            SpokeMetadataCollector::LEN,
+        )?;
+
+        spoke_metadata_collector_info.try_borrow_mut_data()?[current_len..].fill(0);
+
+        Ok(())
+    }
+
+    /// Grows a stake account metadata created with a previous layout. Anyone can migrate it.
+    /// The added fields are zeroed, except the ones that have to match the existing delegations:
+    /// has_delegation_split is read from the DelegationSplit of the owner, and a stake account
+    /// that delegates is counted in the delegating stake accounts of the config and in the
+    /// delegator_count of its delegate, which has to be migrated first. Stake accounts only
+    /// delegate with a previous layout through their delegate, so every delegator of a stake
+    /// account is counted once migrated. A delegate that closed its stake account held no votes
+    /// of its delegators, which then delegate to themselves again
+    pub fn migrate_stake_account_metadata(
+        ctx: Context<MigrateStakeAccountMetadata>,
+        owner: Pubkey,
+    ) -> Result<()> {
+        let stake_account_metadata_info = ctx.accounts.stake_account_metadata.to_account_info();
+        require!(
+            stake_account_metadata_info.data_len() < StakeAccountMetadata::LEN,
+            ErrorCode::AccountAlreadyMigrated
+        );
+        let mut stake_account_metadata = StakeAccountMetadata::from_account_data(
+            &stake_account_metadata_info.try_borrow_data()?,
+        )?;
+
+        stake_account_metadata.has_delegation_split =
+            DelegationSplit::load_active(&ctx.accounts.delegation_split)?.is_some();
+
+        if stake_account_metadata.delegate != owner {
+            let delegate_metadata_info = ctx
+                .accounts
+                .delegate_stake_account_metadata
+                .as_ref()
+                .ok_or(ErrorCode::InvalidCurrentDelegate)?
+                .to_account_info();
+            let (expected_delegate_metadata_address, _) = Pubkey::find_program_address(
+                &[
+                    STAKE_ACCOUNT_METADATA_SEED.as_bytes(),
+                    stake_account_metadata.delegate.as_ref(),
+                ],
+                &crate::ID,
+            );
+            require!(
+                delegate_metadata_info.key() == expected_delegate_metadata_address,
+                ErrorCode::InvalidCurrentDelegate
+            );
+
+            if *delegate_metadata_info.owner == crate::ID {
+                require!(
+                    delegate_metadata_info.data_len() == StakeAccountMetadata::LEN,
+                    ErrorCode::DelegateNotMigrated
+                );
+                let mut delegate_metadata = StakeAccountMetadata::try_deserialize(
+                    &mut &delegate_metadata_info.try_borrow_data()?[..],
+                )?;
+                delegate_metadata.update_delegator_count(&Operation::Add)?;
+                let mut data = delegate_metadata_info.try_borrow_mut_data()?;
+                delegate_metadata.try_serialize(&mut &mut data[..])?;
+            } else {
+                require!(
+                    stake_account_metadata.recorded_balance == 0
+                        && stake_account_metadata.recorded_vesting_balance == 0,
+                    ErrorCode::InvalidCurrentDelegate
+                );
+                stake_account_metadata.delegate = owner;
+            }
+        }
+
+        if !stake_account_metadata.is_self_delegated() {
+            ctx.accounts
+                .config
+                .update_delegating_stake_accounts(&Operation::Add)?;
+        }
+
+        resize_account(
+            &stake_account_metadata_info,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            StakeAccountMetadata::LEN,
+        )?;
+
+        let mut data = stake_account_metadata_info.try_borrow_mut_data()?;
+        stake_account_metadata.try_serialize(&mut &mut data[..])
+    }
 }
 
 /// Creates or appends to a GuardianSignatures account for subsequent use by verify_query.
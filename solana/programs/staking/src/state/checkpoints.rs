@@ -1,7 +1,9 @@
+use crate::context::CHECKPOINT_DATA_SEED;
 use crate::error::ErrorCode;
+use crate::state::stake_account::StakeAccountMetadata;
 use anchor_lang::prelude::borsh::{BorshDeserialize, BorshSerialize};
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::program::invoke;
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
 use anchor_lang::solana_program::system_instruction;
 use std::mem::size_of;
 
@@ -104,10 +106,23 @@ pub fn read_checkpoint_at_index(account_info: &AccountInfo, index: usize) -> Res
     Ok(checkpoint)
 }
 
+#[derive(Debug, PartialEq, Eq)]
 pub enum Operation {
     Add,
     Subtract,
 }
+
+/// Accounts needed by push_checkpoint to roll the checkpoints of a delegate over to a new
+/// CheckpointData account once the current one reaches max_checkpoints_account_limit
+pub struct CheckpointsRollover<'a, 'info> {
+    // StakeAccountMetadata of the CheckpointData owner, its last index is advanced on rollover
+    pub stake_account_metadata: &'a mut StakeAccountMetadata,
+    // CheckpointData PDA (owner, stake_account_checkpoints_last_index + 1), required once the
+    // current account is full
+    pub next_checkpoints_account_info: Option<&'a AccountInfo<'info>>,
+    pub max_checkpoints_account_limit: u32,
+}
+
 pub fn push_checkpoint<'info>(
     checkpoints_loader: &mut AccountLoader<'info, CheckpointData>,
     checkpoints_account_info: &AccountInfo<'info>,
@@ -116,6 +131,37 @@ pub fn push_checkpoint<'info>(
     current_timestamp: u64,
     payer_account_info: &AccountInfo<'info>,
     system_program_account_info: &AccountInfo<'info>,
+    rollover: CheckpointsRollover<'_, 'info>,
+) -> Result<DelegateVotesChanged> {
+    let delegate_votes_changed = append_checkpoint(
+        checkpoints_loader,
+        checkpoints_account_info,
+        amount_delta,
+        operation,
+        current_timestamp,
+        payer_account_info,
+        system_program_account_info,
+    )?;
+
+    rollover_checkpoints_if_full(
+        checkpoints_loader,
+        checkpoints_account_info,
+        payer_account_info,
+        system_program_account_info,
+        rollover,
+    )?;
+
+    Ok(delegate_votes_changed)
+}
+
+fn append_checkpoint<'info>(
+    checkpoints_loader: &mut AccountLoader<'info, CheckpointData>,
+    checkpoints_account_info: &AccountInfo<'info>,
+    amount_delta: u64,
+    operation: Operation,
+    current_timestamp: u64,
+    payer_account_info: &AccountInfo<'info>,
+    system_program_account_info: &AccountInfo<'info>,
 ) -> Result<DelegateVotesChanged> {
     // Step 1: Immutable borrow to get latest_index and latest_checkpoint
     let (current_index, latest_checkpoint, owner) = {
@@ -244,6 +290,154 @@ pub fn push_checkpoint<'info>(
     }
 }
 
+/// Moves the checkpoints of a delegate to the next CheckpointData account once the current one is full.
+/// The next account (owner, stake_account_checkpoints_last_index + 1) is created and seeded with a copy
+/// of the latest checkpoint, and the owner's stake_account_checkpoints_last_index is advanced.
+/// The next account is only required when the current one is full, so that the index is never
+/// advanced to an account that does not exist
+fn rollover_checkpoints_if_full<'info>(
+    checkpoints_loader: &AccountLoader<'info, CheckpointData>,
+    checkpoints_account_info: &AccountInfo<'info>,
+    payer_account_info: &AccountInfo<'info>,
+    system_program_account_info: &AccountInfo<'info>,
+    rollover: CheckpointsRollover<'_, 'info>,
+) -> Result<()> {
+    let (next_index, owner) = {
+        let checkpoint_data = checkpoints_loader.load()?;
+        (checkpoint_data.next_index, checkpoint_data.owner)
+    };
+
+    if next_index < rollover.max_checkpoints_account_limit.into() {
+        return Ok(());
+    }
+
+    let stake_account_metadata = rollover.stake_account_metadata;
+    require!(
+        stake_account_metadata.owner == owner,
+        ErrorCode::InvalidStakeAccountMetadata
+    );
+
+    let new_last_index = stake_account_metadata
+        .stake_account_checkpoints_last_index
+        .checked_add(1)
+        .ok_or_else(|| error!(ErrorCode::GenericOverflow))?;
+
+    let next_checkpoints_account_info = rollover
+        .next_checkpoints_account_info
+        .ok_or(ErrorCode::MissingNextCheckpointDataAccount)?;
+    let latest_checkpoint =
+        read_checkpoint_at_index(checkpoints_account_info, next_index as usize - 1)?;
+
+    create_checkpoints_account(
+        next_checkpoints_account_info,
+        &owner,
+        new_last_index,
+        &latest_checkpoint,
+        payer_account_info,
+        system_program_account_info,
+    )?;
+
+    stake_account_metadata.stake_account_checkpoints_last_index = new_last_index;
+
+    Ok(())
+}
+
+/// Creates the CheckpointData PDA (owner, index) with first_checkpoint as its only checkpoint.
+/// Accounts that were already funded with lamports are allocated and assigned the same way
+/// Anchor handles `init`
+fn create_checkpoints_account<'info>(
+    account_info: &AccountInfo<'info>,
+    owner: &Pubkey,
+    index: u16,
+    first_checkpoint: &Checkpoint,
+    payer_account_info: &AccountInfo<'info>,
+    system_program_account_info: &AccountInfo<'info>,
+) -> Result<()> {
+    let index_bytes = index.to_le_bytes();
+    let (expected_address, bump) = Pubkey::find_program_address(
+        &[
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            owner.as_ref(),
+            index_bytes.as_ref(),
+        ],
+        &crate::ID,
+    );
+    require!(
+        account_info.key() == expected_address,
+        ErrorCode::InvalidNextCheckpointDataAccount
+    );
+
+    let bump_bytes = [bump];
+    let signer_seeds: &[&[u8]] = &[
+        CHECKPOINT_DATA_SEED.as_bytes(),
+        owner.as_ref(),
+        index_bytes.as_ref(),
+        bump_bytes.as_ref(),
+    ];
+
+    let space = CheckpointData::CHECKPOINT_DATA_HEADER_SIZE + CheckpointData::CHECKPOINT_SIZE;
+    let required_lamports = Rent::get()?.minimum_balance(space);
+    let current_lamports = account_info.lamports();
+
+    if current_lamports == 0 {
+        invoke_signed(
+            &system_instruction::create_account(
+                payer_account_info.key,
+                account_info.key,
+                required_lamports,
+                space as u64,
+                &crate::ID,
+            ),
+            &[
+                payer_account_info.clone(),
+                account_info.clone(),
+                system_program_account_info.clone(),
+            ],
+            &[signer_seeds],
+        )?;
+    } else {
+        let lamports_needed = required_lamports.saturating_sub(current_lamports);
+        if lamports_needed > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    payer_account_info.key,
+                    account_info.key,
+                    lamports_needed,
+                ),
+                &[
+                    payer_account_info.clone(),
+                    account_info.clone(),
+                    system_program_account_info.clone(),
+                ],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(account_info.key, space as u64),
+            &[account_info.clone(), system_program_account_info.clone()],
+            &[signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account_info.key, &crate::ID),
+            &[account_info.clone(), system_program_account_info.clone()],
+            &[signer_seeds],
+        )?;
+    }
+
+    {
+        let mut data = account_info.try_borrow_mut_data()?;
+        let discriminator_size = CheckpointData::DISCRIMINATOR.len();
+        data[..discriminator_size].copy_from_slice(CheckpointData::DISCRIMINATOR);
+
+        let checkpoint_data: &mut CheckpointData = bytemuck::from_bytes_mut(
+            &mut data[discriminator_size..CheckpointData::CHECKPOINT_DATA_HEADER_SIZE],
+        );
+        checkpoint_data.initialize(owner);
+        checkpoint_data.next_index = 1;
+    }
+
+    write_checkpoint_at_index(account_info, 0, first_checkpoint)
+}
+
 pub fn push_checkpoint_init<'info>(
     checkpoints_loader: &mut AccountLoader<'info, CheckpointData>,
     checkpoints_account_info: &AccountInfo<'info>,
@@ -284,6 +478,80 @@ pub fn push_checkpoint_init<'info>(
     Ok(())
 }
 
+/// Initializes the checkpoints of the new owner of a stake account with the checkpoints of the
+/// latest CheckpointData account of the previous owner, followed by a checkpoint of value at
+/// current_timestamp. The vote weight windows spanning the owner change can then be read from
+/// the checkpoints of the new owner
+pub fn init_checkpoints_from_history<'info>(
+    checkpoints_loader: &mut AccountLoader<'info, CheckpointData>,
+    checkpoints_account_info: &AccountInfo<'info>,
+    history_loader: &AccountLoader<'info, CheckpointData>,
+    value: u64,
+    current_timestamp: u64,
+    max_checkpoints_account_limit: u32,
+    payer_account_info: &AccountInfo<'info>,
+    system_program_account_info: &AccountInfo<'info>,
+) -> Result<()> {
+    let history_account_info = history_loader.to_account_info();
+    let history_len = history_loader.load()?.next_index as usize;
+    let history = (0..history_len)
+        .map(|index| read_checkpoint_at_index(&history_account_info, index))
+        .collect::<Result<Vec<Checkpoint>>>()?;
+
+    let checkpoints = checkpoints_with_history(
+        history,
+        value,
+        current_timestamp,
+        max_checkpoints_account_limit,
+    );
+
+    let mut checkpoint_data = checkpoints_loader.load_init()?;
+    checkpoint_data.next_index = checkpoints.len() as u64;
+    drop(checkpoint_data);
+
+    let required_size = CheckpointData::CHECKPOINT_DATA_HEADER_SIZE
+        + checkpoints.len() * CheckpointData::CHECKPOINT_SIZE;
+    if required_size > checkpoints_account_info.data_len() {
+        resize_account(
+            checkpoints_account_info,
+            payer_account_info,
+            system_program_account_info,
+            required_size,
+        )?;
+    }
+
+    for (index, checkpoint) in checkpoints.iter().enumerate() {
+        write_checkpoint_at_index(checkpoints_account_info, index, checkpoint)?;
+    }
+
+    Ok(())
+}
+
+/// Keeps the latest checkpoints of history that fit in a CheckpointData account that is not full
+/// once the checkpoint of value at current_timestamp is appended, replacing a checkpoint
+/// of the same timestamp
+fn checkpoints_with_history(
+    mut history: Vec<Checkpoint>,
+    value: u64,
+    current_timestamp: u64,
+    max_checkpoints_account_limit: u32,
+) -> Vec<Checkpoint> {
+    if history
+        .last()
+        .is_some_and(|checkpoint| checkpoint.timestamp == current_timestamp)
+    {
+        history.pop();
+    }
+
+    let max_history_len = (max_checkpoints_account_limit as usize).saturating_sub(2);
+    let mut checkpoints = history.split_off(history.len().saturating_sub(max_history_len));
+    checkpoints.push(Checkpoint {
+        timestamp: current_timestamp,
+        value,
+    });
+    checkpoints
+}
+
 fn calc_new_checkpoint(
     current_value: u64,
     amount_delta: u64,
@@ -350,6 +618,112 @@ pub fn find_checkpoint_le(
     Ok(result)
 }
 
+/// Returns the number of checkpoints stored in a CheckpointData account
+pub fn checkpoints_len(account_info: &AccountInfo) -> Result<usize> {
+    let data = account_info.try_borrow_data()?;
+    let header_size = CheckpointData::CHECKPOINT_DATA_HEADER_SIZE;
+    Ok((data.len() - header_size) / CheckpointData::CHECKPOINT_SIZE)
+}
+
+/// Checks that a checkpoint can no longer be read by cast_vote. A checkpoint is only read for
+/// proposals whose vote weight window starts before the following checkpoint, which start at
+/// most max_window_length after it and accept votes for at most max_voting_period, the longest
+/// voting period of the stored proposals
+pub fn require_checkpoint_expired(
+    checkpoint: &Checkpoint,
+    max_window_length: u64,
+    max_voting_period: u64,
+    current_timestamp: u64,
+) -> Result<()> {
+    let last_read_timestamp = checkpoint
+        .timestamp
+        .checked_add(max_window_length)
+        .and_then(|timestamp| timestamp.checked_add(max_voting_period))
+        .ok_or(ErrorCode::GenericOverflow)?;
+    require!(
+        last_read_timestamp < current_timestamp,
+        ErrorCode::CheckpointsWithinVoteWeightWindow
+    );
+
+    Ok(())
+}
+
+/// Checks that account_info is the CheckpointData PDA (owner, index)
+pub fn check_checkpoints_account(
+    account_info: &AccountInfo,
+    owner: &Pubkey,
+    index: u16,
+) -> Result<()> {
+    let (expected_address, _) = Pubkey::find_program_address(
+        &[
+            CHECKPOINT_DATA_SEED.as_bytes(),
+            owner.as_ref(),
+            index.to_le_bytes().as_ref(),
+        ],
+        &crate::ID,
+    );
+    require!(
+        account_info.key() == expected_address,
+        ErrorCode::InvalidCheckpointDataAccount
+    );
+
+    check_checkpoints_account_data(account_info)
+}
+
+/// Checks that account_info is owned by the program and holds CheckpointData
+pub fn check_checkpoints_account_data(account_info: &AccountInfo) -> Result<()> {
+    require!(
+        *account_info.owner == crate::ID,
+        ErrorCode::InvalidCheckpointDataAccount
+    );
+
+    let data = account_info.try_borrow_data()?;
+    require!(
+        data.len() >= CheckpointData::CHECKPOINT_DATA_HEADER_SIZE
+            && data[..CheckpointData::DISCRIMINATOR.len()] == *CheckpointData::DISCRIMINATOR,
+        ErrorCode::InvalidCheckpointDataAccount
+    );
+
+    Ok(())
+}
+
+/// Finds the latest checkpoint with timestamp <= target_timestamp across consecutive
+/// CheckpointData accounts of the same owner. The account holding it is the last one whose
+/// first checkpoint is <= target_timestamp, which is found with a binary search.
+/// Returns the position of that account in checkpoints_account_infos along with the checkpoint
+pub fn find_checkpoint_le_in_accounts(
+    checkpoints_account_infos: &[AccountInfo],
+    target_timestamp: u64,
+) -> Result<Option<(usize, Checkpoint)>> {
+    let mut low = 0;
+    let mut high = checkpoints_account_infos.len();
+    let mut result = None;
+
+    while low < high {
+        let mid = (low + high) / 2;
+        let account_info = &checkpoints_account_infos[mid];
+
+        if checkpoints_len(account_info)? > 0
+            && read_checkpoint_at_index(account_info, 0)?.timestamp <= target_timestamp
+        {
+            result = Some(mid);
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    let Some(account_position) = result else {
+        return Ok(None);
+    };
+
+    Ok(find_checkpoint_le(
+        &checkpoints_account_infos[account_position],
+        target_timestamp,
+    )?
+    .map(|(_, checkpoint)| (account_position, checkpoint)))
+}
+
 #[derive(Clone, Copy, Default, BorshSerialize, BorshDeserialize, InitSpace)]
 pub struct Checkpoint {
     pub timestamp: u64,
@@ -358,7 +732,21 @@ pub struct Checkpoint {
 
 #[cfg(test)]
 pub mod tests {
-    use super::CheckpointData;
+    use super::{
+        checkpoints_with_history, find_checkpoint_le_in_accounts, require_checkpoint_expired,
+        rollover_checkpoints_if_full, Checkpoint, CheckpointData, CheckpointsRollover,
+    };
+    use crate::state::stake_account::StakeAccountMetadata;
+    use anchor_lang::prelude::*;
+    use anchor_lang::Discriminator;
+
+    fn checkpoints_account_data(checkpoints: &[(u64, u64)]) -> Vec<u8> {
+        let mut data = vec![0u8; CheckpointData::CHECKPOINT_DATA_HEADER_SIZE];
+        for &(timestamp, value) in checkpoints {
+            data.extend(Checkpoint { timestamp, value }.try_to_vec().unwrap());
+        }
+        data
+    }
 
     #[test]
     fn check_checkpoint_size() {
@@ -374,4 +762,194 @@ pub mod tests {
     fn check_checkpoint_data_size() {
         assert!(CheckpointData::LEN == 48); // 48 (header)
     }
+
+    #[test]
+    fn find_checkpoint_le_in_accounts_test() {
+        let key = Pubkey::new_unique();
+        let owner = crate::ID;
+        let mut lamports = [0u64; 3];
+        // The first checkpoint of every account is a copy of the last checkpoint of the previous one,
+        // possibly overwritten within the same timestamp
+        let mut data = [
+            checkpoints_account_data(&[(10, 1), (20, 2), (30, 3)]),
+            checkpoints_account_data(&[(30, 3), (40, 4), (50, 5)]),
+            checkpoints_account_data(&[(50, 6)]),
+        ];
+        let account_infos: Vec<AccountInfo> = lamports
+            .iter_mut()
+            .zip(data.iter_mut())
+            .map(|(lamports, data)| {
+                AccountInfo::new(
+                    &key,
+                    false,
+                    false,
+                    lamports,
+                    data.as_mut_slice(),
+                    &owner,
+                    false,
+                    0,
+                )
+            })
+            .collect();
+
+        let find = |timestamp: u64| {
+            find_checkpoint_le_in_accounts(&account_infos, timestamp)
+                .unwrap()
+                .map(|(position, checkpoint)| (position, checkpoint.timestamp, checkpoint.value))
+        };
+
+        assert_eq!(find(5), None);
+        assert_eq!(find(10), Some((0, 10, 1)));
+        assert_eq!(find(25), Some((0, 20, 2)));
+        assert_eq!(find(30), Some((1, 30, 3)));
+        assert_eq!(find(49), Some((1, 40, 4)));
+        assert_eq!(find(50), Some((2, 50, 6)));
+        assert_eq!(find(100), Some((2, 50, 6)));
+    }
+
+    #[test]
+    fn rollover_requires_next_checkpoints_account_test() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let program_id = crate::ID;
+        let mut lamports = 0u64;
+        let mut data = CheckpointData::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(owner.as_ref());
+        data.extend_from_slice(&2u64.to_le_bytes());
+        data.extend(
+            Checkpoint {
+                timestamp: 10,
+                value: 1,
+            }
+            .try_to_vec()
+            .unwrap(),
+        );
+        data.extend(
+            Checkpoint {
+                timestamp: 20,
+                value: 2,
+            }
+            .try_to_vec()
+            .unwrap(),
+        );
+        let account_info = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+        let checkpoints_loader = AccountLoader::<CheckpointData>::try_from(&account_info).unwrap();
+        let mut stake_account_metadata = StakeAccountMetadata {
+            owner,
+            ..Default::default()
+        };
+
+        // The next account is not needed while the current one is not full
+        rollover_checkpoints_if_full(
+            &checkpoints_loader,
+            &account_info,
+            &account_info,
+            &account_info,
+            CheckpointsRollover {
+                stake_account_metadata: &mut stake_account_metadata,
+                next_checkpoints_account_info: None,
+                max_checkpoints_account_limit: 3,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            stake_account_metadata.stake_account_checkpoints_last_index,
+            0
+        );
+
+        // A full account can not be rolled over without creating the next account
+        assert!(rollover_checkpoints_if_full(
+            &checkpoints_loader,
+            &account_info,
+            &account_info,
+            &account_info,
+            CheckpointsRollover {
+                stake_account_metadata: &mut stake_account_metadata,
+                next_checkpoints_account_info: None,
+                max_checkpoints_account_limit: 2,
+            },
+        )
+        .is_err());
+        assert_eq!(
+            stake_account_metadata.stake_account_checkpoints_last_index,
+            0
+        );
+    }
+
+    #[test]
+    fn require_checkpoint_expired_test() {
+        let checkpoint = Checkpoint {
+            timestamp: 100,
+            value: 0,
+        };
+
+        // The checkpoint can be read by a proposal starting at 150 until 180
+        assert!(require_checkpoint_expired(&checkpoint, 50, 30, 180).is_err());
+        assert!(require_checkpoint_expired(&checkpoint, 50, 30, 181).is_ok());
+        // Without any stored proposal with a known vote end, only the vote weight window bounds it
+        assert!(require_checkpoint_expired(&checkpoint, 50, 0, 150).is_err());
+        assert!(require_checkpoint_expired(&checkpoint, 50, 0, 151).is_ok());
+        assert!(require_checkpoint_expired(&checkpoint, u64::MAX, 30, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn checkpoints_with_history_test() {
+        let history = |checkpoints: &[(u64, u64)]| -> Vec<Checkpoint> {
+            checkpoints
+                .iter()
+                .map(|&(timestamp, value)| Checkpoint { timestamp, value })
+                .collect()
+        };
+        let pairs = |checkpoints: Vec<Checkpoint>| -> Vec<(u64, u64)> {
+            checkpoints
+                .iter()
+                .map(|checkpoint| (checkpoint.timestamp, checkpoint.value))
+                .collect()
+        };
+
+        assert_eq!(
+            pairs(checkpoints_with_history(
+                history(&[(10, 100), (20, 150)]),
+                50,
+                30,
+                10
+            )),
+            vec![(10, 100), (20, 150), (30, 50)]
+        );
+
+        // A checkpoint pushed at the time of the owner change is replaced
+        assert_eq!(
+            pairs(checkpoints_with_history(
+                history(&[(10, 100), (30, 150)]),
+                50,
+                30,
+                10
+            )),
+            vec![(10, 100), (30, 50)]
+        );
+
+        // Only the latest checkpoints are kept, so that the account is not full
+        assert_eq!(
+            pairs(checkpoints_with_history(
+                history(&[(10, 100), (20, 150), (25, 120)]),
+                50,
+                30,
+                4
+            )),
+            vec![(20, 150), (25, 120), (30, 50)]
+        );
+        assert_eq!(
+            pairs(checkpoints_with_history(history(&[(10, 100)]), 50, 30, 2)),
+            vec![(30, 50)]
+        );
+    }
 }
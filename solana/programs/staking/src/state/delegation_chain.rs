@@ -0,0 +1,261 @@
+use crate::context::{CHECKPOINT_DATA_SEED, STAKE_ACCOUNT_METADATA_SEED};
+use crate::error::ErrorCode;
+use crate::state::checkpoints::{
+    push_checkpoint, CheckpointData, CheckpointsRollover, DelegateVotesChanged, Operation,
+};
+use crate::state::global_config::DelegationPolicy;
+use crate::state::stake_account::StakeAccountMetadata;
+use anchor_lang::prelude::*;
+
+/// Delegation chains ending at a stake account that starts or stops delegating to a delegation
+/// chain. Every stake account of the chain counts the chains of delegations ending at it by length,
+/// so a new delegation can not make any chain longer than max_depth, and a removed delegation
+/// shortens the chains it ended
+pub struct DelegationChainChange {
+    pub delegator_chain_counts: [u32; DelegationPolicy::MAX_DEPTH as usize],
+    pub max_depth: u8,
+}
+
+/// Pushes a change of the votes a stake account delegates to its delegate with liquid delegation.
+/// The change is added to the delegated votes of every stake account of the delegation chain
+/// and checkpointed for the stake account at the end of the chain, which delegates to itself.
+/// When the delegate delegates further, the StakeAccountMetadata of the following stake accounts
+/// are passed in chain order as remaining accounts, followed by the current CheckpointData
+/// and the next CheckpointData PDA of the stake account at the end of the chain.
+/// A delegation chain change is added or removed with the same operation as the votes.
+/// Returns the checkpointed change, if any, and the number of remaining accounts used
+pub fn push_delegation_chain_votes<'info>(
+    delegator: &Pubkey,
+    delegate_stake_account_metadata: &mut StakeAccountMetadata,
+    delegate_checkpoints_loader: &mut AccountLoader<'info, CheckpointData>,
+    delegate_next_checkpoints_account_info: Option<&AccountInfo<'info>>,
+    amount_delta: u64,
+    operation: Operation,
+    chain_change: Option<DelegationChainChange>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    payer_account_info: &AccountInfo<'info>,
+    system_program_account_info: &AccountInfo<'info>,
+    current_timestamp: u64,
+    max_checkpoints_account_limit: u32,
+) -> Result<(Option<DelegateVotesChanged>, usize)> {
+    let mut chain = vec![*delegator, delegate_stake_account_metadata.owner];
+    record_delegation_chain_votes(
+        delegate_stake_account_metadata,
+        amount_delta,
+        &operation,
+        chain_change.as_ref(),
+        chain.len() - 1,
+    )?;
+
+    if delegate_stake_account_metadata.is_self_delegated() {
+        if amount_delta == 0 {
+            return Ok((None, 0));
+        }
+
+        let delegate_checkpoints_account_info = delegate_checkpoints_loader.to_account_info();
+        let delegate_votes_changed = push_checkpoint(
+            delegate_checkpoints_loader,
+            &delegate_checkpoints_account_info,
+            amount_delta,
+            operation,
+            current_timestamp,
+            payer_account_info,
+            system_program_account_info,
+            CheckpointsRollover {
+                stake_account_metadata: delegate_stake_account_metadata,
+                next_checkpoints_account_info: delegate_next_checkpoints_account_info,
+                max_checkpoints_account_limit,
+            },
+        )?;
+        return Ok((Some(delegate_votes_changed), 0));
+    }
+
+    let mut next_delegate = delegate_stake_account_metadata.delegate;
+    let mut used_accounts_count = 0;
+    loop {
+        require!(!chain.contains(&next_delegate), ErrorCode::DelegationCycle);
+        chain.push(next_delegate);
+
+        let metadata_account_info = remaining_accounts
+            .get(used_accounts_count)
+            .ok_or(ErrorCode::InvalidDelegationChainAccounts)?;
+        used_accounts_count += 1;
+
+        let mut stake_account_metadata: Account<'info, StakeAccountMetadata> =
+            Account::try_from(metadata_account_info)?;
+        let expected_metadata_address = Pubkey::create_program_address(
+            &[
+                STAKE_ACCOUNT_METADATA_SEED.as_bytes(),
+                next_delegate.as_ref(),
+                &[stake_account_metadata.metadata_bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| error!(ErrorCode::InvalidDelegationChainAccounts))?;
+        require!(
+            metadata_account_info.key() == expected_metadata_address,
+            ErrorCode::InvalidDelegationChainAccounts
+        );
+
+        record_delegation_chain_votes(
+            &mut stake_account_metadata,
+            amount_delta,
+            &operation,
+            chain_change.as_ref(),
+            chain.len() - 1,
+        )?;
+
+        if !stake_account_metadata.is_self_delegated() {
+            next_delegate = stake_account_metadata.delegate;
+            stake_account_metadata.exit(&crate::ID)?;
+            continue;
+        }
+
+        // The stake account at the end of the chain holds the votes
+        let checkpoints_account_info = remaining_accounts
+            .get(used_accounts_count)
+            .ok_or(ErrorCode::InvalidDelegationChainAccounts)?;
+        let next_checkpoints_account_info = remaining_accounts
+            .get(used_accounts_count + 1)
+            .ok_or(ErrorCode::InvalidDelegationChainAccounts)?;
+        used_accounts_count += 2;
+
+        let mut checkpoints_loader: AccountLoader<'info, CheckpointData> =
+            AccountLoader::try_from(checkpoints_account_info)?;
+        let (expected_checkpoints_address, _) = Pubkey::find_program_address(
+            &[
+                CHECKPOINT_DATA_SEED.as_bytes(),
+                next_delegate.as_ref(),
+                stake_account_metadata
+                    .stake_account_checkpoints_last_index
+                    .to_le_bytes()
+                    .as_ref(),
+            ],
+            &crate::ID,
+        );
+        require!(
+            checkpoints_account_info.key() == expected_checkpoints_address,
+            ErrorCode::InvalidDelegationChainAccounts
+        );
+
+        // A delegation change without votes only checks and records the delegation chain
+        if amount_delta == 0 {
+            stake_account_metadata.exit(&crate::ID)?;
+            return Ok((None, used_accounts_count));
+        }
+
+        require!(
+            checkpoints_loader.load()?.next_index < max_checkpoints_account_limit.into(),
+            ErrorCode::TooManyCheckpoints,
+        );
+
+        let delegate_votes_changed = push_checkpoint(
+            &mut checkpoints_loader,
+            checkpoints_account_info,
+            amount_delta,
+            operation,
+            current_timestamp,
+            payer_account_info,
+            system_program_account_info,
+            CheckpointsRollover {
+                stake_account_metadata: &mut *stake_account_metadata,
+                next_checkpoints_account_info: Some(next_checkpoints_account_info),
+                max_checkpoints_account_limit,
+            },
+        )?;
+        stake_account_metadata.exit(&crate::ID)?;
+
+        return Ok((Some(delegate_votes_changed), used_accounts_count));
+    }
+}
+
+/// Records the votes flowing through a stake account of a delegation chain,
+/// and the delegation chains a delegation change adds to it or removes from it
+fn record_delegation_chain_votes(
+    stake_account_metadata: &mut StakeAccountMetadata,
+    amount_delta: u64,
+    operation: &Operation,
+    chain_change: Option<&DelegationChainChange>,
+    distance: usize,
+) -> Result<()> {
+    stake_account_metadata.update_delegated_votes(amount_delta, operation)?;
+
+    if let Some(chain_change) = chain_change {
+        stake_account_metadata.update_delegation_chain_counts(
+            &chain_change.delegator_chain_counts,
+            distance,
+            operation,
+            chain_change.max_depth,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{record_delegation_chain_votes, DelegationChainChange};
+    use crate::state::checkpoints::Operation;
+    use crate::state::stake_account::StakeAccountMetadata;
+
+    #[test]
+    fn record_delegation_chain_votes_test() {
+        let mut stake_account_metadata = StakeAccountMetadata::default();
+        let mut delegator_chain_counts = [0; 8];
+        delegator_chain_counts[0] = 2;
+        let chain_change = DelegationChainChange {
+            delegator_chain_counts,
+            max_depth: 3,
+        };
+
+        // The delegator and the two stake accounts delegating to it are found at distance 2
+        record_delegation_chain_votes(
+            &mut stake_account_metadata,
+            100,
+            &Operation::Add,
+            Some(&chain_change),
+            2,
+        )
+        .unwrap();
+        assert_eq!(stake_account_metadata.delegated_votes, 100);
+        assert_eq!(
+            stake_account_metadata.delegation_chain_counts,
+            [0, 1, 2, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(stake_account_metadata.delegation_depth(), 3);
+
+        // Vote changes leave the delegation chains as they are
+        record_delegation_chain_votes(
+            &mut stake_account_metadata,
+            40,
+            &Operation::Subtract,
+            None,
+            2,
+        )
+        .unwrap();
+        assert_eq!(stake_account_metadata.delegated_votes, 60);
+        assert_eq!(stake_account_metadata.delegation_depth(), 3);
+
+        // A delegation removed without votes still shortens the chains
+        record_delegation_chain_votes(
+            &mut stake_account_metadata,
+            0,
+            &Operation::Subtract,
+            Some(&chain_change),
+            2,
+        )
+        .unwrap();
+        assert_eq!(stake_account_metadata.delegated_votes, 60);
+        assert_eq!(stake_account_metadata.delegation_depth(), 0);
+
+        // The delegation chains can not get longer than max_depth
+        assert!(record_delegation_chain_votes(
+            &mut stake_account_metadata,
+            0,
+            &Operation::Add,
+            Some(&chain_change),
+            3,
+        )
+        .is_err());
+    }
+}
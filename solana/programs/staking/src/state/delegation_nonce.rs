@@ -0,0 +1,75 @@
+use crate::context::DELEGATE_BY_SIG_DOMAIN;
+use anchor_lang::prelude::*;
+
+/// Nonce of the delegations signed off-chain by a stake account owner
+/// It is derived with seeds "delegation_nonce" and the stake account owner
+/// Every delegate_by_sig signature has to use the current nonce, which is then incremented
+#[account]
+#[derive(Default, Debug, InitSpace)]
+pub struct DelegationNonce {
+    pub bump: u8,
+    pub owner: Pubkey,
+    pub nonce: u64,
+}
+
+impl DelegationNonce {
+    pub const LEN: usize = DelegationNonce::DISCRIMINATOR.len() + DelegationNonce::INIT_SPACE;
+
+    /// Message signed by the stake account owner to delegate to the delegatee:
+    /// DELEGATE_BY_SIG_DOMAIN || program id (32 bytes) || delegatee (32 bytes) || nonce (u64 LE) || expiry (i64 LE).
+    /// The domain and the program id keep the signature from being replayed as another message
+    /// or on another deployment of the program
+    pub fn delegate_by_sig_message(
+        program_id: &Pubkey,
+        delegatee: &Pubkey,
+        nonce: u64,
+        expiry: i64,
+    ) -> Vec<u8> {
+        let mut message = Vec::with_capacity(DELEGATE_BY_SIG_DOMAIN.len() + 80);
+        message.extend_from_slice(DELEGATE_BY_SIG_DOMAIN.as_bytes());
+        message.extend_from_slice(program_id.as_ref());
+        message.extend_from_slice(delegatee.as_ref());
+        message.extend_from_slice(&nonce.to_le_bytes());
+        message.extend_from_slice(&expiry.to_le_bytes());
+        message
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::DelegationNonce;
+    use crate::context::DELEGATE_BY_SIG_DOMAIN;
+    use anchor_lang::prelude::Pubkey;
+
+    #[test]
+    fn check_size() {
+        assert!(DelegationNonce::LEN == 8 + 1 + 32 + 8); // == 49
+    }
+
+    #[test]
+    fn delegate_by_sig_message_test() {
+        let program_id = Pubkey::new_unique();
+        let delegatee = Pubkey::new_unique();
+        let message = DelegationNonce::delegate_by_sig_message(&program_id, &delegatee, 7, -1);
+
+        let domain_len = DELEGATE_BY_SIG_DOMAIN.len();
+        assert_eq!(message.len(), domain_len + 80);
+        assert_eq!(&message[..domain_len], DELEGATE_BY_SIG_DOMAIN.as_bytes());
+        assert_eq!(&message[domain_len..domain_len + 32], program_id.as_ref());
+        assert_eq!(
+            &message[domain_len + 32..domain_len + 64],
+            delegatee.as_ref()
+        );
+        assert_eq!(
+            &message[domain_len + 64..domain_len + 72],
+            &7u64.to_le_bytes()
+        );
+        assert_eq!(&message[domain_len + 72..], &[0xff; 8]);
+
+        // A signature for another deployment of the program does not match
+        assert_ne!(
+            message,
+            DelegationNonce::delegate_by_sig_message(&Pubkey::new_unique(), &delegatee, 7, -1)
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use crate::context::{CHECKPOINT_DATA_SEED, STAKE_ACCOUNT_METADATA_SEED};
+use crate::error::ErrorCode;
+use crate::state::checkpoints::{
+    push_checkpoint, CheckpointData, CheckpointsRollover, DelegateVotesChanged, Operation,
+};
+use crate::state::stake_account::StakeAccountMetadata;
+use anchor_lang::prelude::*;
+
+/// Split of the voting power of a stake account between several delegates
+/// It is derived with seeds "delegation_split" and the stake account owner
+/// While the split has shares, has_delegation_split is set on the stake account
+/// and every share delegate holds its part of the owner's voting power
+#[account]
+#[derive(Default, Debug)]
+pub struct DelegationSplit {
+    pub bump: u8,
+    pub owner: Pubkey,
+    pub shares: Vec<DelegationShare>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug, InitSpace)]
+pub struct DelegationShare {
+    pub delegate: Pubkey,
+    pub basis_points: u16,
+    // Votes checkpointed for the delegate on behalf of this split
+    pub votes: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct DelegationShareArgs {
+    pub delegate: Pubkey,
+    pub basis_points: u16,
+}
+
+/// Votes a delegate holds on behalf of a split before and after a change
+#[derive(Debug, PartialEq, Eq)]
+pub struct DelegateVotesUpdate {
+    pub delegate: Pubkey,
+    pub previous_votes: u64,
+    pub new_votes: u64,
+    // Whether the delegate starts or stops being delegated to by the split
+    pub delegator_change: Option<Operation>,
+}
+
+#[event]
+pub struct DelegationSplitChanged {
+    pub delegator: Pubkey,
+    pub shares: Vec<DelegationShare>,
+    pub total_delegated_votes: u64,
+}
+
+impl DelegationSplit {
+    pub const MAX_SHARES: usize = 8;
+    pub const TOTAL_BASIS_POINTS: u16 = 10_000;
+    pub const LEN: usize = DelegationSplit::DISCRIMINATOR.len()
+        + 1 // bump
+        + 32 // owner
+        + 4 + DelegationSplit::MAX_SHARES * DelegationShare::INIT_SPACE; // shares
+
+    // Number of remaining accounts needed for every delegate in push_delegate_votes_updates
+    pub const DELEGATE_ACCOUNTS_COUNT: usize = 3;
+
+    pub fn is_active(&self) -> bool {
+        !self.shares.is_empty()
+    }
+
+    /// Deserializes the split from its PDA, returning None if the split
+    /// has never been initialized or has no shares
+    pub fn load_active(account_info: &AccountInfo) -> Result<Option<DelegationSplit>> {
+        if *account_info.owner != crate::ID || account_info.data_is_empty() {
+            return Ok(None);
+        }
+
+        let data = account_info.try_borrow_data()?;
+        let delegation_split = DelegationSplit::try_deserialize(&mut &data[..])?;
+
+        Ok(if delegation_split.is_active() {
+            Some(delegation_split)
+        } else {
+            None
+        })
+    }
+
+    /// Serializes the split back to its PDA
+    pub fn store(&self, account_info: &AccountInfo) -> Result<()> {
+        let mut data = account_info.try_borrow_mut_data()?;
+        self.try_serialize(&mut &mut data[..])
+    }
+
+    pub fn total_votes(&self) -> Result<u64> {
+        self.shares.iter().try_fold(0u64, |total_votes, share| {
+            total_votes
+                .checked_add(share.votes)
+                .ok_or_else(|| error!(ErrorCode::GenericOverflow))
+        })
+    }
+
+    /// Replaces the shares of the split and divides total_votes between them by basis points.
+    /// Returns the votes update of every new share delegate in order,
+    /// followed by the delegates that are no longer part of the split
+    pub fn update_shares(
+        &mut self,
+        shares: &[DelegationShareArgs],
+        total_votes: u64,
+    ) -> Result<Vec<DelegateVotesUpdate>> {
+        require!(
+            !shares.is_empty() && shares.len() <= DelegationSplit::MAX_SHARES,
+            ErrorCode::InvalidDelegationSplit
+        );
+
+        let mut total_basis_points: u32 = 0;
+        for (i, share) in shares.iter().enumerate() {
+            require!(
+                share.basis_points > 0
+                    && share.delegate != Pubkey::default()
+                    && shares[..i].iter().all(|s| s.delegate != share.delegate),
+                ErrorCode::InvalidDelegationSplit
+            );
+            total_basis_points += u32::from(share.basis_points);
+        }
+        require!(
+            total_basis_points == u32::from(DelegationSplit::TOTAL_BASIS_POINTS),
+            ErrorCode::InvalidDelegationSplit
+        );
+
+        let previous_shares = std::mem::take(&mut self.shares);
+        let previous_votes = |delegate: &Pubkey| {
+            previous_shares
+                .iter()
+                .find(|share| share.delegate == *delegate)
+                .map_or(0, |share| share.votes)
+        };
+
+        // The last share receives the rounding remainder, so the shares always add up to total_votes
+        let mut remaining_votes = total_votes;
+        let mut updates = Vec::with_capacity(shares.len());
+        for (i, share) in shares.iter().enumerate() {
+            let votes = if i + 1 == shares.len() {
+                remaining_votes
+            } else {
+                (u128::from(total_votes) * u128::from(share.basis_points)
+                    / u128::from(DelegationSplit::TOTAL_BASIS_POINTS)) as u64
+            };
+            remaining_votes -= votes;
+
+            self.shares.push(DelegationShare {
+                delegate: share.delegate,
+                basis_points: share.basis_points,
+                votes,
+            });
+            let was_delegate = previous_shares
+                .iter()
+                .any(|previous_share| previous_share.delegate == share.delegate);
+            updates.push(DelegateVotesUpdate {
+                delegate: share.delegate,
+                previous_votes: previous_votes(&share.delegate),
+                new_votes: votes,
+                delegator_change: if was_delegate {
+                    None
+                } else {
+                    Some(Operation::Add)
+                },
+            });
+        }
+
+        for previous_share in previous_shares.iter() {
+            if shares.iter().all(|s| s.delegate != previous_share.delegate) {
+                updates.push(DelegateVotesUpdate {
+                    delegate: previous_share.delegate,
+                    previous_votes: previous_share.votes,
+                    new_votes: 0,
+                    delegator_change: Some(Operation::Subtract),
+                });
+            }
+        }
+
+        Ok(updates)
+    }
+
+    /// Divides total_votes between the current shares
+    pub fn redistribute(&mut self, total_votes: u64) -> Result<Vec<DelegateVotesUpdate>> {
+        let shares: Vec<DelegationShareArgs> = self
+            .shares
+            .iter()
+            .map(|share| DelegationShareArgs {
+                delegate: share.delegate,
+                basis_points: share.basis_points,
+            })
+            .collect();
+
+        self.update_shares(&shares, total_votes)
+    }
+
+    /// Removes all shares, returning the votes each delegate no longer holds
+    pub fn clear(&mut self) -> Vec<DelegateVotesUpdate> {
+        std::mem::take(&mut self.shares)
+            .into_iter()
+            .map(|share| DelegateVotesUpdate {
+                delegate: share.delegate,
+                previous_votes: share.votes,
+                new_votes: 0,
+                delegator_change: Some(Operation::Subtract),
+            })
+            .collect()
+    }
+}
+
+/// Divides the votes of a stake account with an active delegation split between the split delegates.
+/// new_total_votes maps the votes held by the split delegates to their new total, and the accounts
+/// of every split delegate are passed as remaining accounts, as for push_delegate_votes_updates
+pub fn push_delegation_split_votes<'info>(
+    delegation_split_account_info: &AccountInfo<'info>,
+    new_total_votes: impl FnOnce(u64) -> Result<u64>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    stake_account_metadata: &mut StakeAccountMetadata,
+    payer_account_info: &AccountInfo<'info>,
+    system_program_account_info: &AccountInfo<'info>,
+    current_timestamp: u64,
+    max_checkpoints_account_limit: u32,
+) -> Result<Vec<DelegateVotesChanged>> {
+    require!(
+        stake_account_metadata.has_delegation_split,
+        ErrorCode::InvalidDelegationSplitAccounts
+    );
+    let mut delegation_split = DelegationSplit::load_active(delegation_split_account_info)?
+        .ok_or(ErrorCode::InvalidDelegationSplitAccounts)?;
+    require!(
+        delegation_split.owner == stake_account_metadata.owner,
+        ErrorCode::InvalidDelegationSplitAccounts
+    );
+
+    let total_votes = new_total_votes(delegation_split.total_votes()?)?;
+    let updates = delegation_split.redistribute(total_votes)?;
+
+    let delegate_votes_changed = push_delegate_votes_updates(
+        &updates,
+        remaining_accounts,
+        stake_account_metadata,
+        payer_account_info,
+        system_program_account_info,
+        current_timestamp,
+        max_checkpoints_account_limit,
+    )?;
+    delegation_split.store(delegation_split_account_info)?;
+
+    Ok(delegate_votes_changed)
+}
+
+/// Pushes a checkpoint for every delegate whose votes changed and updates the delegator count
+/// of the delegates that start or stop being delegated to, other than the stake account owner.
+/// The accounts of every update are passed in the same order as remaining accounts,
+/// DELEGATE_ACCOUNTS_COUNT per delegate: the current CheckpointData,
+/// the next CheckpointData PDA and the StakeAccountMetadata of the delegate
+pub fn push_delegate_votes_updates<'info>(
+    updates: &[DelegateVotesUpdate],
+    remaining_accounts: &'info [AccountInfo<'info>],
+    stake_account_metadata: &mut StakeAccountMetadata,
+    payer_account_info: &AccountInfo<'info>,
+    system_program_account_info: &AccountInfo<'info>,
+    current_timestamp: u64,
+    max_checkpoints_account_limit: u32,
+) -> Result<Vec<DelegateVotesChanged>> {
+    require!(
+        remaining_accounts.len() == updates.len() * DelegationSplit::DELEGATE_ACCOUNTS_COUNT,
+        ErrorCode::InvalidDelegationSplitAccounts
+    );
+
+    let mut delegate_votes_changed = Vec::with_capacity(updates.len());
+    for (update, delegate_accounts) in updates
+        .iter()
+        .zip(remaining_accounts.chunks(DelegationSplit::DELEGATE_ACCOUNTS_COUNT))
+    {
+        let checkpoints_account_info = &delegate_accounts[0];
+        let next_checkpoints_account_info = &delegate_accounts[1];
+        let delegate_metadata_account_info = &delegate_accounts[2];
+
+        let mut delegate_stake_account_metadata: Account<'info, StakeAccountMetadata> =
+            Account::try_from(delegate_metadata_account_info)?;
+        let expected_delegate_metadata_address = Pubkey::create_program_address(
+            &[
+                STAKE_ACCOUNT_METADATA_SEED.as_bytes(),
+                update.delegate.as_ref(),
+                &[delegate_stake_account_metadata.metadata_bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| error!(ErrorCode::InvalidDelegationSplitAccounts))?;
+        require!(
+            delegate_metadata_account_info.key() == expected_delegate_metadata_address,
+            ErrorCode::InvalidDelegationSplitAccounts
+        );
+
+        let mut checkpoints_loader: AccountLoader<'info, CheckpointData> =
+            AccountLoader::try_from(checkpoints_account_info)?;
+        let (expected_checkpoints_address, _) = Pubkey::find_program_address(
+            &[
+                CHECKPOINT_DATA_SEED.as_bytes(),
+                update.delegate.as_ref(),
+                delegate_stake_account_metadata
+                    .stake_account_checkpoints_last_index
+                    .to_le_bytes()
+                    .as_ref(),
+            ],
+            &crate::ID,
+        );
+        require!(
+            checkpoints_account_info.key() == expected_checkpoints_address,
+            ErrorCode::InvalidDelegationSplitAccounts
+        );
+
+        // The owner's StakeAccountMetadata is serialized by the instruction context,
+        // so it has to be the one updated on rollover when the owner is one of the delegates
+        let delegate_is_owner =
+            delegate_stake_account_metadata.owner == stake_account_metadata.owner;
+
+        let delegator_count_changed = match &update.delegator_change {
+            Some(operation) if !delegate_is_owner => {
+                delegate_stake_account_metadata.update_delegator_count(operation)?;
+                true
+            }
+            _ => false,
+        };
+
+        if update.previous_votes == update.new_votes {
+            if delegator_count_changed {
+                delegate_stake_account_metadata.exit(&crate::ID)?;
+            }
+            continue;
+        }
+
+        require!(
+            checkpoints_loader.load()?.next_index < max_checkpoints_account_limit.into(),
+            ErrorCode::TooManyCheckpoints,
+        );
+
+        let (amount_delta, operation) = if update.new_votes > update.previous_votes {
+            (update.new_votes - update.previous_votes, Operation::Add)
+        } else {
+            (
+                update.previous_votes - update.new_votes,
+                Operation::Subtract,
+            )
+        };
+
+        let last_index = delegate_stake_account_metadata.stake_account_checkpoints_last_index;
+
+        delegate_votes_changed.push(push_checkpoint(
+            &mut checkpoints_loader,
+            checkpoints_account_info,
+            amount_delta,
+            operation,
+            current_timestamp,
+            payer_account_info,
+            system_program_account_info,
+            CheckpointsRollover {
+                stake_account_metadata: if delegate_is_owner {
+                    &mut *stake_account_metadata
+                } else {
+                    &mut *delegate_stake_account_metadata
+                },
+                next_checkpoints_account_info: Some(next_checkpoints_account_info),
+                max_checkpoints_account_limit,
+            },
+        )?);
+
+        if !delegate_is_owner
+            && (delegator_count_changed
+                || delegate_stake_account_metadata.stake_account_checkpoints_last_index
+                    != last_index)
+        {
+            delegate_stake_account_metadata.exit(&crate::ID)?;
+        }
+    }
+
+    Ok(delegate_votes_changed)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{DelegateVotesUpdate, DelegationShareArgs, DelegationSplit};
+    use crate::state::checkpoints::Operation;
+    use anchor_lang::prelude::Pubkey;
+
+    #[test]
+    fn check_size() {
+        assert!(DelegationSplit::LEN == 8 + 1 + 32 + 4 + 8 * (32 + 2 + 8)); // == 381
+    }
+
+    #[test]
+    fn update_shares_test() {
+        let delegate_a = Pubkey::new_unique();
+        let delegate_b = Pubkey::new_unique();
+        let delegate_c = Pubkey::new_unique();
+        let mut delegation_split = DelegationSplit::default();
+
+        let updates = delegation_split
+            .update_shares(
+                &[
+                    DelegationShareArgs {
+                        delegate: delegate_a,
+                        basis_points: 3_333,
+                    },
+                    DelegationShareArgs {
+                        delegate: delegate_b,
+                        basis_points: 6_667,
+                    },
+                ],
+                1_000,
+            )
+            .unwrap();
+        assert_eq!(
+            updates,
+            vec![
+                DelegateVotesUpdate {
+                    delegate: delegate_a,
+                    previous_votes: 0,
+                    new_votes: 333,
+                    delegator_change: Some(Operation::Add),
+                },
+                DelegateVotesUpdate {
+                    delegate: delegate_b,
+                    previous_votes: 0,
+                    new_votes: 667,
+                    delegator_change: Some(Operation::Add),
+                },
+            ]
+        );
+        assert_eq!(delegation_split.total_votes().unwrap(), 1_000);
+
+        let updates = delegation_split
+            .update_shares(
+                &[
+                    DelegationShareArgs {
+                        delegate: delegate_c,
+                        basis_points: 5_000,
+                    },
+                    DelegationShareArgs {
+                        delegate: delegate_b,
+                        basis_points: 5_000,
+                    },
+                ],
+                999,
+            )
+            .unwrap();
+        assert_eq!(
+            updates,
+            vec![
+                DelegateVotesUpdate {
+                    delegate: delegate_c,
+                    previous_votes: 0,
+                    new_votes: 499,
+                    delegator_change: Some(Operation::Add),
+                },
+                DelegateVotesUpdate {
+                    delegate: delegate_b,
+                    previous_votes: 667,
+                    new_votes: 500,
+                    delegator_change: None,
+                },
+                DelegateVotesUpdate {
+                    delegate: delegate_a,
+                    previous_votes: 333,
+                    new_votes: 0,
+                    delegator_change: Some(Operation::Subtract),
+                },
+            ]
+        );
+        assert_eq!(delegation_split.total_votes().unwrap(), 999);
+
+        let updates = delegation_split.clear();
+        assert_eq!(updates.len(), 2);
+        assert!(!delegation_split.is_active());
+    }
+
+    #[test]
+    fn update_shares_invalid_test() {
+        let delegate = Pubkey::new_unique();
+        let mut delegation_split = DelegationSplit::default();
+
+        // Basis points must add up to 10000
+        assert!(delegation_split
+            .update_shares(
+                &[DelegationShareArgs {
+                    delegate,
+                    basis_points: 9_999,
+                }],
+                100,
+            )
+            .is_err());
+
+        // Delegates must be distinct
+        assert!(delegation_split
+            .update_shares(
+                &[
+                    DelegationShareArgs {
+                        delegate,
+                        basis_points: 5_000,
+                    },
+                    DelegationShareArgs {
+                        delegate,
+                        basis_points: 5_000,
+                    },
+                ],
+                100,
+            )
+            .is_err());
+
+        assert!(delegation_split.update_shares(&[], 100).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::error::ErrorCode;
+use crate::state::checkpoints::Operation;
 use anchor_lang::prelude::borsh::BorshSchema;
 use anchor_lang::prelude::*;
 
@@ -13,18 +15,203 @@ pub struct GlobalConfig {
     // Pending new admins (before claiming ownership)
     pub pending_vesting_admin: Option<Pubkey>,
     pub pending_governance_authority: Option<Pubkey>,
+    // Delay in seconds between a withdrawal request and its completion, 0 disables the cooldown
+    pub withdrawal_cooldown: u64,
+    pub delegation_policy: DelegationPolicy,
+    // Seconds before the vote end of a proposal after which votes can no longer be revised
+    pub vote_revision_cutoff: u64,
+    // Longest period in seconds between the vote start and the vote end of the stored proposals,
+    // raised as proposals are added or extended. Bounds the checkpoints cast_vote can still read
+    pub max_voting_period: u64,
+    // Stake accounts delegating their votes to other stake accounts, directly or through a
+    // delegation split. The delegation policy can only be switched while there are none
+    pub delegating_stake_accounts: u64,
+}
+
+/// How the votes of a stake account flow through the delegations of its delegates
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Default,
+    Debug,
+    PartialEq,
+    Eq,
+    BorshSchema,
+    InitSpace,
+)]
+pub enum DelegationPolicy {
+    // The votes are held by the delegate of the stake account
+    #[default]
+    SingleHop,
+    // The votes follow the delegations of the delegates up to the stake account that delegates
+    // to itself, with chains of at most max_depth delegations
+    Liquid {
+        max_depth: u8,
+    },
+}
+
+impl DelegationPolicy {
+    // Bounds the number of stake accounts passed to an instruction for a delegation chain
+    pub const MAX_DEPTH: u8 = 8;
+
+    pub fn is_liquid(&self) -> bool {
+        matches!(self, DelegationPolicy::Liquid { .. })
+    }
+
+    pub fn max_depth(&self) -> Option<u8> {
+        match self {
+            DelegationPolicy::SingleHop => None,
+            DelegationPolicy::Liquid { max_depth } => Some(*max_depth),
+        }
+    }
 }
 
 impl GlobalConfig {
     pub const LEN: usize = GlobalConfig::DISCRIMINATOR.len() + GlobalConfig::INIT_SPACE;
+
+    /// Reads a global config account created with a previous, shorter layout. New fields are
+    /// only appended to the layout, so they read as zero
+    pub fn from_previous_account_data(data: &[u8]) -> Result<GlobalConfig> {
+        require!(
+            data.len() < GlobalConfig::LEN,
+            ErrorCode::AccountAlreadyMigrated
+        );
+
+        let mut padded_data = data.to_vec();
+        padded_data.resize(GlobalConfig::LEN, 0);
+        GlobalConfig::try_deserialize(&mut padded_data.as_slice())
+    }
+
+    /// Raises the max voting period to cover the voting period of a proposal.
+    /// Proposals whose vote end is unknown are only covered once their vote end is extended
+    pub fn record_voting_period(&mut self, vote_start: u64, vote_end: u64) {
+        if vote_end != 0 {
+            self.max_voting_period = self
+                .max_voting_period
+                .max(vote_end.saturating_sub(vote_start));
+        }
+    }
+
+    /// Counts a stake account that starts or stops delegating its votes to other stake accounts.
+    /// Stake accounts are only counted once migrated, so removing one saturates at zero
+    pub fn update_delegating_stake_accounts(&mut self, operation: &Operation) -> Result<()> {
+        self.delegating_stake_accounts = match operation {
+            Operation::Add => self
+                .delegating_stake_accounts
+                .checked_add(1)
+                .ok_or(ErrorCode::GenericOverflow)?,
+            Operation::Subtract => self.delegating_stake_accounts.saturating_sub(1),
+        };
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use super::GlobalConfig;
+    use super::{DelegationPolicy, GlobalConfig};
+    use crate::state::checkpoints::Operation;
+    use anchor_lang::prelude::*;
+
+    // Length of the global config accounts created before the withdrawal cooldown
+    const V0_GLOBAL_CONFIG_LEN: usize = 8 + 1 + 4 + 32 + 32 + 32 + 33 + 33;
 
     #[test]
     fn check_size() {
-        assert!(GlobalConfig::LEN == 8 + 1 + 4 + 32 + 32 + 32 + 33 + 33) // == 175
+        assert!(GlobalConfig::LEN == 8 + 1 + 4 + 32 + 32 + 32 + 33 + 33 + 8 + 2 + 8 + 8 + 8)
+        // == 209
+    }
+
+    #[test]
+    fn delegation_policy_test() {
+        assert_eq!(DelegationPolicy::default(), DelegationPolicy::SingleHop);
+        assert!(!DelegationPolicy::SingleHop.is_liquid());
+        assert!(DelegationPolicy::Liquid { max_depth: 3 }.is_liquid());
+        assert_eq!(DelegationPolicy::SingleHop.max_depth(), None);
+        assert_eq!(
+            DelegationPolicy::Liquid { max_depth: 3 }.max_depth(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn from_previous_account_data_test() {
+        let config = GlobalConfig {
+            bump: 254,
+            max_checkpoints_account_limit: 654_998,
+            governance_authority: Pubkey::new_unique(),
+            voting_token_mint: Pubkey::new_unique(),
+            vesting_admin: Pubkey::new_unique(),
+            pending_vesting_admin: Some(Pubkey::new_unique()),
+            pending_governance_authority: Some(Pubkey::new_unique()),
+            ..Default::default()
+        };
+        let mut data = vec![];
+        config.try_serialize(&mut data).unwrap();
+        data.resize(GlobalConfig::LEN, 0);
+
+        let migrated =
+            GlobalConfig::from_previous_account_data(&data[..V0_GLOBAL_CONFIG_LEN]).unwrap();
+        assert_eq!(migrated.bump, config.bump);
+        assert_eq!(
+            migrated.max_checkpoints_account_limit,
+            config.max_checkpoints_account_limit
+        );
+        assert_eq!(migrated.governance_authority, config.governance_authority);
+        assert_eq!(migrated.voting_token_mint, config.voting_token_mint);
+        assert_eq!(migrated.vesting_admin, config.vesting_admin);
+        assert_eq!(migrated.pending_vesting_admin, config.pending_vesting_admin);
+        assert_eq!(
+            migrated.pending_governance_authority,
+            config.pending_governance_authority
+        );
+        assert_eq!(migrated.withdrawal_cooldown, 0);
+
+        // An account with the current layout is already migrated
+        assert!(GlobalConfig::from_previous_account_data(&data).is_err());
+
+        // An account of another type is rejected
+        data[0] ^= 1;
+        assert!(GlobalConfig::from_previous_account_data(&data[..V0_GLOBAL_CONFIG_LEN]).is_err());
+    }
+
+    #[test]
+    fn record_voting_period_test() {
+        let mut config = GlobalConfig::default();
+
+        config.record_voting_period(100, 160);
+        assert_eq!(config.max_voting_period, 60);
+        config.record_voting_period(200, 230);
+        assert_eq!(config.max_voting_period, 60);
+        // An unknown vote end does not bound the voting period
+        config.record_voting_period(300, 0);
+        assert_eq!(config.max_voting_period, 60);
+        config.record_voting_period(300, 400);
+        assert_eq!(config.max_voting_period, 100);
+    }
+
+    #[test]
+    fn update_delegating_stake_accounts_test() {
+        let mut config = GlobalConfig::default();
+
+        config
+            .update_delegating_stake_accounts(&Operation::Add)
+            .unwrap();
+        config
+            .update_delegating_stake_accounts(&Operation::Add)
+            .unwrap();
+        config
+            .update_delegating_stake_accounts(&Operation::Subtract)
+            .unwrap();
+        assert_eq!(config.delegating_stake_accounts, 1);
+
+        config
+            .update_delegating_stake_accounts(&Operation::Subtract)
+            .unwrap();
+        config
+            .update_delegating_stake_accounts(&Operation::Subtract)
+            .unwrap();
+        assert_eq!(config.delegating_stake_accounts, 0);
     }
 }
@@ -8,12 +8,23 @@ pub mod proposal;
 pub mod proposal_voters_weight_cast;
 pub mod stake_account;
 
+pub mod delegation_chain;
+pub mod delegation_nonce;
+pub mod delegation_split;
+pub mod pending_withdrawal;
+
 pub mod vesting;
 pub use vesting::*;
 
 pub mod vesting_balance;
 pub use vesting_balance::*;
 
+pub mod vesting_schedule;
+pub use vesting_schedule::*;
+
+pub mod vesting_merkle_distributor;
+pub use vesting_merkle_distributor::*;
+
 pub mod spoke_message_executor;
 pub use spoke_message_executor::*;
 
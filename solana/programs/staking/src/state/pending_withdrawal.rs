@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+/// Withdrawal requested by a stake account owner while the withdrawal cooldown is enabled
+/// It is derived with seeds "pending_withdrawal" and the stake account owner
+/// The requested tokens are held in the withdrawal custody until the release time
+#[account]
+#[derive(Default, Debug, InitSpace)]
+pub struct PendingWithdrawal {
+    pub bump: u8,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub release_time: u64,
+}
+
+impl PendingWithdrawal {
+    pub const LEN: usize = PendingWithdrawal::DISCRIMINATOR.len() + PendingWithdrawal::INIT_SPACE;
+}
+
+#[event]
+pub struct WithdrawalRequested {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub total_pending_amount: u64,
+    pub release_time: u64,
+}
+
+#[event]
+pub struct WithdrawalCompleted {
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::PendingWithdrawal;
+
+    #[test]
+    fn check_size() {
+        assert!(PendingWithdrawal::LEN == 8 + 1 + 32 + 8 + 8); // == 57
+    }
+}
@@ -1,6 +1,9 @@
+use crate::context::PROPOSAL_SEED;
 use crate::error::ErrorCode;
 use anchor_lang::prelude::borsh::BorshSchema;
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
+use anchor_lang::solana_program::system_instruction;
 
 #[account]
 #[derive(Debug, BorshSchema, InitSpace)]
@@ -10,29 +13,163 @@ pub struct ProposalData {
     pub for_votes: u64,
     pub abstain_votes: u64,
     pub vote_start: u64,
+    // 0 when the vote end is unknown: for proposals migrated from the legacy layout
+    // and for proposals whose hub metadata does not carry it
+    pub vote_end: u64,
+    // Tallies read by the hub. The hub rejects tallies lower than the ones it already counted,
+    // so the published tallies never decrease
+    pub published_against_votes: u64,
+    pub published_for_votes: u64,
+    pub published_abstain_votes: u64,
+}
+
+/// Proposal accounts created before the vote end and the published tallies were added
+pub const LEGACY_PROPOSAL_DATA_LEN: usize = 72;
+
+#[derive(AnchorDeserialize)]
+struct LegacyProposalData {
+    id: [u8; 32],
+    against_votes: u64,
+    for_votes: u64,
+    abstain_votes: u64,
+    vote_start: u64,
 }
 
 impl ProposalData {
     pub const LEN: usize = ProposalData::DISCRIMINATOR.len() + ProposalData::INIT_SPACE;
 
-    fn initialize(&mut self, proposal_id: [u8; 32], vote_start: u64) {
+    fn initialize(&mut self, proposal_id: [u8; 32], vote_start: u64, vote_end: u64) {
         self.id = proposal_id;
         self.against_votes = 0;
         self.for_votes = 0;
         self.abstain_votes = 0;
         self.vote_start = vote_start;
+        self.vote_end = vote_end;
+        self.published_against_votes = 0;
+        self.published_for_votes = 0;
+        self.published_abstain_votes = 0;
     }
 
     pub fn add_proposal(
         &mut self,
         proposal_id: [u8; 32],
         vote_start: u64,
+        vote_end: u64,
     ) -> anchor_lang::Result<()> {
         require!(self.vote_start == 0, ErrorCode::ProposalAlreadyExists);
-        self.initialize(proposal_id, vote_start);
+        self.initialize(proposal_id, vote_start, vote_end);
         Ok(())
     }
 
+    /// Reads a proposal account with the legacy layout. The migrated proposal keeps its tallies,
+    /// which are published as the hub may already have read them. Its vote end is unknown
+    pub fn from_legacy_account_data(data: &[u8]) -> Result<ProposalData> {
+        require!(
+            data.len() == LEGACY_PROPOSAL_DATA_LEN,
+            ErrorCode::AccountAlreadyMigrated
+        );
+        require!(
+            data[..8] == *ProposalData::DISCRIMINATOR,
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        let legacy_proposal = LegacyProposalData::deserialize(&mut &data[8..])?;
+
+        Ok(ProposalData {
+            id: legacy_proposal.id,
+            against_votes: legacy_proposal.against_votes,
+            for_votes: legacy_proposal.for_votes,
+            abstain_votes: legacy_proposal.abstain_votes,
+            vote_start: legacy_proposal.vote_start,
+            vote_end: 0,
+            published_against_votes: legacy_proposal.against_votes,
+            published_for_votes: legacy_proposal.for_votes,
+            published_abstain_votes: legacy_proposal.abstain_votes,
+        })
+    }
+
+    /// Pushes back the vote end of the proposal to the deadline set by the hub proposal extender
+    pub fn extend_vote_end(&mut self, new_vote_end: u64) -> Result<()> {
+        require!(
+            new_vote_end > self.vote_end && new_vote_end >= self.vote_start,
+            ErrorCode::VoteEndNotExtended
+        );
+        self.vote_end = new_vote_end;
+        Ok(())
+    }
+
+    /// Votes are accepted until the vote end, as on the hub governor.
+    /// The voting period of a proposal whose vote end is unknown does not end
+    pub fn voting_period_ended(&self, current_timestamp: u64) -> bool {
+        self.vote_end != 0 && current_timestamp > self.vote_end
+    }
+
+    pub fn add_votes(
+        &mut self,
+        against_votes: u64,
+        for_votes: u64,
+        abstain_votes: u64,
+    ) -> Result<()> {
+        self.against_votes = self
+            .against_votes
+            .checked_add(against_votes)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        self.for_votes = self
+            .for_votes
+            .checked_add(for_votes)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        self.abstain_votes = self
+            .abstain_votes
+            .checked_add(abstain_votes)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        Ok(())
+    }
+
+    pub fn remove_votes(
+        &mut self,
+        against_votes: u64,
+        for_votes: u64,
+        abstain_votes: u64,
+    ) -> Result<()> {
+        self.against_votes = self
+            .against_votes
+            .checked_sub(against_votes)
+            .ok_or(ErrorCode::GenericUnderflow)?;
+        self.for_votes = self
+            .for_votes
+            .checked_sub(for_votes)
+            .ok_or(ErrorCode::GenericUnderflow)?;
+        self.abstain_votes = self
+            .abstain_votes
+            .checked_sub(abstain_votes)
+            .ok_or(ErrorCode::GenericUnderflow)?;
+        Ok(())
+    }
+
+    /// Publishes the current tallies to the hub
+    pub fn publish_votes(&mut self) {
+        self.published_against_votes = self.against_votes;
+        self.published_for_votes = self.for_votes;
+        self.published_abstain_votes = self.abstain_votes;
+    }
+
+    /// Whether no tally is below its published value. Revisions have to keep it that way,
+    /// so that the published tallies never decrease
+    pub fn covers_published_votes(&self) -> bool {
+        self.against_votes >= self.published_against_votes
+            && self.for_votes >= self.published_for_votes
+            && self.abstain_votes >= self.published_abstain_votes
+    }
+
+    /// Votes can be revised until vote_revision_cutoff seconds before the vote end,
+    /// which leaves time to bridge the final tallies to the hub.
+    /// Revisions are closed while the vote end is unknown
+    pub fn vote_revision_open(&self, current_timestamp: u64, vote_revision_cutoff: u64) -> bool {
+        self.vote_end != 0
+            && current_timestamp
+                .checked_add(vote_revision_cutoff)
+                .map_or(false, |deadline| deadline < self.vote_end)
+    }
+
     pub fn proposal_votes(&self) -> Result<Option<([u8; 32], u64, u64, u64)>> {
         Ok(Some((
             self.id,
@@ -43,13 +180,105 @@ impl ProposalData {
     }
 }
 
+/// Creates the ProposalData PDA of the proposal id for proposals added in a batch,
+/// where the proposal accounts are passed as remaining accounts.
+/// Accounts that were already funded with lamports are allocated and assigned the same way
+/// Anchor handles `init`
+pub fn create_proposal_account<'info>(
+    account_info: &AccountInfo<'info>,
+    proposal_id: [u8; 32],
+    vote_start: u64,
+    vote_end: u64,
+    payer_account_info: &AccountInfo<'info>,
+    system_program_account_info: &AccountInfo<'info>,
+) -> Result<()> {
+    let (expected_address, bump) =
+        Pubkey::find_program_address(&[PROPOSAL_SEED.as_bytes(), &proposal_id], &crate::ID);
+    require!(
+        account_info.key() == expected_address,
+        ErrorCode::InvalidProposalAccounts
+    );
+
+    let bump_bytes = [bump];
+    let signer_seeds: &[&[u8]] = &[
+        PROPOSAL_SEED.as_bytes(),
+        proposal_id.as_ref(),
+        bump_bytes.as_ref(),
+    ];
+
+    let space = ProposalData::LEN;
+    let required_lamports = Rent::get()?.minimum_balance(space);
+    let current_lamports = account_info.lamports();
+
+    if current_lamports == 0 {
+        invoke_signed(
+            &system_instruction::create_account(
+                payer_account_info.key,
+                account_info.key,
+                required_lamports,
+                space as u64,
+                &crate::ID,
+            ),
+            &[
+                payer_account_info.clone(),
+                account_info.clone(),
+                system_program_account_info.clone(),
+            ],
+            &[signer_seeds],
+        )?;
+    } else {
+        let lamports_needed = required_lamports.saturating_sub(current_lamports);
+        if lamports_needed > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    payer_account_info.key,
+                    account_info.key,
+                    lamports_needed,
+                ),
+                &[
+                    payer_account_info.clone(),
+                    account_info.clone(),
+                    system_program_account_info.clone(),
+                ],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(account_info.key, space as u64),
+            &[account_info.clone(), system_program_account_info.clone()],
+            &[signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account_info.key, &crate::ID),
+            &[account_info.clone(), system_program_account_info.clone()],
+            &[signer_seeds],
+        )?;
+    }
+
+    let mut proposal = ProposalData {
+        id: [0; 32],
+        against_votes: 0,
+        for_votes: 0,
+        abstain_votes: 0,
+        vote_start: 0,
+        vote_end: 0,
+        published_against_votes: 0,
+        published_for_votes: 0,
+        published_abstain_votes: 0,
+    };
+    proposal.add_proposal(proposal_id, vote_start, vote_end)?;
+
+    let mut data = account_info.try_borrow_mut_data()?;
+    proposal.try_serialize(&mut &mut data[..])
+}
+
 #[cfg(test)]
 pub mod tests {
-    use super::ProposalData;
+    use super::{ProposalData, LEGACY_PROPOSAL_DATA_LEN};
+    use anchor_lang::Discriminator;
 
     #[test]
     fn check_size() {
-        assert!(ProposalData::LEN == 8 + 32 + 4 * 8); // 72
+        assert!(ProposalData::LEN == 8 + 32 + 8 * 8); // 104
     }
 
     #[test]
@@ -61,6 +290,10 @@ pub mod tests {
             for_votes: 40,
             abstain_votes: 30,
             vote_start: 10,
+            vote_end: 20,
+            published_against_votes: 0,
+            published_for_votes: 0,
+            published_abstain_votes: 0,
         };
 
         assert_eq!(
@@ -68,4 +301,188 @@ pub mod tests {
             Some((proposal_id, 50, 40, 30))
         );
     }
+
+    #[test]
+    fn add_votes_test() {
+        let proposal = &mut ProposalData {
+            id: [1; 32],
+            against_votes: 0,
+            for_votes: 0,
+            abstain_votes: 0,
+            vote_start: 0,
+            vote_end: 0,
+            published_against_votes: 0,
+            published_for_votes: 0,
+            published_abstain_votes: 0,
+        };
+
+        proposal.add_proposal([1; 32], 10, 20).unwrap();
+        assert!(proposal.add_proposal([1; 32], 10, 20).is_err());
+
+        proposal.add_votes(10, 40, 0).unwrap();
+        proposal.add_votes(0, 0, 30).unwrap();
+        assert!(proposal.add_votes(0, u64::MAX, 0).is_err());
+        assert_eq!(
+            proposal.proposal_votes().unwrap(),
+            Some(([1; 32], 10, 40, 30))
+        );
+
+        proposal.remove_votes(5, 20, 0).unwrap();
+        assert!(proposal.remove_votes(0, 0, 31).is_err());
+        assert_eq!(
+            proposal.proposal_votes().unwrap(),
+            Some(([1; 32], 5, 20, 30))
+        );
+    }
+
+    #[test]
+    fn publish_votes_test() {
+        let proposal = &mut ProposalData {
+            id: [1; 32],
+            against_votes: 0,
+            for_votes: 0,
+            abstain_votes: 0,
+            vote_start: 10,
+            vote_end: 100,
+            published_against_votes: 0,
+            published_for_votes: 0,
+            published_abstain_votes: 0,
+        };
+
+        proposal.add_votes(10, 20, 0).unwrap();
+        assert!(proposal.covers_published_votes());
+        proposal.publish_votes();
+        assert_eq!(
+            (
+                proposal.published_against_votes,
+                proposal.published_for_votes,
+                proposal.published_abstain_votes
+            ),
+            (10, 20, 0)
+        );
+
+        // Unpublished votes can be moved, published ones can not
+        proposal.add_votes(5, 0, 0).unwrap();
+        proposal.remove_votes(5, 0, 0).unwrap();
+        proposal.add_votes(0, 0, 5).unwrap();
+        assert!(proposal.covers_published_votes());
+        proposal.remove_votes(0, 1, 0).unwrap();
+        assert!(!proposal.covers_published_votes());
+        proposal.add_votes(0, 1, 0).unwrap();
+
+        proposal.publish_votes();
+        assert_eq!(
+            (
+                proposal.published_against_votes,
+                proposal.published_for_votes,
+                proposal.published_abstain_votes
+            ),
+            (10, 20, 5)
+        );
+    }
+
+    #[test]
+    fn vote_revision_open_test() {
+        let proposal = &mut ProposalData {
+            id: [1; 32],
+            against_votes: 0,
+            for_votes: 0,
+            abstain_votes: 0,
+            vote_start: 10,
+            vote_end: 0,
+            published_against_votes: 0,
+            published_for_votes: 0,
+            published_abstain_votes: 0,
+        };
+
+        assert!(!proposal.vote_revision_open(15, 0));
+
+        proposal.vote_end = 100;
+        assert!(proposal.vote_revision_open(15, 0));
+        assert!(proposal.vote_revision_open(15, 84));
+        assert!(!proposal.vote_revision_open(15, 85));
+        assert!(!proposal.vote_revision_open(100, 0));
+        assert!(!proposal.vote_revision_open(15, u64::MAX));
+    }
+
+    #[test]
+    fn voting_period_ended_test() {
+        let proposal = &mut ProposalData {
+            id: [1; 32],
+            against_votes: 0,
+            for_votes: 0,
+            abstain_votes: 0,
+            vote_start: 10,
+            vote_end: 0,
+            published_against_votes: 0,
+            published_for_votes: 0,
+            published_abstain_votes: 0,
+        };
+
+        assert!(!proposal.voting_period_ended(u64::MAX));
+
+        proposal.vote_end = 100;
+        assert!(!proposal.voting_period_ended(100));
+        assert!(proposal.voting_period_ended(101));
+    }
+
+    #[test]
+    fn extend_vote_end_test() {
+        let proposal = &mut ProposalData {
+            id: [1; 32],
+            against_votes: 0,
+            for_votes: 0,
+            abstain_votes: 0,
+            vote_start: 10,
+            vote_end: 100,
+            published_against_votes: 0,
+            published_for_votes: 0,
+            published_abstain_votes: 0,
+        };
+
+        assert!(proposal.extend_vote_end(0).is_err());
+        assert!(proposal.extend_vote_end(100).is_err());
+        proposal.extend_vote_end(150).unwrap();
+        assert_eq!(proposal.vote_end, 150);
+
+        // The vote end of a migrated proposal becomes known once it is extended
+        proposal.vote_end = 0;
+        assert!(proposal.extend_vote_end(5).is_err());
+        proposal.extend_vote_end(150).unwrap();
+        assert!(proposal.voting_period_ended(151));
+    }
+
+    #[test]
+    fn from_legacy_account_data_test() {
+        let mut data = ProposalData::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[1; 32]);
+        for value in [50u64, 40, 30, 10] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        assert_eq!(data.len(), LEGACY_PROPOSAL_DATA_LEN);
+
+        let proposal = ProposalData::from_legacy_account_data(&data).unwrap();
+        assert_eq!(
+            proposal.proposal_votes().unwrap(),
+            Some(([1; 32], 50, 40, 30))
+        );
+        assert_eq!(proposal.vote_start, 10);
+        assert_eq!(proposal.vote_end, 0);
+        assert_eq!(
+            (
+                proposal.published_against_votes,
+                proposal.published_for_votes,
+                proposal.published_abstain_votes
+            ),
+            (50, 40, 30)
+        );
+
+        // Migrated accounts are rejected
+        let mut migrated_data = data.clone();
+        migrated_data.resize(ProposalData::LEN, 0);
+        assert!(ProposalData::from_legacy_account_data(&migrated_data).is_err());
+
+        data[0] ^= 1;
+        assert!(ProposalData::from_legacy_account_data(&data).is_err());
+    }
 }
@@ -1,14 +1,30 @@
+use crate::error::ErrorCode;
+use crate::state::proposal::ProposalData;
 use crate::Pubkey;
 use anchor_lang::account;
 use anchor_lang::prelude::borsh::BorshSchema;
 use anchor_lang::prelude::*;
 
+/// Accounts created before the breakdown of the cast weight was added
+pub const LEGACY_PROPOSAL_VOTERS_WEIGHT_CAST_LEN: usize = 80;
+
+#[derive(AnchorDeserialize)]
+struct LegacyProposalVotersWeightCast {
+    proposal_id: [u8; 32],
+    voter: Pubkey,
+    value: u64,
+}
+
 #[account]
 #[derive(BorshSchema, InitSpace)]
 pub struct ProposalVotersWeightCast {
     pub proposal_id: [u8; 32],
     pub voter: Pubkey,
     pub value: u64,
+    // Breakdown of the weight cast, adding up to value
+    pub against_votes: u64,
+    pub for_votes: u64,
+    pub abstain_votes: u64,
 }
 
 impl ProposalVotersWeightCast {
@@ -19,19 +35,179 @@ impl ProposalVotersWeightCast {
         self.proposal_id = proposal_id;
         self.voter = *voter;
         self.value = 0;
+        self.against_votes = 0;
+        self.for_votes = 0;
+        self.abstain_votes = 0;
+    }
+
+    /// Reads an account with the legacy layout. The breakdown of the weight cast before
+    /// the migration is unknown, so these votes can not be revised
+    pub fn from_legacy_account_data(data: &[u8]) -> Result<ProposalVotersWeightCast> {
+        require!(
+            data.len() == LEGACY_PROPOSAL_VOTERS_WEIGHT_CAST_LEN,
+            ErrorCode::AccountAlreadyMigrated
+        );
+        require!(
+            data[..8] == *ProposalVotersWeightCast::DISCRIMINATOR,
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        let legacy_weight_cast = LegacyProposalVotersWeightCast::deserialize(&mut &data[8..])?;
+
+        Ok(ProposalVotersWeightCast {
+            proposal_id: legacy_weight_cast.proposal_id,
+            voter: legacy_weight_cast.voter,
+            value: legacy_weight_cast.value,
+            against_votes: 0,
+            for_votes: 0,
+            abstain_votes: 0,
+        })
     }
 
     pub fn set(&mut self, new_value: u64) {
         self.value = new_value;
     }
+
+    /// Adds the votes to the tallies of the proposal and to the breakdown of the cast weight,
+    /// returning the weight of the votes. The cast weight itself is left to the caller
+    pub fn cast_votes(
+        &mut self,
+        proposal: &mut ProposalData,
+        against_votes: u64,
+        for_votes: u64,
+        abstain_votes: u64,
+    ) -> Result<u64> {
+        let weight = against_votes
+            .checked_add(for_votes)
+            .and_then(|v| v.checked_add(abstain_votes))
+            .ok_or(ErrorCode::VoteWouldExceedWeight)?;
+        proposal.add_votes(against_votes, for_votes, abstain_votes)?;
+        self.against_votes = self
+            .against_votes
+            .checked_add(against_votes)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        self.for_votes = self
+            .for_votes
+            .checked_add(for_votes)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        self.abstain_votes = self
+            .abstain_votes
+            .checked_add(abstain_votes)
+            .ok_or(ErrorCode::GenericOverflow)?;
+        Ok(weight)
+    }
+
+    /// Whether the breakdown adds up to the cast weight, which is not the case for
+    /// the weight cast before the account was migrated
+    pub fn has_votes_breakdown(&self) -> bool {
+        self.against_votes
+            .checked_add(self.for_votes)
+            .and_then(|v| v.checked_add(self.abstain_votes))
+            == Some(self.value)
+    }
+
+    /// Removes the votes of the breakdown from the tallies of the proposal
+    /// and clears the breakdown. The cast weight itself is left to the caller
+    pub fn retract_votes(&mut self, proposal: &mut ProposalData) -> Result<()> {
+        proposal.remove_votes(self.against_votes, self.for_votes, self.abstain_votes)?;
+        self.against_votes = 0;
+        self.for_votes = 0;
+        self.abstain_votes = 0;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use super::ProposalVotersWeightCast;
+    use super::{ProposalVotersWeightCast, LEGACY_PROPOSAL_VOTERS_WEIGHT_CAST_LEN};
+    use crate::state::proposal::ProposalData;
+    use anchor_lang::prelude::Pubkey;
+    use anchor_lang::Discriminator;
 
     #[test]
     fn check_size() {
-        assert!(ProposalVotersWeightCast::LEN == 8 + 32 + 32 + 8); // == 80
+        assert!(ProposalVotersWeightCast::LEN == 8 + 32 + 32 + 4 * 8); // == 104
+    }
+
+    #[test]
+    fn cast_votes_test() {
+        let proposal = &mut ProposalData {
+            id: [1; 32],
+            against_votes: 0,
+            for_votes: 0,
+            abstain_votes: 0,
+            vote_start: 10,
+            vote_end: 20,
+            published_against_votes: 0,
+            published_for_votes: 0,
+            published_abstain_votes: 0,
+        };
+        let proposal_voters_weight_cast = &mut ProposalVotersWeightCast {
+            proposal_id: [1; 32],
+            voter: Pubkey::default(),
+            value: 0,
+            against_votes: 0,
+            for_votes: 0,
+            abstain_votes: 0,
+        };
+
+        assert_eq!(
+            proposal_voters_weight_cast
+                .cast_votes(proposal, 10, 0, 5)
+                .unwrap(),
+            15
+        );
+        assert_eq!(
+            proposal_voters_weight_cast
+                .cast_votes(proposal, 2, 0, 0)
+                .unwrap(),
+            2
+        );
+        proposal_voters_weight_cast.set(17);
+        assert!(proposal_voters_weight_cast.has_votes_breakdown());
+        assert_eq!(proposal.against_votes, 12);
+        assert_eq!(proposal.abstain_votes, 5);
+        assert_eq!(proposal_voters_weight_cast.against_votes, 12);
+        assert_eq!(proposal_voters_weight_cast.abstain_votes, 5);
+        assert!(proposal_voters_weight_cast
+            .cast_votes(proposal, u64::MAX, 1, 0)
+            .is_err());
+
+        // Votes of other voters stay in the tallies
+        proposal.for_votes = 30;
+        proposal_voters_weight_cast.retract_votes(proposal).unwrap();
+        assert_eq!(
+            proposal.proposal_votes().unwrap(),
+            Some(([1; 32], 0, 30, 0))
+        );
+        assert!(!proposal_voters_weight_cast.has_votes_breakdown());
+        proposal_voters_weight_cast.set(0);
+        assert!(proposal_voters_weight_cast.has_votes_breakdown());
+    }
+
+    #[test]
+    fn from_legacy_account_data_test() {
+        let voter = Pubkey::new_unique();
+        let mut data = ProposalVotersWeightCast::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[1; 32]);
+        data.extend_from_slice(voter.as_ref());
+        data.extend_from_slice(&42u64.to_le_bytes());
+        assert_eq!(data.len(), LEGACY_PROPOSAL_VOTERS_WEIGHT_CAST_LEN);
+
+        let proposal_voters_weight_cast =
+            ProposalVotersWeightCast::from_legacy_account_data(&data).unwrap();
+        assert_eq!(proposal_voters_weight_cast.proposal_id, [1; 32]);
+        assert_eq!(proposal_voters_weight_cast.voter, voter);
+        assert_eq!(proposal_voters_weight_cast.value, 42);
+        assert_eq!(proposal_voters_weight_cast.against_votes, 0);
+        assert_eq!(proposal_voters_weight_cast.for_votes, 0);
+        assert_eq!(proposal_voters_weight_cast.abstain_votes, 0);
+
+        let mut migrated_data = data.clone();
+        migrated_data.resize(ProposalVotersWeightCast::LEN, 0);
+        assert!(ProposalVotersWeightCast::from_legacy_account_data(&migrated_data).is_err());
+        assert!(!proposal_voters_weight_cast.has_votes_breakdown());
+
+        data[0] ^= 1;
+        assert!(ProposalVotersWeightCast::from_legacy_account_data(&data).is_err());
     }
 }
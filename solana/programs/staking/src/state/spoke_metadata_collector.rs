@@ -1,10 +1,11 @@
-use crate::error::ProposalWormholeMessageError;
+use crate::error::{ErrorCode, ProposalWormholeMessageError};
 use anchor_lang::prelude::borsh::BorshSchema;
 use anchor_lang::prelude::*;
 
 pub struct ProposalDataFromEthResponse {
     pub proposal_id: [u8; 32],
     pub vote_start: u64,
+    pub vote_end: u64,
 }
 
 pub struct ProposalQueryRequestData {
@@ -12,6 +13,61 @@ pub struct ProposalQueryRequestData {
     pub proposal_id: [u8; 32],
 }
 
+/// Wormhole queries of the hub that are accepted besides eth_calls on finalized blocks
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Default,
+    Debug,
+    PartialEq,
+    Eq,
+    BorshSchema,
+    InitSpace,
+)]
+pub struct QueryPolicy {
+    // eth_calls on blocks with the "safe" finality are accepted
+    pub allow_safe_finality: bool,
+    // eth_calls on the block of a timestamp are accepted
+    pub allow_eth_call_by_timestamp: bool,
+    // Minimum age in seconds of the blocks of accepted queries that are not finalized
+    pub min_confirmation_age: u64,
+}
+
+/// Maximum number of hub proposal metadata sources besides the hub proposal metadata contract
+pub const MAX_HUB_PROPOSAL_METADATA_SOURCES: usize = 4;
+
+/// A hub proposal metadata contract whose proposals are accepted from the activation
+/// until the deprecation
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Default,
+    Debug,
+    PartialEq,
+    Eq,
+    BorshSchema,
+    InitSpace,
+)]
+pub struct HubProposalMetadataSource {
+    pub chain_id: u16,
+    // Zero for an unused entry
+    pub contract: [u8; 20],
+    pub activation_time: u64,
+    // 0 while the source is not deprecated
+    pub deprecation_time: u64,
+}
+
+impl HubProposalMetadataSource {
+    pub fn is_active(&self, current_timestamp: u64) -> bool {
+        self.activation_time <= current_timestamp
+            && (self.deprecation_time == 0 || current_timestamp < self.deprecation_time)
+    }
+}
+
 #[account]
 #[derive(Default, Debug, BorshSchema, InitSpace)]
 pub struct SpokeMetadataCollector {
@@ -24,6 +80,14 @@ pub struct SpokeMetadataCollector {
     pub wormhole_core: Pubkey,
     // Updates to hub_proposal_metadata are governance controlled
     pub updates_controlled_by_governance: bool,
+    // Wormhole Hub Proposal Extender Contract (Ethereum address), zero while extensions are not
+    // synced
+    pub hub_proposal_extender: [u8; 20],
+    pub query_policy: QueryPolicy,
+    // Hub proposal metadata contracts accepted besides hub_proposal_metadata on hub_chain_id,
+    // e.g. the previous contract while its proposals are still in flight after a migration
+    pub hub_proposal_metadata_sources:
+        [HubProposalMetadataSource; MAX_HUB_PROPOSAL_METADATA_SOURCES],
 }
 
 impl SpokeMetadataCollector {
@@ -55,12 +119,137 @@ impl SpokeMetadataCollector {
         Ok(())
     }
 
+    pub fn update_hub_proposal_extender(
+        &mut self,
+        new_hub_proposal_extender: [u8; 20],
+    ) -> Result<()> {
+        self.hub_proposal_extender = new_hub_proposal_extender;
+
+        Ok(())
+    }
+
+    pub fn update_query_policy(&mut self, new_query_policy: QueryPolicy) -> Result<()> {
+        self.query_policy = new_query_policy;
+
+        Ok(())
+    }
+
+    /// Adds the metadata source or updates its activation and deprecation
+    pub fn set_hub_proposal_metadata_source(
+        &mut self,
+        source: HubProposalMetadataSource,
+    ) -> Result<()> {
+        require!(
+            source.contract != [0; 20]
+                && (source.deprecation_time == 0
+                    || source.activation_time < source.deprecation_time),
+            ErrorCode::InvalidHubProposalMetadataSource
+        );
+
+        let index = self
+            .hub_proposal_metadata_sources
+            .iter()
+            .position(|entry| {
+                entry.chain_id == source.chain_id && entry.contract == source.contract
+            })
+            .or_else(|| {
+                self.hub_proposal_metadata_sources
+                    .iter()
+                    .position(|entry| entry.contract == [0; 20])
+            })
+            .ok_or(ErrorCode::TooManyHubProposalMetadataSources)?;
+
+        self.hub_proposal_metadata_sources[index] = source;
+
+        Ok(())
+    }
+
+    pub fn remove_hub_proposal_metadata_source(
+        &mut self,
+        chain_id: u16,
+        contract: [u8; 20],
+    ) -> Result<()> {
+        let entry = self
+            .hub_proposal_metadata_sources
+            .iter_mut()
+            .find(|entry| {
+                entry.chain_id == chain_id && entry.contract == contract && contract != [0; 20]
+            })
+            .ok_or(ErrorCode::HubProposalMetadataSourceNotFound)?;
+
+        *entry = HubProposalMetadataSource::default();
+
+        Ok(())
+    }
+
+    /// Checks that proposals are accepted from the contract on the chain: the hub proposal
+    /// metadata contract or a metadata source that is currently active
+    pub fn check_hub_proposal_metadata_source(
+        &self,
+        chain_id: u16,
+        contract: &[u8; 20],
+        current_timestamp: u64,
+    ) -> Result<()> {
+        if chain_id == self.hub_chain_id && *contract == self.hub_proposal_metadata {
+            return Ok(());
+        }
+
+        let matching_source = self.hub_proposal_metadata_sources.iter().find(|entry| {
+            entry.contract != [0; 20] && entry.chain_id == chain_id && entry.contract == *contract
+        });
+
+        match matching_source {
+            Some(source) => {
+                require!(
+                    source.is_active(current_timestamp),
+                    ProposalWormholeMessageError::InactiveHubProposalMetadataSource
+                );
+                Ok(())
+            }
+            None => {
+                let known_chain = chain_id == self.hub_chain_id
+                    || self
+                        .hub_proposal_metadata_sources
+                        .iter()
+                        .any(|entry| entry.contract != [0; 20] && entry.chain_id == chain_id);
+                require!(
+                    known_chain,
+                    ProposalWormholeMessageError::SenderChainMismatch
+                );
+                err!(ProposalWormholeMessageError::InvalidHubProposalMetadataContract)
+            }
+        }
+    }
+
+    /// Parses the result of the extendedDeadlines(uint256) call to the hub proposal extender
+    pub fn parse_eth_response_extended_vote_end(&mut self, data: &[u8]) -> Result<u64> {
+        require!(
+            data.len() == 32,
+            ProposalWormholeMessageError::InvalidDataLength
+        );
+
+        // Validate that bytes [0..24] are zeroed
+        if data[0..24].iter().any(|&byte| byte != 0) {
+            return err!(ProposalWormholeMessageError::ErrorOfVoteEndParsing);
+        }
+
+        // Parse the extended vote end (32 bytes), a uint48 timestamp
+        let vote_end = u64::from_be_bytes(
+            data[24..32]
+                .try_into()
+                .map_err(|_| ProposalWormholeMessageError::ErrorOfVoteEndParsing)?,
+        );
+
+        Ok(vote_end)
+    }
+
     pub fn parse_eth_response_proposal_data(
         &mut self,
         data: &[u8],
     ) -> Result<ProposalDataFromEthResponse> {
+        // The vote end is only returned by hub metadata contracts that report it
         require!(
-            data.len() == 64, // 32 + 32
+            data.len() == 64 || data.len() == 96, // 32 + 32 (+ 32)
             ProposalWormholeMessageError::InvalidDataLength
         );
 
@@ -82,9 +271,32 @@ impl SpokeMetadataCollector {
                 .map_err(|_| ProposalWormholeMessageError::ErrorOfVoteStartParsing)?,
         );
 
+        // The vote end of a proposal whose hub metadata does not carry it is unknown
+        let vote_end = if data.len() >= 96 {
+            // Validate that bytes [64..88] are zeroed
+            if data[64..88].iter().any(|&byte| byte != 0) {
+                return err!(ProposalWormholeMessageError::ErrorOfVoteEndParsing);
+            }
+
+            // Parse vote_end (32 bytes), converted to u64 like vote_start
+            let vote_end = u64::from_be_bytes(
+                data[88..96]
+                    .try_into()
+                    .map_err(|_| ProposalWormholeMessageError::ErrorOfVoteEndParsing)?,
+            );
+            require!(
+                vote_end >= vote_start,
+                ProposalWormholeMessageError::ErrorOfVoteEndParsing
+            );
+            vote_end
+        } else {
+            0
+        };
+
         Ok(ProposalDataFromEthResponse {
             proposal_id,
             vote_start,
+            vote_end,
         })
     }
 
@@ -116,10 +328,181 @@ impl SpokeMetadataCollector {
 
 #[cfg(test)]
 mod tests {
-    use super::SpokeMetadataCollector;
+    use super::{
+        HubProposalMetadataSource, SpokeMetadataCollector, MAX_HUB_PROPOSAL_METADATA_SOURCES,
+    };
 
     #[test]
     fn check_spoke_metadata_collector_size() {
-        assert!(SpokeMetadataCollector::LEN == 8 + 1 + 2 + 20 + 32 + 1); // 64
+        assert!(
+            SpokeMetadataCollector::LEN
+                == 8 + 1 + 2 + 20 + 32 + 1 + 20 + 1 + 1 + 8 + 4 * (2 + 20 + 8 + 8)
+        ); // 246
+    }
+
+    #[test]
+    fn check_hub_proposal_metadata_source_test() {
+        let mut spoke_metadata_collector = SpokeMetadataCollector {
+            hub_chain_id: 2,
+            hub_proposal_metadata: [1; 20],
+            ..Default::default()
+        };
+
+        // The hub proposal metadata contract is always accepted
+        assert!(spoke_metadata_collector
+            .check_hub_proposal_metadata_source(2, &[1; 20], 0)
+            .is_ok());
+        assert!(spoke_metadata_collector
+            .check_hub_proposal_metadata_source(2, &[2; 20], 0)
+            .is_err());
+        assert!(spoke_metadata_collector
+            .check_hub_proposal_metadata_source(3, &[2; 20], 0)
+            .is_err());
+
+        spoke_metadata_collector
+            .set_hub_proposal_metadata_source(HubProposalMetadataSource {
+                chain_id: 3,
+                contract: [2; 20],
+                activation_time: 100,
+                deprecation_time: 200,
+            })
+            .unwrap();
+
+        assert!(spoke_metadata_collector
+            .check_hub_proposal_metadata_source(3, &[2; 20], 99)
+            .is_err());
+        assert!(spoke_metadata_collector
+            .check_hub_proposal_metadata_source(3, &[2; 20], 100)
+            .is_ok());
+        assert!(spoke_metadata_collector
+            .check_hub_proposal_metadata_source(3, &[2; 20], 200)
+            .is_err());
+
+        // Setting the source again updates its deprecation
+        spoke_metadata_collector
+            .set_hub_proposal_metadata_source(HubProposalMetadataSource {
+                chain_id: 3,
+                contract: [2; 20],
+                activation_time: 100,
+                deprecation_time: 0,
+            })
+            .unwrap();
+        assert!(spoke_metadata_collector
+            .check_hub_proposal_metadata_source(3, &[2; 20], 200)
+            .is_ok());
+        assert_eq!(
+            spoke_metadata_collector
+                .hub_proposal_metadata_sources
+                .iter()
+                .filter(|entry| entry.contract != [0; 20])
+                .count(),
+            1
+        );
+
+        spoke_metadata_collector
+            .remove_hub_proposal_metadata_source(3, [2; 20])
+            .unwrap();
+        assert!(spoke_metadata_collector
+            .check_hub_proposal_metadata_source(3, &[2; 20], 200)
+            .is_err());
+        assert!(spoke_metadata_collector
+            .remove_hub_proposal_metadata_source(3, [2; 20])
+            .is_err());
+    }
+
+    #[test]
+    fn set_hub_proposal_metadata_source_limits_test() {
+        let mut spoke_metadata_collector = SpokeMetadataCollector::default();
+
+        // The deprecation has to follow the activation
+        assert!(spoke_metadata_collector
+            .set_hub_proposal_metadata_source(HubProposalMetadataSource {
+                chain_id: 2,
+                contract: [1; 20],
+                activation_time: 100,
+                deprecation_time: 100,
+            })
+            .is_err());
+
+        for index in 0..MAX_HUB_PROPOSAL_METADATA_SOURCES {
+            spoke_metadata_collector
+                .set_hub_proposal_metadata_source(HubProposalMetadataSource {
+                    chain_id: 2,
+                    contract: [index as u8 + 1; 20],
+                    activation_time: 0,
+                    deprecation_time: 0,
+                })
+                .unwrap();
+        }
+
+        assert!(spoke_metadata_collector
+            .set_hub_proposal_metadata_source(HubProposalMetadataSource {
+                chain_id: 2,
+                contract: [0xff; 20],
+                activation_time: 0,
+                deprecation_time: 0,
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn parse_eth_response_extended_vote_end_test() {
+        let mut spoke_metadata_collector = SpokeMetadataCollector::default();
+        let mut data = [0u8; 32];
+        data[26..32].copy_from_slice(&[0, 0, 0x67, 0x0c, 0xd1, 0x12]);
+
+        assert_eq!(
+            spoke_metadata_collector
+                .parse_eth_response_extended_vote_end(&data)
+                .unwrap(),
+            0x670cd112
+        );
+
+        assert!(spoke_metadata_collector
+            .parse_eth_response_extended_vote_end(&data[..31])
+            .is_err());
+
+        data[0] = 1;
+        assert!(spoke_metadata_collector
+            .parse_eth_response_extended_vote_end(&data)
+            .is_err());
+    }
+
+    #[test]
+    fn parse_eth_response_proposal_data_vote_end_test() {
+        let mut spoke_metadata_collector = SpokeMetadataCollector::default();
+        let mut data = [0u8; 96];
+        data[0..32].copy_from_slice(&[1; 32]);
+        data[63] = 10;
+        data[95] = 20;
+
+        let proposal_data = spoke_metadata_collector
+            .parse_eth_response_proposal_data(&data)
+            .unwrap();
+        assert_eq!(proposal_data.vote_start, 10);
+        assert_eq!(proposal_data.vote_end, 20);
+
+        // The vote end can not precede the vote start
+        data[95] = 9;
+        assert!(spoke_metadata_collector
+            .parse_eth_response_proposal_data(&data)
+            .is_err());
+
+        data[95] = 20;
+        data[64] = 1;
+        assert!(spoke_metadata_collector
+            .parse_eth_response_proposal_data(&data)
+            .is_err());
+
+        // The vote end is unknown when the hub metadata does not carry it
+        let proposal_data = spoke_metadata_collector
+            .parse_eth_response_proposal_data(&data[..64])
+            .unwrap();
+        assert_eq!(proposal_data.vote_start, 10);
+        assert_eq!(proposal_data.vote_end, 0);
+
+        assert!(spoke_metadata_collector
+            .parse_eth_response_proposal_data(&data[..80])
+            .is_err());
     }
 }
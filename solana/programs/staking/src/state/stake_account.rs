@@ -1,3 +1,6 @@
+use crate::error::ErrorCode;
+use crate::state::checkpoints::Operation;
+use crate::state::global_config::DelegationPolicy;
 use anchor_lang::prelude::borsh::BorshSchema;
 use anchor_lang::prelude::*;
 
@@ -17,6 +20,40 @@ pub struct StakeAccountMetadata {
     pub owner: Pubkey,
     pub delegate: Pubkey,
     pub stake_account_checkpoints_last_index: u16,
+    // Votes delegated to this stake account by other stake accounts, tracked with liquid delegation
+    pub delegated_votes: u64,
+    // Stake accounts whose chain of delegations ends at this stake account, by chain length from 1
+    // to DelegationPolicy::MAX_DEPTH, tracked with liquid delegation
+    pub delegation_chain_counts: [u32; DelegationPolicy::MAX_DEPTH as usize],
+    // Set while the voting power is divided by the DelegationSplit of the owner. The split delegates
+    // hold the votes and delegate stays at the owner, so the accounts derived from it stay valid
+    pub has_delegation_split: bool,
+    // Other stake accounts delegating to this stake account, directly or through a delegation split
+    pub delegator_count: u32,
+    // Time the stake account moved to the owner with accept_stake_owner. The checkpoints before
+    // it are copied from the previous owner, who can still vote with them
+    pub owner_changed_at: u64,
+}
+
+/// Owner proposed by the current owner of a stake account, who can accept the stake account
+/// It is derived with seeds "pending_stake_owner" and the current stake account owner
+#[account]
+#[derive(Default, Debug, InitSpace)]
+pub struct PendingStakeOwner {
+    pub bump: u8,
+    pub owner: Pubkey,
+    pub pending_owner: Pubkey,
+}
+
+impl PendingStakeOwner {
+    pub const LEN: usize = PendingStakeOwner::DISCRIMINATOR.len() + PendingStakeOwner::INIT_SPACE;
+}
+
+#[event]
+pub struct StakeOwnerChanged {
+    pub previous_owner: Pubkey,
+    pub new_owner: Pubkey,
+    pub stake_account_metadata: Pubkey,
 }
 
 #[event]
@@ -54,6 +91,12 @@ impl StakeAccountMetadata {
         self.stake_account_checkpoints_last_index = stake_account_checkpoints_last;
     }
 
+    /// Whether the stake account holds its own votes, as opposed to a single
+    /// delegate or the delegates of a delegation split
+    pub fn is_self_delegated(&self) -> bool {
+        self.delegate == self.owner && !self.has_delegation_split
+    }
+
     pub fn update_recorded_balance(&mut self, new_recorded_balance: u64) -> RecordedBalanceChanged {
         let recorded_balance_changed = RecordedBalanceChanged {
             owner: self.owner,
@@ -82,14 +125,262 @@ impl StakeAccountMetadata {
         emit!(recorded_vesting_balance_changed);
         recorded_vesting_balance_changed
     }
+
+    pub fn update_delegated_votes(
+        &mut self,
+        amount_delta: u64,
+        operation: &Operation,
+    ) -> Result<()> {
+        self.delegated_votes = match operation {
+            Operation::Add => self.delegated_votes.checked_add(amount_delta),
+            Operation::Subtract => self.delegated_votes.checked_sub(amount_delta),
+        }
+        .ok_or(ErrorCode::GenericOverflow)?;
+        Ok(())
+    }
+
+    /// Delegators with a previous layout are counted when they are migrated, before they can
+    /// change their delegation
+    pub fn update_delegator_count(&mut self, operation: &Operation) -> Result<()> {
+        self.delegator_count = match operation {
+            Operation::Add => self
+                .delegator_count
+                .checked_add(1)
+                .ok_or(ErrorCode::GenericOverflow)?,
+            Operation::Subtract => self.delegator_count.saturating_sub(1),
+        };
+        Ok(())
+    }
+
+    /// Longest chain of delegations ending at this stake account
+    pub fn delegation_depth(&self) -> u8 {
+        self.delegation_chain_counts
+            .iter()
+            .rposition(|count| *count > 0)
+            .map_or(0, |index| index as u8 + 1)
+    }
+
+    /// Adds or removes the delegation chains of a delegator found at the given distance in the
+    /// delegation chain of this stake account: the delegator itself and the chains ending at it,
+    /// each made longer by the distance. Added chains can not be longer than max_depth
+    pub fn update_delegation_chain_counts(
+        &mut self,
+        delegator_chain_counts: &[u32; DelegationPolicy::MAX_DEPTH as usize],
+        distance: usize,
+        operation: &Operation,
+        max_depth: u8,
+    ) -> Result<()> {
+        let delegator_chains = std::iter::once(1).chain(delegator_chain_counts.iter().copied());
+        for (delegator_chain_length, count) in delegator_chains.enumerate() {
+            if count == 0 {
+                continue;
+            }
+
+            let chain_length = delegator_chain_length
+                .checked_add(distance)
+                .ok_or(ErrorCode::GenericOverflow)?;
+            match operation {
+                Operation::Add => {
+                    require!(
+                        chain_length <= usize::from(max_depth),
+                        ErrorCode::DelegationChainTooDeep
+                    );
+                    let chain_count = self
+                        .delegation_chain_counts
+                        .get_mut(chain_length - 1)
+                        .ok_or(ErrorCode::DelegationChainTooDeep)?;
+                    *chain_count = chain_count
+                        .checked_add(count)
+                        .ok_or(ErrorCode::GenericOverflow)?;
+                }
+                Operation::Subtract => {
+                    if let Some(chain_count) =
+                        self.delegation_chain_counts.get_mut(chain_length - 1)
+                    {
+                        *chain_count = chain_count.saturating_sub(count);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a stake account metadata account created with a previous, shorter layout, or with
+    /// the current one. New fields are only appended to the layout, so they read as zero
+    pub fn from_account_data(data: &[u8]) -> Result<StakeAccountMetadata> {
+        let mut padded_data = data.to_vec();
+        if padded_data.len() < StakeAccountMetadata::LEN {
+            padded_data.resize(StakeAccountMetadata::LEN, 0);
+        }
+        StakeAccountMetadata::try_deserialize(&mut padded_data.as_slice())
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use super::StakeAccountMetadata;
+    use super::{PendingStakeOwner, StakeAccountMetadata};
+    use crate::state::checkpoints::Operation;
+    use anchor_lang::prelude::*;
+
+    // Length of the stake account metadata accounts created before liquid delegation
+    const V0_STAKE_ACCOUNT_METADATA_LEN: usize = 8 + 3 + 8 + 8 + 32 + 32 + 2;
 
     #[test]
     fn check_size() {
-        assert!(StakeAccountMetadata::LEN == 8 + 3 + 8 + 8 + 32 + 32 + 2); // == 93
+        assert!(StakeAccountMetadata::LEN == 8 + 3 + 8 + 8 + 32 + 32 + 2 + 8 + 4 * 8 + 1 + 4 + 8); // == 146
+        assert!(PendingStakeOwner::LEN == 8 + 1 + 32 + 32); // == 73
+    }
+
+    #[test]
+    fn is_self_delegated_test() {
+        let owner = Pubkey::new_unique();
+        let mut stake_account_metadata = StakeAccountMetadata {
+            owner,
+            delegate: owner,
+            ..Default::default()
+        };
+        assert!(stake_account_metadata.is_self_delegated());
+
+        // The delegate stays at the owner while a delegation split is active
+        stake_account_metadata.has_delegation_split = true;
+        assert!(!stake_account_metadata.is_self_delegated());
+
+        stake_account_metadata.has_delegation_split = false;
+        stake_account_metadata.delegate = Pubkey::new_unique();
+        assert!(!stake_account_metadata.is_self_delegated());
+    }
+
+    #[test]
+    fn update_delegated_votes_test() {
+        let mut stake_account_metadata = StakeAccountMetadata::default();
+
+        stake_account_metadata
+            .update_delegated_votes(100, &Operation::Add)
+            .unwrap();
+        stake_account_metadata
+            .update_delegated_votes(40, &Operation::Subtract)
+            .unwrap();
+        assert_eq!(stake_account_metadata.delegated_votes, 60);
+
+        assert!(stake_account_metadata
+            .update_delegated_votes(61, &Operation::Subtract)
+            .is_err());
+    }
+
+    #[test]
+    fn update_delegator_count_test() {
+        let mut stake_account_metadata = StakeAccountMetadata::default();
+
+        stake_account_metadata
+            .update_delegator_count(&Operation::Add)
+            .unwrap();
+        stake_account_metadata
+            .update_delegator_count(&Operation::Add)
+            .unwrap();
+        stake_account_metadata
+            .update_delegator_count(&Operation::Subtract)
+            .unwrap();
+        assert_eq!(stake_account_metadata.delegator_count, 1);
+
+        stake_account_metadata
+            .update_delegator_count(&Operation::Subtract)
+            .unwrap();
+        stake_account_metadata
+            .update_delegator_count(&Operation::Subtract)
+            .unwrap();
+        assert_eq!(stake_account_metadata.delegator_count, 0);
+    }
+
+    #[test]
+    fn update_delegation_chain_counts_test() {
+        let mut stake_account_metadata = StakeAccountMetadata::default();
+
+        // A delegator at distance 1 with one chain of length 1 ending at it
+        let mut delegator_chain_counts = [0; 8];
+        delegator_chain_counts[0] = 1;
+        stake_account_metadata
+            .update_delegation_chain_counts(&delegator_chain_counts, 1, &Operation::Add, 3)
+            .unwrap();
+        assert_eq!(
+            stake_account_metadata.delegation_chain_counts,
+            [1, 1, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(stake_account_metadata.delegation_depth(), 2);
+
+        // A delegator without delegators at distance 2
+        stake_account_metadata
+            .update_delegation_chain_counts(&[0; 8], 2, &Operation::Add, 3)
+            .unwrap();
+        assert_eq!(
+            stake_account_metadata.delegation_chain_counts,
+            [1, 2, 0, 0, 0, 0, 0, 0]
+        );
+
+        // The delegation would make a chain longer than max_depth
+        assert!(stake_account_metadata
+            .update_delegation_chain_counts(&delegator_chain_counts, 3, &Operation::Add, 3)
+            .is_err());
+
+        // Removing the first delegator decreases the depth
+        stake_account_metadata
+            .update_delegation_chain_counts(&delegator_chain_counts, 1, &Operation::Subtract, 3)
+            .unwrap();
+        assert_eq!(
+            stake_account_metadata.delegation_chain_counts,
+            [0, 1, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(stake_account_metadata.delegation_depth(), 2);
+
+        stake_account_metadata
+            .update_delegation_chain_counts(&[0; 8], 2, &Operation::Subtract, 3)
+            .unwrap();
+        assert_eq!(stake_account_metadata.delegation_depth(), 0);
+    }
+
+    #[test]
+    fn from_account_data_test() {
+        let owner = Pubkey::new_unique();
+        let stake_account_metadata = StakeAccountMetadata {
+            metadata_bump: 255,
+            custody_bump: 254,
+            authority_bump: 253,
+            recorded_balance: 100,
+            recorded_vesting_balance: 50,
+            owner,
+            delegate: Pubkey::new_unique(),
+            stake_account_checkpoints_last_index: 3,
+            delegated_votes: 7,
+            delegator_count: 2,
+            ..Default::default()
+        };
+        let mut data = vec![];
+        stake_account_metadata.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), StakeAccountMetadata::LEN);
+
+        let migrated =
+            StakeAccountMetadata::from_account_data(&data[..V0_STAKE_ACCOUNT_METADATA_LEN])
+                .unwrap();
+        assert_eq!(migrated.metadata_bump, 255);
+        assert_eq!(migrated.custody_bump, 254);
+        assert_eq!(migrated.authority_bump, 253);
+        assert_eq!(migrated.recorded_balance, 100);
+        assert_eq!(migrated.recorded_vesting_balance, 50);
+        assert_eq!(migrated.owner, owner);
+        assert_eq!(migrated.delegate, stake_account_metadata.delegate);
+        assert_eq!(migrated.stake_account_checkpoints_last_index, 3);
+        assert_eq!(migrated.delegated_votes, 0);
+        assert_eq!(migrated.delegator_count, 0);
+
+        // An account with the current layout is read as is
+        let current = StakeAccountMetadata::from_account_data(&data).unwrap();
+        assert_eq!(current.delegated_votes, 7);
+        assert_eq!(current.delegator_count, 2);
+
+        // An account of another type is rejected
+        data[0] ^= 1;
+        assert!(
+            StakeAccountMetadata::from_account_data(&data[..V0_STAKE_ACCOUNT_METADATA_LEN])
+                .is_err()
+        );
     }
 }
@@ -0,0 +1,169 @@
+use crate::error::VestingError;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::keccak;
+
+// Domain separators of the leaves and the inner nodes, so that an inner node cannot be
+// presented as a leaf
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// Root of a merkle tree of (vester, amount, maturation) vests committed by the vesting admin.
+/// Each vester creates their own vest by proving their leaf, so the admin does not pay the
+/// rent of the vests. A config can have several distributors, told apart by their index
+#[account]
+#[derive(Default, InitSpace)]
+pub struct VestingMerkleDistributor {
+    pub config: Pubkey,
+    pub index: u64,
+    pub root: [u8; 32],
+    // Sum of the amounts of the leaves, added to the vested amount of the config on creation
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    pub num_leaves: u32,
+    // The leaves can be claimed until the deadline, the unclaimed ones are then removed from
+    // the vested amount of the config when the distributor is closed
+    pub claim_deadline: i64,
+    pub bump: u8,
+}
+
+impl VestingMerkleDistributor {
+    pub const LEN: usize =
+        VestingMerkleDistributor::DISCRIMINATOR.len() + VestingMerkleDistributor::INIT_SPACE;
+
+    pub fn leaf_hash(index: u32, vester: &Pubkey, amount: u64, maturation: i64) -> [u8; 32] {
+        keccak::hashv(&[
+            &[LEAF_PREFIX],
+            &index.to_le_bytes(),
+            vester.as_ref(),
+            &amount.to_le_bytes(),
+            &maturation.to_le_bytes(),
+        ])
+        .to_bytes()
+    }
+
+    /// Hash of two sibling nodes, sorted so that proofs do not need the position of the nodes
+    pub fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        let (first, second) = if left <= right {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        keccak::hashv(&[&[NODE_PREFIX], first, second]).to_bytes()
+    }
+
+    pub fn verify_proof(&self, leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+        let computed_root = proof
+            .iter()
+            .fold(leaf, |node, sibling| Self::node_hash(&node, sibling));
+
+        computed_root == self.root
+    }
+}
+
+/// One bit per leaf of a VestingMerkleDistributor, set once the vest of the leaf is claimed
+#[account]
+#[derive(Default)]
+pub struct VestingMerkleClaimBitmap {
+    pub distributor: Pubkey,
+    pub bits: Vec<u8>,
+}
+
+impl VestingMerkleClaimBitmap {
+    // Accounts created through a CPI are limited to 10240 bytes
+    pub const MAX_LEAVES: u32 = (10240 - Self::space(0) as u32) * 8;
+
+    pub const fn space(num_leaves: u32) -> usize {
+        VestingMerkleClaimBitmap::DISCRIMINATOR.len() + 32 + 4 + (num_leaves as usize).div_ceil(8)
+    }
+
+    pub fn is_claimed(&self, index: u32) -> bool {
+        let index = index as usize;
+        self.bits
+            .get(index / 8)
+            .is_some_and(|byte| byte & (1 << (index % 8)) != 0)
+    }
+
+    pub fn set_claimed(&mut self, index: u32) -> Result<()> {
+        let index = index as usize;
+        let byte = self
+            .bits
+            .get_mut(index / 8)
+            .ok_or(VestingError::InvalidMerkleLeafIndex)?;
+
+        require!(
+            *byte & (1 << (index % 8)) == 0,
+            VestingError::VestAlreadyClaimed
+        );
+        *byte |= 1 << (index % 8);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{VestingMerkleClaimBitmap, VestingMerkleDistributor};
+    use anchor_lang::prelude::*;
+
+    #[test]
+    fn check_size() {
+        assert!(VestingMerkleDistributor::LEN == 8 + 32 + 8 + 32 + 8 + 8 + 4 + 8 + 1); // 109
+        assert!(VestingMerkleClaimBitmap::space(0) == 8 + 32 + 4); // 44
+        assert!(VestingMerkleClaimBitmap::space(9) == 8 + 32 + 4 + 2); // 46
+        assert!(VestingMerkleClaimBitmap::MAX_LEAVES == 81568);
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let vesters = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let leaves: Vec<[u8; 32]> = vesters
+            .iter()
+            .enumerate()
+            .map(|(index, vester)| {
+                VestingMerkleDistributor::leaf_hash(index as u32, vester, 100, 1_000)
+            })
+            .collect();
+
+        let node = VestingMerkleDistributor::node_hash(&leaves[0], &leaves[1]);
+        let distributor = VestingMerkleDistributor {
+            root: VestingMerkleDistributor::node_hash(&node, &leaves[2]),
+            num_leaves: 3,
+            ..Default::default()
+        };
+
+        assert!(distributor.verify_proof(leaves[0], &[leaves[1], leaves[2]]));
+        assert!(distributor.verify_proof(leaves[1], &[leaves[0], leaves[2]]));
+        assert!(distributor.verify_proof(leaves[2], &[node]));
+
+        // A leaf with another amount, maturation or index is not in the tree
+        for leaf in [
+            VestingMerkleDistributor::leaf_hash(0, &vesters[0], 101, 1_000),
+            VestingMerkleDistributor::leaf_hash(0, &vesters[0], 100, 1_001),
+            VestingMerkleDistributor::leaf_hash(1, &vesters[0], 100, 1_000),
+        ] {
+            assert!(!distributor.verify_proof(leaf, &[leaves[1], leaves[2]]));
+        }
+    }
+
+    #[test]
+    fn test_set_claimed() {
+        let mut bitmap = VestingMerkleClaimBitmap {
+            distributor: Pubkey::new_unique(),
+            bits: vec![0; 2],
+        };
+
+        assert!(!bitmap.is_claimed(9));
+        bitmap.set_claimed(9).unwrap();
+        assert!(bitmap.is_claimed(9));
+        assert!(!bitmap.is_claimed(8));
+        assert!(!bitmap.is_claimed(10));
+
+        assert!(bitmap.set_claimed(9).is_err());
+        assert!(bitmap.set_claimed(16).is_err());
+        assert!(!bitmap.is_claimed(16));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::error::VestingError;
+use anchor_lang::prelude::*;
+
+/// A vest unlocked in equal parts at the end of every period between the start and the end.
+/// Nothing is unlocked before the cliff, after which the periods elapsed since the start
+/// are unlocked at once. A cliff equal to the start gives a linear unlock
+#[account]
+#[derive(Default, InitSpace)]
+pub struct VestingSchedule {
+    pub vester: Pubkey,
+    pub config: Pubkey,
+    // Amount vested over the whole schedule, including the claimed part
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    pub start: i64,
+    pub cliff: i64,
+    pub end: i64,
+    pub period: i64,
+    pub bump: u8,
+}
+
+impl VestingSchedule {
+    pub const LEN: usize = VestingSchedule::DISCRIMINATOR.len() + VestingSchedule::INIT_SPACE;
+
+    pub fn check_schedule(
+        amount: u64,
+        start: i64,
+        cliff: i64,
+        end: i64,
+        period: i64,
+    ) -> Result<()> {
+        let duration = end
+            .checked_sub(start)
+            .ok_or(VestingError::InvalidVestingSchedule)?;
+        require!(
+            amount > 0
+                && start <= cliff
+                && cliff <= end
+                && start < end
+                && period > 0
+                && period <= duration,
+            VestingError::InvalidVestingSchedule
+        );
+        Ok(())
+    }
+
+    /// Amount unlocked at the timestamp, including the claimed part
+    pub fn unlocked_amount(&self, current_timestamp: i64) -> Result<u64> {
+        if current_timestamp < self.cliff {
+            return Ok(0);
+        }
+        if current_timestamp >= self.end {
+            return Ok(self.total_amount);
+        }
+
+        let elapsed_duration = current_timestamp
+            .checked_sub(self.start)
+            .ok_or(VestingError::InvalidVestingSchedule)?;
+        let duration = self
+            .end
+            .checked_sub(self.start)
+            .ok_or(VestingError::InvalidVestingSchedule)?;
+        let elapsed_periods = elapsed_duration / self.period;
+        let unlocked_duration = u128::try_from(elapsed_periods * self.period)?;
+        let duration = u128::try_from(duration)?;
+
+        let unlocked_amount = u128::from(self.total_amount)
+            .checked_mul(unlocked_duration)
+            .ok_or(VestingError::Overflow)?
+            / duration;
+
+        Ok(u64::try_from(unlocked_amount)?)
+    }
+
+    /// Amount unlocked at the timestamp that has not been claimed yet
+    pub fn claimable_amount(&self, current_timestamp: i64) -> Result<u64> {
+        self.unlocked_amount(current_timestamp)?
+            .checked_sub(self.claimed_amount)
+            .ok_or(VestingError::Underflow.into())
+    }
+
+    pub fn remaining_amount(&self) -> u64 {
+        self.total_amount.saturating_sub(self.claimed_amount)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::VestingSchedule;
+
+    #[test]
+    fn check_size() {
+        assert!(VestingSchedule::LEN == 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1);
+        // 121
+    }
+
+    #[test]
+    fn check_schedule_test() {
+        assert!(VestingSchedule::check_schedule(100, 0, 0, 100, 10).is_ok());
+        assert!(VestingSchedule::check_schedule(100, 0, 100, 100, 100).is_ok());
+        assert!(VestingSchedule::check_schedule(0, 0, 0, 100, 10).is_err());
+        assert!(VestingSchedule::check_schedule(100, 10, 0, 100, 10).is_err());
+        assert!(VestingSchedule::check_schedule(100, 0, 101, 100, 10).is_err());
+        assert!(VestingSchedule::check_schedule(100, 100, 100, 100, 10).is_err());
+        assert!(VestingSchedule::check_schedule(100, 0, 0, 100, 0).is_err());
+        assert!(VestingSchedule::check_schedule(100, 0, 0, 100, 101).is_err());
+        // The duration of the schedule does not fit in an i64
+        assert!(VestingSchedule::check_schedule(100, i64::MIN, 0, i64::MAX, 10).is_err());
+        assert!(VestingSchedule::check_schedule(100, -1, -1, i64::MAX, 10).is_err());
+        assert!(VestingSchedule::check_schedule(100, 0, 0, i64::MAX, 10).is_ok());
+    }
+
+    #[test]
+    fn extreme_bounds_unlock_test() {
+        let vesting_schedule = VestingSchedule {
+            total_amount: 1_000,
+            start: i64::MIN,
+            cliff: 0,
+            end: i64::MAX,
+            period: 10,
+            ..Default::default()
+        };
+
+        assert_eq!(vesting_schedule.unlocked_amount(-1).unwrap(), 0);
+        assert!(vesting_schedule.unlocked_amount(0).is_err());
+        assert_eq!(vesting_schedule.unlocked_amount(i64::MAX).unwrap(), 1_000);
+
+        let vesting_schedule = VestingSchedule {
+            total_amount: 1_000,
+            start: 0,
+            cliff: 0,
+            end: i64::MAX,
+            period: i64::MAX,
+            ..Default::default()
+        };
+
+        assert_eq!(vesting_schedule.unlocked_amount(i64::MAX - 1).unwrap(), 0);
+        assert_eq!(vesting_schedule.unlocked_amount(i64::MAX).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn linear_unlock_test() {
+        let mut vesting_schedule = VestingSchedule {
+            total_amount: 1_000,
+            start: 100,
+            cliff: 100,
+            end: 500,
+            period: 100,
+            ..Default::default()
+        };
+
+        assert_eq!(vesting_schedule.unlocked_amount(99).unwrap(), 0);
+        assert_eq!(vesting_schedule.unlocked_amount(100).unwrap(), 0);
+        assert_eq!(vesting_schedule.unlocked_amount(199).unwrap(), 0);
+        assert_eq!(vesting_schedule.unlocked_amount(200).unwrap(), 250);
+        assert_eq!(vesting_schedule.unlocked_amount(450).unwrap(), 750);
+        assert_eq!(vesting_schedule.unlocked_amount(500).unwrap(), 1_000);
+        assert_eq!(vesting_schedule.unlocked_amount(i64::MAX).unwrap(), 1_000);
+
+        vesting_schedule.claimed_amount = 250;
+        assert_eq!(vesting_schedule.claimable_amount(450).unwrap(), 500);
+        assert_eq!(vesting_schedule.remaining_amount(), 750);
+    }
+
+    #[test]
+    fn cliff_unlock_test() {
+        let vesting_schedule = VestingSchedule {
+            total_amount: 1_200,
+            start: 0,
+            cliff: 300,
+            end: 1_200,
+            period: 100,
+            ..Default::default()
+        };
+
+        assert_eq!(vesting_schedule.unlocked_amount(299).unwrap(), 0);
+        // The periods elapsed before the cliff are unlocked at the cliff
+        assert_eq!(vesting_schedule.unlocked_amount(300).unwrap(), 300);
+        assert_eq!(vesting_schedule.unlocked_amount(1_199).unwrap(), 1_100);
+        assert_eq!(vesting_schedule.unlocked_amount(1_200).unwrap(), 1_200);
+    }
+}
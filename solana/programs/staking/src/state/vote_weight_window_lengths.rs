@@ -200,6 +200,25 @@ pub fn find_window_length_le(
     Ok(result)
 }
 
+/// Returns the largest window length that has ever been recorded
+pub fn find_max_window_length(account_info: &AccountInfo) -> Result<u64> {
+    let total_elements = {
+        let data = account_info.try_borrow_data()?;
+        let header_size = VoteWeightWindowLengths::VOTE_WEIGHT_WINDOW_LENGTHS_HEADER_SIZE;
+        (data.len() - header_size) / VoteWeightWindowLengths::WINDOW_LENGTH_SIZE
+    };
+
+    let mut max_window_length = 0;
+    for index in 0..total_elements {
+        let window_length = read_window_length_at_index(account_info, index)?;
+        if window_length.value > max_window_length {
+            max_window_length = window_length.value;
+        }
+    }
+
+    Ok(max_window_length)
+}
+
 #[derive(Clone, Copy, Default, BorshSerialize, BorshDeserialize, InitSpace)]
 pub struct WindowLength {
     pub timestamp: u64,
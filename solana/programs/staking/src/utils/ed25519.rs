@@ -0,0 +1,134 @@
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+
+// Layout of the ed25519 program instruction data: the number of signatures and a padding byte,
+// followed by the offsets of every signature
+const SIGNATURE_OFFSETS_START: usize = 2;
+const SIGNATURE_OFFSETS_SERIALIZED_SIZE: usize = 14;
+const PUBKEY_SERIALIZED_SIZE: usize = 32;
+// Instruction index telling the ed25519 program that the data is in its own instruction
+const CURRENT_INSTRUCTION_INDEX: u16 = u16::MAX;
+
+/// Checks that the instruction preceding the current one is an ed25519 program instruction
+/// verifying a single signature of the message by the signer.
+/// The signature itself is verified by the ed25519 program, which fails the transaction otherwise
+pub fn verify_ed25519_instruction(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    require!(current_index > 0, ErrorCode::InvalidEd25519Instruction);
+
+    let instruction = load_instruction_at_checked((current_index - 1).into(), instructions_sysvar)?;
+    require!(
+        instruction.program_id == ed25519_program::ID && instruction.accounts.is_empty(),
+        ErrorCode::InvalidEd25519Instruction
+    );
+
+    check_ed25519_instruction_data(&instruction.data, signer, message)
+}
+
+fn check_ed25519_instruction_data(data: &[u8], signer: &Pubkey, message: &[u8]) -> Result<()> {
+    require!(
+        data.len() >= SIGNATURE_OFFSETS_START + SIGNATURE_OFFSETS_SERIALIZED_SIZE && data[0] == 1,
+        ErrorCode::InvalidEd25519Instruction
+    );
+
+    let read_u16 = |position: usize| {
+        let offset = SIGNATURE_OFFSETS_START + position * 2;
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    };
+    let signature_instruction_index = read_u16(1);
+    let public_key_offset = usize::from(read_u16(2));
+    let public_key_instruction_index = read_u16(3);
+    let message_data_offset = usize::from(read_u16(4));
+    let message_data_size = usize::from(read_u16(5));
+    let message_instruction_index = read_u16(6);
+
+    // The signature, public key and message have to be read from the ed25519 instruction itself,
+    // otherwise the checked bytes could differ from the verified ones
+    require!(
+        signature_instruction_index == CURRENT_INSTRUCTION_INDEX
+            && public_key_instruction_index == CURRENT_INSTRUCTION_INDEX
+            && message_instruction_index == CURRENT_INSTRUCTION_INDEX,
+        ErrorCode::InvalidEd25519Instruction
+    );
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + PUBKEY_SERIALIZED_SIZE)
+        .ok_or(ErrorCode::InvalidEd25519Instruction)?;
+    let signed_message = data
+        .get(message_data_offset..message_data_offset + message_data_size)
+        .ok_or(ErrorCode::InvalidEd25519Instruction)?;
+
+    require!(
+        public_key == signer.as_ref() && signed_message == message,
+        ErrorCode::InvalidDelegationSignature
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // Builds the instruction data the same way as the ed25519 program helpers,
+    // with the public key, the signature and the message following the offsets
+    fn ed25519_instruction_data(public_key: &Pubkey, message: &[u8]) -> Vec<u8> {
+        let public_key_offset: u16 = 16;
+        let signature_offset = public_key_offset + 32;
+        let message_data_offset = signature_offset + 64;
+
+        let mut data = vec![1, 0];
+        for value in [
+            signature_offset,
+            CURRENT_INSTRUCTION_INDEX,
+            public_key_offset,
+            CURRENT_INSTRUCTION_INDEX,
+            message_data_offset,
+            message.len() as u16,
+            CURRENT_INSTRUCTION_INDEX,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(public_key.as_ref());
+        data.extend_from_slice(&[0; 64]);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn check_ed25519_instruction_data_test() {
+        let signer = Pubkey::new_unique();
+        let data = ed25519_instruction_data(&signer, b"message");
+
+        assert!(check_ed25519_instruction_data(&data, &signer, b"message").is_ok());
+        assert!(check_ed25519_instruction_data(&data, &Pubkey::new_unique(), b"message").is_err());
+        assert!(check_ed25519_instruction_data(&data, &signer, b"other message").is_err());
+    }
+
+    #[test]
+    fn check_ed25519_instruction_data_invalid_test() {
+        let signer = Pubkey::new_unique();
+        let data = ed25519_instruction_data(&signer, b"message");
+
+        // More than one signature
+        let mut invalid_data = data.clone();
+        invalid_data[0] = 2;
+        assert!(check_ed25519_instruction_data(&invalid_data, &signer, b"message").is_err());
+
+        // Message read from another instruction
+        let mut invalid_data = data.clone();
+        invalid_data[14..16].copy_from_slice(&0u16.to_le_bytes());
+        assert!(check_ed25519_instruction_data(&invalid_data, &signer, b"message").is_err());
+
+        // Truncated data
+        assert!(check_ed25519_instruction_data(&data[..40], &signer, b"message").is_err());
+    }
+}
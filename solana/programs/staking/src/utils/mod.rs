@@ -1,2 +1,4 @@
 pub mod clock;
+pub mod ed25519;
 pub mod execute_message;
+pub mod query;
@@ -0,0 +1,250 @@
+use crate::error::{ProposalWormholeMessageError, QueriesSolanaVerifyError};
+use crate::state::spoke_metadata_collector::QueryPolicy;
+use crate::state::{GuardianSignatures, WormholeGuardianSet};
+use crate::utils::clock::get_current_time;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::keccak;
+use anchor_lang::solana_program::program_memory::sol_memcpy;
+use anchor_lang::solana_program::secp256k1_recover::secp256k1_recover;
+use wormhole_query_sdk::structs::{ChainSpecificQuery, ChainSpecificResponse, QueryResponse};
+use wormhole_query_sdk::{MESSAGE_PREFIX, QUERY_MESSAGE_LEN};
+use wormhole_raw_vaas::utils::quorum;
+use wormhole_raw_vaas::GuardianSetSig;
+
+/// A Wormhole query response whose bytes were signed by a quorum of an active guardian set
+pub struct VerifiedQueryResponse {
+    pub response: QueryResponse,
+    /// Digest of the query response signed by the guardians
+    pub digest: [u8; 32],
+}
+
+/// The single eth_call of a verified query response, together with its result
+pub struct VerifiedEthCall<'a> {
+    pub chain_id: u16,
+    pub to: &'a [u8; 20],
+    pub data: &'a [u8],
+    pub result: &'a [u8],
+}
+
+impl VerifiedQueryResponse {
+    /// Verifies the guardian signatures of the query response bytes and parses them
+    pub fn verify(
+        guardian_set: &WormholeGuardianSet,
+        guardian_signatures: &GuardianSignatures,
+        bytes: &[u8],
+    ) -> Result<Self> {
+        let digest = verify_query_signatures(guardian_set, guardian_signatures, bytes)?;
+
+        let response = QueryResponse::deserialize(bytes)
+            .map_err(|_| QueriesSolanaVerifyError::FailedToParseResponse)?;
+
+        Ok(VerifiedQueryResponse { response, digest })
+    }
+
+    /// Returns the eth_call of a query response holding exactly one eth_call accepted by
+    /// the query policy
+    pub fn eth_call(&self, query_policy: &QueryPolicy) -> Result<VerifiedEthCall<'_>> {
+        let mut eth_calls = self.eth_calls(query_policy)?;
+
+        require!(
+            eth_calls.len() == 1,
+            ProposalWormholeMessageError::TooManyEthCallResults
+        );
+
+        Ok(eth_calls.remove(0))
+    }
+
+    /// Returns the eth_calls of a query response holding a single eth_call request accepted by
+    /// the query policy, each paired with its result.
+    /// Requests on finalized blocks are always accepted, while requests on "safe" blocks or
+    /// on the block of a timestamp have to be allowed and their block old enough
+    pub fn eth_calls(&self, query_policy: &QueryPolicy) -> Result<Vec<VerifiedEthCall<'_>>> {
+        require!(
+            self.response.responses.len() == 1,
+            ProposalWormholeMessageError::TooManyQueryResponses
+        );
+
+        let response = &self.response.responses[0];
+
+        let (call_data, results, block_time) = match &self.response.request.requests[0].query {
+            ChainSpecificQuery::EthCallWithFinalityQueryRequest(eth_request) => {
+                let finalized = eth_request.finality == "finalized";
+                require!(
+                    finalized
+                        || (eth_request.finality == "safe" && query_policy.allow_safe_finality),
+                    ProposalWormholeMessageError::NonFinalizedBlock
+                );
+
+                let eth_response =
+                    if let ChainSpecificResponse::EthCallWithFinalityQueryResponse(eth_response) =
+                        &response.response
+                    {
+                        eth_response
+                    } else {
+                        return Err(
+                            ProposalWormholeMessageError::InvalidChainSpecificResponse.into()
+                        );
+                    };
+
+                (
+                    &eth_request.call_data,
+                    &eth_response.results,
+                    (!finalized).then_some(eth_response.block_time),
+                )
+            }
+            ChainSpecificQuery::EthCallByTimestampQueryRequest(eth_request)
+                if query_policy.allow_eth_call_by_timestamp =>
+            {
+                let eth_response =
+                    if let ChainSpecificResponse::EthCallByTimestampQueryResponse(eth_response) =
+                        &response.response
+                    {
+                        eth_response
+                    } else {
+                        return Err(
+                            ProposalWormholeMessageError::InvalidChainSpecificResponse.into()
+                        );
+                    };
+
+                (
+                    &eth_request.call_data,
+                    &eth_response.results,
+                    Some(eth_response.target_block_time),
+                )
+            }
+            _ => return Err(ProposalWormholeMessageError::InvalidChainSpecificQuery.into()),
+        };
+
+        // Query block times are in microseconds
+        if let Some(block_time) = block_time {
+            let current_timestamp: u64 = get_current_time().try_into()?;
+            require!(
+                (block_time / 1_000_000).saturating_add(query_policy.min_confirmation_age)
+                    <= current_timestamp,
+                ProposalWormholeMessageError::QueryBlockTooRecent
+            );
+        }
+
+        require!(
+            results.len() == call_data.len(),
+            ProposalWormholeMessageError::TooManyEthCallResults
+        );
+
+        Ok(call_data
+            .iter()
+            .zip(results.iter())
+            .map(|(call_data, result)| VerifiedEthCall {
+                chain_id: response.chain_id,
+                to: &call_data.to,
+                data: &call_data.data,
+                result,
+            })
+            .collect())
+    }
+}
+
+/// Verifies that a quorum of the guardian set signed the Wormhole query response bytes
+/// and returns the signed digest
+pub fn verify_query_signatures(
+    guardian_set: &WormholeGuardianSet,
+    guardian_signatures: &GuardianSignatures,
+    bytes: &[u8],
+) -> Result<[u8; 32]> {
+    // Check that the guardian set is still active.
+    let timestamp = Clock::get()?
+        .unix_timestamp
+        .try_into()
+        .expect("timestamp overflow");
+    require!(
+        guardian_set.is_active(&timestamp),
+        QueriesSolanaVerifyError::GuardianSetExpired
+    );
+
+    // Compute the message hash.
+    let message_hash = [MESSAGE_PREFIX, &keccak::hashv(&[bytes]).to_bytes()].concat();
+
+    // SECURITY: defense-in-depth, check again that these are the expected length
+    require_eq!(
+        message_hash.len(),
+        QUERY_MESSAGE_LEN,
+        QueriesSolanaVerifyError::InvalidMessageHash
+    );
+
+    let guardian_signatures = &guardian_signatures.guardian_signatures;
+
+    // This section is borrowed from https://github.com/wormhole-foundation/wormhole/blob/wen/solana-rewrite/solana/programs/core-bridge/src/processor/parse_and_verify_vaa/verify_encoded_vaa_v1.rs#L72-L103
+    // Also similarly used here https://github.com/pyth-network/pyth-crosschain/blob/6771c2c6998f53effee9247347cb0ac71612b3dc/target_chains/solana/programs/pyth-solana-receiver/src/lib.rs#L121-L159
+    // Do we have enough signatures for quorum?
+    let guardian_keys = &guardian_set.keys;
+    let quorum = quorum(guardian_keys.len());
+    require!(
+        guardian_signatures.len() >= quorum,
+        QueriesSolanaVerifyError::NoQuorum
+    );
+
+    let digest = keccak::hash(message_hash.as_slice());
+
+    // Verify signatures
+    let mut last_guardian_index = None;
+    for sig_bytes in guardian_signatures {
+        let sig = GuardianSetSig::try_from(sig_bytes.as_slice())
+            .map_err(|_| QueriesSolanaVerifyError::InvalidSignature)?;
+        // We do not allow for non-increasing guardian signature indices.
+        let index = usize::from(sig.guardian_index());
+        if let Some(last_index) = last_guardian_index {
+            require!(
+                index > last_index,
+                QueriesSolanaVerifyError::InvalidGuardianIndexNonIncreasing
+            );
+        }
+
+        // Does this guardian index exist in this guardian set?
+        let guardian_pubkey = guardian_keys
+            .get(index)
+            .ok_or_else(|| error!(QueriesSolanaVerifyError::InvalidGuardianIndexOutOfRange))?;
+
+        // Now verify that the signature agrees with the expected Guardian's pubkey.
+        verify_guardian_signature(&sig, guardian_pubkey, digest.as_ref())?;
+
+        last_guardian_index = Some(index);
+    }
+    // End borrowed section
+
+    // Done.
+    Ok(digest.to_bytes())
+}
+
+/**
+ * Borrowed from https://github.com/wormhole-foundation/wormhole/blob/wen/solana-rewrite/solana/programs/core-bridge/src/processor/parse_and_verify_vaa/verify_encoded_vaa_v1.rs#L121
+ * Also used here https://github.com/pyth-network/pyth-crosschain/blob/6771c2c6998f53effee9247347cb0ac71612b3dc/target_chains/solana/programs/pyth-solana-receiver/src/lib.rs#L432
+ */
+fn verify_guardian_signature(
+    sig: &GuardianSetSig,
+    guardian_pubkey: &[u8; 20],
+    digest: &[u8],
+) -> Result<()> {
+    // Recover using `solana_program::secp256k1_recover`. Public key recovery costs 25k compute
+    // units. And hashing this public key to recover the Ethereum public key costs about 13k.
+    let recovered = {
+        // Recover EC public key (64 bytes).
+        let pubkey = secp256k1_recover(digest, sig.recovery_id(), &sig.rs())
+            .map_err(|_| QueriesSolanaVerifyError::InvalidSignature)?;
+
+        // The Ethereum public key is the last 20 bytes of keccak hashed public key above.
+        let hashed = keccak::hash(&pubkey.to_bytes());
+
+        let mut eth_pubkey = [0; 20];
+        sol_memcpy(&mut eth_pubkey, &hashed.0[12..], 20);
+
+        eth_pubkey
+    };
+
+    // The recovered public key should agree with the Guardian's public key at this index.
+    require!(
+        recovered == *guardian_pubkey,
+        QueriesSolanaVerifyError::InvalidGuardianKeyRecovery
+    );
+
+    // Done.
+    Ok(())
+}
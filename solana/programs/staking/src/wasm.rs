@@ -176,10 +176,19 @@ reexport_seed_const!(VESTING_CONFIG_SEED);
 reexport_seed_const!(VESTING_BALANCE_SEED);
 reexport_seed_const!(AIRLOCK_SEED);
 reexport_seed_const!(VEST_SEED);
+reexport_seed_const!(VESTING_SCHEDULE_SEED);
+reexport_seed_const!(VESTING_MERKLE_DISTRIBUTOR_SEED);
+reexport_seed_const!(VESTING_MERKLE_CLAIM_BITMAP_SEED);
 reexport_seed_const!(SPOKE_METADATA_COLLECTOR_SEED);
 reexport_seed_const!(VOTE_WEIGHT_WINDOW_LENGTHS_SEED);
 reexport_seed_const!(GUARDIAN_SIGNATURES_SEED);
 reexport_seed_const!(SPOKE_MESSAGE_EXECUTOR_SEED);
+reexport_seed_const!(DELEGATION_SPLIT_SEED);
+reexport_seed_const!(DELEGATION_NONCE_SEED);
+reexport_seed_const!(PENDING_STAKE_OWNER_SEED);
+reexport_seed_const!(PENDING_WITHDRAWAL_SEED);
+reexport_seed_const!(WITHDRAWAL_CUSTODY_SEED);
+reexport_seed_const!(DELEGATE_BY_SIG_DOMAIN);
 
 #[wasm_bindgen]
 impl Constants {
@@ -0,0 +1,223 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+  sleep,
+} from "./utils/before";
+import BN from "bn.js";
+import path from "path";
+import crypto from "crypto";
+import { createProposalQueryResponseBytes } from "./utils/api_utils";
+import { StakeConnection, WHTokenBalance } from "../app";
+import { QueryProxyMock } from "@wormhole-foundation/wormhole-query-sdk";
+import { AnchorError } from "@coral-xyz/anchor";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+describe("accept_stake_owner", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  let stakeConnection: StakeConnection;
+  let user2StakeConnection: StakeConnection;
+  let user3StakeConnection: StakeConnection;
+
+  let controller;
+  let user2;
+  let user3;
+
+  let delegatedAt: number;
+  let proposalBeforeTransfer: Buffer;
+  let proposalAfterTransfer: Buffer;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    user2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user2 = user2StakeConnection.provider.wallet.publicKey;
+
+    user3StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user3 = user3StakeConnection.provider.wallet.publicKey;
+
+    await user2StakeConnection.delegate(
+      user2,
+      WHTokenBalance.fromString("100"),
+    );
+    delegatedAt = Math.floor(Date.now() / 1000);
+  });
+
+  it("should copy the checkpoint history to the new owner", async () => {
+    proposalBeforeTransfer = await addTestProposal(
+      stakeConnection,
+      delegatedAt + 3,
+    );
+    // The vote weight window of 10s of the next proposal has to start after the delegation
+    await sleep(8000);
+
+    await user2StakeConnection.proposeStakeOwner(user3);
+    await user3StakeConnection.acceptStakeOwner(user2);
+
+    const user3StakeAccountMetadata =
+      await user3StakeConnection.fetchStakeAccountMetadata(user3);
+    assert(user3StakeAccountMetadata.delegate.equals(user3));
+    assert.equal(
+      user3StakeAccountMetadata.recordedBalance.toString(),
+      WHTokenBalance.fromString("100").toBN().toString(),
+    );
+    const ownerChangedAt = user3StakeAccountMetadata.ownerChangedAt.toNumber();
+    assert(ownerChangedAt > delegatedAt);
+
+    const user3Checkpoints = await checkpoints(stakeConnection, user3);
+    assert.equal(user3Checkpoints.length, 2);
+    assert(Number(user3Checkpoints[0].timestamp) < ownerChangedAt);
+    assert.equal(Number(user3Checkpoints[1].timestamp), ownerChangedAt);
+    for (const checkpoint of user3Checkpoints) {
+      assert.equal(
+        checkpoint.value.toString(),
+        WHTokenBalance.fromString("100").toBN().toString(),
+      );
+    }
+
+    const user2Checkpoints = await checkpoints(stakeConnection, user2);
+    assert.equal(
+      user2Checkpoints[user2Checkpoints.length - 1].value.toString(),
+      "0",
+    );
+
+    proposalAfterTransfer = await addTestProposal(
+      stakeConnection,
+      ownerChangedAt + 4,
+    );
+  });
+
+  it("should fail to castVote on a proposal that started before the owner change", async () => {
+    try {
+      await user3StakeConnection.castVote(
+        proposalBeforeTransfer,
+        new BN(0),
+        new BN(1),
+        new BN(0),
+      );
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "ProposalPredatesStakeOwnerChange",
+      );
+    }
+  });
+
+  it("should castVote with a vote weight window spanning the owner change", async () => {
+    const { proposalAccount } =
+      await stakeConnection.fetchProposalAccount(proposalAfterTransfer);
+    const voteStart = (
+      await stakeConnection.program.account.proposalData.fetch(proposalAccount)
+    ).voteStart.toNumber();
+    while (voteStart >= Math.floor(Date.now() / 1000)) {
+      await sleep(1000);
+    }
+    await sleep(1000);
+
+    await user3StakeConnection.castVote(
+      proposalAfterTransfer,
+      new BN(0),
+      WHTokenBalance.fromString("100").toBN(),
+      new BN(0),
+    );
+
+    const { forVotes } = await stakeConnection.proposalVotes(
+      proposalAfterTransfer,
+    );
+    assert.equal(
+      forVotes.toString(),
+      WHTokenBalance.fromString("100").toBN().toString(),
+    );
+
+    // The previous owner has no weight left in the window
+    try {
+      await user2StakeConnection.castVote(
+        proposalAfterTransfer,
+        new BN(0),
+        new BN(1),
+        new BN(0),
+      );
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert((e as AnchorError).error?.errorCode?.code === "NoWeight");
+    }
+  });
+});
+
+async function checkpoints(stakeConnection: StakeConnection, user: PublicKey) {
+  const checkpointsAddress =
+    await stakeConnection.getStakeAccountCheckpointsAddressByMetadata(
+      await stakeConnection.getStakeMetadataAddress(user),
+      false,
+    );
+  return (await stakeConnection.fetchCheckpointAccount(checkpointsAddress))
+    .checkpoints;
+}
+
+async function addTestProposal(
+  stakeConnection: StakeConnection,
+  voteStart: number,
+) {
+  const proposalIdInput = crypto
+    .createHash("sha256")
+    .update("proposalId" + Date.now())
+    .digest();
+
+  const ethProposalResponseBytes = createProposalQueryResponseBytes(
+    proposalIdInput,
+    voteStart,
+  );
+  const mock = new QueryProxyMock({});
+  const mockSignatures = mock.sign(ethProposalResponseBytes);
+  const guardianSignaturesPda =
+    await stakeConnection.postSignatures(mockSignatures);
+  const mockGuardianSetIndex = 5;
+
+  await stakeConnection.addProposal(
+    proposalIdInput,
+    ethProposalResponseBytes,
+    guardianSignaturesPda,
+    mockGuardianSetIndex,
+  );
+
+  return proposalIdInput;
+}
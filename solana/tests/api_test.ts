@@ -17,6 +17,7 @@ import {
   createProposalQueryResponseBytesWithInvalidChainSpecificQuery,
   createProposalQueryResponseBytesWithInvalidChainSpecificResponse,
   createProposalQueryResponseBytesWithInvalidFunctionSignature,
+  createProposalsQueryResponseBytes,
 } from "./utils/api_utils";
 import { StakeConnection, WHTokenBalance } from "../app";
 import { TEST_CHECKPOINTS_ACCOUNT_LIMIT } from "./utils/constants";
@@ -182,6 +183,58 @@ describe("api", async () => {
       assert.equal(proposalAccountData.abstainVotes.toString(), "0");
     });
 
+    it("should add a proposal whose hub metadata does not carry the vote end", async () => {
+      const proposalIdInput = crypto
+        .createHash("sha256")
+        .update("proposalIdWithoutVoteEnd")
+        .digest();
+      const voteStart = Math.floor(Date.now() / 1000);
+
+      const ethProposalResponseBytes = createProposalQueryResponseBytes(
+        proposalIdInput,
+        voteStart,
+        null,
+      );
+
+      const mock = new QueryProxyMock({});
+      const mockSignatures = mock.sign(ethProposalResponseBytes);
+      const guardianSignaturesPda =
+        await stakeConnection.postSignatures(mockSignatures);
+      const mockGuardianSetIndex = 5;
+
+      await stakeConnection.addProposal(
+        proposalIdInput,
+        ethProposalResponseBytes,
+        guardianSignaturesPda,
+        mockGuardianSetIndex,
+      );
+
+      const { proposalAccountData } =
+        await stakeConnection.fetchProposalAccountData(proposalIdInput);
+      assert.equal(
+        proposalAccountData.voteStart.toString(),
+        voteStart.toString(),
+      );
+      assert.equal(proposalAccountData.voteEnd.toString(), "0");
+    });
+
+    it("should not migrate a proposal that has the current layout", async () => {
+      const proposalIdInput = crypto
+        .createHash("sha256")
+        .update("proposalId11")
+        .digest();
+
+      try {
+        await stakeConnection.migrateProposal(proposalIdInput);
+        assert.fail("Expected error was not thrown");
+      } catch (e) {
+        assert(
+          (e as AnchorError).error?.errorCode?.code ===
+            "AccountAlreadyMigrated",
+        );
+      }
+    });
+
     it.skip("should correctly add a real sepolia proposal", async () => {
       const proposalIdArray = Buffer.from(
         "462c69856d29579a9fd5d80ced46f98862f1c83b47c04b928676f7e6919ad1f2",
@@ -218,6 +271,45 @@ describe("api", async () => {
       assert.equal(proposalAccountData.abstainVotes.toString(), "0");
     });
 
+    it("should revert if the guardians did not sign the query response", async () => {
+      const proposalIdInput = crypto
+        .createHash("sha256")
+        .update("proposalId19")
+        .digest();
+      const voteStart = Math.floor(Date.now() / 1000);
+
+      const signedBytes = createProposalQueryResponseBytes(
+        crypto.createHash("sha256").update("proposalId20").digest(),
+        voteStart,
+      );
+      const unsignedBytes = createProposalQueryResponseBytes(
+        proposalIdInput,
+        voteStart,
+      );
+
+      const mock = new QueryProxyMock({});
+      const mockSignatures = mock.sign(signedBytes);
+      const guardianSignaturesPda =
+        await stakeConnection.postSignatures(mockSignatures);
+      const mockGuardianSetIndex = 5;
+
+      try {
+        await stakeConnection.addProposal(
+          proposalIdInput,
+          unsignedBytes,
+          guardianSignaturesPda,
+          mockGuardianSetIndex,
+          true,
+        );
+        assert.fail("Expected error was not thrown");
+      } catch (e) {
+        assert(
+          (e as AnchorError).error?.errorCode?.code ===
+            "InvalidGuardianKeyRecovery",
+        );
+      }
+    });
+
     it("should revert if query block is not finalized", async () => {
       const proposalIdInput = crypto
         .createHash("sha256")
@@ -438,6 +530,86 @@ describe("api", async () => {
     });
   });
 
+  describe("addProposals", () => {
+    it("should add a batch of proposals from one query response", async () => {
+      const voteStart = Math.floor(Date.now() / 1000);
+      const proposalIds = ["batchProposalId1", "batchProposalId2"].map((id) =>
+        crypto.createHash("sha256").update(id).digest(),
+      );
+
+      const ethProposalsResponseBytes = createProposalsQueryResponseBytes(
+        proposalIds.map((proposalId, index) => ({
+          proposalId,
+          voteStart: voteStart + index,
+        })),
+      );
+      const mock = new QueryProxyMock({});
+      const mockSignatures = mock.sign(ethProposalsResponseBytes);
+      const guardianSignaturesPda =
+        await stakeConnection.postSignatures(mockSignatures);
+      const mockGuardianSetIndex = 5;
+
+      await stakeConnection.addProposals(
+        proposalIds,
+        ethProposalsResponseBytes,
+        guardianSignaturesPda,
+        mockGuardianSetIndex,
+      );
+
+      for (const [index, proposalId] of proposalIds.entries()) {
+        const { proposalAccountData } =
+          await stakeConnection.fetchProposalAccountData(proposalId);
+        assert.equal(
+          Buffer.from(proposalAccountData.id).toString("hex"),
+          proposalId.toString("hex"),
+        );
+        assert.equal(
+          proposalAccountData.voteStart.toString(),
+          (voteStart + index).toString(),
+        );
+        assert.equal(proposalAccountData.forVotes.toString(), "0");
+      }
+    });
+
+    it("should revert if the proposal accounts do not match the calls", async () => {
+      const voteStart = Math.floor(Date.now() / 1000);
+      const proposalIds = ["batchProposalId3", "batchProposalId4"].map((id) =>
+        crypto.createHash("sha256").update(id).digest(),
+      );
+
+      const ethProposalsResponseBytes = createProposalsQueryResponseBytes(
+        proposalIds.map((proposalId) => ({ proposalId, voteStart })),
+      );
+      const mock = new QueryProxyMock({});
+      const mockSignatures = mock.sign(ethProposalsResponseBytes);
+      const mockGuardianSetIndex = 5;
+
+      // One proposal account is missing, then the accounts are out of order
+      for (const accountsProposalIds of [
+        proposalIds.slice(0, 1),
+        [...proposalIds].reverse(),
+      ]) {
+        const guardianSignaturesPda =
+          await stakeConnection.postSignatures(mockSignatures);
+        try {
+          await stakeConnection.addProposals(
+            accountsProposalIds,
+            ethProposalsResponseBytes,
+            guardianSignaturesPda,
+            mockGuardianSetIndex,
+          );
+
+          assert.fail("Expected error was not thrown");
+        } catch (e) {
+          assert(
+            (e as AnchorError).error?.errorCode?.code ===
+              "InvalidProposalAccounts",
+          );
+        }
+      }
+    });
+  });
+
   it("proposalVotes", async () => {
     const proposalIdInput = crypto
       .createHash("sha256")
@@ -1,11 +1,4 @@
-import {
-  Keypair,
-  LAMPORTS_PER_SOL,
-  PublicKey,
-  SystemProgram,
-  Transaction,
-  TransactionInstruction,
-} from "@solana/web3.js";
+import { Keypair, PublicKey, TransactionInstruction } from "@solana/web3.js";
 import assert from "assert";
 import {
   ANCHOR_CONFIG_PATH,
@@ -458,7 +451,7 @@ describe("castVote", async () => {
     }
   });
 
-  it("should successfully castVote with new checkpoint account created by any random user", async () => {
+  it("should successfully castVote with new checkpoint account created by the rollover", async () => {
     // filling the checkpoint account to the limit
     for (let i = 0; i < TEST_CHECKPOINTS_ACCOUNT_LIMIT; i++) {
       await sleep(1000);
@@ -470,35 +463,38 @@ describe("castVote", async () => {
 
     let user5StakeAccountMetadataAddress =
       await user5StakeConnection.getStakeMetadataAddress(user5);
-    let user5StakeAccountCheckpointsAddress =
+    let previousUser5StakeAccountCheckpointsAddress =
       await user5StakeConnection.getStakeAccountCheckpointsAddressByMetadata(
         user5StakeAccountMetadataAddress,
         true,
       );
+    let user5StakeAccountCheckpointsAddress =
+      await user5StakeConnection.getStakeAccountCheckpointsAddressByMetadata(
+        user5StakeAccountMetadataAddress,
+        false,
+      );
 
-    const randomUser = new Keypair();
-    let tx = new Transaction();
-    tx.instructions = [
-      SystemProgram.transfer({
-        fromPubkey: stakeConnection.program.provider.publicKey,
-        toPubkey: randomUser.publicKey,
-        lamports: 10 * LAMPORTS_PER_SOL,
-      }),
-    ];
-    await stakeConnection.program.provider.sendAndConfirm(tx, [
-      stakeConnection.program.provider.wallet.payer,
-    ]);
-
-    await user5StakeConnection.program.methods
-      .createCheckpoints()
-      .accounts({
-        payer: randomUser.publicKey,
-        stakeAccountCheckpoints: user5StakeAccountCheckpointsAddress,
-        stakeAccountMetadata: user5StakeAccountMetadataAddress,
-      })
-      .signers([randomUser])
-      .rpc({ skipPreflight: true })
-      .then(confirm);
+    // The new checkpoint account is created in the instruction that filled the previous one,
+    // and starts with a copy of its latest checkpoint
+    let previousUser5StakeAccountCheckpoints: CheckpointAccount =
+      await user5StakeConnection.fetchCheckpointAccount(
+        previousUser5StakeAccountCheckpointsAddress,
+      );
+    let user5StakeAccountCheckpoints: CheckpointAccount =
+      await user5StakeConnection.fetchCheckpointAccount(
+        user5StakeAccountCheckpointsAddress,
+      );
+    assert.equal(
+      previousUser5StakeAccountCheckpoints.getCheckpointCount(),
+      TEST_CHECKPOINTS_ACCOUNT_LIMIT,
+    );
+    assert.equal(user5StakeAccountCheckpoints.getCheckpointCount(), 1);
+    assert.equal(
+      user5StakeAccountCheckpoints.getLastCheckpoint().value.toString(),
+      previousUser5StakeAccountCheckpoints
+        .getLastCheckpoint()
+        .value.toString(),
+    );
 
     await user5StakeConnection.delegate(
       user5,
@@ -561,6 +557,30 @@ describe("castVote", async () => {
         false,
       );
 
+    // The checkpoint account can not be filled without creating the next one
+    await sleep(1000);
+    try {
+      await user8StakeConnection.program.methods
+        .delegate(user6, user8)
+        .accountsPartial({
+          currentDelegateStakeAccountCheckpoints:
+            previousUser8StakeAccountCheckpointsAddress,
+          currentDelegateStakeAccountCheckpointsNext: null,
+          delegateeStakeAccountCheckpoints: user6StakeAccountCheckpointsAddress,
+          vestingConfig: null,
+          vestingBalance: null,
+          mint: user8StakeConnection.config.votingTokenMint,
+        })
+        .rpc();
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "MissingNextCheckpointDataAccount",
+      );
+    }
+
     await sleep(1000);
     const instructions: TransactionInstruction[] = [];
     instructions.push(
@@ -575,6 +595,8 @@ describe("castVote", async () => {
         .accountsPartial({
           currentDelegateStakeAccountCheckpoints:
             previousUser8StakeAccountCheckpointsAddress,
+          currentDelegateStakeAccountCheckpointsNext:
+            user8StakeAccountCheckpointsAddress,
           delegateeStakeAccountCheckpoints: user6StakeAccountCheckpointsAddress,
           vestingConfig: null,
           vestingBalance: null,
@@ -582,17 +604,6 @@ describe("castVote", async () => {
         })
         .instruction(),
     );
-    instructions.push(
-      await user8StakeConnection.program.methods
-        .createCheckpoints()
-        .accounts({
-          payer: user8,
-          stakeAccountCheckpoints: previousUser8StakeAccountCheckpointsAddress,
-          newStakeAccountCheckpoints: user8StakeAccountCheckpointsAddress,
-          stakeAccountMetadata: user8StakeAccountMetadataAddress,
-        })
-        .instruction(),
-    );
     instructions.push(
       await user8StakeConnection.program.methods
         .delegate(user8, user6)
@@ -709,25 +720,18 @@ describe("castVote", async () => {
         .accountsPartial({
           currentDelegateStakeAccountCheckpoints:
             previousUser9StakeAccountCheckpointsAddress,
+          currentDelegateStakeAccountCheckpointsNext:
+            user9StakeAccountCheckpointsAddress,
           delegateeStakeAccountCheckpoints:
             previousUser9StakeAccountCheckpointsAddress,
+          delegateeStakeAccountCheckpointsNext:
+            user9StakeAccountCheckpointsAddress,
           vestingConfig: null,
           vestingBalance: null,
           mint: user9StakeConnection.config.votingTokenMint,
         })
         .instruction(),
     );
-    instructions.push(
-      await user9StakeConnection.program.methods
-        .createCheckpoints()
-        .accounts({
-          payer: user9,
-          stakeAccountCheckpoints: previousUser9StakeAccountCheckpointsAddress,
-          newStakeAccountCheckpoints: user9StakeAccountCheckpointsAddress,
-          stakeAccountMetadata: user9StakeAccountMetadataAddress,
-        })
-        .instruction(),
-    );
     instructions.push(
       await user9StakeConnection.program.methods
         .delegate(user6, user9)
@@ -810,7 +814,13 @@ describe("castVote", async () => {
 
     try {
       await user2StakeConnection.program.methods
-        .castVote(Array.from(proposalId), new BN(10), new BN(20), new BN(12), 1)
+        .castVote(
+          Array.from(proposalId),
+          new BN(10),
+          new BN(20),
+          new BN(12),
+          1,
+        )
         .accountsPartial({
           proposal: proposalAccount,
           voterCheckpoints:
@@ -894,6 +904,25 @@ describe("castVote", async () => {
     assert.equal(forVotes.toString(), "20");
     assert.equal(abstainVotes.toString(), "12");
   });
+
+  it("should fail to compact checkpoints that proposals accepting votes can still read", async () => {
+    // The latest checkpoint is older than the vote weight window, but not than
+    // the voting periods of the stored proposals
+    await sleep(11000);
+    try {
+      await user5StakeConnection.program.methods
+        .compactCheckpoints(0)
+        .accounts({ owner: user5 })
+        .rpc();
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "CheckpointsWithinVoteWeightWindow",
+      );
+    }
+  });
 });
 
 async function addTestProposal(
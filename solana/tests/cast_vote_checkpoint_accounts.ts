@@ -0,0 +1,279 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+  sleep,
+} from "./utils/before";
+import BN from "bn.js";
+import path from "path";
+import { createProposalQueryResponseBytes } from "./utils/api_utils";
+import { StakeConnection, WHTokenBalance } from "../app";
+import crypto from "crypto";
+import { QueryProxyMock } from "@wormhole-foundation/wormhole-query-sdk";
+import { AnchorError, utils } from "@coral-xyz/anchor";
+import * as wasm from "@wormhole/staking-wasm";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+// Every checkpoint account holds a copy of the latest checkpoint of the previous account
+// and a single new checkpoint, so a vote weight window spans many checkpoint accounts
+const MIN_CHECKPOINTS_ACCOUNT_LIMIT = 2;
+
+describe("castVote with checkpoint accounts", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  let stakeConnection: StakeConnection;
+  let user2StakeConnection: StakeConnection;
+  let user3StakeConnection: StakeConnection;
+
+  let controller;
+  let user2;
+  let user3;
+
+  let proposalIdInput: Buffer;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(
+        whMintAccount.publicKey,
+        undefined,
+        MIN_CHECKPOINTS_ACCOUNT_LIMIT,
+      ),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    user2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user2 = user2StakeConnection.provider.wallet.publicKey;
+
+    user3StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user3 = user3StakeConnection.provider.wallet.publicKey;
+  });
+
+  it("should roll over to a new checkpoint account on every checkpoint", async () => {
+    await user2StakeConnection.delegate(
+      user2,
+      WHTokenBalance.fromString("100"),
+    );
+    await sleep(1000);
+    await user3StakeConnection.delegate(user2, WHTokenBalance.fromString("50"));
+
+    const user2StakeAccountMetadata =
+      await user2StakeConnection.fetchStakeAccountMetadata(user2);
+    assert.equal(user2StakeAccountMetadata.stakeAccountCheckpointsLastIndex, 1);
+  });
+
+  it("should castVote with a vote weight window spanning more than two checkpoint accounts", async () => {
+    // voteWeightWindow is 10s, it starts after the checkpoint of 150 votes
+    const voteStart = Math.floor(Date.now() / 1000) + 12;
+    proposalIdInput = await addTestProposal(user2StakeConnection, voteStart);
+    await sleep(3000);
+
+    await user2StakeConnection.delegate(user2, WHTokenBalance.fromString("10"));
+    await sleep(1000);
+    // The lowest weight of the window is only recorded in the fourth checkpoint account
+    await user3StakeConnection.delegate(user3, WHTokenBalance.fromString("1"));
+    await sleep(1000);
+    await user2StakeConnection.delegate(user2, WHTokenBalance.fromString("10"));
+
+    const user2StakeAccountMetadata =
+      await user2StakeConnection.fetchStakeAccountMetadata(user2);
+    assert.equal(user2StakeAccountMetadata.stakeAccountCheckpointsLastIndex, 4);
+
+    while (voteStart >= Math.floor(Date.now() / 1000)) {
+      await sleep(1000);
+    }
+    await sleep(1000);
+
+    // The checkpoint of 150 votes is the last one of the first, filled account,
+    // so the window is read from its copy in the second account
+    try {
+      await castVoteWithCheckpointAccounts(
+        user2StakeConnection,
+        proposalIdInput,
+        new BN(110_000_001),
+        1,
+        4,
+      );
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "VoteWouldExceedWeight",
+      );
+    }
+
+    await user2StakeConnection.castVote(
+      proposalIdInput,
+      new BN(60_000_000),
+      new BN(50_000_000),
+      new BN(0),
+      1,
+      4,
+    );
+
+    const { againstVotes, forVotes, abstainVotes } =
+      await user2StakeConnection.proposalVotes(proposalIdInput);
+    assert.equal(againstVotes.toString(), "60000000");
+    assert.equal(forVotes.toString(), "50000000");
+    assert.equal(abstainVotes.toString(), "0");
+  });
+
+  it("should fail to castVote if a checkpoint account of the vote weight window is missing", async () => {
+    try {
+      await castVoteWithCheckpointAccounts(
+        user2StakeConnection,
+        proposalIdInput,
+        new BN(1),
+        1,
+        3,
+      );
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "MissingNextCheckpointDataAccount",
+      );
+    }
+  });
+
+  it("should fail to castVote if the checkpoint accounts are not consecutive", async () => {
+    try {
+      await castVoteWithCheckpointAccounts(
+        user2StakeConnection,
+        proposalIdInput,
+        new BN(1),
+        1,
+        4,
+        [1, 2, 4, 3],
+      );
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "InvalidNextVoterCheckpoints",
+      );
+    }
+  });
+});
+
+function checkpointsAddress(
+  stakeConnection: StakeConnection,
+  owner: PublicKey,
+  index: number,
+): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [
+      utils.bytes.utf8.encode(wasm.Constants.CHECKPOINT_DATA_SEED()),
+      owner.toBuffer(),
+      new BN(index).toArrayLike(Buffer, "le", 2),
+    ],
+    stakeConnection.program.programId,
+  )[0];
+}
+
+async function castVoteWithCheckpointAccounts(
+  stakeConnection: StakeConnection,
+  proposalId: Buffer,
+  againstVotes: BN,
+  firstCheckpointIndex: number,
+  lastCheckpointIndex: number,
+  checkpointIndexes?: number[],
+) {
+  const owner = stakeConnection.userPublicKey();
+  const indexes =
+    checkpointIndexes ??
+    Array.from(
+      { length: lastCheckpointIndex - firstCheckpointIndex + 1 },
+      (_, offset) => firstCheckpointIndex + offset,
+    );
+  const { proposalAccount } =
+    await stakeConnection.fetchProposalAccount(proposalId);
+
+  await stakeConnection.program.methods
+    .castVote(
+      Array.from(proposalId),
+      againstVotes,
+      new BN(0),
+      new BN(0),
+      firstCheckpointIndex,
+    )
+    .accountsPartial({
+      proposal: proposalAccount,
+      voterCheckpoints: checkpointsAddress(stakeConnection, owner, indexes[0]),
+      voterCheckpointsNext: checkpointsAddress(
+        stakeConnection,
+        owner,
+        indexes[1],
+      ),
+    })
+    .remainingAccounts(
+      indexes.slice(2).map((index) => ({
+        pubkey: checkpointsAddress(stakeConnection, owner, index),
+        isSigner: false,
+        isWritable: false,
+      })),
+    )
+    .rpc();
+}
+
+async function addTestProposal(
+  stakeConnection: StakeConnection,
+  voteStart: number,
+) {
+  const proposalIdInput = crypto
+    .createHash("sha256")
+    .update("proposalId" + Date.now())
+    .digest();
+
+  const ethProposalResponseBytes = createProposalQueryResponseBytes(
+    proposalIdInput,
+    voteStart,
+  );
+  const mock = new QueryProxyMock({});
+  const mockSignatures = mock.sign(ethProposalResponseBytes);
+  const guardianSignaturesPda =
+    await stakeConnection.postSignatures(mockSignatures);
+  const mockGuardianSetIndex = 5;
+
+  await stakeConnection.addProposal(
+    proposalIdInput,
+    ethProposalResponseBytes,
+    guardianSignaturesPda,
+    mockGuardianSetIndex,
+  );
+
+  return proposalIdInput;
+}
@@ -0,0 +1,571 @@
+import BN from "bn.js";
+import { randomBytes } from "crypto";
+import { Keypair } from "@solana/web3.js";
+import { getAccount } from "@solana/spl-token";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  sleep,
+  standardSetup,
+} from "./utils/before";
+import {
+  buildVestingMerkleTree,
+  finalizeVestingConfig,
+  getVestAddress,
+  getVestingBalanceAddress,
+  getVestingMerkleClaimBitmapAddress,
+  getVestingMerkleDistributorAddress,
+  getVestingVotesAccounts,
+  lastCheckpointValue,
+  setupVestingConfig,
+} from "./utils/vesting_utils";
+import path from "path";
+import { AnchorError } from "@coral-xyz/anchor";
+import { StakeConnection, WHTokenBalance } from "../app";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+// Time left to claim the leaves of the distributors once they are created
+const CLAIM_PERIOD = 30;
+
+describe("claim_vest_from_merkle", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  const MATURATION = new BN(Math.floor(Date.now() / 1000));
+  const VEST_AMOUNT = WHTokenBalance.fromString("100").toBN();
+  const UNCLAIMED_AMOUNT = WHTokenBalance.fromString("50").toBN();
+
+  // vester claims a leaf of both distributors, delegatedVester has a vest
+  // delegated to delegatee before claiming its leaf and the leaf of
+  // unclaimedVester is never claimed
+  const vester = Keypair.generate();
+  const delegatedVester = Keypair.generate();
+  const unclaimedVester = Keypair.generate();
+  const seed = new BN(randomBytes(8));
+
+  const firstLeaves = [
+    { vester: vester.publicKey, amount: VEST_AMOUNT, maturation: MATURATION },
+    {
+      vester: delegatedVester.publicKey,
+      amount: VEST_AMOUNT,
+      maturation: MATURATION,
+    },
+    {
+      vester: unclaimedVester.publicKey,
+      amount: UNCLAIMED_AMOUNT,
+      maturation: MATURATION,
+    },
+  ];
+  const secondLeaves = [
+    {
+      vester: vester.publicKey,
+      amount: WHTokenBalance.fromString("30").toBN(),
+      maturation: MATURATION,
+    },
+  ];
+  const firstTree = buildVestingMerkleTree(firstLeaves);
+  const secondTree = buildVestingMerkleTree(secondLeaves);
+
+  let stakeConnection: StakeConnection;
+  let delegateeStakeConnection: StakeConnection;
+  let vesterStakeConnections: Map<Keypair, StakeConnection>;
+
+  let controller;
+  let accounts;
+  let delegatee;
+  let claimDeadline: BN;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey, whMintAuthority.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    vesterStakeConnections = new Map();
+    for (const user of [vester, delegatedVester, unclaimedVester]) {
+      vesterStakeConnections.set(
+        user,
+        await newUserStakeConnection(
+          stakeConnection,
+          user,
+          config,
+          whMintAccount,
+          whMintAuthority,
+          WHTokenBalance.fromString("1000"),
+        ),
+      );
+    }
+
+    delegateeStakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    delegatee = delegateeStakeConnection.provider.wallet.publicKey;
+    await delegateeStakeConnection.createStakeAccount();
+
+    // The vest of delegatedVester matures later than its leaf, so that
+    // the leaf opens a vest of its own
+    accounts = await setupVestingConfig(
+      stakeConnection,
+      whMintAccount.publicKey,
+      whMintAuthority,
+      seed,
+      [
+        {
+          vester: delegatedVester.publicKey,
+          maturation: MATURATION.addn(1000),
+          amount: VEST_AMOUNT,
+        },
+      ],
+    );
+  });
+
+  it("should fail to create a distributor whose claim deadline has passed", async () => {
+    try {
+      await createVestingMerkleDistributor(
+        stakeConnection,
+        accounts,
+        whMintAuthority,
+        new BN(0),
+        firstTree.root,
+        firstLeaves,
+        (await stakeConnection.getTime()).subn(1),
+      );
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "InvalidMerkleDistributor",
+      );
+    }
+  });
+
+  it("should create two distributors for the same config", async () => {
+    const vestedBefore = (
+      await stakeConnection.program.account.vestingConfig.fetch(accounts.config)
+    ).vested;
+    claimDeadline = (await stakeConnection.getTime()).addn(CLAIM_PERIOD);
+
+    await createVestingMerkleDistributor(
+      stakeConnection,
+      accounts,
+      whMintAuthority,
+      new BN(0),
+      firstTree.root,
+      firstLeaves,
+      claimDeadline,
+    );
+    await createVestingMerkleDistributor(
+      stakeConnection,
+      accounts,
+      whMintAuthority,
+      new BN(1),
+      secondTree.root,
+      secondLeaves,
+      claimDeadline,
+    );
+
+    const vestedAfter = (
+      await stakeConnection.program.account.vestingConfig.fetch(accounts.config)
+    ).vested;
+    assert.equal(
+      vestedAfter.sub(vestedBefore).toString(),
+      WHTokenBalance.fromString("280").toBN().toString(),
+    );
+
+    const secondDistributor =
+      await stakeConnection.program.account.vestingMerkleDistributor.fetch(
+        getVestingMerkleDistributorAddress(
+          stakeConnection,
+          accounts.config,
+          new BN(1),
+        ),
+      );
+    assert.equal(secondDistributor.index.toNumber(), 1);
+    assert.equal(secondDistributor.numLeaves, 1);
+    assert.equal(
+      secondDistributor.claimDeadline.toString(),
+      claimDeadline.toString(),
+    );
+
+    await finalizeVestingConfig(stakeConnection, whMintAuthority, accounts);
+    await vesterStakeConnections
+      .get(delegatedVester)
+      .delegateWithVest(
+        delegatee,
+        WHTokenBalance.fromString("0"),
+        true,
+        accounts.config,
+      );
+  });
+
+  it("should fail to claim a leaf with another amount", async () => {
+    try {
+      await claimVestFromMerkle(
+        stakeConnection,
+        accounts,
+        vester,
+        new BN(0),
+        0,
+        { ...firstLeaves[0], amount: VEST_AMOUNT.addn(1) },
+        firstTree.proofs[0],
+      );
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "InvalidMerkleProof",
+      );
+    }
+  });
+
+  it("should claim a leaf into a new vest", async () => {
+    await claimVestFromMerkle(
+      stakeConnection,
+      accounts,
+      vester,
+      new BN(0),
+      0,
+      firstLeaves[0],
+      firstTree.proofs[0],
+    );
+
+    const vest = await stakeConnection.program.account.vesting.fetch(
+      getVestAddress(
+        stakeConnection,
+        accounts.config,
+        vester.publicKey,
+        MATURATION,
+      ),
+    );
+    assert.equal(vest.amount.toString(), VEST_AMOUNT.toString());
+
+    const vestingBalance =
+      await stakeConnection.program.account.vestingBalance.fetch(
+        getVestingBalanceAddress(
+          stakeConnection,
+          accounts.config,
+          vester.publicKey,
+        ),
+      );
+    assert.equal(
+      vestingBalance.totalVestingBalance.toString(),
+      VEST_AMOUNT.toString(),
+    );
+
+    const distributor =
+      await stakeConnection.program.account.vestingMerkleDistributor.fetch(
+        getVestingMerkleDistributorAddress(
+          stakeConnection,
+          accounts.config,
+          new BN(0),
+        ),
+      );
+    assert.equal(distributor.claimedAmount.toString(), VEST_AMOUNT.toString());
+  });
+
+  it("should fail to claim a leaf twice", async () => {
+    try {
+      await claimVestFromMerkle(
+        stakeConnection,
+        accounts,
+        vester,
+        new BN(0),
+        0,
+        firstLeaves[0],
+        firstTree.proofs[0],
+      );
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "VestAlreadyClaimed",
+      );
+    }
+  });
+
+  it("should top up the vest with a leaf of another distributor", async () => {
+    await claimVestFromMerkle(
+      stakeConnection,
+      accounts,
+      vester,
+      new BN(1),
+      0,
+      secondLeaves[0],
+      secondTree.proofs[0],
+    );
+
+    const vest = await stakeConnection.program.account.vesting.fetch(
+      getVestAddress(
+        stakeConnection,
+        accounts.config,
+        vester.publicKey,
+        MATURATION,
+      ),
+    );
+    assert.equal(
+      vest.amount.toString(),
+      WHTokenBalance.fromString("130").toBN().toString(),
+    );
+  });
+
+  it("should add the votes of a leaf claimed into a delegated vesting balance", async () => {
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, delegatee),
+      VEST_AMOUNT.toString(),
+    );
+
+    await claimVestFromMerkle(
+      stakeConnection,
+      accounts,
+      delegatedVester,
+      new BN(0),
+      1,
+      firstLeaves[1],
+      firstTree.proofs[1],
+    );
+
+    const stakeAccountMetadata =
+      await stakeConnection.fetchStakeAccountMetadata(
+        delegatedVester.publicKey,
+      );
+    assert.equal(
+      stakeAccountMetadata.recordedVestingBalance.toString(),
+      WHTokenBalance.fromString("200").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, delegatee),
+      WHTokenBalance.fromString("200").toBN().toString(),
+    );
+  });
+
+  it("should fail to close a distributor before its claim deadline", async () => {
+    try {
+      await closeVestingMerkleDistributor(
+        stakeConnection,
+        accounts,
+        whMintAuthority,
+        new BN(0),
+      );
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "MerkleClaimDeadlineNotReached",
+      );
+    }
+  });
+
+  it("should fail to claim a leaf after the claim deadline", async () => {
+    while ((await stakeConnection.getTime()).lt(claimDeadline)) {
+      await sleep(1000);
+    }
+
+    try {
+      await claimVestFromMerkle(
+        stakeConnection,
+        accounts,
+        unclaimedVester,
+        new BN(0),
+        2,
+        firstLeaves[2],
+        firstTree.proofs[2],
+      );
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "MerkleClaimDeadlinePassed",
+      );
+    }
+  });
+
+  it("should close a distributor and release its unclaimed leaves as surplus", async () => {
+    const vestedBefore = (
+      await stakeConnection.program.account.vestingConfig.fetch(accounts.config)
+    ).vested;
+
+    await closeVestingMerkleDistributor(
+      stakeConnection,
+      accounts,
+      whMintAuthority,
+      new BN(0),
+    );
+
+    const vestedAfter = (
+      await stakeConnection.program.account.vestingConfig.fetch(accounts.config)
+    ).vested;
+    assert.equal(
+      vestedBefore.sub(vestedAfter).toString(),
+      UNCLAIMED_AMOUNT.toString(),
+    );
+
+    const distributor = getVestingMerkleDistributorAddress(
+      stakeConnection,
+      accounts.config,
+      new BN(0),
+    );
+    assert.equal(
+      await stakeConnection.provider.connection.getAccountInfo(distributor),
+      null,
+    );
+    assert.equal(
+      await stakeConnection.provider.connection.getAccountInfo(
+        getVestingMerkleClaimBitmapAddress(stakeConnection, distributor),
+      ),
+      null,
+    );
+
+    const recoveryBalanceBefore = (
+      await getAccount(stakeConnection.provider.connection, accounts.recovery)
+    ).amount;
+    await stakeConnection.program.methods
+      .withdrawSurplus()
+      .accounts({ ...accounts })
+      .signers([whMintAuthority])
+      .rpc();
+    const recoveryBalanceAfter = (
+      await getAccount(stakeConnection.provider.connection, accounts.recovery)
+    ).amount;
+    assert.equal(
+      (recoveryBalanceAfter - recoveryBalanceBefore).toString(),
+      UNCLAIMED_AMOUNT.toString(),
+    );
+  });
+});
+
+async function createVestingMerkleDistributor(
+  stakeConnection: StakeConnection,
+  accounts,
+  vestingAdmin: Keypair,
+  index: BN,
+  root: number[],
+  leaves: { amount: BN }[],
+  claimDeadline: BN,
+) {
+  const distributor = getVestingMerkleDistributorAddress(
+    stakeConnection,
+    accounts.config,
+    index,
+  );
+
+  await stakeConnection.program.methods
+    .createVestingMerkleDistributor(
+      index,
+      root,
+      leaves.reduce((sum, leaf) => sum.add(leaf.amount), new BN(0)),
+      leaves.length,
+      claimDeadline,
+    )
+    .accounts({
+      ...accounts,
+      distributor,
+      claimBitmap: getVestingMerkleClaimBitmapAddress(
+        stakeConnection,
+        distributor,
+      ),
+    })
+    .signers([vestingAdmin])
+    .rpc();
+}
+
+/** Claims the leaf of the vester, passing the accounts that carry the votes
+ *  of the vests of the vester.
+ */
+async function claimVestFromMerkle(
+  stakeConnection: StakeConnection,
+  accounts,
+  vester: Keypair,
+  distributorIndex: BN,
+  leafIndex: number,
+  leaf: { amount: BN; maturation: BN },
+  proof: number[][],
+) {
+  const distributor = getVestingMerkleDistributorAddress(
+    stakeConnection,
+    accounts.config,
+    distributorIndex,
+  );
+  const { accounts: votesAccounts, remainingAccounts } =
+    await getVestingVotesAccounts(
+      stakeConnection,
+      accounts.config,
+      vester.publicKey,
+    );
+
+  await stakeConnection.program.methods
+    .claimVestFromMerkle(leafIndex, leaf.amount, leaf.maturation, proof)
+    .accounts({
+      ...accounts,
+      ...votesAccounts,
+      vester: vester.publicKey,
+      distributor,
+      claimBitmap: getVestingMerkleClaimBitmapAddress(
+        stakeConnection,
+        distributor,
+      ),
+      vest: getVestAddress(
+        stakeConnection,
+        accounts.config,
+        vester.publicKey,
+        leaf.maturation,
+      ),
+      vestingBalance: getVestingBalanceAddress(
+        stakeConnection,
+        accounts.config,
+        vester.publicKey,
+      ),
+    })
+    .remainingAccounts(remainingAccounts)
+    .signers([vester])
+    .rpc();
+}
+
+async function closeVestingMerkleDistributor(
+  stakeConnection: StakeConnection,
+  accounts,
+  vestingAdmin: Keypair,
+  index: BN,
+) {
+  const distributor = getVestingMerkleDistributorAddress(
+    stakeConnection,
+    accounts.config,
+    index,
+  );
+
+  await stakeConnection.program.methods
+    .closeVestingMerkleDistributor()
+    .accounts({
+      ...accounts,
+      distributor,
+      claimBitmap: getVestingMerkleClaimBitmapAddress(
+        stakeConnection,
+        distributor,
+      ),
+    })
+    .signers([vestingAdmin])
+    .rpc();
+}
@@ -0,0 +1,434 @@
+import BN from "bn.js";
+import { randomBytes } from "crypto";
+import { Keypair } from "@solana/web3.js";
+import { getAccount, getAssociatedTokenAddressSync } from "@solana/spl-token";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+} from "./utils/before";
+import {
+  getVestAddress,
+  getVestingBalanceAddress,
+  getVestingVotesAccounts,
+  lastCheckpointValue,
+  setupFinalizedVestingConfig,
+} from "./utils/vesting_utils";
+import path from "path";
+import { AnchorError } from "@coral-xyz/anchor";
+import { StakeConnection, WHTokenBalance } from "../app";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+describe("claim_vesting_partial", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  const MATURATION = new BN(Math.floor(Date.now() / 1000));
+  const VEST_AMOUNT = WHTokenBalance.fromString("100").toBN();
+
+  // undelegatedVester does not link its vests to a stake account,
+  // selfDelegatedVester keeps the votes, delegatedVester delegates them to
+  // delegatee and splitVester splits them between splitDelegate1 and
+  // splitDelegate2
+  const undelegatedVester = Keypair.generate();
+  const selfDelegatedVester = Keypair.generate();
+  const delegatedVester = Keypair.generate();
+  const splitVester = Keypair.generate();
+  const seed = new BN(randomBytes(8));
+
+  let stakeConnection: StakeConnection;
+  let delegateeStakeConnection: StakeConnection;
+  let splitDelegate1StakeConnection: StakeConnection;
+  let splitDelegate2StakeConnection: StakeConnection;
+  let vesterStakeConnections: Map<Keypair, StakeConnection>;
+
+  let controller;
+  let accounts;
+  let delegatee;
+  let splitDelegate1;
+  let splitDelegate2;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey, whMintAuthority.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    vesterStakeConnections = new Map();
+    for (const vester of [
+      undelegatedVester,
+      selfDelegatedVester,
+      delegatedVester,
+      splitVester,
+    ]) {
+      vesterStakeConnections.set(
+        vester,
+        await newUserStakeConnection(
+          stakeConnection,
+          vester,
+          config,
+          whMintAccount,
+          whMintAuthority,
+          WHTokenBalance.fromString("1000"),
+        ),
+      );
+    }
+
+    delegateeStakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    delegatee = delegateeStakeConnection.provider.wallet.publicKey;
+
+    splitDelegate1StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    splitDelegate1 = splitDelegate1StakeConnection.provider.wallet.publicKey;
+
+    splitDelegate2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    splitDelegate2 = splitDelegate2StakeConnection.provider.wallet.publicKey;
+
+    await delegateeStakeConnection.createStakeAccount();
+    await splitDelegate1StakeConnection.createStakeAccount();
+    await splitDelegate2StakeConnection.createStakeAccount();
+
+    accounts = await setupFinalizedVestingConfig(
+      stakeConnection,
+      whMintAccount.publicKey,
+      whMintAuthority,
+      seed,
+      [...vesterStakeConnections.keys()].map((vester) => ({
+        vester: vester.publicKey,
+        maturation: MATURATION,
+        amount: VEST_AMOUNT,
+      })),
+    );
+
+    await vesterStakeConnections
+      .get(selfDelegatedVester)
+      .delegateWithVest(
+        selfDelegatedVester.publicKey,
+        WHTokenBalance.fromString("0"),
+        true,
+        accounts.config,
+      );
+    await vesterStakeConnections
+      .get(delegatedVester)
+      .delegateWithVest(
+        delegatee,
+        WHTokenBalance.fromString("0"),
+        true,
+        accounts.config,
+      );
+    await vesterStakeConnections
+      .get(splitVester)
+      .delegateWithVest(
+        splitVester.publicKey,
+        WHTokenBalance.fromString("0"),
+        true,
+        accounts.config,
+      );
+    await vesterStakeConnections.get(splitVester).setDelegationSplit([
+      { delegate: splitDelegate1, basisPoints: 5_000 },
+      { delegate: splitDelegate2, basisPoints: 5_000 },
+    ]);
+  });
+
+  it("should fail to claim nothing or more than the vest", async () => {
+    for (const amount of [new BN(0), VEST_AMOUNT.addn(1)]) {
+      try {
+        await claimVestingPartial(
+          stakeConnection,
+          accounts,
+          undelegatedVester,
+          MATURATION,
+          amount,
+        );
+
+        assert.fail("Expected error was not thrown");
+      } catch (e) {
+        assert(
+          (e as AnchorError).error?.errorCode?.code === "InvalidClaimAmount",
+        );
+      }
+    }
+  });
+
+  it("should claim part of a vest and leave the rest vested", async () => {
+    const vesterTa = getAssociatedTokenAddressSync(
+      whMintAccount.publicKey,
+      undelegatedVester.publicKey,
+    );
+    const vesterTaBalanceBefore = (
+      await getAccount(stakeConnection.provider.connection, vesterTa)
+    ).amount;
+    const vestedBefore = (
+      await stakeConnection.program.account.vestingConfig.fetch(accounts.config)
+    ).vested;
+
+    await claimVestingPartial(
+      stakeConnection,
+      accounts,
+      undelegatedVester,
+      MATURATION,
+      WHTokenBalance.fromString("40").toBN(),
+    );
+
+    const vest = await stakeConnection.program.account.vesting.fetch(
+      getVestAddress(
+        stakeConnection,
+        accounts.config,
+        undelegatedVester.publicKey,
+        MATURATION,
+      ),
+    );
+    assert.equal(
+      vest.amount.toString(),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+
+    const vestingBalance =
+      await stakeConnection.program.account.vestingBalance.fetch(
+        getVestingBalanceAddress(
+          stakeConnection,
+          accounts.config,
+          undelegatedVester.publicKey,
+        ),
+      );
+    assert.equal(
+      vestingBalance.totalVestingBalance.toString(),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+
+    const vestedAfter = (
+      await stakeConnection.program.account.vestingConfig.fetch(accounts.config)
+    ).vested;
+    assert.equal(
+      vestedBefore.sub(vestedAfter).toString(),
+      WHTokenBalance.fromString("40").toBN().toString(),
+    );
+
+    const vesterTaBalanceAfter = (
+      await getAccount(stakeConnection.provider.connection, vesterTa)
+    ).amount;
+    assert.equal(
+      (vesterTaBalanceAfter - vesterTaBalanceBefore).toString(),
+      WHTokenBalance.fromString("40").toBN().toString(),
+    );
+  });
+
+  it("should close the vest once the rest is claimed", async () => {
+    await claimVestingPartial(
+      stakeConnection,
+      accounts,
+      undelegatedVester,
+      MATURATION,
+      WHTokenBalance.fromString("60").toBN(),
+    );
+
+    assert.equal(
+      await stakeConnection.provider.connection.getAccountInfo(
+        getVestAddress(
+          stakeConnection,
+          accounts.config,
+          undelegatedVester.publicKey,
+          MATURATION,
+        ),
+      ),
+      null,
+    );
+
+    const vestingBalance =
+      await stakeConnection.program.account.vestingBalance.fetch(
+        getVestingBalanceAddress(
+          stakeConnection,
+          accounts.config,
+          undelegatedVester.publicKey,
+        ),
+      );
+    assert.equal(vestingBalance.totalVestingBalance.toString(), "0");
+  });
+
+  it("should subtract the claimed votes from a self-delegated stake account", async () => {
+    await claimVestingPartial(
+      stakeConnection,
+      accounts,
+      selfDelegatedVester,
+      MATURATION,
+      WHTokenBalance.fromString("40").toBN(),
+    );
+
+    const stakeAccountMetadata =
+      await stakeConnection.fetchStakeAccountMetadata(
+        selfDelegatedVester.publicKey,
+      );
+    assert.equal(
+      stakeAccountMetadata.recordedVestingBalance.toString(),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(
+        stakeConnection,
+        selfDelegatedVester.publicKey,
+      ),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+  });
+
+  it("should subtract the claimed votes from the delegate", async () => {
+    await claimVestingPartial(
+      stakeConnection,
+      accounts,
+      delegatedVester,
+      MATURATION,
+      WHTokenBalance.fromString("40").toBN(),
+    );
+
+    const stakeAccountMetadata =
+      await stakeConnection.fetchStakeAccountMetadata(
+        delegatedVester.publicKey,
+      );
+    assert.equal(
+      stakeAccountMetadata.recordedVestingBalance.toString(),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, delegatee),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+  });
+
+  it("should subtract the claimed votes from the split delegates", async () => {
+    await claimVestingPartial(
+      stakeConnection,
+      accounts,
+      splitVester,
+      MATURATION,
+      WHTokenBalance.fromString("40").toBN(),
+    );
+
+    assert.equal(
+      (
+        await stakeConnection.fetchStakeAccountMetadata(splitVester.publicKey)
+      ).recordedVestingBalance.toString(),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, splitDelegate1),
+      WHTokenBalance.fromString("30").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, splitDelegate2),
+      WHTokenBalance.fromString("30").toBN().toString(),
+    );
+  });
+
+  it("should close a split delegated vest and take all of its votes once the rest is claimed", async () => {
+    await claimVestingPartial(
+      stakeConnection,
+      accounts,
+      splitVester,
+      MATURATION,
+      WHTokenBalance.fromString("60").toBN(),
+    );
+
+    assert.equal(
+      await stakeConnection.provider.connection.getAccountInfo(
+        getVestAddress(
+          stakeConnection,
+          accounts.config,
+          splitVester.publicKey,
+          MATURATION,
+        ),
+      ),
+      null,
+    );
+    assert.equal(
+      (
+        await stakeConnection.fetchStakeAccountMetadata(splitVester.publicKey)
+      ).recordedVestingBalance.toString(),
+      "0",
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, splitDelegate1),
+      "0",
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, splitDelegate2),
+      "0",
+    );
+  });
+});
+
+/** Claims part of the vest of the vester, passing the accounts that carry
+ *  the votes of the vests.
+ */
+async function claimVestingPartial(
+  stakeConnection: StakeConnection,
+  accounts,
+  vester: Keypair,
+  maturation: BN,
+  amount: BN,
+) {
+  const { accounts: votesAccounts, remainingAccounts } =
+    await getVestingVotesAccounts(
+      stakeConnection,
+      accounts.config,
+      vester.publicKey,
+    );
+
+  await stakeConnection.program.methods
+    .claimVestingPartial(amount)
+    .accounts({
+      ...accounts,
+      ...votesAccounts,
+      vester: vester.publicKey,
+      vesterTa: getAssociatedTokenAddressSync(accounts.mint, vester.publicKey),
+      vest: getVestAddress(
+        stakeConnection,
+        accounts.config,
+        vester.publicKey,
+        maturation,
+      ),
+      vestingBalance: getVestingBalanceAddress(
+        stakeConnection,
+        accounts.config,
+        vester.publicKey,
+      ),
+    })
+    .remainingAccounts(remainingAccounts)
+    .signers([vester])
+    .rpc();
+}
@@ -0,0 +1,295 @@
+import BN from "bn.js";
+import { randomBytes } from "crypto";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { getAccount } from "@solana/spl-token";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+} from "./utils/before";
+import {
+  getVestAddress,
+  getVestingBalanceAddress,
+  lastCheckpointValue,
+  setupFinalizedVestingConfig,
+} from "./utils/vesting_utils";
+import path from "path";
+import { AnchorError, utils } from "@coral-xyz/anchor";
+import { StakeConnection, WHTokenBalance } from "../app";
+import * as wasm from "@wormhole/staking-wasm";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+describe("claim_vesting_to_stake", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  const MATURATION = new BN(Math.floor(Date.now() / 1000));
+  const VEST_AMOUNT = WHTokenBalance.fromString("100").toBN();
+
+  // undelegatedVester has a stake account its vests are not linked to,
+  // selfDelegatedVester keeps the votes and delegatedVester delegates them
+  // to delegatee
+  const undelegatedVester = Keypair.generate();
+  const selfDelegatedVester = Keypair.generate();
+  const delegatedVester = Keypair.generate();
+  const seed = new BN(randomBytes(8));
+
+  let stakeConnection: StakeConnection;
+  let delegateeStakeConnection: StakeConnection;
+  let vesterStakeConnections: Map<Keypair, StakeConnection>;
+
+  let controller;
+  let accounts;
+  let delegatee;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey, whMintAuthority.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    vesterStakeConnections = new Map();
+    for (const vester of [
+      undelegatedVester,
+      selfDelegatedVester,
+      delegatedVester,
+    ]) {
+      vesterStakeConnections.set(
+        vester,
+        await newUserStakeConnection(
+          stakeConnection,
+          vester,
+          config,
+          whMintAccount,
+          whMintAuthority,
+          WHTokenBalance.fromString("1000"),
+        ),
+      );
+    }
+
+    delegateeStakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    delegatee = delegateeStakeConnection.provider.wallet.publicKey;
+
+    await delegateeStakeConnection.createStakeAccount();
+    await vesterStakeConnections.get(undelegatedVester).createStakeAccount();
+
+    accounts = await setupFinalizedVestingConfig(
+      stakeConnection,
+      whMintAccount.publicKey,
+      whMintAuthority,
+      seed,
+      [...vesterStakeConnections.keys()].map((vester) => ({
+        vester: vester.publicKey,
+        maturation: MATURATION,
+        amount: VEST_AMOUNT,
+      })),
+    );
+
+    await vesterStakeConnections
+      .get(selfDelegatedVester)
+      .delegateWithVest(
+        selfDelegatedVester.publicKey,
+        WHTokenBalance.fromString("0"),
+        true,
+        accounts.config,
+      );
+    await vesterStakeConnections
+      .get(delegatedVester)
+      .delegateWithVest(
+        delegatee,
+        WHTokenBalance.fromString("0"),
+        true,
+        accounts.config,
+      );
+  });
+
+  it("should fail to claim to stake a vest that is not linked to a stake account", async () => {
+    try {
+      await claimVestingToStake(
+        stakeConnection,
+        accounts,
+        undelegatedVester,
+        MATURATION,
+      );
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "NoStakeAccountMetadata",
+      );
+    }
+  });
+
+  it("should claim a self-delegated vest to stake and keep the votes", async () => {
+    const custodyBalanceBefore = await custodyBalance(
+      stakeConnection,
+      selfDelegatedVester.publicKey,
+    );
+
+    await claimVestingToStake(
+      stakeConnection,
+      accounts,
+      selfDelegatedVester,
+      MATURATION,
+    );
+
+    const stakeAccountMetadata =
+      await stakeConnection.fetchStakeAccountMetadata(
+        selfDelegatedVester.publicKey,
+      );
+    assert.equal(stakeAccountMetadata.recordedVestingBalance.toString(), "0");
+    assert.equal(
+      stakeAccountMetadata.recordedBalance.toString(),
+      VEST_AMOUNT.toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(
+        stakeConnection,
+        selfDelegatedVester.publicKey,
+      ),
+      VEST_AMOUNT.toString(),
+    );
+    assert.equal(
+      (
+        (await custodyBalance(stakeConnection, selfDelegatedVester.publicKey)) -
+        custodyBalanceBefore
+      ).toString(),
+      VEST_AMOUNT.toString(),
+    );
+
+    assert.equal(
+      await stakeConnection.provider.connection.getAccountInfo(
+        getVestAddress(
+          stakeConnection,
+          accounts.config,
+          selfDelegatedVester.publicKey,
+          MATURATION,
+        ),
+      ),
+      null,
+    );
+    const vestingBalance =
+      await stakeConnection.program.account.vestingBalance.fetch(
+        getVestingBalanceAddress(
+          stakeConnection,
+          accounts.config,
+          selfDelegatedVester.publicKey,
+        ),
+      );
+    assert.equal(vestingBalance.totalVestingBalance.toString(), "0");
+  });
+
+  it("should claim a delegated vest to stake and keep the votes of the delegate", async () => {
+    const vestedBefore = (
+      await stakeConnection.program.account.vestingConfig.fetch(accounts.config)
+    ).vested;
+
+    await claimVestingToStake(
+      stakeConnection,
+      accounts,
+      delegatedVester,
+      MATURATION,
+    );
+
+    const stakeAccountMetadata =
+      await stakeConnection.fetchStakeAccountMetadata(
+        delegatedVester.publicKey,
+      );
+    assert.equal(stakeAccountMetadata.recordedVestingBalance.toString(), "0");
+    assert.equal(
+      stakeAccountMetadata.recordedBalance.toString(),
+      VEST_AMOUNT.toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, delegatee),
+      VEST_AMOUNT.toString(),
+    );
+
+    const vestedAfter = (
+      await stakeConnection.program.account.vestingConfig.fetch(accounts.config)
+    ).vested;
+    assert.equal(
+      vestedBefore.sub(vestedAfter).toString(),
+      VEST_AMOUNT.toString(),
+    );
+  });
+});
+
+function getCustodyAddress(
+  stakeConnection: StakeConnection,
+  owner: PublicKey,
+): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [utils.bytes.utf8.encode(wasm.Constants.CUSTODY_SEED()), owner.toBuffer()],
+    stakeConnection.program.programId,
+  )[0];
+}
+
+async function custodyBalance(
+  stakeConnection: StakeConnection,
+  owner: PublicKey,
+): Promise<bigint> {
+  return (
+    await getAccount(
+      stakeConnection.provider.connection,
+      getCustodyAddress(stakeConnection, owner),
+    )
+  ).amount;
+}
+
+/** Claims the vest of the vester into the custody of the stake account of
+ *  the vester.
+ */
+async function claimVestingToStake(
+  stakeConnection: StakeConnection,
+  accounts,
+  vester: Keypair,
+  maturation: BN,
+) {
+  await stakeConnection.program.methods
+    .claimVestingToStake()
+    .accounts({
+      ...accounts,
+      vester: vester.publicKey,
+      vest: getVestAddress(
+        stakeConnection,
+        accounts.config,
+        vester.publicKey,
+        maturation,
+      ),
+      vestingBalance: getVestingBalanceAddress(
+        stakeConnection,
+        accounts.config,
+        vester.publicKey,
+      ),
+      stakeAccountMetadata: await stakeConnection.getStakeMetadataAddress(
+        vester.publicKey,
+      ),
+      stakeAccountCustody: getCustodyAddress(stakeConnection, vester.publicKey),
+    })
+    .signers([vester])
+    .rpc();
+}
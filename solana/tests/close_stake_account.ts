@@ -0,0 +1,156 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+  sleep,
+} from "./utils/before";
+import path from "path";
+import { StakeConnection, WHTokenBalance } from "../app";
+import { AnchorError, utils } from "@coral-xyz/anchor";
+import * as wasm from "@wormhole/staking-wasm";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+describe("close_stake_account", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  let stakeConnection: StakeConnection;
+  let user2StakeConnection: StakeConnection;
+  let user3StakeConnection: StakeConnection;
+
+  let controller;
+  let user2;
+  let user3;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    user2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user2 = user2StakeConnection.provider.wallet.publicKey;
+
+    user3StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user3 = user3StakeConnection.provider.wallet.publicKey;
+
+    await user2StakeConnection.delegate(
+      user2,
+      WHTokenBalance.fromString("100"),
+    );
+    const stakeAccount = await user2StakeConnection.loadStakeAccount(
+      await user2StakeConnection.getStakeAccountCheckpointsAddressByMetadata(
+        await user2StakeConnection.getStakeMetadataAddress(user2),
+        false,
+      ),
+    );
+    await user2StakeConnection.withdrawTokens(
+      stakeAccount,
+      WHTokenBalance.fromString("100"),
+    );
+  });
+
+  it("should count a stake account delegating to another one without votes", async () => {
+    await user3StakeConnection.delegate(user2, WHTokenBalance.fromString("0"));
+
+    const user2StakeAccountMetadata =
+      await stakeConnection.fetchStakeAccountMetadata(user2);
+    assert.equal(user2StakeAccountMetadata.delegatorCount, 1);
+  });
+
+  it("should fail to close a stake account other stake accounts delegate to", async () => {
+    try {
+      await user2StakeConnection.closeStakeAccount();
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "StakeAccountHasDelegators",
+      );
+    }
+  });
+
+  it("should fail to close a stake account delegating to another one", async () => {
+    try {
+      await user3StakeConnection.closeStakeAccount();
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "StakeAccountDelegated",
+      );
+    }
+  });
+
+  it("should close the stake account and its pending stake owner", async () => {
+    await user3StakeConnection.delegate(user3, WHTokenBalance.fromString("0"));
+    assert.equal(
+      (await stakeConnection.fetchStakeAccountMetadata(user2)).delegatorCount,
+      0,
+    );
+
+    await user2StakeConnection.proposeStakeOwner(Keypair.generate().publicKey);
+    const pendingStakeOwnerAddress = PublicKey.findProgramAddressSync(
+      [
+        utils.bytes.utf8.encode(wasm.Constants.PENDING_STAKE_OWNER_SEED()),
+        user2.toBuffer(),
+      ],
+      stakeConnection.program.programId,
+    )[0];
+    assert.notEqual(
+      await stakeConnection.provider.connection.getAccountInfo(
+        pendingStakeOwnerAddress,
+      ),
+      null,
+    );
+
+    // The latest checkpoint has to be older than the vote weight window of 10s
+    await sleep(12000);
+    await user2StakeConnection.closeStakeAccount();
+
+    assert.equal(
+      await stakeConnection.provider.connection.getAccountInfo(
+        await stakeConnection.getStakeMetadataAddress(user2),
+      ),
+      null,
+    );
+    assert.equal(
+      await stakeConnection.provider.connection.getAccountInfo(
+        pendingStakeOwnerAddress,
+      ),
+      null,
+    );
+  });
+});
@@ -0,0 +1,261 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+  sleep,
+} from "./utils/before";
+import path from "path";
+import crypto from "crypto";
+import { createProposalQueryResponseBytes } from "./utils/api_utils";
+import { StakeConnection, WHTokenBalance } from "../app";
+import { TEST_CHECKPOINTS_ACCOUNT_LIMIT } from "./utils/constants";
+import { CheckpointAccount } from "../app/checkpoints";
+import { QueryProxyMock } from "@wormhole-foundation/wormhole-query-sdk";
+import { AnchorError, utils } from "@coral-xyz/anchor";
+import * as wasm from "@wormhole/staking-wasm";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+// vote weight window length is 10 seconds in the test setup
+const VOTE_WEIGHT_WINDOW_LENGTH = 10;
+const VOTING_PERIOD = 20;
+
+describe("compact_checkpoints", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  let stakeConnection: StakeConnection;
+  let user2StakeConnection: StakeConnection;
+  let user3StakeConnection: StakeConnection;
+
+  let controller;
+  let user2;
+  let user3;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    user2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user2 = user2StakeConnection.provider.wallet.publicKey;
+
+    user3StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user3 = user3StakeConnection.provider.wallet.publicKey;
+
+    // filling the first checkpoint accounts to the limit to retire them
+    for (let i = 0; i < TEST_CHECKPOINTS_ACCOUNT_LIMIT; i++) {
+      await sleep(1000);
+      await user2StakeConnection.delegate(
+        user2,
+        WHTokenBalance.fromString("5"),
+      );
+      await user3StakeConnection.delegate(
+        user3,
+        WHTokenBalance.fromString("5"),
+      );
+    }
+  });
+
+  it("should compact checkpoints after the vote weight window while no proposal bounds the voting period", async () => {
+    assert.equal((await maxVotingPeriod(stakeConnection)).toString(), "0");
+
+    await waitUntilExpired(
+      user2StakeConnection,
+      user2,
+      VOTE_WEIGHT_WINDOW_LENGTH,
+    );
+    await user2StakeConnection.compactCheckpoints(0);
+
+    assert.equal(
+      await user2StakeConnection.provider.connection.getAccountInfo(
+        retiredCheckpointsAddress(user2StakeConnection, user2),
+      ),
+      null,
+    );
+    assert.equal(
+      (await user2StakeConnection.fetchStakeAccountMetadata(user2))
+        .stakeAccountCheckpointsLastIndex,
+      1,
+    );
+  });
+
+  it("should raise the max voting period with the voting period of the added proposals", async () => {
+    const voteStart = Math.floor(Date.now() / 1000);
+    await addTestProposal(
+      stakeConnection,
+      voteStart,
+      voteStart + VOTING_PERIOD,
+    );
+    assert.equal(
+      (await maxVotingPeriod(stakeConnection)).toString(),
+      VOTING_PERIOD.toString(),
+    );
+
+    // A shorter voting period does not lower it
+    await addTestProposal(stakeConnection, voteStart, voteStart + 5);
+    assert.equal(
+      (await maxVotingPeriod(stakeConnection)).toString(),
+      VOTING_PERIOD.toString(),
+    );
+
+    // A proposal whose vote end is unknown does not bound it
+    await addTestProposal(stakeConnection, voteStart, null);
+    assert.equal(
+      (await maxVotingPeriod(stakeConnection)).toString(),
+      VOTING_PERIOD.toString(),
+    );
+  });
+
+  it("should fail to compact checkpoints within the voting period of the stored proposals", async () => {
+    // The latest checkpoint is older than the vote weight window, but not than
+    // the vote weight window plus the voting period of the added proposal
+    await waitUntilExpired(
+      user3StakeConnection,
+      user3,
+      VOTE_WEIGHT_WINDOW_LENGTH,
+    );
+    try {
+      await user3StakeConnection.program.methods
+        .compactCheckpoints(0)
+        .accounts({ owner: user3 })
+        .rpc();
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "CheckpointsWithinVoteWeightWindow",
+      );
+    }
+  });
+
+  it("should compact checkpoints after the vote weight window and the voting period of the stored proposals", async () => {
+    await waitUntilExpired(
+      user3StakeConnection,
+      user3,
+      VOTE_WEIGHT_WINDOW_LENGTH + VOTING_PERIOD,
+    );
+    await user3StakeConnection.compactCheckpoints(0);
+
+    assert.equal(
+      await user3StakeConnection.provider.connection.getAccountInfo(
+        retiredCheckpointsAddress(user3StakeConnection, user3),
+      ),
+      null,
+    );
+    assert.equal(
+      (await user3StakeConnection.fetchStakeAccountMetadata(user3))
+        .stakeAccountCheckpointsLastIndex,
+      1,
+    );
+  });
+});
+
+async function maxVotingPeriod(stakeConnection: StakeConnection) {
+  const config = await stakeConnection.program.account.globalConfig.fetch(
+    stakeConnection.configAddress,
+  );
+  return config.maxVotingPeriod;
+}
+
+function retiredCheckpointsAddress(
+  stakeConnection: StakeConnection,
+  owner: PublicKey,
+): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [
+      utils.bytes.utf8.encode(wasm.Constants.CHECKPOINT_DATA_SEED()),
+      owner.toBuffer(),
+      Buffer.from([0, 0]),
+    ],
+    stakeConnection.program.programId,
+  )[0];
+}
+
+/** Waits until the latest checkpoint of the first CheckpointData account of
+ *  the owner is older than the given number of seconds.
+ */
+async function waitUntilExpired(
+  stakeConnection: StakeConnection,
+  owner: PublicKey,
+  seconds: number,
+) {
+  const retiredCheckpoints: CheckpointAccount =
+    await stakeConnection.fetchCheckpointAccount(
+      retiredCheckpointsAddress(stakeConnection, owner),
+    );
+  const latestCheckpointTimestamp = Number(
+    retiredCheckpoints.getLastCheckpoint().timestamp,
+  );
+
+  while (
+    latestCheckpointTimestamp + seconds + 1 >=
+    Math.floor(Date.now() / 1000)
+  ) {
+    await sleep(1000);
+  }
+  await sleep(1000);
+}
+
+async function addTestProposal(
+  stakeConnection: StakeConnection,
+  voteStart: number,
+  voteEnd: number | null,
+) {
+  const proposalIdInput = crypto
+    .createHash("sha256")
+    .update("proposalId" + Date.now())
+    .digest();
+
+  const ethProposalResponseBytes = createProposalQueryResponseBytes(
+    proposalIdInput,
+    voteStart,
+    voteEnd,
+  );
+  const mock = new QueryProxyMock({});
+  const mockSignatures = mock.sign(ethProposalResponseBytes);
+  const guardianSignaturesPda =
+    await stakeConnection.postSignatures(mockSignatures);
+  const mockGuardianSetIndex = 5;
+
+  await stakeConnection.addProposal(
+    proposalIdInput,
+    ethProposalResponseBytes,
+    guardianSignaturesPda,
+    mockGuardianSetIndex,
+  );
+
+  return proposalIdInput;
+}
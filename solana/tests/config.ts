@@ -98,6 +98,26 @@ describe("config", async () => {
       .rpc();
   });
 
+  it("fails to initialize config with a checkpoint account limit below 2", async () => {
+    try {
+      await program.methods
+        .initConfig({
+          governanceAuthority: program.provider.wallet.publicKey,
+          votingTokenMint: whMintAccount.publicKey,
+          vestingAdmin: vestingAdmin,
+          maxCheckpointsAccountLimit: 1,
+        })
+        .rpc();
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "InvalidCheckpointAccountLimit",
+      );
+    }
+  });
+
   it("initializes config", async () => {
     [configAccount, bump] = PublicKey.findProgramAddressSync(
       [utils.bytes.utf8.encode(wasm.Constants.CONFIG_SEED())],
@@ -136,6 +156,11 @@ describe("config", async () => {
         vestingAdmin: vestingAdmin,
         pendingVestingAdmin: null,
         pendingGovernanceAuthority: null,
+        withdrawalCooldown: new BN(0),
+        delegationPolicy: { singleHop: {} },
+        voteRevisionCutoff: new BN(0),
+        maxVotingPeriod: new BN(0),
+        delegatingStakeAccounts: new BN(0),
       }),
     );
   });
@@ -396,6 +421,11 @@ describe("config", async () => {
         vestingAdmin: vestingAdmin,
         pendingVestingAdmin: null,
         pendingGovernanceAuthority: null,
+        withdrawalCooldown: new BN(0),
+        delegationPolicy: { singleHop: {} },
+        voteRevisionCutoff: new BN(0),
+        maxVotingPeriod: new BN(0),
+        delegatingStakeAccounts: new BN(0),
       }),
     );
 
@@ -448,6 +478,17 @@ describe("config", async () => {
     );
   });
 
+  it("does not migrate a config that has the current layout", async () => {
+    await expectFail(
+      program.methods.migrateGlobalConfig().accounts({
+        governanceSigner: program.provider.wallet.publicKey,
+        config: configAccount,
+      }),
+      "Account already migrated",
+      errMap,
+    );
+  });
+
   it("updates vesting admin", async () => {
     // governance authority can't update vesting admin
     await expectFail(
@@ -489,6 +530,11 @@ describe("config", async () => {
         vestingAdmin: vestingAdmin,
         pendingVestingAdmin: program.provider.wallet.publicKey,
         pendingGovernanceAuthority: null,
+        withdrawalCooldown: new BN(0),
+        delegationPolicy: { singleHop: {} },
+        voteRevisionCutoff: new BN(0),
+        maxVotingPeriod: new BN(0),
+        delegatingStakeAccounts: new BN(0),
       }),
     );
 
@@ -509,6 +555,11 @@ describe("config", async () => {
         vestingAdmin: program.provider.wallet.publicKey,
         pendingVestingAdmin: null,
         pendingGovernanceAuthority: null,
+        withdrawalCooldown: new BN(0),
+        delegationPolicy: { singleHop: {} },
+        voteRevisionCutoff: new BN(0),
+        maxVotingPeriod: new BN(0),
+        delegatingStakeAccounts: new BN(0),
       }),
     );
 
@@ -529,6 +580,11 @@ describe("config", async () => {
         vestingAdmin: program.provider.wallet.publicKey,
         pendingVestingAdmin: vestingAdmin,
         pendingGovernanceAuthority: null,
+        withdrawalCooldown: new BN(0),
+        delegationPolicy: { singleHop: {} },
+        voteRevisionCutoff: new BN(0),
+        maxVotingPeriod: new BN(0),
+        delegatingStakeAccounts: new BN(0),
       }),
     );
 
@@ -549,6 +605,11 @@ describe("config", async () => {
         vestingAdmin: vestingAdmin,
         pendingVestingAdmin: null,
         pendingGovernanceAuthority: null,
+        withdrawalCooldown: new BN(0),
+        delegationPolicy: { singleHop: {} },
+        voteRevisionCutoff: new BN(0),
+        maxVotingPeriod: new BN(0),
+        delegatingStakeAccounts: new BN(0),
       }),
     );
   });
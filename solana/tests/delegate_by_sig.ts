@@ -0,0 +1,152 @@
+import { Keypair } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+} from "./utils/before";
+import BN from "bn.js";
+import path from "path";
+import crypto from "crypto";
+import { StakeConnection, WHTokenBalance } from "../app";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+describe("delegate_by_sig", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  let stakeConnection: StakeConnection;
+  let user2StakeConnection: StakeConnection;
+  let user3StakeConnection: StakeConnection;
+
+  let controller;
+  const user2Keypair = Keypair.generate();
+  let user2;
+  let user3;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    user2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      user2Keypair,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user2 = user2StakeConnection.provider.wallet.publicKey;
+
+    user3StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user3 = user3StakeConnection.provider.wallet.publicKey;
+
+    await user2StakeConnection.delegate(
+      user2,
+      WHTokenBalance.fromString("100"),
+    );
+    await user3StakeConnection.delegate(user3, WHTokenBalance.fromString("10"));
+  });
+
+  it("should reject a signature for another program id", async () => {
+    const nonce = await stakeConnection.getDelegationNonce(user2);
+    const expiry = new BN(Math.floor(Date.now() / 1000) + 3600);
+    const signature = sign(
+      user2Keypair,
+      StakeConnection.delegateBySigMessage(
+        Keypair.generate().publicKey,
+        user3,
+        nonce,
+        expiry,
+      ),
+    );
+
+    try {
+      await stakeConnection.delegateBySig(
+        user2,
+        user3,
+        nonce,
+        expiry,
+        signature,
+      );
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert.notEqual(
+        (e as Error).message,
+        "Expected an error but none was thrown",
+      );
+    }
+
+    assert((await stakeConnection.delegates(user2)).equals(user2));
+    assert.equal(
+      (await stakeConnection.getDelegationNonce(user2)).toString(),
+      nonce.toString(),
+    );
+  });
+
+  it("should delegate with a signature of the stake account owner", async () => {
+    const nonce = await stakeConnection.getDelegationNonce(user2);
+    const expiry = new BN(Math.floor(Date.now() / 1000) + 3600);
+    const signature = sign(
+      user2Keypair,
+      StakeConnection.delegateBySigMessage(
+        stakeConnection.program.programId,
+        user3,
+        nonce,
+        expiry,
+      ),
+    );
+
+    // The relayer pays for the delegation of the stake account of user2
+    await stakeConnection.delegateBySig(user2, user3, nonce, expiry, signature);
+
+    assert((await stakeConnection.delegates(user2)).equals(user3));
+    assert.equal(
+      (await stakeConnection.getDelegationNonce(user2)).toString(),
+      nonce.addn(1).toString(),
+    );
+  });
+});
+
+// PKCS#8 DER prefix of an ed25519 private key, followed by its 32-byte seed
+const ED25519_PKCS8_PREFIX = Buffer.from(
+  "302e020100300506032b657004220420",
+  "hex",
+);
+
+function sign(signer: Keypair, message: Buffer): Uint8Array {
+  const privateKey = crypto.createPrivateKey({
+    key: Buffer.concat([
+      ED25519_PKCS8_PREFIX,
+      Buffer.from(signer.secretKey.slice(0, 32)),
+    ]),
+    format: "der",
+    type: "pkcs8",
+  });
+  return crypto.sign(null, message, privateKey);
+}
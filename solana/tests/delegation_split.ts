@@ -0,0 +1,236 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+} from "./utils/before";
+import path from "path";
+import { EventParser } from "@coral-xyz/anchor";
+import { StakeConnection, WHTokenBalance } from "../app";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+describe("delegation split", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  let stakeConnection: StakeConnection;
+  let user2StakeConnection: StakeConnection;
+  let user3StakeConnection: StakeConnection;
+  let user4StakeConnection: StakeConnection;
+
+  let controller;
+  let user2;
+  let user3;
+  let user4;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    user2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user2 = user2StakeConnection.provider.wallet.publicKey;
+
+    user3StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user3 = user3StakeConnection.provider.wallet.publicKey;
+
+    user4StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user4 = user4StakeConnection.provider.wallet.publicKey;
+
+    await user2StakeConnection.delegate(
+      user2,
+      WHTokenBalance.fromString("100"),
+    );
+    await user3StakeConnection.delegate(user3, WHTokenBalance.fromString("10"));
+    await user4StakeConnection.delegate(user4, WHTokenBalance.fromString("20"));
+  });
+
+  it("should set a delegation split and flag the stake account", async () => {
+    await user2StakeConnection.setDelegationSplit([
+      { delegate: user3, basisPoints: 6_000 },
+      { delegate: user4, basisPoints: 4_000 },
+    ]);
+
+    const user2StakeAccountMetadata =
+      await user2StakeConnection.fetchStakeAccountMetadata(user2);
+    assert.equal(user2StakeAccountMetadata.hasDelegationSplit, true);
+    // The delegate stays at the owner while the split is active
+    assert(user2StakeAccountMetadata.delegate.equals(user2));
+
+    assert.equal(await lastCheckpointValue(stakeConnection, user2), "0");
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, user3),
+      WHTokenBalance.fromString("70").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, user4),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+    assert.equal(await delegatorCount(stakeConnection, user3), 1);
+    assert.equal(await delegatorCount(stakeConnection, user4), 1);
+  });
+
+  it("should remove the delegation split when delegating to a single delegate", async () => {
+    await user2StakeConnection.delegate(user3, WHTokenBalance.fromString("0"));
+
+    const user2StakeAccountMetadata =
+      await user2StakeConnection.fetchStakeAccountMetadata(user2);
+    assert.equal(user2StakeAccountMetadata.hasDelegationSplit, false);
+    assert(user2StakeAccountMetadata.delegate.equals(user3));
+    assert.deepEqual(
+      await user2StakeConnection.getDelegationSplitDelegates(user2),
+      [],
+    );
+
+    // The votes move away from every split delegate
+    const delegateChangedEvents = await lastDelegateChangedEvents(
+      stakeConnection,
+      user2,
+    );
+    assert.deepEqual(
+      delegateChangedEvents.map((event) => event.fromDelegate.toBase58()),
+      [user3.toBase58(), user4.toBase58()],
+    );
+    assert(
+      delegateChangedEvents.every((event) => event.toDelegate.equals(user3)),
+    );
+
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, user3),
+      WHTokenBalance.fromString("110").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, user4),
+      WHTokenBalance.fromString("20").toBN().toString(),
+    );
+    // The delegatee keeps counting user2 as a delegator
+    assert.equal(await delegatorCount(stakeConnection, user3), 1);
+    assert.equal(await delegatorCount(stakeConnection, user4), 0);
+  });
+
+  it("should move the votes back to the owner when self-delegating after a split", async () => {
+    await user2StakeConnection.setDelegationSplit([
+      { delegate: user2, basisPoints: 5_000 },
+      { delegate: user4, basisPoints: 5_000 },
+    ]);
+
+    let user2StakeAccountMetadata =
+      await user2StakeConnection.fetchStakeAccountMetadata(user2);
+    assert.equal(user2StakeAccountMetadata.hasDelegationSplit, true);
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, user2),
+      WHTokenBalance.fromString("50").toBN().toString(),
+    );
+    // The owner is not counted as its own delegator
+    assert.equal(await delegatorCount(stakeConnection, user2), 0);
+    assert.equal(await delegatorCount(stakeConnection, user3), 0);
+    assert.equal(await delegatorCount(stakeConnection, user4), 1);
+
+    await user2StakeConnection.delegate(user2, WHTokenBalance.fromString("0"));
+
+    user2StakeAccountMetadata =
+      await user2StakeConnection.fetchStakeAccountMetadata(user2);
+    assert.equal(user2StakeAccountMetadata.hasDelegationSplit, false);
+    assert(user2StakeAccountMetadata.delegate.equals(user2));
+
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, user2),
+      WHTokenBalance.fromString("100").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, user3),
+      WHTokenBalance.fromString("10").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, user4),
+      WHTokenBalance.fromString("20").toBN().toString(),
+    );
+    assert.equal(await delegatorCount(stakeConnection, user4), 0);
+  });
+});
+
+async function delegatorCount(
+  stakeConnection: StakeConnection,
+  user: PublicKey,
+): Promise<number> {
+  return (await stakeConnection.fetchStakeAccountMetadata(user))
+    .delegatorCount;
+}
+
+async function lastCheckpointValue(
+  stakeConnection: StakeConnection,
+  user: PublicKey,
+): Promise<string> {
+  const checkpointsAddress =
+    await stakeConnection.getStakeAccountCheckpointsAddressByMetadata(
+      await stakeConnection.getStakeMetadataAddress(user),
+      false,
+    );
+  const checkpoints =
+    await stakeConnection.fetchCheckpointAccount(checkpointsAddress);
+  return checkpoints.getLastCheckpoint().value.toString();
+}
+
+/** Returns the DelegateChanged events logged by the latest transaction that
+ *  used the stake account of the user.
+ */
+async function lastDelegateChangedEvents(
+  stakeConnection: StakeConnection,
+  user: PublicKey,
+) {
+  const connection = stakeConnection.provider.connection;
+  const [{ signature }] = await connection.getSignaturesForAddress(
+    await stakeConnection.getStakeMetadataAddress(user),
+    { limit: 1 },
+    "confirmed",
+  );
+  const transaction = await connection.getTransaction(signature, {
+    commitment: "confirmed",
+    maxSupportedTransactionVersion: 0,
+  });
+  const eventParser = new EventParser(
+    stakeConnection.program.programId,
+    stakeConnection.program.coder,
+  );
+  return [...eventParser.parseLogs(transaction.meta.logMessages)]
+    .filter((event) => event.name === "delegateChanged")
+    .map((event) => event.data);
+}
@@ -0,0 +1,149 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+} from "./utils/before";
+import path from "path";
+import { StakeConnection, WHTokenBalance } from "../app";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+describe("deposit_tokens", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  let stakeConnection: StakeConnection;
+  let user2StakeConnection: StakeConnection;
+  let user3StakeConnection: StakeConnection;
+  let user4StakeConnection: StakeConnection;
+
+  let controller;
+  let user2;
+  let user3;
+  let user4;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    user2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user2 = user2StakeConnection.provider.wallet.publicKey;
+
+    user3StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user3 = user3StakeConnection.provider.wallet.publicKey;
+
+    user4StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user4 = user4StakeConnection.provider.wallet.publicKey;
+
+    await user2StakeConnection.delegate(
+      user2,
+      WHTokenBalance.fromString("100"),
+    );
+    await user3StakeConnection.delegate(user3, WHTokenBalance.fromString("10"));
+    await user4StakeConnection.delegate(user4, WHTokenBalance.fromString("20"));
+  });
+
+  it("should checkpoint deposited tokens for the stake account owner", async () => {
+    await user2StakeConnection.depositTokens(WHTokenBalance.fromString("50"));
+
+    await assertRecordedBalance(user2StakeConnection, user2, "150");
+    await assertVotes(stakeConnection, user2, "150");
+  });
+
+  it("should checkpoint deposited tokens for the current delegate", async () => {
+    await user3StakeConnection.delegate(user4, WHTokenBalance.fromString("0"));
+    await assertVotes(stakeConnection, user4, "30");
+
+    await user3StakeConnection.depositTokens(WHTokenBalance.fromString("10"));
+
+    await assertRecordedBalance(user3StakeConnection, user3, "20");
+    await assertVotes(stakeConnection, user3, "0");
+    await assertVotes(stakeConnection, user4, "40");
+  });
+
+  it("should divide deposited tokens between the split delegates", async () => {
+    await user2StakeConnection.setDelegationSplit([
+      { delegate: user3, basisPoints: 6_000 },
+      { delegate: user4, basisPoints: 4_000 },
+    ]);
+    await assertVotes(stakeConnection, user3, "90");
+    await assertVotes(stakeConnection, user4, "100");
+
+    await user2StakeConnection.depositTokens(WHTokenBalance.fromString("25"));
+
+    await assertRecordedBalance(user2StakeConnection, user2, "175");
+    await assertVotes(stakeConnection, user2, "0");
+    await assertVotes(stakeConnection, user3, "105");
+    await assertVotes(stakeConnection, user4, "110");
+  });
+});
+
+async function assertRecordedBalance(
+  stakeConnection: StakeConnection,
+  user: PublicKey,
+  expected: string,
+) {
+  const stakeAccountMetadata =
+    await stakeConnection.fetchStakeAccountMetadata(user);
+  assert.equal(
+    stakeAccountMetadata.recordedBalance.toString(),
+    WHTokenBalance.fromString(expected).toBN().toString(),
+  );
+}
+
+async function assertVotes(
+  stakeConnection: StakeConnection,
+  user: PublicKey,
+  expected: string,
+) {
+  const checkpointsAddress =
+    await stakeConnection.getStakeAccountCheckpointsAddressByMetadata(
+      await stakeConnection.getStakeMetadataAddress(user),
+      false,
+    );
+  const checkpoints =
+    await stakeConnection.fetchCheckpointAccount(checkpointsAddress);
+  assert.equal(
+    checkpoints.getLastCheckpoint().value.toString(),
+    WHTokenBalance.fromString(expected).toBN().toString(),
+  );
+}
@@ -0,0 +1,391 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+} from "./utils/before";
+import path from "path";
+import fs from "fs";
+import os from "os";
+import crypto from "crypto";
+import { StakeConnection, WHTokenBalance } from "../app";
+import { AnchorError, utils } from "@coral-xyz/anchor";
+import * as wasm from "@wormhole/staking-wasm";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+// Length of the stake account metadata created before liquid delegation
+const V0_STAKE_ACCOUNT_METADATA_LEN = 93;
+
+describe("migrate_stake_account_metadata", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  // Stake accounts created with the previous layout. legacyDelegator delegates
+  // to legacyOwner, legacyChainDelegator to legacyChainOwner, and legacyOrphan
+  // to closedOwner, whose stake account was closed
+  const legacyOwner = Keypair.generate().publicKey;
+  const legacyDelegator = Keypair.generate().publicKey;
+  const legacyChainOwner = Keypair.generate().publicKey;
+  const legacyChainDelegator = Keypair.generate().publicKey;
+  const legacyOrphan = Keypair.generate().publicKey;
+  const closedOwner = Keypair.generate().publicKey;
+
+  let stakeConnection: StakeConnection;
+  let user2StakeConnection: StakeConnection;
+  let user3StakeConnection: StakeConnection;
+
+  let controller;
+  let user2;
+  let user3;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    const programId = new PublicKey(config.programs.localnet.staking);
+    const accountsDir = fs.mkdtempSync(path.join(os.tmpdir(), "accounts-"));
+
+    const extraAccounts = [
+      { owner: legacyOwner, delegate: legacyOwner },
+      { owner: legacyDelegator, delegate: legacyOwner },
+      { owner: legacyChainOwner, delegate: legacyChainOwner },
+      { owner: legacyChainDelegator, delegate: legacyChainOwner },
+      { owner: legacyOrphan, delegate: closedOwner },
+    ].map(({ owner, delegate }) => {
+      const [address, filename] = writeV0StakeAccountMetadata(
+        accountsDir,
+        programId,
+        owner,
+        delegate,
+      );
+      return { address, filename };
+    });
+
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey),
+      WHTokenBalance.fromString("1000"),
+      extraAccounts,
+    ));
+
+    user2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user2 = user2StakeConnection.provider.wallet.publicKey;
+
+    user3StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user3 = user3StakeConnection.provider.wallet.publicKey;
+  });
+
+  it("should switch the delegation policy while no stake account delegates", async () => {
+    await stakeConnection.program.methods
+      .updateDelegationPolicy({ liquid: { maxDepth: 3 } })
+      .accounts({ governanceSigner: governanceAuthority.publicKey })
+      .signers([governanceAuthority])
+      .rpc();
+    await stakeConnection.program.methods
+      .updateDelegationPolicy({ singleHop: {} })
+      .accounts({ governanceSigner: governanceAuthority.publicKey })
+      .signers([governanceAuthority])
+      .rpc();
+
+    const config = await stakeConnection.program.account.globalConfig.fetch(
+      stakeConnection.configAddress,
+    );
+    assert("singleHop" in config.delegationPolicy);
+    assert.equal(config.delegatingStakeAccounts.toNumber(), 0);
+  });
+
+  it("should fail to migrate a stake account before its delegate", async () => {
+    try {
+      await migrateStakeAccountMetadata(
+        user2StakeConnection,
+        legacyDelegator,
+        legacyOwner,
+      );
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "DelegateNotMigrated",
+      );
+    }
+  });
+
+  it("should fail to migrate a stake account with another delegate", async () => {
+    try {
+      await migrateStakeAccountMetadata(
+        user2StakeConnection,
+        legacyDelegator,
+        user3,
+      );
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "InvalidCurrentDelegate",
+      );
+    }
+  });
+
+  it("should migrate a stake account with any payer", async () => {
+    await migrateStakeAccountMetadata(user2StakeConnection, legacyOwner, null);
+
+    const accountInfo =
+      await stakeConnection.provider.connection.getAccountInfo(
+        metadataAddress(stakeConnection, legacyOwner),
+      );
+    assert(accountInfo.data.length > V0_STAKE_ACCOUNT_METADATA_LEN);
+    assert(
+      accountInfo.data
+        .subarray(V0_STAKE_ACCOUNT_METADATA_LEN)
+        .every((byte) => byte === 0),
+    );
+
+    const stakeAccountMetadata =
+      await stakeConnection.fetchStakeAccountMetadata(legacyOwner);
+    assert(stakeAccountMetadata.owner.equals(legacyOwner));
+    assert(stakeAccountMetadata.delegate.equals(legacyOwner));
+    assert.equal(stakeAccountMetadata.hasDelegationSplit, false);
+    assert.equal(stakeAccountMetadata.delegatorCount, 0);
+
+    // A self-delegated stake account is not counted as delegating
+    const config = await stakeConnection.program.account.globalConfig.fetch(
+      stakeConnection.configAddress,
+    );
+    assert.equal(config.delegatingStakeAccounts.toNumber(), 0);
+  });
+
+  it("should count a migrated stake account as a delegator of its delegate", async () => {
+    await migrateStakeAccountMetadata(
+      user3StakeConnection,
+      legacyDelegator,
+      legacyOwner,
+    );
+
+    const stakeAccountMetadata =
+      await stakeConnection.fetchStakeAccountMetadata(legacyDelegator);
+    assert(stakeAccountMetadata.delegate.equals(legacyOwner));
+    assert.equal(
+      (await stakeConnection.fetchStakeAccountMetadata(legacyOwner))
+        .delegatorCount,
+      1,
+    );
+
+    const config = await stakeConnection.program.account.globalConfig.fetch(
+      stakeConnection.configAddress,
+    );
+    assert.equal(config.delegatingStakeAccounts.toNumber(), 1);
+  });
+
+  it("should fail to migrate a stake account twice", async () => {
+    try {
+      await migrateStakeAccountMetadata(
+        user2StakeConnection,
+        legacyDelegator,
+        legacyOwner,
+      );
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "AccountAlreadyMigrated",
+      );
+    }
+  });
+
+  it("should delegate a stake account whose delegate closed its stake account to itself", async () => {
+    await migrateStakeAccountMetadata(
+      user2StakeConnection,
+      legacyOrphan,
+      closedOwner,
+    );
+
+    const stakeAccountMetadata =
+      await stakeConnection.fetchStakeAccountMetadata(legacyOrphan);
+    assert(stakeAccountMetadata.delegate.equals(legacyOrphan));
+
+    const config = await stakeConnection.program.account.globalConfig.fetch(
+      stakeConnection.configAddress,
+    );
+    assert.equal(config.delegatingStakeAccounts.toNumber(), 1);
+  });
+
+  it("should migrate a stake account after its delegate on first use", async () => {
+    assert(
+      await user2StakeConnection.migrateStakeAccountMetadata(
+        legacyChainDelegator,
+      ),
+    );
+
+    assert.equal(
+      (await stakeConnection.fetchStakeAccountMetadata(legacyChainOwner))
+        .delegatorCount,
+      1,
+    );
+    assert(
+      (
+        await stakeConnection.fetchStakeAccountMetadata(legacyChainDelegator)
+      ).delegate.equals(legacyChainOwner),
+    );
+
+    // Stake accounts with the current layout are left as is
+    assert(
+      !(await user2StakeConnection.migrateStakeAccountMetadata(
+        legacyChainDelegator,
+      )),
+    );
+    assert(!(await user2StakeConnection.migrateStakeAccountMetadata(user2)));
+
+    const config = await stakeConnection.program.account.globalConfig.fetch(
+      stakeConnection.configAddress,
+    );
+    assert.equal(config.delegatingStakeAccounts.toNumber(), 2);
+  });
+
+  it("should count the stake accounts that start or stop delegating", async () => {
+    await user2StakeConnection.delegate(user3, WHTokenBalance.fromString("10"));
+    let config = await stakeConnection.program.account.globalConfig.fetch(
+      stakeConnection.configAddress,
+    );
+    assert.equal(config.delegatingStakeAccounts.toNumber(), 3);
+
+    await user2StakeConnection.delegate(user2, WHTokenBalance.fromString("0"));
+    config = await stakeConnection.program.account.globalConfig.fetch(
+      stakeConnection.configAddress,
+    );
+    assert.equal(config.delegatingStakeAccounts.toNumber(), 2);
+  });
+
+  it("should fail to switch the delegation policy while a stake account delegates", async () => {
+    try {
+      await stakeConnection.program.methods
+        .updateDelegationPolicy({ liquid: { maxDepth: 3 } })
+        .accounts({ governanceSigner: governanceAuthority.publicKey })
+        .signers([governanceAuthority])
+        .rpc();
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "DelegationPolicySwitchWithDelegations",
+      );
+    }
+  });
+});
+
+function metadataAddress(
+  stakeConnection: StakeConnection,
+  owner: PublicKey,
+): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [
+      utils.bytes.utf8.encode(wasm.Constants.STAKE_ACCOUNT_METADATA_SEED()),
+      owner.toBuffer(),
+    ],
+    stakeConnection.program.programId,
+  )[0];
+}
+
+/** Migrates the StakeAccountMetadata of the owner, passing the
+ *  StakeAccountMetadata of the given delegate, paid by the wallet of the
+ *  stake connection.
+ */
+async function migrateStakeAccountMetadata(
+  stakeConnection: StakeConnection,
+  owner: PublicKey,
+  delegate: PublicKey | null,
+) {
+  await stakeConnection.program.methods
+    .migrateStakeAccountMetadata(owner)
+    .accountsPartial({
+      delegateStakeAccountMetadata:
+        delegate !== null ? metadataAddress(stakeConnection, delegate) : null,
+    })
+    .rpc();
+}
+
+/** Writes a StakeAccountMetadata account with the layout used before liquid
+ *  delegation, to be loaded by the validator, and returns its address and file
+ *  name.
+ */
+function writeV0StakeAccountMetadata(
+  accountsDir: string,
+  programId: PublicKey,
+  owner: PublicKey,
+  delegate: PublicKey,
+): [PublicKey, string] {
+  const [address, metadataBump] = PublicKey.findProgramAddressSync(
+    [
+      utils.bytes.utf8.encode(wasm.Constants.STAKE_ACCOUNT_METADATA_SEED()),
+      owner.toBuffer(),
+    ],
+    programId,
+  );
+  const custodyBump = PublicKey.findProgramAddressSync(
+    [utils.bytes.utf8.encode(wasm.Constants.CUSTODY_SEED()), owner.toBuffer()],
+    programId,
+  )[1];
+  const authorityBump = PublicKey.findProgramAddressSync(
+    [
+      utils.bytes.utf8.encode(wasm.Constants.AUTHORITY_SEED()),
+      owner.toBuffer(),
+    ],
+    programId,
+  )[1];
+
+  const data = Buffer.alloc(V0_STAKE_ACCOUNT_METADATA_LEN);
+  crypto
+    .createHash("sha256")
+    .update("account:StakeAccountMetadata")
+    .digest()
+    .copy(data, 0, 0, 8);
+  data.writeUInt8(metadataBump, 8);
+  data.writeUInt8(custodyBump, 9);
+  data.writeUInt8(authorityBump, 10);
+  // The recorded balances and the checkpoints last index stay zero
+  owner.toBuffer().copy(data, 27);
+  delegate.toBuffer().copy(data, 59);
+
+  const filename = path.join(accountsDir, `${address.toBase58()}.json`);
+  fs.writeFileSync(
+    filename,
+    JSON.stringify({
+      pubkey: address.toBase58(),
+      account: {
+        lamports: 1_000_000_000,
+        data: [data.toString("base64"), "base64"],
+        owner: programId.toBase58(),
+        executable: false,
+        rentEpoch: 0,
+        space: V0_STAKE_ACCOUNT_METADATA_LEN,
+      },
+    }),
+  );
+  return [address, filename];
+}
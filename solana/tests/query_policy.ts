@@ -0,0 +1,212 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  readAnchorConfig,
+  standardSetup,
+} from "./utils/before";
+import BN from "bn.js";
+import path from "path";
+import crypto from "crypto";
+import {
+  createNonFinalizedProposalQueryResponseBytes,
+  createProposalQueryResponseBytesByTimestamp,
+} from "./utils/api_utils";
+import { StakeConnection, WHTokenBalance } from "../app";
+import { QueryProxyMock } from "@wormhole-foundation/wormhole-query-sdk";
+import { AnchorError, utils } from "@coral-xyz/anchor";
+import * as wasm from "@wormhole/staking-wasm";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+// Minimum age of the blocks of the accepted queries that are not finalized
+const MIN_CONFIRMATION_AGE = 60;
+
+describe("query_policy", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  let stakeConnection: StakeConnection;
+  let controller;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+  });
+
+  it("should fail to add a proposal from a query by timestamp by default", async () => {
+    const now = Math.floor(Date.now() / 1000);
+    try {
+      await addTestProposal(
+        stakeConnection,
+        createProposalQueryResponseBytesByTimestamp(
+          proposalId("proposalByTimestamp1"),
+          now,
+          now - 2 * MIN_CONFIRMATION_AGE,
+        ),
+        proposalId("proposalByTimestamp1"),
+      );
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "InvalidChainSpecificQuery",
+      );
+    }
+  });
+
+  it("should fail to update the query policy without the governance authority", async () => {
+    const randomUser = Keypair.generate();
+    try {
+      await stakeConnection.program.methods
+        .updateQueryPolicy({
+          allowSafeFinality: true,
+          allowEthCallByTimestamp: true,
+          minConfirmationAge: new BN(MIN_CONFIRMATION_AGE),
+        })
+        .accounts({ payer: randomUser.publicKey })
+        .signers([randomUser])
+        .rpc();
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "NotGovernanceAuthority",
+      );
+    }
+  });
+
+  it("should update the query policy", async () => {
+    await stakeConnection.program.methods
+      .updateQueryPolicy({
+        allowSafeFinality: true,
+        allowEthCallByTimestamp: true,
+        minConfirmationAge: new BN(MIN_CONFIRMATION_AGE),
+      })
+      .accounts({ payer: governanceAuthority.publicKey })
+      .signers([governanceAuthority])
+      .rpc();
+
+    const spokeMetadataCollector =
+      await stakeConnection.program.account.spokeMetadataCollector.fetch(
+        PublicKey.findProgramAddressSync(
+          [
+            utils.bytes.utf8.encode(
+              wasm.Constants.SPOKE_METADATA_COLLECTOR_SEED(),
+            ),
+          ],
+          stakeConnection.program.programId,
+        )[0],
+      );
+    assert.equal(spokeMetadataCollector.queryPolicy.allowSafeFinality, true);
+    assert.equal(
+      spokeMetadataCollector.queryPolicy.allowEthCallByTimestamp,
+      true,
+    );
+    assert.equal(
+      spokeMetadataCollector.queryPolicy.minConfirmationAge.toNumber(),
+      MIN_CONFIRMATION_AGE,
+    );
+  });
+
+  it("should fail to add a proposal from a query on a safe block younger than the minimum confirmation age", async () => {
+    const now = Math.floor(Date.now() / 1000);
+    try {
+      await addTestProposal(
+        stakeConnection,
+        createNonFinalizedProposalQueryResponseBytes(
+          proposalId("proposalOnSafeBlock1"),
+          now,
+          undefined,
+          now,
+        ),
+        proposalId("proposalOnSafeBlock1"),
+      );
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "QueryBlockTooRecent",
+      );
+    }
+  });
+
+  it("should add a proposal from a query on a safe block", async () => {
+    const now = Math.floor(Date.now() / 1000);
+    await addTestProposal(
+      stakeConnection,
+      createNonFinalizedProposalQueryResponseBytes(
+        proposalId("proposalOnSafeBlock2"),
+        now,
+        undefined,
+        now - 2 * MIN_CONFIRMATION_AGE,
+      ),
+      proposalId("proposalOnSafeBlock2"),
+    );
+
+    const { proposalAccountData } =
+      await stakeConnection.fetchProposalAccountData(
+        proposalId("proposalOnSafeBlock2"),
+      );
+    assert.equal(proposalAccountData.voteStart.toNumber(), now);
+  });
+
+  it("should add a proposal from a query by timestamp", async () => {
+    const now = Math.floor(Date.now() / 1000);
+    await addTestProposal(
+      stakeConnection,
+      createProposalQueryResponseBytesByTimestamp(
+        proposalId("proposalByTimestamp2"),
+        now,
+        now - 2 * MIN_CONFIRMATION_AGE,
+      ),
+      proposalId("proposalByTimestamp2"),
+    );
+
+    const { proposalAccountData } =
+      await stakeConnection.fetchProposalAccountData(
+        proposalId("proposalByTimestamp2"),
+      );
+    assert.equal(proposalAccountData.voteStart.toNumber(), now);
+  });
+});
+
+function proposalId(name: string): Buffer {
+  return crypto.createHash("sha256").update(name).digest();
+}
+
+async function addTestProposal(
+  stakeConnection: StakeConnection,
+  ethProposalResponseBytes: Uint8Array,
+  proposalIdInput: Buffer,
+) {
+  const mock = new QueryProxyMock({});
+  const mockSignatures = mock.sign(ethProposalResponseBytes);
+  const guardianSignaturesPda =
+    await stakeConnection.postSignatures(mockSignatures);
+  const mockGuardianSetIndex = 5;
+
+  await stakeConnection.addProposal(
+    proposalIdInput,
+    ethProposalResponseBytes,
+    guardianSignaturesPda,
+    mockGuardianSetIndex,
+    true,
+  );
+}
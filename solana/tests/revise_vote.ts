@@ -0,0 +1,229 @@
+import { Keypair } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+  sleep,
+} from "./utils/before";
+import BN from "bn.js";
+import path from "path";
+import crypto from "crypto";
+import { createProposalQueryResponseBytes } from "./utils/api_utils";
+import { StakeConnection, WHTokenBalance } from "../app";
+import { QueryProxyMock } from "@wormhole-foundation/wormhole-query-sdk";
+import { AnchorError } from "@coral-xyz/anchor";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+describe("revise_vote", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  let stakeConnection: StakeConnection;
+  let user2StakeConnection: StakeConnection;
+
+  let controller;
+  let user2;
+  let proposalId: Buffer;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    user2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    user2 = user2StakeConnection.provider.wallet.publicKey;
+
+    await user2StakeConnection.delegate(
+      user2,
+      WHTokenBalance.fromString("100"),
+    );
+
+    const voteStart = Math.floor(Date.now() / 1000) + 12;
+    proposalId = await addTestProposal(user2StakeConnection, voteStart);
+
+    while (voteStart >= Math.floor(Date.now() / 1000)) {
+      await sleep(1000);
+    }
+    await sleep(1000);
+  });
+
+  it("should publish the votes only once asked while votes can be revised", async () => {
+    await user2StakeConnection.castVote(
+      proposalId,
+      new BN(10),
+      new BN(20),
+      new BN(0),
+      0,
+    );
+
+    assert.deepEqual(
+      await publishedVotes(user2StakeConnection, proposalId),
+      ["0", "0", "0"],
+    );
+
+    await stakeConnection.publishVotes(proposalId);
+
+    assert.deepEqual(
+      await publishedVotes(user2StakeConnection, proposalId),
+      ["10", "20", "0"],
+    );
+  });
+
+  it("should fail to revise published votes", async () => {
+    try {
+      await user2StakeConnection.reviseVote(
+        proposalId,
+        new BN(0),
+        new BN(30),
+        new BN(0),
+      );
+
+      assert.fail("Expected an error but none was thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code ===
+          "VoteRevisionBelowPublishedVotes",
+      );
+    }
+  });
+
+  it("should bridge, revise the unpublished votes and bridge again without lowering the published tallies", async () => {
+    await user2StakeConnection.castVote(
+      proposalId,
+      new BN(5),
+      new BN(0),
+      new BN(0),
+      0,
+    );
+    // The hub reads the published tallies, without the 5 new against votes
+    const bridgedVotes = await publishedVotes(user2StakeConnection, proposalId);
+    assert.deepEqual(bridgedVotes, ["10", "20", "0"]);
+
+    await user2StakeConnection.reviseVote(
+      proposalId,
+      new BN(10),
+      new BN(20),
+      new BN(5),
+    );
+
+    const { againstVotes, forVotes, abstainVotes } =
+      await user2StakeConnection.proposalVotes(proposalId);
+    assert.equal(againstVotes.toString(), "10");
+    assert.equal(forVotes.toString(), "20");
+    assert.equal(abstainVotes.toString(), "5");
+    assert.deepEqual(
+      await publishedVotes(user2StakeConnection, proposalId),
+      bridgedVotes,
+    );
+
+    await stakeConnection.publishVotes(proposalId);
+
+    const republishedVotes = await publishedVotes(
+      user2StakeConnection,
+      proposalId,
+    );
+    assert.deepEqual(republishedVotes, ["10", "20", "5"]);
+    republishedVotes.forEach((votes, index) =>
+      assert(new BN(votes).gte(new BN(bridgedVotes[index]))),
+    );
+  });
+
+  it("should publish the votes right away once they can no longer be revised", async () => {
+    const voteStart = Math.floor(Date.now() / 1000) + 3;
+    // Votes on a proposal whose vote end is unknown can not be revised
+    const proposalIdWithoutVoteEnd = await addTestProposal(
+      user2StakeConnection,
+      voteStart,
+      null,
+    );
+
+    while (voteStart >= Math.floor(Date.now() / 1000)) {
+      await sleep(1000);
+    }
+    await sleep(1000);
+
+    await user2StakeConnection.castVote(
+      proposalIdWithoutVoteEnd,
+      new BN(10),
+      new BN(20),
+      new BN(5),
+      0,
+    );
+
+    assert.deepEqual(
+      await publishedVotes(user2StakeConnection, proposalIdWithoutVoteEnd),
+      ["10", "20", "5"],
+    );
+  });
+});
+
+/** Returns the published against, for and abstain tallies of the proposal,
+ *  which the hub reads.
+ */
+async function publishedVotes(
+  stakeConnection: StakeConnection,
+  proposalId: Buffer,
+): Promise<string[]> {
+  const { proposalAccountData } =
+    await stakeConnection.fetchProposalAccountData(proposalId);
+  return [
+    proposalAccountData.publishedAgainstVotes.toString(),
+    proposalAccountData.publishedForVotes.toString(),
+    proposalAccountData.publishedAbstainVotes.toString(),
+  ];
+}
+
+async function addTestProposal(
+  stakeConnection: StakeConnection,
+  voteStart: number,
+  voteEnd?: number | null,
+) {
+  const proposalIdInput = crypto
+    .createHash("sha256")
+    .update("proposalId" + Date.now())
+    .digest();
+
+  const ethProposalResponseBytes = createProposalQueryResponseBytes(
+    proposalIdInput,
+    voteStart,
+    voteEnd,
+  );
+  const mock = new QueryProxyMock({});
+  const mockSignatures = mock.sign(ethProposalResponseBytes);
+  const guardianSignaturesPda =
+    await stakeConnection.postSignatures(mockSignatures);
+  const mockGuardianSetIndex = 5;
+
+  await stakeConnection.addProposal(
+    proposalIdInput,
+    ethProposalResponseBytes,
+    guardianSignaturesPda,
+    mockGuardianSetIndex,
+  );
+
+  return proposalIdInput;
+}
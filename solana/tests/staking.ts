@@ -130,6 +130,11 @@ describe("staking", async () => {
         owner,
         delegate: owner,
         stakeAccountCheckpointsLastIndex: 0,
+        delegatedVotes: new BN(0),
+        delegationChainCounts: [0, 0, 0, 0, 0, 0, 0, 0],
+        hasDelegationSplit: false,
+        delegatorCount: 0,
+        ownerChangedAt: new BN(0),
       }),
     );
   });
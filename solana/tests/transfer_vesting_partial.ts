@@ -0,0 +1,465 @@
+import BN from "bn.js";
+import { randomBytes } from "crypto";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import assert from "assert";
+import {
+  ANCHOR_CONFIG_PATH,
+  getPortNumber,
+  makeTestConfig,
+  newUserStakeConnection,
+  readAnchorConfig,
+  standardSetup,
+} from "./utils/before";
+import {
+  getVestAddress,
+  getVestingBalanceAddress,
+  getVestingVotesAccounts,
+  lastCheckpointValue,
+  setupFinalizedVestingConfig,
+} from "./utils/vesting_utils";
+import path from "path";
+import { AnchorError } from "@coral-xyz/anchor";
+import { StakeConnection, WHTokenBalance } from "../app";
+
+const portNumber = getPortNumber(path.basename(__filename));
+
+describe("transfer_vesting_partial", async () => {
+  const whMintAccount = new Keypair();
+  const whMintAuthority = new Keypair();
+  const governanceAuthority = new Keypair();
+
+  const MATURATION = new BN(Math.floor(Date.now() / 1000));
+  const VEST_AMOUNT = WHTokenBalance.fromString("100").toBN();
+
+  // undelegatedVester does not link its vests to a stake account,
+  // delegatedVester and delegatedRecipient delegate their votes to delegatee
+  // and splitVester splits them between splitDelegate1 and splitDelegate2.
+  // The recipient does not have any vest before the first transfer
+  const undelegatedVester = Keypair.generate();
+  const delegatedVester = Keypair.generate();
+  const delegatedRecipient = Keypair.generate();
+  const splitVester = Keypair.generate();
+  const recipient = Keypair.generate().publicKey;
+  const seed = new BN(randomBytes(8));
+
+  let stakeConnection: StakeConnection;
+  let delegateeStakeConnection: StakeConnection;
+  let splitDelegate1StakeConnection: StakeConnection;
+  let splitDelegate2StakeConnection: StakeConnection;
+  let vesterStakeConnections: Map<Keypair, StakeConnection>;
+
+  let controller;
+  let accounts;
+  let delegatee;
+  let splitDelegate1;
+  let splitDelegate2;
+
+  after(async () => {
+    controller.abort();
+  });
+
+  before(async () => {
+    const config = readAnchorConfig(ANCHOR_CONFIG_PATH);
+    ({ controller, stakeConnection } = await standardSetup(
+      portNumber,
+      config,
+      whMintAccount,
+      whMintAuthority,
+      governanceAuthority,
+      makeTestConfig(whMintAccount.publicKey, whMintAuthority.publicKey),
+      WHTokenBalance.fromString("1000"),
+    ));
+
+    vesterStakeConnections = new Map();
+    for (const vester of [
+      undelegatedVester,
+      delegatedVester,
+      delegatedRecipient,
+      splitVester,
+    ]) {
+      vesterStakeConnections.set(
+        vester,
+        await newUserStakeConnection(
+          stakeConnection,
+          vester,
+          config,
+          whMintAccount,
+          whMintAuthority,
+          WHTokenBalance.fromString("1000"),
+        ),
+      );
+    }
+
+    delegateeStakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    delegatee = delegateeStakeConnection.provider.wallet.publicKey;
+
+    splitDelegate1StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    splitDelegate1 = splitDelegate1StakeConnection.provider.wallet.publicKey;
+
+    splitDelegate2StakeConnection = await newUserStakeConnection(
+      stakeConnection,
+      Keypair.generate(),
+      config,
+      whMintAccount,
+      whMintAuthority,
+      WHTokenBalance.fromString("1000"),
+    );
+    splitDelegate2 = splitDelegate2StakeConnection.provider.wallet.publicKey;
+
+    await delegateeStakeConnection.createStakeAccount();
+    await splitDelegate1StakeConnection.createStakeAccount();
+    await splitDelegate2StakeConnection.createStakeAccount();
+
+    accounts = await setupFinalizedVestingConfig(
+      stakeConnection,
+      whMintAccount.publicKey,
+      whMintAuthority,
+      seed,
+      [...vesterStakeConnections.keys()].map((vester) => ({
+        vester: vester.publicKey,
+        maturation: MATURATION,
+        amount: VEST_AMOUNT,
+      })),
+    );
+
+    for (const vester of [delegatedVester, delegatedRecipient]) {
+      await vesterStakeConnections
+        .get(vester)
+        .delegateWithVest(
+          delegatee,
+          WHTokenBalance.fromString("0"),
+          true,
+          accounts.config,
+        );
+    }
+    await vesterStakeConnections
+      .get(splitVester)
+      .delegateWithVest(
+        splitVester.publicKey,
+        WHTokenBalance.fromString("0"),
+        true,
+        accounts.config,
+      );
+    await vesterStakeConnections.get(splitVester).setDelegationSplit([
+      { delegate: splitDelegate1, basisPoints: 5_000 },
+      { delegate: splitDelegate2, basisPoints: 5_000 },
+    ]);
+  });
+
+  it("should fail to transfer nothing or more than the vest", async () => {
+    for (const amount of [new BN(0), VEST_AMOUNT.addn(1)]) {
+      try {
+        await transferVestingPartial(
+          stakeConnection,
+          accounts,
+          undelegatedVester,
+          recipient,
+          MATURATION,
+          amount,
+        );
+
+        assert.fail("Expected error was not thrown");
+      } catch (e) {
+        assert(
+          (e as AnchorError).error?.errorCode?.code === "InvalidTransferAmount",
+        );
+      }
+    }
+  });
+
+  it("should transfer part of a vest and leave the rest with the vester", async () => {
+    await transferVestingPartial(
+      stakeConnection,
+      accounts,
+      undelegatedVester,
+      recipient,
+      MATURATION,
+      WHTokenBalance.fromString("40").toBN(),
+    );
+
+    assert.equal(
+      await vestAmount(
+        stakeConnection,
+        accounts.config,
+        undelegatedVester.publicKey,
+        MATURATION,
+      ),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+    assert.equal(
+      await vestAmount(stakeConnection, accounts.config, recipient, MATURATION),
+      WHTokenBalance.fromString("40").toBN().toString(),
+    );
+    assert.equal(
+      await totalVestingBalance(
+        stakeConnection,
+        accounts.config,
+        undelegatedVester.publicKey,
+      ),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+    assert.equal(
+      await totalVestingBalance(stakeConnection, accounts.config, recipient),
+      WHTokenBalance.fromString("40").toBN().toString(),
+    );
+  });
+
+  it("should close the vest once the rest is transferred", async () => {
+    await transferVestingPartial(
+      stakeConnection,
+      accounts,
+      undelegatedVester,
+      recipient,
+      MATURATION,
+      WHTokenBalance.fromString("60").toBN(),
+    );
+
+    assert.equal(
+      await stakeConnection.provider.connection.getAccountInfo(
+        getVestAddress(
+          stakeConnection,
+          accounts.config,
+          undelegatedVester.publicKey,
+          MATURATION,
+        ),
+      ),
+      null,
+    );
+    assert.equal(
+      await vestAmount(stakeConnection, accounts.config, recipient, MATURATION),
+      VEST_AMOUNT.toString(),
+    );
+    assert.equal(
+      await totalVestingBalance(
+        stakeConnection,
+        accounts.config,
+        undelegatedVester.publicKey,
+      ),
+      "0",
+    );
+    assert.equal(
+      await totalVestingBalance(stakeConnection, accounts.config, recipient),
+      VEST_AMOUNT.toString(),
+    );
+  });
+
+  it("should keep the votes of the delegate when the recipient delegates to it as well", async () => {
+    await transferVestingPartial(
+      stakeConnection,
+      accounts,
+      delegatedVester,
+      delegatedRecipient.publicKey,
+      MATURATION,
+      WHTokenBalance.fromString("40").toBN(),
+    );
+
+    assert.equal(
+      (
+        await stakeConnection.fetchStakeAccountMetadata(
+          delegatedVester.publicKey,
+        )
+      ).recordedVestingBalance.toString(),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+    assert.equal(
+      (
+        await stakeConnection.fetchStakeAccountMetadata(
+          delegatedRecipient.publicKey,
+        )
+      ).recordedVestingBalance.toString(),
+      WHTokenBalance.fromString("140").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, delegatee),
+      WHTokenBalance.fromString("200").toBN().toString(),
+    );
+  });
+
+  it("should subtract the transferred votes from the delegate when the recipient does not delegate", async () => {
+    await transferVestingPartial(
+      stakeConnection,
+      accounts,
+      delegatedVester,
+      recipient,
+      MATURATION,
+      WHTokenBalance.fromString("40").toBN(),
+    );
+
+    assert.equal(
+      (
+        await stakeConnection.fetchStakeAccountMetadata(
+          delegatedVester.publicKey,
+        )
+      ).recordedVestingBalance.toString(),
+      WHTokenBalance.fromString("20").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, delegatee),
+      WHTokenBalance.fromString("160").toBN().toString(),
+    );
+    assert.equal(
+      await totalVestingBalance(stakeConnection, accounts.config, recipient),
+      WHTokenBalance.fromString("140").toBN().toString(),
+    );
+  });
+
+  it("should fail to transfer a split delegated vest to a recipient that delegates", async () => {
+    try {
+      await transferVestingPartial(
+        stakeConnection,
+        accounts,
+        splitVester,
+        delegatedRecipient.publicKey,
+        MATURATION,
+        WHTokenBalance.fromString("40").toBN(),
+      );
+
+      assert.fail("Expected error was not thrown");
+    } catch (e) {
+      assert(
+        (e as AnchorError).error?.errorCode?.code === "DelegationSplitActive",
+      );
+    }
+  });
+
+  it("should subtract the transferred votes from the split delegates", async () => {
+    await transferVestingPartial(
+      stakeConnection,
+      accounts,
+      splitVester,
+      recipient,
+      MATURATION,
+      WHTokenBalance.fromString("40").toBN(),
+    );
+
+    assert.equal(
+      (
+        await stakeConnection.fetchStakeAccountMetadata(splitVester.publicKey)
+      ).recordedVestingBalance.toString(),
+      WHTokenBalance.fromString("60").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, splitDelegate1),
+      WHTokenBalance.fromString("30").toBN().toString(),
+    );
+    assert.equal(
+      await lastCheckpointValue(stakeConnection, splitDelegate2),
+      WHTokenBalance.fromString("30").toBN().toString(),
+    );
+    assert.equal(
+      await totalVestingBalance(stakeConnection, accounts.config, recipient),
+      WHTokenBalance.fromString("180").toBN().toString(),
+    );
+  });
+});
+
+async function vestAmount(
+  stakeConnection: StakeConnection,
+  config: PublicKey,
+  vester: PublicKey,
+  maturation: BN,
+): Promise<string> {
+  return (
+    await stakeConnection.program.account.vesting.fetch(
+      getVestAddress(stakeConnection, config, vester, maturation),
+    )
+  ).amount.toString();
+}
+
+async function totalVestingBalance(
+  stakeConnection: StakeConnection,
+  config: PublicKey,
+  vester: PublicKey,
+): Promise<string> {
+  return (
+    await stakeConnection.program.account.vestingBalance.fetch(
+      getVestingBalanceAddress(stakeConnection, config, vester),
+    )
+  ).totalVestingBalance.toString();
+}
+
+/** Transfers part of the vest of the vester to the new vester. The delegate
+ *  accounts are only passed when the votes leave the delegate, a recipient
+ *  whose vests are linked to a stake account has to delegate to the same
+ *  delegate as the vester.
+ */
+async function transferVestingPartial(
+  stakeConnection: StakeConnection,
+  accounts,
+  vester: Keypair,
+  newVester: PublicKey,
+  maturation: BN,
+  amount: BN,
+) {
+  const { accounts: votesAccounts, remainingAccounts } =
+    await getVestingVotesAccounts(
+      stakeConnection,
+      accounts.config,
+      vester.publicKey,
+    );
+
+  const newVestingBalance = getVestingBalanceAddress(
+    stakeConnection,
+    accounts.config,
+    newVester,
+  );
+  const newStakeAccountMetadata = (
+    await stakeConnection.program.account.vestingBalance.fetchNullable(
+      newVestingBalance,
+    )
+  )?.stakeAccountMetadata;
+  const recipientHasDelegatedVest =
+    newStakeAccountMetadata &&
+    !newStakeAccountMetadata.equals(PublicKey.default);
+  if (recipientHasDelegatedVest) {
+    votesAccounts.delegateStakeAccountCheckpoints = null;
+    votesAccounts.delegateStakeAccountCheckpointsNext = null;
+    votesAccounts.delegateStakeAccountMetadata = null;
+  }
+
+  await stakeConnection.program.methods
+    .transferVestingPartial(newVester, amount)
+    .accounts({
+      ...accounts,
+      ...votesAccounts,
+      vester: vester.publicKey,
+      vest: getVestAddress(
+        stakeConnection,
+        accounts.config,
+        vester.publicKey,
+        maturation,
+      ),
+      newVest: getVestAddress(
+        stakeConnection,
+        accounts.config,
+        newVester,
+        maturation,
+      ),
+      vestingBalance: getVestingBalanceAddress(
+        stakeConnection,
+        accounts.config,
+        vester.publicKey,
+      ),
+      newVestingBalance,
+      newStakeAccountMetadata: recipientHasDelegatedVest
+        ? newStakeAccountMetadata
+        : null,
+    })
+    .remainingAccounts(remainingAccounts)
+    .signers([vester])
+    .rpc();
+}
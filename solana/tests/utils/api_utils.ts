@@ -12,11 +12,16 @@ import {
   PerChainQueryResponse,
   EthCallQueryResponse,
   EthCallWithFinalityQueryResponse,
+  EthCallByTimestampQueryRequest,
+  EthCallByTimestampQueryResponse,
   uint8ArrayToHex,
   EthCallData,
 } from "@wormhole-foundation/wormhole-query-sdk";
 import { ethers } from "ethers";
 
+// Voting period of the test proposals, in seconds
+const DEFAULT_VOTING_PERIOD = 7 * 24 * 60 * 60;
+
 function encodeSignature(signature: string): string {
   return ethers.id(signature).substring(0, 10);
 }
@@ -79,9 +84,14 @@ function getQueryRequestCalldataWithInvalidFunctionSignature(
   return calldata;
 }
 
+/**
+ * Creates a query response with the metadata of a proposal, leaving out the
+ * vote end when `voteEndInput` is null, as older hub metadata contracts do
+ */
 export function createProposalQueryResponseBytes(
   proposalIdInput: Uint8Array,
   voteStartInput: number,
+  voteEndInput: number | null = voteStartInput + DEFAULT_VOTING_PERIOD,
 ): Uint8Array {
   const queryRequest = new QueryRequest(
     42, // nonce
@@ -101,10 +111,13 @@ export function createProposalQueryResponseBytes(
   const proposalId = proposalIdInput; // proposal id (32 bytes)
   const voteStart = toUint256Bytes(voteStartInput); // vote start (32 bytes)
 
-  // one result as the 64-byte array
-  const result = new Uint8Array(64); // 32 + 32 = 64 bytes
+  // one result as the 96-byte array, 64 bytes without the vote end
+  const result = new Uint8Array(voteEndInput === null ? 64 : 96);
   result.set(proposalId, 0); // proposal id (32 bytes)
   result.set(voteStart, 32); // vote start (32 bytes)
+  if (voteEndInput !== null) {
+    result.set(toUint256Bytes(voteEndInput), 64); // vote end (32 bytes)
+  }
 
   const serializedQueryResponse = new QueryResponse(
     HUB_CHAIN_ID, // chain id
@@ -126,9 +139,61 @@ export function createProposalQueryResponseBytes(
   return serializedQueryResponse;
 }
 
+/**
+ * Creates a query response with one getProposalMetadata call per proposal,
+ * in the order of `proposals`
+ */
+export function createProposalsQueryResponseBytes(
+  proposals: { proposalId: Uint8Array; voteStart: number }[],
+): Uint8Array {
+  const queryRequest = new QueryRequest(
+    42, // nonce
+    [
+      new PerChainQueryRequest(
+        HUB_CHAIN_ID, // chain id
+        new EthCallWithFinalityQueryRequest(
+          987654, // block number
+          "finalized",
+          proposals.map(({ proposalId }) =>
+            getQueryRequestCalldata(proposalId),
+          ),
+        ),
+      ),
+    ], // requests
+  );
+
+  // one 96-byte result per proposal: proposal id, vote start and vote end
+  const results = proposals.map(({ proposalId, voteStart }) => {
+    const result = new Uint8Array(96);
+    result.set(proposalId, 0);
+    result.set(toUint256Bytes(voteStart), 32);
+    result.set(toUint256Bytes(voteStart + DEFAULT_VOTING_PERIOD), 64);
+    return uint8ArrayToHex(result);
+  });
+
+  return new QueryResponse(
+    HUB_CHAIN_ID, // chain id
+    Buffer.from(new Array(32).fill(3)).toString("hex"), // request id (32 bytes for on-chain request since chainId != 0)
+    queryRequest,
+    [
+      new PerChainQueryResponse(
+        HUB_CHAIN_ID, // chain id
+        new EthCallWithFinalityQueryResponse(
+          BigInt(987654), // block number
+          "0x123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123a", // block hash
+          BigInt(Date.now()), // block time
+          results,
+        ),
+      ),
+    ],
+  ).serialize();
+}
+
 export function createNonFinalizedProposalQueryResponseBytes(
   proposalIdInput: Uint8Array,
   voteStartInput: number,
+  voteEndInput: number = voteStartInput + DEFAULT_VOTING_PERIOD,
+  blockTimeInput: number = Math.floor(Date.now() / 1000),
 ): Uint8Array {
   const queryRequest = new QueryRequest(
     42, // nonce
@@ -147,11 +212,13 @@ export function createNonFinalizedProposalQueryResponseBytes(
   // first results fields
   const proposalId = proposalIdInput; // proposal id (32 bytes)
   const voteStart = toUint256Bytes(voteStartInput); // vote start (32 bytes)
+  const voteEnd = toUint256Bytes(voteEndInput); // vote end (32 bytes)
 
-  // one result as the 64-byte array
-  const result = new Uint8Array(64); // 32 + 32 = 64 bytes
+  // one result as the 96-byte array
+  const result = new Uint8Array(96); // 32 + 32 + 32 = 96 bytes
   result.set(proposalId, 0); // proposal id (32 bytes)
   result.set(voteStart, 32); // vote start (32 bytes)
+  result.set(voteEnd, 64); // vote end (32 bytes)
 
   const serializedQueryResponse = new QueryResponse(
     HUB_CHAIN_ID, // chain id
@@ -163,7 +230,7 @@ export function createNonFinalizedProposalQueryResponseBytes(
         new EthCallWithFinalityQueryResponse(
           BigInt(987654), // block number
           "0x123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123a", // block hash
-          BigInt(Date.now()), // block time
+          BigInt(blockTimeInput) * BigInt(1_000_000), // block time (microseconds)
           [uint8ArrayToHex(result)], // results
         ),
       ),
@@ -173,9 +240,62 @@ export function createNonFinalizedProposalQueryResponseBytes(
   return serializedQueryResponse;
 }
 
+/**
+ * Creates a query response with the metadata of a proposal read on the block
+ * of `blockTimeInput`, in seconds
+ */
+export function createProposalQueryResponseBytesByTimestamp(
+  proposalIdInput: Uint8Array,
+  voteStartInput: number,
+  blockTimeInput: number,
+): Uint8Array {
+  const blockTime = BigInt(blockTimeInput) * BigInt(1_000_000); // microseconds
+  const queryRequest = new QueryRequest(
+    42, // nonce
+    [
+      new PerChainQueryRequest(
+        HUB_CHAIN_ID, // chain id
+        new EthCallByTimestampQueryRequest(
+          blockTime, // target timestamp
+          987654, // target block hint
+          987655, // following block hint
+          [getQueryRequestCalldata(proposalIdInput)],
+        ),
+      ),
+    ], // requests
+  );
+
+  // one result as the 96-byte array
+  const result = new Uint8Array(96); // 32 + 32 + 32 = 96 bytes
+  result.set(proposalIdInput, 0); // proposal id (32 bytes)
+  result.set(toUint256Bytes(voteStartInput), 32); // vote start (32 bytes)
+  result.set(toUint256Bytes(voteStartInput + DEFAULT_VOTING_PERIOD), 64); // vote end (32 bytes)
+
+  return new QueryResponse(
+    HUB_CHAIN_ID, // chain id
+    Buffer.from(new Array(32).fill(3)).toString("hex"), // request id (32 bytes for on-chain request since chainId != 0)
+    queryRequest,
+    [
+      new PerChainQueryResponse(
+        HUB_CHAIN_ID, // chain id
+        new EthCallByTimestampQueryResponse(
+          BigInt(987654), // target block number
+          "0x123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123a", // target block hash
+          blockTime, // target block time
+          BigInt(987655), // following block number
+          "0x223abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123a", // following block hash
+          blockTime + BigInt(12_000_000), // following block time
+          [uint8ArrayToHex(result)], // results
+        ),
+      ),
+    ],
+  ).serialize();
+}
+
 export function createProposalQueryResponseBytesWithInvalidChainSpecificQuery(
   proposalIdInput: Uint8Array,
   voteStartInput: number,
+  voteEndInput: number = voteStartInput + DEFAULT_VOTING_PERIOD,
 ): Uint8Array {
   const queryRequest = new QueryRequest(
     42, // nonce
@@ -193,11 +313,13 @@ export function createProposalQueryResponseBytesWithInvalidChainSpecificQuery(
   // first results fields
   const proposalId = proposalIdInput; // proposal id (32 bytes)
   const voteStart = toUint256Bytes(voteStartInput); // vote start (32 bytes)
+  const voteEnd = toUint256Bytes(voteEndInput); // vote end (32 bytes)
 
-  // one result as the 64-byte array
-  const result = new Uint8Array(64); // 32 + 32 = 64 bytes
+  // one result as the 96-byte array
+  const result = new Uint8Array(96); // 32 + 32 + 32 = 96 bytes
   result.set(proposalId, 0); // proposal id (32 bytes)
   result.set(voteStart, 32); // vote start (32 bytes)
+  result.set(voteEnd, 64); // vote end (32 bytes)
 
   const serializedQueryResponse = new QueryResponse(
     HUB_CHAIN_ID, // chain id
@@ -222,6 +344,7 @@ export function createProposalQueryResponseBytesWithInvalidChainSpecificQuery(
 export function createProposalQueryResponseBytesWithInvalidChainSpecificResponse(
   proposalIdInput: Uint8Array,
   voteStartInput: number,
+  voteEndInput: number = voteStartInput + DEFAULT_VOTING_PERIOD,
 ): Uint8Array {
   const queryRequest = new QueryRequest(
     42, // nonce
@@ -240,11 +363,13 @@ export function createProposalQueryResponseBytesWithInvalidChainSpecificResponse
   // first results fields
   const proposalId = proposalIdInput; // proposal id (32 bytes)
   const voteStart = toUint256Bytes(voteStartInput); // vote start (32 bytes)
+  const voteEnd = toUint256Bytes(voteEndInput); // vote end (32 bytes)
 
-  // one result as the 64-byte array
-  const result = new Uint8Array(64); // 32 + 32 = 64 bytes
+  // one result as the 96-byte array
+  const result = new Uint8Array(96); // 32 + 32 + 32 = 96 bytes
   result.set(proposalId, 0); // proposal id (32 bytes)
   result.set(voteStart, 32); // vote start (32 bytes)
+  result.set(voteEnd, 64); // vote end (32 bytes)
 
   const serializedQueryResponse = new QueryResponse(
     HUB_CHAIN_ID, // chain id
@@ -269,6 +394,7 @@ export function createProposalQueryResponseBytesWithInvalidChainSpecificResponse
 export function createProposalQueryResponseBytesWithInvalidFunctionSignature(
   proposalIdInput: Uint8Array,
   voteStartInput: number,
+  voteEndInput: number = voteStartInput + DEFAULT_VOTING_PERIOD,
 ): Uint8Array {
   const queryRequest = new QueryRequest(
     42, // nonce
@@ -291,11 +417,13 @@ export function createProposalQueryResponseBytesWithInvalidFunctionSignature(
   // first results fields
   const proposalId = proposalIdInput; // proposal id (32 bytes)
   const voteStart = toUint256Bytes(voteStartInput); // vote start (32 bytes)
+  const voteEnd = toUint256Bytes(voteEndInput); // vote end (32 bytes)
 
-  // one result as the 64-byte array
-  const result = new Uint8Array(64); // 32 + 32 = 64 bytes
+  // one result as the 96-byte array
+  const result = new Uint8Array(96); // 32 + 32 + 32 = 96 bytes
   result.set(proposalId, 0); // proposal id (32 bytes)
   result.set(voteStart, 32); // vote start (32 bytes)
+  result.set(voteEnd, 64); // vote end (32 bytes)
 
   const serializedQueryResponse = new QueryResponse(
     HUB_CHAIN_ID, // chain id
@@ -156,11 +156,17 @@ export async function startValidatorRaw(portNumber: number, otherArgs: string) {
  *
  * ```const config = readAnchorConfig(ANCHOR_CONFIG_PATH)```
  *
+ * extraAccounts are loaded from their JSON files at genesis, like the accounts of Anchor.toml
+ *
  * returns a `{controller, program, provider}` struct. Users of this method have to terminate the
  * validator by calling :
  * ```controller.abort()```
  */
-export async function startValidator(portNumber: number, config: AnchorConfig) {
+export async function startValidator(
+  portNumber: number,
+  config: AnchorConfig,
+  extraAccounts: { address: PublicKey; filename: string }[] = [],
+) {
   const programAddress = new PublicKey(config.programs.localnet.staking);
   const idlPath = config.path.idl_path;
   const binaryPath = config.path.binary_path;
@@ -172,6 +178,11 @@ export async function startValidator(portNumber: number, config: AnchorConfig) {
   --account ${config.config.address} ${config.config.filename}  \
   --account ${config.fee_collector.address} ${config.fee_collector.filename} \
   --account ${config.guardian_set_5.address} ${config.guardian_set_5.filename} \
+  ${extraAccounts
+    .map(
+      ({ address, filename }) => `--account ${address.toBase58()} ${filename}`,
+    )
+    .join(" ")} \
   --mint ${user.publicKey}  \
   --reset \
   --bpf-program ${programAddress.toBase58()} ${binaryPath} \
@@ -303,6 +314,11 @@ export function makeTestConfig(
     maxCheckpointsAccountLimit: maxCheckpointsAccountLimit,
     pendingVestingAdmin: null,
     pendingGovernanceAuthority: null,
+    withdrawalCooldown: new BN(0),
+    delegationPolicy: { singleHop: {} },
+    voteRevisionCutoff: new BN(0),
+    maxVotingPeriod: new BN(0),
+    delegatingStakeAccounts: new BN(0),
   };
 }
 
@@ -389,10 +405,12 @@ export async function standardSetup(
   governanceAuthority: Keypair,
   globalConfig: GlobalConfig,
   amount?: WHTokenBalance,
+  extraAccounts: { address: PublicKey; filename: string }[] = [],
 ) {
   const { controller, program, provider } = await startValidator(
     portNumber,
     config,
+    extraAccounts,
   );
 
   await createMint(
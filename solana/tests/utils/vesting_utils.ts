@@ -0,0 +1,365 @@
+import BN from "bn.js";
+import {
+  AccountMeta,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+} from "@solana/web3.js";
+import {
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  createAssociatedTokenAccountIdempotentInstruction,
+  createMintToInstruction,
+  createTransferCheckedInstruction,
+  getAssociatedTokenAddressSync,
+  TOKEN_PROGRAM_ID,
+} from "@solana/spl-token";
+import { ethers } from "ethers";
+import { StakeConnection } from "../../app";
+import * as wasm from "@wormhole/staking-wasm";
+
+export function getVestingConfigAddress(
+  stakeConnection: StakeConnection,
+  mint: PublicKey,
+  seed: BN,
+): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [
+      Buffer.from(wasm.Constants.VESTING_CONFIG_SEED()),
+      mint.toBuffer(),
+      seed.toBuffer("le", 8),
+    ],
+    stakeConnection.program.programId,
+  )[0];
+}
+
+export function getVestAddress(
+  stakeConnection: StakeConnection,
+  config: PublicKey,
+  vester: PublicKey,
+  maturation: BN,
+): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [
+      Buffer.from(wasm.Constants.VEST_SEED()),
+      config.toBuffer(),
+      vester.toBuffer(),
+      maturation.toBuffer("le", 8),
+    ],
+    stakeConnection.program.programId,
+  )[0];
+}
+
+export function getVestingBalanceAddress(
+  stakeConnection: StakeConnection,
+  config: PublicKey,
+  vester: PublicKey,
+): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [
+      Buffer.from(wasm.Constants.VESTING_BALANCE_SEED()),
+      config.toBuffer(),
+      vester.toBuffer(),
+    ],
+    stakeConnection.program.programId,
+  )[0];
+}
+
+export function getVestingMerkleDistributorAddress(
+  stakeConnection: StakeConnection,
+  config: PublicKey,
+  index: BN,
+): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [
+      Buffer.from(wasm.Constants.VESTING_MERKLE_DISTRIBUTOR_SEED()),
+      config.toBuffer(),
+      index.toBuffer("le", 8),
+    ],
+    stakeConnection.program.programId,
+  )[0];
+}
+
+export function getVestingMerkleClaimBitmapAddress(
+  stakeConnection: StakeConnection,
+  distributor: PublicKey,
+): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [
+      Buffer.from(wasm.Constants.VESTING_MERKLE_CLAIM_BITMAP_SEED()),
+      distributor.toBuffer(),
+    ],
+    stakeConnection.program.programId,
+  )[0];
+}
+
+function keccak256(...data: Buffer[]): Buffer {
+  return Buffer.from(ethers.keccak256(Buffer.concat(data)).slice(2), "hex");
+}
+
+/** Builds the merkle tree of vests of a VestingMerkleDistributor, hashing the
+ *  leaves and the sorted sibling nodes as the program does. A node without a
+ *  sibling moves up to the next level unchanged.
+ *  Returns the root and the proof of every leaf, in the order of the vests.
+ */
+export function buildVestingMerkleTree(
+  vests: { vester: PublicKey; amount: BN; maturation: BN }[],
+): { root: number[]; proofs: number[][][] } {
+  let level = vests.map(({ vester, amount, maturation }, index) => {
+    const indexBuffer = Buffer.alloc(4);
+    indexBuffer.writeUInt32LE(index);
+    return keccak256(
+      Buffer.from([0]),
+      indexBuffer,
+      vester.toBuffer(),
+      amount.toBuffer("le", 8),
+      maturation.toTwos(64).toArrayLike(Buffer, "le", 8),
+    );
+  });
+  // Position of every leaf in the current level
+  const positions = vests.map((_, index) => index);
+  const proofs: number[][][] = vests.map(() => []);
+
+  while (level.length > 1) {
+    positions.forEach((position, leaf) => {
+      const sibling = level[position ^ 1];
+      if (sibling) {
+        proofs[leaf].push(Array.from(sibling));
+      }
+      positions[leaf] = position >> 1;
+    });
+
+    const nextLevel: Buffer[] = [];
+    for (let i = 0; i < level.length; i += 2) {
+      if (i + 1 === level.length) {
+        nextLevel.push(level[i]);
+      } else {
+        const [first, second] = [level[i], level[i + 1]].sort(Buffer.compare);
+        nextLevel.push(keccak256(Buffer.from([1]), first, second));
+      }
+    }
+    level = nextLevel;
+  }
+
+  return { root: Array.from(level[0]), proofs };
+}
+
+/** Initializes a vesting config administered by the vesting admin and creates
+ *  the VestingBalance of every vester and the given vests.
+ *  Returns the accounts shared by the vesting instructions of the config.
+ */
+export async function setupVestingConfig(
+  stakeConnection: StakeConnection,
+  mint: PublicKey,
+  vestingAdmin: Keypair,
+  seed: BN,
+  vests: { vester: PublicKey; maturation: BN; amount: BN }[],
+) {
+  const config = getVestingConfigAddress(stakeConnection, mint, seed);
+  const vault = getAssociatedTokenAddressSync(
+    mint,
+    config,
+    true,
+    TOKEN_PROGRAM_ID,
+  );
+  const adminAta = getAssociatedTokenAddressSync(
+    mint,
+    vestingAdmin.publicKey,
+    false,
+    TOKEN_PROGRAM_ID,
+  );
+  const accounts = {
+    admin: vestingAdmin.publicKey,
+    payer: vestingAdmin.publicKey,
+    mint,
+    config,
+    vault,
+    adminAta,
+    recovery: adminAta,
+    globalConfig: stakeConnection.configAddress,
+    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    tokenProgram: TOKEN_PROGRAM_ID,
+    systemProgram: SystemProgram.programId,
+  };
+
+  const tx = new Transaction();
+  tx.instructions = [
+    SystemProgram.transfer({
+      fromPubkey: stakeConnection.provider.publicKey,
+      toPubkey: vestingAdmin.publicKey,
+      lamports: 10 * LAMPORTS_PER_SOL,
+    }),
+    createAssociatedTokenAccountIdempotentInstruction(
+      stakeConnection.provider.publicKey,
+      adminAta,
+      vestingAdmin.publicKey,
+      mint,
+    ),
+  ];
+  await stakeConnection.provider.sendAndConfirm(tx);
+
+  await stakeConnection.program.methods
+    .initializeVestingConfig(seed)
+    .accounts({ ...accounts })
+    .signers([vestingAdmin])
+    .rpc();
+
+  const vestingBalances = new Set<string>();
+  for (const { vester, maturation, amount } of vests) {
+    const vestingBalance = getVestingBalanceAddress(
+      stakeConnection,
+      config,
+      vester,
+    );
+    if (!vestingBalances.has(vestingBalance.toBase58())) {
+      await stakeConnection.program.methods
+        .createVestingBalance(vester)
+        .accounts({ ...accounts, vestingBalance })
+        .signers([vestingAdmin])
+        .rpc();
+      vestingBalances.add(vestingBalance.toBase58());
+    }
+
+    await stakeConnection.program.methods
+      .createVesting(vester, maturation, amount)
+      .accounts({
+        ...accounts,
+        vest: getVestAddress(stakeConnection, config, vester, maturation),
+        vestingBalance,
+      })
+      .signers([vestingAdmin])
+      .rpc();
+  }
+
+  return accounts;
+}
+
+/** Mints the vested amount of the config, deposits it into the vault and
+ *  finalizes the config. The vesting admin has to be the mint authority.
+ */
+export async function finalizeVestingConfig(
+  stakeConnection: StakeConnection,
+  vestingAdmin: Keypair,
+  accounts,
+) {
+  const vested = (
+    await stakeConnection.program.account.vestingConfig.fetch(accounts.config)
+  ).vested;
+
+  const tx = new Transaction();
+  tx.instructions = [
+    createMintToInstruction(
+      accounts.mint,
+      accounts.adminAta,
+      vestingAdmin.publicKey,
+      BigInt(vested.toString()),
+    ),
+    createTransferCheckedInstruction(
+      accounts.adminAta,
+      accounts.mint,
+      accounts.vault,
+      vestingAdmin.publicKey,
+      BigInt(vested.toString()),
+      6,
+      undefined,
+      TOKEN_PROGRAM_ID,
+    ),
+  ];
+  await stakeConnection.provider.sendAndConfirm(tx, [vestingAdmin]);
+
+  await stakeConnection.program.methods
+    .finalizeVestingConfig()
+    .accounts({ ...accounts })
+    .signers([vestingAdmin])
+    .rpc();
+}
+
+/** Sets up a vesting config with the given vests and finalizes it. */
+export async function setupFinalizedVestingConfig(
+  stakeConnection: StakeConnection,
+  mint: PublicKey,
+  vestingAdmin: Keypair,
+  seed: BN,
+  vests: { vester: PublicKey; maturation: BN; amount: BN }[],
+) {
+  const accounts = await setupVestingConfig(
+    stakeConnection,
+    mint,
+    vestingAdmin,
+    seed,
+    vests,
+  );
+  await finalizeVestingConfig(stakeConnection, vestingAdmin, accounts);
+  return accounts;
+}
+
+/** Gets the accounts that carry the votes of the vests of the vester in the
+ *  vesting config: the delegate accounts of the stake account the vests are
+ *  linked to, or the accounts of its split delegates as remaining accounts
+ *  while a delegation split is active. All of them are null for vests that
+ *  are not linked to a stake account, or whose VestingBalance does not exist
+ *  yet.
+ */
+export async function getVestingVotesAccounts(
+  stakeConnection: StakeConnection,
+  config: PublicKey,
+  vester: PublicKey,
+) {
+  const accounts = {
+    delegateStakeAccountCheckpoints: null,
+    delegateStakeAccountCheckpointsNext: null,
+    delegateStakeAccountMetadata: null,
+    stakeAccountMetadata: null,
+  };
+  const remainingAccounts: AccountMeta[] = [];
+
+  const vestingBalance =
+    await stakeConnection.program.account.vestingBalance.fetchNullable(
+      getVestingBalanceAddress(stakeConnection, config, vester),
+    );
+  if (
+    !vestingBalance ||
+    vestingBalance.stakeAccountMetadata.equals(PublicKey.default)
+  ) {
+    return { accounts, remainingAccounts };
+  }
+
+  accounts.stakeAccountMetadata = vestingBalance.stakeAccountMetadata;
+  const stakeAccountMetadata =
+    await stakeConnection.fetchStakeAccountMetadata(vester);
+  if (stakeAccountMetadata.hasDelegationSplit) {
+    remainingAccounts.push(
+      ...(await stakeConnection.getDelegationSplitRemainingAccounts(
+        await stakeConnection.getDelegationSplitDelegates(vester),
+      )),
+    );
+  } else {
+    const delegate = stakeAccountMetadata.delegate;
+    accounts.delegateStakeAccountMetadata =
+      await stakeConnection.getStakeMetadataAddress(delegate);
+    accounts.delegateStakeAccountCheckpoints =
+      await stakeConnection.getStakeAccountCheckpointsAddressByMetadata(
+        accounts.delegateStakeAccountMetadata,
+        false,
+      );
+    accounts.delegateStakeAccountCheckpointsNext =
+      await stakeConnection.getNextStakeAccountCheckpointsAddress(delegate);
+  }
+  return { accounts, remainingAccounts };
+}
+
+/** Returns the value of the latest checkpoint of the user's stake account. */
+export async function lastCheckpointValue(
+  stakeConnection: StakeConnection,
+  user: PublicKey,
+): Promise<string> {
+  const checkpointsAddress =
+    await stakeConnection.getStakeAccountCheckpointsAddressByMetadata(
+      await stakeConnection.getStakeMetadataAddress(user),
+      false,
+    );
+  const checkpoints =
+    await stakeConnection.fetchCheckpointAccount(checkpointsAddress);
+  return checkpoints.getLastCheckpoint().value.toString();
+}
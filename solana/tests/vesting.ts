@@ -1927,8 +1927,12 @@ describe("vesting", () => {
         stakeAccountMetadataAddress,
         false,
       );
-    // current checkpoint account does not exist
-    assert.equal(currentStakeAccountCheckpointsAddress, undefined);
+    currentStakeAccountCheckpoints =
+      await vesterStakeConnection.fetchCheckpointAccount(
+        currentStakeAccountCheckpointsAddress,
+      );
+    // current checkpoint account is created by the rollover
+    assert.equal(currentStakeAccountCheckpoints.getCheckpointCount(), 1);
 
     let previousStakeAccountCheckpointsAddress =
       await vesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
@@ -2057,13 +2061,6 @@ describe("vesting", () => {
       await vesterStakeConnection.getStakeMetadataAddress(
         vesterStakeConnection.userPublicKey(),
       );
-    let currentStakeAccountCheckpointsAddress =
-      await vesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
-        stakeAccountMetadataAddress,
-        false,
-      );
-    // current checkpoint account does not exist
-    assert.equal(currentStakeAccountCheckpointsAddress, undefined);
 
     let previousStakeAccountCheckpointsAddress =
       await vesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
@@ -2108,18 +2105,10 @@ describe("vesting", () => {
     }
   });
 
-  it("should successfully create a new checkpoints account", async () => {
+  it("should successfully create a new checkpoints account on rollover", async () => {
     let stakeAccountMetadataAddress =
       await vesterStakeConnection.getStakeMetadataAddress(vester.publicKey);
 
-    let currentStakeAccountCheckpointsAddress =
-      await vesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
-        stakeAccountMetadataAddress,
-        false,
-      );
-
-    assert.equal(currentStakeAccountCheckpointsAddress, undefined);
-
     let stakeAccountCheckpointsAddress =
       await vesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
         stakeAccountMetadataAddress,
@@ -2149,18 +2138,6 @@ describe("vesting", () => {
       "4131000000",
     );
 
-    await sleep(1500);
-    await stakeConnection.program.methods
-      .createCheckpoints()
-      .accounts({
-        payer: accounts.vester,
-        stakeAccountCheckpoints: stakeAccountCheckpointsAddress,
-        stakeAccountMetadata: stakeAccountMetadataAddress,
-      })
-      .signers([vester])
-      .rpc({ skipPreflight: true })
-      .then(confirm);
-
     let previousStakeAccountCheckpointsAddress =
       await vesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
         stakeAccountMetadataAddress,
@@ -2443,6 +2420,11 @@ describe("vesting", () => {
         delegateStakeAccountMetadataAddress,
         false,
       );
+    // the claim fills the checkpoint account, so the next one is created in the same instruction
+    let delegateStakeAccountCheckpointsNextAddress =
+      await vesterStakeConnection.getNextStakeAccountCheckpointsAddress(
+        delegateStakeAccountCheckpointsOwner,
+      );
 
     await sleep(1500);
     await stakeConnection.program.methods
@@ -2452,6 +2434,8 @@ describe("vesting", () => {
         vesterTa,
         vest: vestFewLater,
         delegateStakeAccountCheckpoints: delegateStakeAccountCheckpointsAddress,
+        delegateStakeAccountCheckpointsNext:
+          delegateStakeAccountCheckpointsNextAddress,
         delegateStakeAccountMetadata: stakeAccountMetadataAddress,
         stakeAccountMetadata: stakeAccountMetadataAddress,
         globalConfig: stakeConnection.configAddress,
@@ -2479,16 +2463,9 @@ describe("vesting", () => {
     );
   });
 
-  it("should successfully create a new checkpoints account after claim", async () => {
+  it("should successfully create a new checkpoints account on rollover after claim", async () => {
     let stakeAccountMetadataAddress =
       await vesterStakeConnection.getStakeMetadataAddress(vester.publicKey);
-    let currentStakeAccountCheckpointsAddress =
-      await vesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
-        stakeAccountMetadataAddress,
-        false,
-      );
-    // current checkpoint account does not exist
-    assert.equal(currentStakeAccountCheckpointsAddress, undefined);
 
     let vesterStakeAccountMetadata =
       await vesterStakeConnection.fetchStakeAccountMetadata(vester.publicKey);
@@ -2526,18 +2503,6 @@ describe("vesting", () => {
       "2794000000",
     );
 
-    await sleep(1500);
-    await stakeConnection.program.methods
-      .createCheckpoints()
-      .accounts({
-        payer: accounts.vester,
-        stakeAccountCheckpoints: previousStakeAccountCheckpointsAddress,
-        stakeAccountMetadata: stakeAccountMetadataAddress,
-      })
-      .signers([vester])
-      .rpc({ skipPreflight: true })
-      .then(confirm);
-
     vesterStakeAccountMetadata =
       await vesterStakeConnection.fetchStakeAccountMetadata(vester.publicKey);
 
@@ -2775,33 +2740,6 @@ describe("vesting", () => {
         false,
       );
 
-    if (newVesterDelegateStakeAccountCheckpointsAddress == undefined) {
-      let previousNewVesterDelegateStakeAccountCheckpointsAddress =
-        await newVesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
-          newVesterDelegateStakeAccountMetadataAddress,
-          true,
-        );
-
-      await sleep(1500);
-      await stakeConnection.program.methods
-        .createCheckpoints()
-        .accounts({
-          payer: accounts.vester,
-          stakeAccountCheckpoints:
-            previousNewVesterDelegateStakeAccountCheckpointsAddress,
-          stakeAccountMetadata: newVesterDelegateStakeAccountMetadataAddress,
-        })
-        .signers([vester])
-        .rpc({ skipPreflight: true })
-        .then(confirm);
-
-      newVesterDelegateStakeAccountCheckpointsAddress =
-        await newVesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
-          newVesterDelegateStakeAccountMetadataAddress,
-          false,
-        );
-    }
-
     let tx = new Transaction();
     tx.instructions = [
       await vesterStakeConnection.program.methods
@@ -3475,44 +3413,6 @@ describe("vesting", () => {
         false,
       );
 
-    if (newVesterStakeAccountCheckpointsAddress == undefined) {
-      let previousNewVesterStakeAccountCheckpointsAddress =
-        await newVesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
-          newVesterStakeAccountMetadataAddress,
-          true,
-        );
-
-      let previousNewVesterStakeCheckpoints: CheckpointAccount =
-        await newVesterStakeConnection.fetchCheckpointAccount(
-          previousNewVesterStakeAccountCheckpointsAddress,
-        );
-
-      // previous checkpoint account is filled
-      assert.equal(
-        previousNewVesterStakeCheckpoints.getCheckpointCount(),
-        TINY_CHECKPOINTS_ACCOUNT_LIMIT,
-      );
-
-      await sleep(1500);
-      await stakeConnection.program.methods
-        .createCheckpoints()
-        .accounts({
-          payer: accounts.vester,
-          stakeAccountCheckpoints:
-            previousNewVesterStakeAccountCheckpointsAddress,
-          stakeAccountMetadata: newVesterStakeAccountMetadataAddress,
-        })
-        .signers([vester])
-        .rpc({ skipPreflight: true })
-        .then(confirm);
-
-      newVesterStakeAccountCheckpointsAddress =
-        await newVesterStakeConnection.getStakeAccountCheckpointsAddressByMetadata(
-          newVesterStakeAccountMetadataAddress,
-          false,
-        );
-    }
-
     let newVesterStakeCheckpointsBefore: CheckpointAccount =
       await newVesterStakeConnection.fetchCheckpointAccount(
         newVesterStakeAccountCheckpointsAddress,